@@ -428,15 +428,12 @@ mod test {
     #[tokio::test]
     async fn test_creation_of_retry_config_from_profile() {
         let env = Env::from_slice(&[("AWS_CONFIG_FILE", "config")]);
-        // TODO(https://github.com/awslabs/aws-sdk-rust/issues/247): standard is the default mode;
-        // this test would be better if it was setting it to adaptive mode
-        // adaptive mode is currently unsupported so that would panic
         let fs = Fs::from_slice(&[(
             "config",
             // If the lines with the vars have preceding spaces, they don't get read
             r#"[default]
 max_attempts = 1
-retry_mode = standard
+retry_mode = adaptive
             "#,
         )]);
 
@@ -449,7 +446,7 @@ retry_mode = standard
 
         let expected_retry_config = RetryConfig::new()
             .with_max_attempts(1)
-            .with_retry_mode(RetryMode::Standard);
+            .with_retry_mode(RetryMode::Adaptive);
 
         assert_eq!(actual_retry_config, expected_retry_config)
     }
@@ -461,15 +458,12 @@ retry_mode = standard
             ("AWS_MAX_ATTEMPTS", "42"),
             ("AWS_RETRY_MODE", "standard"),
         ]);
-        // TODO(https://github.com/awslabs/aws-sdk-rust/issues/247) standard is the default mode;
-        // this test would be better if it was setting it to adaptive mode
-        // adaptive mode is currently unsupported so that would panic
         let fs = Fs::from_slice(&[(
             "config",
             // If the lines with the vars have preceding spaces, they don't get read
             r#"[default]
 max_attempts = 88
-retry_mode = standard
+retry_mode = adaptive
             "#,
         )]);
 
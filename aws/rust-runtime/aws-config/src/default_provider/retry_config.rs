@@ -83,7 +83,8 @@ impl Builder {
     /// # Panics
     ///
     /// - Panics if the `AWS_MAX_ATTEMPTS` env var or `max_attempts` profile var is set to 0
-    /// - Panics if the `AWS_RETRY_MODE` env var or `retry_mode` profile var is set to "adaptive" (it's not yet supported)
+    /// - Panics if the `AWS_RETRY_MODE` env var or `retry_mode` profile var is set to something
+    ///   other than "standard" or "adaptive"
     pub async fn retry_config(self) -> RetryConfig {
         // Both of these can return errors due to invalid config settings and we want to surface those as early as possible
         // hence, we'll panic if any config values are invalid (missing values are OK though)
@@ -125,6 +125,23 @@ mod test {
                 .build(),
             RetryConfig::new().with_retry_mode(RetryMode::Standard)
         );
+        assert_eq!(
+            test_provider(&[(ENV_VAR_RETRY_MODE, "adaptive")])
+                .retry_config_builder()
+                .unwrap()
+                .build(),
+            RetryConfig::new().with_retry_mode(RetryMode::Adaptive)
+        );
+    }
+
+    #[test]
+    fn retry_mode_errors_when_it_is_not_recognized() {
+        assert!(matches!(
+            test_provider(&[(ENV_VAR_RETRY_MODE, "not a retry mode")])
+                .retry_config_builder()
+                .unwrap_err(),
+            RetryConfigErr::InvalidRetryMode { .. }
+        ));
     }
 
     #[test]
@@ -211,6 +211,7 @@ impl Client {
                 },
                 SdkError::TimeoutError(err) => ImdsError::IoError(err),
                 SdkError::DispatchFailure(err) => ImdsError::IoError(err.into()),
+                SdkError::CircuitBreakerOpen(err) => ImdsError::IoError(err.into()),
                 SdkError::ResponseError { err, .. } => ImdsError::IoError(err),
                 SdkError::ServiceError {
                     err: InnerImdsError::BadStatus,
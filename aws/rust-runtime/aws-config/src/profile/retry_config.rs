@@ -25,11 +25,11 @@ use crate::provider_config::ProviderConfig;
 /// max_attempts = 2
 /// ```
 ///
-/// **Loads `standard` as the `retry_mode` _if and only if_ the `other` profile is selected.**
+/// **Loads `adaptive` as the `retry_mode` _if and only if_ the `other` profile is selected.**
 ///
 /// ```ini
 /// [profile other]
-/// retry_mode = standard
+/// retry_mode = adaptive
 /// ```
 ///
 /// This provider is part of the [default retry_config provider chain](crate::default_provider::retry_config).
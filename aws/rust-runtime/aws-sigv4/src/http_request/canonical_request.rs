@@ -42,6 +42,72 @@ pub(crate) const HMAC_256: &str = "AWS4-HMAC-SHA256";
 const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
 const STREAMING_UNSIGNED_PAYLOAD_TRAILER: &str = "STREAMING-UNSIGNED-PAYLOAD-TRAILER";
 
+/// Rebuilds the canonical request of a request that was signed by a client.
+///
+/// `signed_headers` is the `;`-separated list of lowercase header names from the `Authorization`
+/// header or the `X-Amz-SignedHeaders` query parameter. If `host` is signed but missing from
+/// `headers`, as in HTTP/2 requests, the authority of `uri` is used instead. `payload_hash` is the
+/// hex-encoded SHA-256 digest of the body, or `UNSIGNED-PAYLOAD`. Every query parameter except
+/// `X-Amz-Signature` is part of the canonical query string.
+pub(super) fn from_signed_request(
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    signed_headers: &str,
+    payload_hash: &str,
+    percent_encoding_mode: PercentEncodingMode,
+) -> Result<String, Error> {
+    let path = match percent_encoding_mode {
+        PercentEncodingMode::Double => Cow::Owned(percent_encode_path(uri.path())),
+        PercentEncodingMode::Single => Cow::Borrowed(uri.path()),
+    };
+
+    let mut canonical_headers = HeaderMap::new();
+    let mut names = Vec::new();
+    for name in signed_headers.split(';') {
+        let name = HeaderName::from_str(name)?;
+        let value = match headers.get(&name) {
+            Some(value) => normalize_header_value(value),
+            None if name == HOST && uri.authority().is_some() => {
+                HeaderValue::from_str(uri.authority().expect("checked above").as_str())?
+            }
+            None => return Err(format!("signed header `{}` is missing", name).into()),
+        };
+        // A non-UTF-8 value would make the `Display` implementation panic
+        value.to_str()?;
+        canonical_headers.insert(name.clone(), value);
+        names.push(CanonicalHeaderName(name));
+    }
+
+    let params = form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+        .filter(|(key, _value)| key != param::X_AMZ_SIGNATURE)
+        .collect();
+    let creq = CanonicalRequest {
+        method,
+        path,
+        params: CanonicalRequest::canonical_query_string(uri, params),
+        headers: canonical_headers,
+        values: SignatureValues::Headers(HeaderValues {
+            content_sha256: Cow::Borrowed(payload_hash),
+            date_time: String::new(),
+            security_token: None,
+            signed_headers: SignedHeaders::new(names),
+        }),
+    };
+    Ok(creq.to_string())
+}
+
+/// Returns the string to sign for `canonical_request`, signed at `time` for `region` and `service`.
+pub(super) fn string_to_sign(
+    canonical_request: &str,
+    time: SystemTime,
+    region: &str,
+    service: &str,
+) -> String {
+    let hashed_creq = sha256_hex_string(canonical_request.as_bytes());
+    StringToSign::new(time, region, service, &hashed_creq).to_string()
+}
+
 #[derive(Debug, PartialEq)]
 pub(super) struct HeaderValues<'a> {
     pub(super) content_sha256: Cow<'a, str>,
@@ -236,7 +302,7 @@ impl<'a> CanonicalRequest<'a> {
         Ok((signed_headers, canonical_headers))
     }
 
-    fn payload_hash<'b>(body: &'b SignableBody<'b>) -> Cow<'b, str> {
+    pub(super) fn payload_hash<'b>(body: &'b SignableBody<'b>) -> Cow<'b, str> {
         // Payload hash computation
         //
         // Based on the input body, set the payload_hash of the canonical request:
@@ -277,6 +343,13 @@ impl<'a> CanonicalRequest<'a> {
                 add_param(&mut params, param::X_AMZ_SECURITY_TOKEN, security_token);
             }
         }
+        Self::canonical_query_string(uri, params)
+    }
+
+    fn canonical_query_string(
+        uri: &Uri,
+        mut params: Vec<(Cow<'_, str>, Cow<'_, str>)>,
+    ) -> Option<String> {
         // Sort by param name, and then by param value
         params.sort();
 
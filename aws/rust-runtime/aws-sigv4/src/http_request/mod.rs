@@ -46,6 +46,7 @@ mod query_writer;
 mod settings;
 mod sign;
 mod url_escape;
+mod verify;
 
 #[cfg(test)]
 pub(crate) mod test;
@@ -54,3 +55,4 @@ pub use settings::{
     PayloadChecksumKind, PercentEncodingMode, SignatureLocation, SigningParams, SigningSettings,
 };
 pub use sign::{sign, Error, SignableBody, SignableRequest};
+pub use verify::{RequestSignature, VerifyError, VerifyErrorKind};
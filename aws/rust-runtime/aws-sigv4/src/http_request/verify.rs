@@ -0,0 +1,465 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use super::canonical_request::{self, header, param, CanonicalRequest, HMAC_256};
+use super::{PercentEncodingMode, SignableRequest};
+use crate::sign::{calculate_signature, generate_signing_key};
+use http::header::{AUTHORIZATION, HOST};
+use http::{HeaderMap, Uri};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+use time::{Date, Month, PrimitiveDateTime, Time};
+
+const TERMINATOR: &str = "aws4_request";
+/// The prefix of the payload hashes of chunked payloads, e.g. `STREAMING-AWS4-HMAC-SHA256-PAYLOAD`
+const STREAMING_PAYLOAD_PREFIX: &str = "STREAMING-";
+
+/// The SigV4 signature of a request that was signed by a client
+///
+/// The signature is read from the `Authorization` header or, for presigned URLs, from the query
+/// string. Reading it checks that it is well formed and that its credential scope matches its
+/// signing time; checking the access key, region, service and signing time against what the
+/// receiver accepts is up to the caller. [`verify`](RequestSignature::verify) then recomputes
+/// the signature with the secret access key of the signer.
+///
+/// Payloads signed in chunks (`STREAMING-*`) are rejected, since their chunk signatures can't be
+/// verified.
+#[derive(Debug, Clone)]
+pub struct RequestSignature {
+    access_key_id: String,
+    region: String,
+    service: String,
+    signed_headers: String,
+    signature: String,
+    time: SystemTime,
+    security_token: Option<String>,
+    expires_in: Option<Duration>,
+}
+
+impl RequestSignature {
+    /// Reads the signature of a request from its `Authorization` header or query string
+    pub fn from_request(uri: &Uri, headers: &HeaderMap) -> Result<Self, VerifyError> {
+        if let Some(payload_hash) = headers.get(header::X_AMZ_CONTENT_SHA_256) {
+            if payload_hash
+                .as_bytes()
+                .starts_with(STREAMING_PAYLOAD_PREFIX.as_bytes())
+            {
+                return Err(VerifyError::invalid(
+                    "payloads signed in chunks (`STREAMING-*`) are not supported",
+                ));
+            }
+        }
+        if let Some(authorization) = headers.get(AUTHORIZATION) {
+            let authorization = authorization.to_str().map_err(|_| {
+                VerifyError::invalid("the `Authorization` header is not valid ASCII")
+            })?;
+            return Self::from_authorization(authorization, headers);
+        }
+        let params: Vec<(String, String)> =
+            form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect();
+        if params
+            .iter()
+            .any(|(name, _)| name == param::X_AMZ_SIGNATURE)
+        {
+            return Self::from_query(&params);
+        }
+        Err(VerifyError::new(
+            VerifyErrorKind::MissingSignature,
+            "the request is missing an `Authorization` header or a presigned URL signature",
+        ))
+    }
+
+    /// Parses `AWS4-HMAC-SHA256 Credential=<credential>, SignedHeaders=<headers>, Signature=<signature>`
+    fn from_authorization(authorization: &str, headers: &HeaderMap) -> Result<Self, VerifyError> {
+        let invalid = || {
+            VerifyError::invalid(format!(
+                "invalid `Authorization` header `{}`",
+                authorization
+            ))
+        };
+        let fields = authorization
+            .strip_prefix(HMAC_256)
+            .filter(|fields| fields.starts_with(' '))
+            .ok_or_else(invalid)?;
+        let (mut credential, mut signed_headers, mut signature) = (None, None, None);
+        for field in fields.split(',') {
+            match field.trim().split_once('=').ok_or_else(invalid)? {
+                ("Credential", value) => credential = Some(value),
+                ("SignedHeaders", value) => signed_headers = Some(value),
+                ("Signature", value) => signature = Some(value),
+                _ => return Err(invalid()),
+            }
+        }
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .map(|value| value.to_str())
+                .transpose()
+                .map_err(|_| {
+                    VerifyError::invalid(format!("the `{}` header is not valid ASCII", name))
+                })
+        };
+        let date_time = header(header::X_AMZ_DATE)?.ok_or_else(|| {
+            VerifyError::invalid(format!("the `{}` header is missing", header::X_AMZ_DATE))
+        })?;
+        Self::new(
+            credential.ok_or_else(invalid)?,
+            signed_headers.ok_or_else(invalid)?,
+            signature.ok_or_else(invalid)?,
+            date_time,
+            header(header::X_AMZ_SECURITY_TOKEN)?,
+            None,
+        )
+    }
+
+    fn from_query(params: &[(String, String)]) -> Result<Self, VerifyError> {
+        let value = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let required = |name: &str| {
+            value(name).ok_or_else(|| {
+                VerifyError::invalid(format!("the `{}` query parameter is missing", name))
+            })
+        };
+        let algorithm = required(param::X_AMZ_ALGORITHM)?;
+        if algorithm != HMAC_256 {
+            return Err(VerifyError::invalid(format!(
+                "unsupported algorithm `{}`",
+                algorithm
+            )));
+        }
+        let expires = required(param::X_AMZ_EXPIRES)?;
+        let expires = expires.parse::<u64>().map_err(|_| {
+            VerifyError::invalid(format!("invalid `{}` `{}`", param::X_AMZ_EXPIRES, expires))
+        })?;
+        Self::new(
+            required(param::X_AMZ_CREDENTIAL)?,
+            required(param::X_AMZ_SIGNED_HEADERS)?,
+            required(param::X_AMZ_SIGNATURE)?,
+            required(param::X_AMZ_DATE)?,
+            value(param::X_AMZ_SECURITY_TOKEN),
+            Some(Duration::from_secs(expires)),
+        )
+    }
+
+    fn new(
+        credential: &str,
+        signed_headers: &str,
+        signature: &str,
+        date_time: &str,
+        security_token: Option<&str>,
+        expires_in: Option<Duration>,
+    ) -> Result<Self, VerifyError> {
+        // `<access key id>/<date>/<region>/<service>/aws4_request`
+        let scope: Vec<&str> = credential.split('/').collect();
+        let (access_key_id, scope_date, region, service, terminator) = match scope[..] {
+            [access_key_id, scope_date, region, service, terminator] => {
+                (access_key_id, scope_date, region, service, terminator)
+            }
+            _ => {
+                return Err(VerifyError::invalid(format!(
+                    "invalid credential `{}`",
+                    credential
+                )))
+            }
+        };
+        if !signed_headers.split(';').any(|name| name == HOST) {
+            return Err(VerifyError::invalid("the `host` header must be signed"));
+        }
+        let time = parse_date_time(date_time)?;
+        if terminator != TERMINATOR || date_time.get(..8) != Some(scope_date) {
+            return Err(VerifyError::new(
+                VerifyErrorKind::SignatureDoesNotMatch,
+                format!(
+                    "the credential scope `{}/{}/{}/{}` does not match the signing date `{}`",
+                    scope_date, region, service, terminator, date_time
+                ),
+            ));
+        }
+        Ok(Self {
+            access_key_id: access_key_id.to_string(),
+            region: region.to_string(),
+            service: service.to_string(),
+            signed_headers: signed_headers.to_string(),
+            signature: signature.to_string(),
+            time,
+            security_token: security_token.map(String::from),
+            expires_in,
+        })
+    }
+
+    /// The access key id the request was signed with
+    pub fn access_key_id(&self) -> &str {
+        &self.access_key_id
+    }
+
+    /// The region of the credential scope
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    /// The service of the credential scope
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// The signing time, from `x-amz-date` or the `X-Amz-Date` query parameter
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// The session token sent with the request, if any
+    pub fn security_token(&self) -> Option<&str> {
+        self.security_token.as_deref()
+    }
+
+    /// How long a presigned URL is valid for after its signing time, or `None` if the request
+    /// was signed in its `Authorization` header
+    pub fn expires_in(&self) -> Option<Duration> {
+        self.expires_in
+    }
+
+    /// Recomputes the signature of `request` with `secret_key`, and checks that it matches
+    ///
+    /// The body of `request` must be the payload that was signed: the bytes of the body, or
+    /// [`UnsignedPayload`](super::SignableBody::UnsignedPayload) if the client didn't sign it.
+    pub fn verify(
+        &self,
+        request: &SignableRequest<'_>,
+        secret_key: &str,
+        percent_encoding_mode: PercentEncodingMode,
+    ) -> Result<(), VerifyError> {
+        let payload_hash = CanonicalRequest::payload_hash(request.body());
+        let creq = canonical_request::from_signed_request(
+            request.method(),
+            request.uri(),
+            request.headers(),
+            &self.signed_headers,
+            &payload_hash,
+            percent_encoding_mode,
+        )
+        .map_err(|err| VerifyError::invalid(err.to_string()))?;
+        let sts = canonical_request::string_to_sign(&creq, self.time, &self.region, &self.service);
+        let signing_key = generate_signing_key(secret_key, self.time, &self.region, &self.service);
+        let expected = calculate_signature(signing_key, sts.as_bytes());
+        if constant_time_eq(expected.as_bytes(), self.signature.as_bytes()) {
+            Ok(())
+        } else {
+            Err(VerifyError::new(
+                VerifyErrorKind::SignatureDoesNotMatch,
+                "the request signature we calculated does not match the signature you provided",
+            ))
+        }
+    }
+}
+
+/// Parses a SigV4 signing time, e.g. `20150830T123600Z`
+fn parse_date_time(value: &str) -> Result<SystemTime, VerifyError> {
+    let invalid = || VerifyError::invalid(format!("invalid signing time `{}`", value));
+    let bytes = value.as_bytes();
+    if bytes.len() != 16 || bytes[8] != b'T' || bytes[15] != b'Z' {
+        return Err(invalid());
+    }
+    let number = |range: std::ops::Range<usize>| {
+        value
+            .get(range)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse::<u16>().ok())
+            .ok_or_else(invalid)
+    };
+    let month = Month::try_from(number(4..6)? as u8).map_err(|_| invalid())?;
+    let date = Date::from_calendar_date(number(0..4)? as i32, month, number(6..8)? as u8)
+        .map_err(|_| invalid())?;
+    let time = Time::from_hms(
+        number(9..11)? as u8,
+        number(11..13)? as u8,
+        number(13..15)? as u8,
+    )
+    .map_err(|_| invalid())?;
+    Ok(PrimitiveDateTime::new(date, time).assume_utc().into())
+}
+
+/// Compares two signatures in time that only depends on their length
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Why a request failed verification
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyErrorKind {
+    /// The request isn't signed
+    MissingSignature,
+    /// The signature is malformed, or signs the request in a way that isn't supported
+    InvalidSignature,
+    /// The signature doesn't match the request or its credential scope
+    SignatureDoesNotMatch,
+}
+
+/// A request failed SigV4 verification
+#[derive(Debug)]
+pub struct VerifyError {
+    kind: VerifyErrorKind,
+    message: String,
+}
+
+impl VerifyError {
+    fn new(kind: VerifyErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    fn invalid(message: impl Into<String>) -> Self {
+        Self::new(VerifyErrorKind::InvalidSignature, message)
+    }
+
+    /// Why the request failed verification
+    pub fn kind(&self) -> VerifyErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for VerifyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{RequestSignature, VerifyErrorKind};
+    use crate::http_request::{
+        sign, PayloadChecksumKind, PercentEncodingMode, SignableBody, SignableRequest,
+        SignatureLocation, SigningParams, SigningSettings,
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn signed_request(settings: SigningSettings) -> http::Request<&'static str> {
+        let mut request = http::Request::builder()
+            .method("PUT")
+            .uri("https://some-endpoint.some-region.amazonaws.com/a%20b/c?z=1&a=2&a=1")
+            .header("content-type", "application/json")
+            .header("x-custom", "  spaced   value ")
+            .body("{}")
+            .unwrap();
+        let params = SigningParams::builder()
+            .access_key("AKIDEXAMPLE")
+            .secret_key("secret")
+            .security_token("token")
+            .region("us-east-1")
+            .service_name("service")
+            .time(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+            .settings(settings)
+            .build()
+            .unwrap();
+        let (instructions, _signature) = sign(SignableRequest::from(&request), &params)
+            .unwrap()
+            .into_parts();
+        instructions.apply_to_request(&mut request);
+        request
+    }
+
+    fn verify(
+        request: &http::Request<&'static str>,
+        body: SignableBody<'_>,
+    ) -> Result<(), VerifyErrorKind> {
+        let signature = RequestSignature::from_request(request.uri(), request.headers())
+            .map_err(|err| err.kind())?;
+        let request =
+            SignableRequest::new(request.method(), request.uri(), request.headers(), body);
+        signature
+            .verify(&request, "secret", PercentEncodingMode::Double)
+            .map_err(|err| err.kind())
+    }
+
+    #[test]
+    fn signed_requests_are_verified() {
+        let settings = SigningSettings {
+            payload_checksum_kind: PayloadChecksumKind::XAmzSha256,
+            ..Default::default()
+        };
+        let mut request = signed_request(settings);
+        let signature = RequestSignature::from_request(request.uri(), request.headers()).unwrap();
+        assert_eq!("AKIDEXAMPLE", signature.access_key_id());
+        assert_eq!("us-east-1", signature.region());
+        assert_eq!("service", signature.service());
+        assert_eq!(Some("token"), signature.security_token());
+        assert_eq!(None, signature.expires_in());
+        assert_eq!(
+            UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            signature.time()
+        );
+
+        assert_eq!(Ok(()), verify(&request, SignableBody::Bytes(b"{}")));
+        assert_eq!(
+            Err(VerifyErrorKind::SignatureDoesNotMatch),
+            verify(&request, SignableBody::Bytes(b"tampered"))
+        );
+
+        request.headers_mut().remove("x-custom");
+        assert_eq!(
+            Err(VerifyErrorKind::InvalidSignature),
+            verify(&request, SignableBody::Bytes(b"{}"))
+        );
+    }
+
+    #[test]
+    fn presigned_urls_are_verified() {
+        let settings = SigningSettings {
+            signature_location: SignatureLocation::QueryParams,
+            expires_in: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let request = signed_request(settings);
+        let signature = RequestSignature::from_request(request.uri(), request.headers()).unwrap();
+        assert_eq!(Some(Duration::from_secs(30)), signature.expires_in());
+        assert_eq!(Ok(()), verify(&request, SignableBody::Bytes(b"{}")));
+    }
+
+    #[test]
+    fn malformed_signatures_are_rejected() {
+        let with_header = |name: &'static str, value: &'static str| {
+            let mut request = signed_request(SigningSettings::default());
+            request.headers_mut().insert(name, value.parse().unwrap());
+            verify(&request, SignableBody::Bytes(b"{}"))
+        };
+        // the credential scope is for 2020-09-13
+        assert_eq!(
+            Err(VerifyErrorKind::SignatureDoesNotMatch),
+            with_header("x-amz-date", "20200914T122640Z")
+        );
+        assert_eq!(
+            Err(VerifyErrorKind::InvalidSignature),
+            with_header("x-amz-date", "2020-09-13")
+        );
+        assert_eq!(
+            Err(VerifyErrorKind::InvalidSignature),
+            with_header("x-amz-content-sha256", "STREAMING-AWS4-HMAC-SHA256-PAYLOAD")
+        );
+        assert_eq!(
+            Err(VerifyErrorKind::InvalidSignature),
+            with_header("authorization", "AWS4-HMAC-SHA256 Signature=abc")
+        );
+
+        let unsigned = http::Request::builder()
+            .uri("https://some-endpoint.some-region.amazonaws.com/")
+            .body("")
+            .unwrap();
+        assert_eq!(
+            Err(VerifyErrorKind::MissingSignature),
+            verify(&unsigned, SignableBody::Bytes(b""))
+        );
+    }
+}
@@ -0,0 +1,363 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! In-process HTTP server for end-to-end client tests.
+//!
+//! Unlike the test connections in `aws-smithy-client`, [`TestServer`] listens on a real socket, so
+//! requests go through the same hyper client and plaintext HTTP path that the SDK uses in
+//! production. The server listens on an ephemeral port on `127.0.0.1`. It replies with scripted
+//! responses (or echoes the request body back) and records every request it receives. With the
+//! `sigv4` feature, it can also verify SigV4 signatures.
+//!
+//! # Examples
+//! ```no_run
+//! use aws_test_server::TestServer;
+//! use bytes::Bytes;
+//!
+//! # async fn example() {
+//! let server = TestServer::builder()
+//!     .respond_with(
+//!         http::Response::builder()
+//!             .status(200)
+//!             .body(Bytes::from_static(b"{}"))
+//!             .unwrap(),
+//!     )
+//!     .start()
+//!     .expect("failed to start test server");
+//!
+//! // point a client at `server.endpoint()` and send a request
+//!
+//! let requests = server.requests();
+//! assert_eq!(requests.len(), 1);
+//! # }
+//! ```
+
+#![warn(
+    missing_docs,
+    rustdoc::missing_crate_level_docs,
+    missing_debug_implementations,
+    rust_2018_idioms,
+    unreachable_pub
+)]
+
+#[cfg(feature = "sigv4")]
+mod sigv4;
+
+#[cfg(feature = "sigv4")]
+pub use sigv4::{SigV4Verifier, VerificationError};
+
+use bytes::Bytes;
+use http::{Request, Response, StatusCode, Uri};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Server};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+type Responder = Box<dyn Fn(&Request<Bytes>) -> Response<Bytes> + Send + Sync>;
+
+/// Builder for [`TestServer`]
+pub struct Builder {
+    script: VecDeque<Response<Bytes>>,
+    fallback: Responder,
+    #[cfg(feature = "sigv4")]
+    verifier: Option<SigV4Verifier>,
+}
+
+impl fmt::Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("script", &self.script)
+            .finish()
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            script: VecDeque::new(),
+            fallback: Box::new(echo),
+            #[cfg(feature = "sigv4")]
+            verifier: None,
+        }
+    }
+}
+
+impl Builder {
+    /// Queue a response
+    ///
+    /// Queued responses are sent in order, one per request. Once the queue is empty, the server
+    /// replies with the [fallback](Builder::fallback).
+    pub fn respond_with(mut self, response: Response<Bytes>) -> Self {
+        self.script.push_back(response);
+        self
+    }
+
+    /// Set the responder used once all queued responses have been sent
+    ///
+    /// By default, the server echoes the request: it replies with `200 OK`, the request body, and
+    /// the request's `Content-Type`.
+    pub fn fallback(
+        mut self,
+        responder: impl Fn(&Request<Bytes>) -> Response<Bytes> + Send + Sync + 'static,
+    ) -> Self {
+        self.fallback = Box::new(responder);
+        self
+    }
+
+    /// Verify the SigV4 signature of every request
+    ///
+    /// Requests that fail verification are still recorded, but the server replies with
+    /// `403 Forbidden` and a `SignatureDoesNotMatch` error instead of the scripted response.
+    #[cfg(feature = "sigv4")]
+    pub fn verify_sigv4(mut self, verifier: SigV4Verifier) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Start the server on an ephemeral port on `127.0.0.1`
+    ///
+    /// This must be called from within a Tokio runtime. The server shuts down when the returned
+    /// [`TestServer`] is dropped.
+    pub fn start(self) -> Result<TestServer, hyper::Error> {
+        let incoming = AddrIncoming::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))?;
+        let addr = incoming.local_addr();
+        let state = Arc::new(State {
+            script: Mutex::new(self.script),
+            fallback: self.fallback,
+            #[cfg(feature = "sigv4")]
+            verifier: self.verifier,
+            requests: Mutex::new(Vec::new()),
+        });
+        let make_service = {
+            let state = state.clone();
+            make_service_fn(move |_conn| {
+                let state = state.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        let state = state.clone();
+                        async move { state.handle(request).await }
+                    }))
+                }
+            })
+        };
+        let (shutdown, rx) = oneshot::channel();
+        let server = Server::builder(incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                let _ = rx.await;
+            });
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                tracing::warn!(err = %err, "test server failed");
+            }
+        });
+        tracing::debug!(addr = %addr, "started test server");
+        Ok(TestServer {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+}
+
+struct State {
+    script: Mutex<VecDeque<Response<Bytes>>>,
+    fallback: Responder,
+    #[cfg(feature = "sigv4")]
+    verifier: Option<SigV4Verifier>,
+    requests: Mutex<Vec<Request<Bytes>>>,
+}
+
+impl State {
+    async fn handle(&self, request: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+        let (parts, body) = request.into_parts();
+        let request = Request::from_parts(parts, hyper::body::to_bytes(body).await?);
+        let response = self.respond(&request);
+        self.requests.lock().unwrap().push(request);
+        Ok(response.map(Body::from))
+    }
+
+    fn respond(&self, request: &Request<Bytes>) -> Response<Bytes> {
+        #[cfg(feature = "sigv4")]
+        if let Some(verifier) = &self.verifier {
+            if let Err(err) = verifier.verify(request) {
+                tracing::debug!(err = %err, "request failed SigV4 verification");
+                return Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .header("x-amzn-errortype", "SignatureDoesNotMatch")
+                    .body(Bytes::from(err.to_string()))
+                    .expect("valid response");
+            }
+        }
+        match self.script.lock().unwrap().pop_front() {
+            Some(response) => response,
+            None => (self.fallback)(request),
+        }
+    }
+}
+
+fn echo(request: &Request<Bytes>) -> Response<Bytes> {
+    let mut response = Response::builder().status(StatusCode::OK);
+    if let Some(content_type) = request.headers().get(http::header::CONTENT_TYPE) {
+        response = response.header(http::header::CONTENT_TYPE, content_type);
+    }
+    response
+        .body(request.body().clone())
+        .expect("valid response")
+}
+
+/// In-process HTTP server that replies with scripted responses and records requests
+///
+/// Create a server with [`TestServer::builder`] or [`TestServer::echo`]. The server shuts down
+/// when it is dropped.
+pub struct TestServer {
+    addr: SocketAddr,
+    state: Arc<State>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl fmt::Debug for TestServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestServer")
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+impl TestServer {
+    /// Returns a builder for a test server
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Start a server that echoes every request
+    ///
+    /// This must be called from within a Tokio runtime.
+    pub fn echo() -> Result<Self, hyper::Error> {
+        Self::builder().start()
+    }
+
+    /// The address the server listens on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The endpoint of the server, e.g. `http://127.0.0.1:49152`
+    pub fn endpoint(&self) -> Uri {
+        self.uri("/")
+    }
+
+    /// A URI on the server for the given path and query string, e.g. `/bucket/key?acl`
+    pub fn uri(&self, path_and_query: &str) -> Uri {
+        format!("http://{}{}", self.addr, path_and_query)
+            .parse()
+            .expect("valid URI")
+    }
+
+    /// The requests received so far, in the order they were received
+    pub fn requests(&self) -> Vec<Request<Bytes>> {
+        self.state
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| {
+                let mut copy = Request::builder()
+                    .method(request.method().clone())
+                    .uri(request.uri().clone())
+                    .version(request.version())
+                    .body(request.body().clone())
+                    .expect("valid request");
+                *copy.headers_mut() = request.headers().clone();
+                copy
+            })
+            .collect()
+    }
+
+    /// Take the requests received so far, leaving the server's record empty
+    pub fn take_requests(&self) -> Vec<Request<Bytes>> {
+        std::mem::take(&mut *self.state.requests.lock().unwrap())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::TestServer;
+    use aws_smithy_client::hyper_ext::Adapter;
+    use aws_smithy_http::body::SdkBody;
+    use bytes::Bytes;
+    use http::{Request, Response};
+    use tower::{Service, ServiceExt};
+
+    pub(crate) async fn send(request: Request<SdkBody>) -> Response<Bytes> {
+        let mut adapter = Adapter::builder().build(hyper::client::HttpConnector::new());
+        let response = adapter
+            .ready()
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .expect("request succeeds");
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        Response::from_parts(parts, body)
+    }
+
+    #[tokio::test]
+    async fn echoes_requests_by_default() {
+        let server = TestServer::echo().unwrap();
+        let request = Request::post(server.uri("/echo?a=b"))
+            .header("content-type", "application/json")
+            .body(SdkBody::from(r#"{"hello":"world"}"#))
+            .unwrap();
+        let response = send(request).await;
+        assert_eq!(200, response.status());
+        assert_eq!("application/json", response.headers()["content-type"]);
+        assert_eq!(&br#"{"hello":"world"}"#[..], response.body());
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!("/echo?a=b", requests[0].uri());
+        assert_eq!(&br#"{"hello":"world"}"#[..], requests[0].body());
+    }
+
+    #[tokio::test]
+    async fn scripted_responses_are_sent_in_order() {
+        let server = TestServer::builder()
+            .respond_with(
+                Response::builder()
+                    .status(503)
+                    .body(Bytes::from_static(b"slow down"))
+                    .unwrap(),
+            )
+            .respond_with(Response::new(Bytes::from_static(b"ok")))
+            .fallback(|_| Response::builder().status(404).body(Bytes::new()).unwrap())
+            .start()
+            .unwrap();
+        let mut statuses = vec![];
+        for _ in 0..3 {
+            let request = Request::get(server.endpoint())
+                .body(SdkBody::empty())
+                .unwrap();
+            statuses.push(send(request).await.status().as_u16());
+        }
+        assert_eq!(vec![503, 200, 404], statuses);
+        assert_eq!(3, server.take_requests().len());
+        assert!(server.requests().is_empty());
+    }
+}
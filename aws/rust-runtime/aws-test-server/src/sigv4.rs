@@ -0,0 +1,216 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use aws_sigv4::http_request::{
+    PercentEncodingMode, RequestSignature, SignableBody, SignableRequest,
+};
+use bytes::Bytes;
+use http::Request;
+use std::error::Error;
+use std::fmt;
+
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+const X_AMZ_CONTENT_SHA_256: &str = "x-amz-content-sha256";
+
+/// Verifies the SigV4 signature in the `Authorization` header of a request
+///
+/// The signature is read and recomputed with
+/// [`RequestSignature`](aws_sigv4::http_request::RequestSignature), which also checks that the
+/// credential scope matches the signing time in `x-amz-date`.
+#[derive(Debug)]
+pub struct SigV4Verifier {
+    access_key_id: String,
+    secret_access_key: String,
+    region: Option<String>,
+    service: Option<String>,
+    double_uri_encode: bool,
+}
+
+impl SigV4Verifier {
+    /// Creates a verifier for requests signed with the given credentials
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            region: None,
+            service: None,
+            double_uri_encode: true,
+        }
+    }
+
+    /// Require requests to be signed for `region`
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Require requests to be signed for `service`, e.g. `dynamodb`
+    pub fn with_service(mut self, service: impl Into<String>) -> Self {
+        self.service = Some(service.into());
+        self
+    }
+
+    /// Canonicalize the path without re-encoding it, as S3 does
+    pub fn single_uri_encoding(mut self) -> Self {
+        self.double_uri_encode = false;
+        self
+    }
+
+    /// Verify the signature of `request`
+    pub fn verify(&self, request: &Request<Bytes>) -> Result<(), VerificationError> {
+        let signature = RequestSignature::from_request(request.uri(), request.headers())
+            .map_err(|err| VerificationError::new(err.to_string()))?;
+        if signature.access_key_id() != self.access_key_id {
+            return Err(VerificationError::new(format!(
+                "unknown access key `{}`",
+                signature.access_key_id()
+            )));
+        }
+        check_scope("region", signature.region(), self.region.as_deref())?;
+        check_scope("service", signature.service(), self.service.as_deref())?;
+
+        let body = match request.headers().get(X_AMZ_CONTENT_SHA_256) {
+            Some(value) if value == UNSIGNED_PAYLOAD => SignableBody::UnsignedPayload,
+            _ => SignableBody::Bytes(request.body()),
+        };
+        let mode = if self.double_uri_encode {
+            PercentEncodingMode::Double
+        } else {
+            PercentEncodingMode::Single
+        };
+        let signable =
+            SignableRequest::new(request.method(), request.uri(), request.headers(), body);
+        signature
+            .verify(&signable, &self.secret_access_key, mode)
+            .map_err(|err| VerificationError::new(err.to_string()))
+    }
+}
+
+fn check_scope(field: &str, actual: &str, expected: Option<&str>) -> Result<(), VerificationError> {
+    match expected {
+        Some(expected) if expected != actual => Err(VerificationError::new(format!(
+            "request was signed for {} `{}` instead of `{}`",
+            field, actual, expected
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// A request failed SigV4 verification
+#[derive(Debug)]
+pub struct VerificationError {
+    message: String,
+}
+
+impl VerificationError {
+    fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SignatureDoesNotMatch: {}", self.message)
+    }
+}
+
+impl Error for VerificationError {}
+
+#[cfg(test)]
+mod test {
+    use crate::test::send;
+    use crate::{SigV4Verifier, TestServer};
+    use aws_sigv4::http_request::{sign, SignableRequest, SigningParams, SigningSettings};
+    use aws_smithy_http::body::SdkBody;
+    use http::Request;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn signed_request(uri: http::Uri, body: &'static str, secret_key: &str) -> Request<SdkBody> {
+        let mut request = Request::put(uri)
+            .header("content-type", "application/json")
+            .header("x-custom", "value")
+            .body(body)
+            .unwrap();
+        let host = request.uri().authority().unwrap().to_string();
+        request.headers_mut().insert("host", host.parse().unwrap());
+        let params = SigningParams::builder()
+            .access_key("AKIDEXAMPLE")
+            .secret_key(secret_key)
+            .security_token("session-token")
+            .region("us-east-1")
+            .service_name("dynamodb")
+            .time(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+            .settings(SigningSettings::default())
+            .build()
+            .unwrap();
+        let (instructions, _signature) = sign(SignableRequest::from(&request), &params)
+            .unwrap()
+            .into_parts();
+        instructions.apply_to_request(&mut request);
+        request.map(SdkBody::from)
+    }
+
+    #[tokio::test]
+    async fn valid_signatures_are_accepted() {
+        let server = TestServer::builder()
+            .verify_sigv4(
+                SigV4Verifier::new("AKIDEXAMPLE", "secret")
+                    .with_region("us-east-1")
+                    .with_service("dynamodb"),
+            )
+            .start()
+            .unwrap();
+        let request = signed_request(server.uri("/path/a%20b?x=1"), "{}", "secret");
+        let response = send(request).await;
+        assert_eq!(200, response.status(), "{:?}", response.body());
+        assert_eq!(1, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn invalid_signatures_are_rejected() {
+        let server = TestServer::builder()
+            .verify_sigv4(SigV4Verifier::new("AKIDEXAMPLE", "secret"))
+            .start()
+            .unwrap();
+        let response = send(signed_request(server.endpoint(), "{}", "wrong")).await;
+        assert_eq!(403, response.status());
+        assert_eq!(
+            "SignatureDoesNotMatch",
+            response.headers()["x-amzn-errortype"]
+        );
+
+        // tamper with the body after signing
+        let request = signed_request(server.endpoint(), "{}", "secret")
+            .map(|_| SdkBody::from(r#"{"tampered":true}"#));
+        assert_eq!(403, send(request).await.status());
+
+        let verifier = SigV4Verifier::new("AKIDEXAMPLE", "secret").with_region("us-west-2");
+        let err = verifier
+            .verify(&server.requests()[0])
+            .expect_err("region does not match");
+        assert!(err.to_string().contains("us-east-1"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn credential_scope_must_match_the_signing_date() {
+        let server = TestServer::builder()
+            .verify_sigv4(SigV4Verifier::new("AKIDEXAMPLE", "secret"))
+            .start()
+            .unwrap();
+        // the request is signed on 2020-09-13, but claims a credential scope for another day
+        let mut request = signed_request(server.endpoint(), "{}", "secret");
+        let authorization = request.headers()["authorization"].to_str().unwrap();
+        let authorization = authorization.replace("/20200913/", "/20200914/");
+        request
+            .headers_mut()
+            .insert("authorization", authorization.parse().unwrap());
+        let response = send(request).await;
+        assert_eq!(403, response.status());
+        assert_eq!(
+            "SignatureDoesNotMatch",
+            response.headers()["x-amzn-errortype"]
+        );
+    }
+}
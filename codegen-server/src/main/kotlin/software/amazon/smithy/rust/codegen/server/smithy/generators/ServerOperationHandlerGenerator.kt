@@ -6,6 +6,10 @@
 package software.amazon.smithy.rust.codegen.server.smithy.generators
 
 import software.amazon.smithy.model.shapes.OperationShape
+import software.amazon.smithy.model.traits.HttpQueryParamsTrait
+import software.amazon.smithy.model.traits.HttpQueryTrait
+import software.amazon.smithy.model.traits.HttpTrait
+import software.amazon.smithy.model.traits.SensitiveTrait
 import software.amazon.smithy.rust.codegen.rustlang.CargoDependency
 import software.amazon.smithy.rust.codegen.rustlang.RustWriter
 import software.amazon.smithy.rust.codegen.rustlang.asType
@@ -19,7 +23,9 @@ import software.amazon.smithy.rust.codegen.smithy.RuntimeType
 import software.amazon.smithy.rust.codegen.smithy.generators.CodegenTarget
 import software.amazon.smithy.rust.codegen.smithy.generators.error.errorSymbol
 import software.amazon.smithy.rust.codegen.smithy.transformers.operationErrors
+import software.amazon.smithy.rust.codegen.util.getTrait
 import software.amazon.smithy.rust.codegen.util.hasStreamingMember
+import software.amazon.smithy.rust.codegen.util.hasTrait
 import software.amazon.smithy.rust.codegen.util.inputShape
 import software.amazon.smithy.rust.codegen.util.outputShape
 import software.amazon.smithy.rust.codegen.util.toPascalCase
@@ -63,6 +69,20 @@ open class ServerOperationHandlerGenerator(
             val inputName = "crate::input::${operationName}Input"
             val inputWrapperName = "crate::operation::$operationName${ServerHttpBoundProtocolGenerator.OPERATION_INPUT_WRAPPER_SUFFIX}"
             val outputWrapperName = "crate::operation::$operationName${ServerHttpBoundProtocolGenerator.OPERATION_OUTPUT_WRAPPER_SUFFIX}"
+            val sensitivityExtension = sensitivityExtension(operation)
+            val sensitivityConst = sensitivityExtension?.let {
+                "const SENSITIVITY: $serverCrate::extension::SensitivityExtension = $it;"
+            } ?: ""
+            val insertSensitivity = sensitivityExtension?.let { "response.extensions_mut().insert(SENSITIVITY);" } ?: ""
+            val runtimeErrorResponse = if (sensitivityExtension == null) {
+                "return runtime_error.into_response().map($serverCrate::body::boxed);"
+            } else {
+                """
+                let mut response = runtime_error.into_response();
+                $insertSensitivity
+                return response.map($serverCrate::body::boxed);
+                """.trimIndent()
+            }
             val fnSignature = if (state) {
                 "impl<B, Fun, Fut, S> #{ServerOperationHandler}::Handler<B, $serverCrate::Extension<S>, $inputName> for Fun"
             } else {
@@ -89,6 +109,7 @@ open class ServerOperationHandlerGenerator(
                             };
                             let mut response = runtime_error.into_response();
                             response.extensions_mut().insert(extension);
+                            $insertSensitivity
                             return response.map($serverCrate::body::boxed);
                         }
                     };
@@ -105,11 +126,12 @@ open class ServerOperationHandlerGenerator(
                     """
                     type Sealed = #{ServerOperationHandler}::sealed::Hidden;
                     async fn call(self, req: #{http}::Request<B>) -> #{http}::Response<#{SmithyHttpServer}::body::BoxBody> {
+                        $sensitivityConst
                         let mut req = #{SmithyHttpServer}::request::RequestParts::new(req);
                         let input_wrapper = match $inputWrapperName::from_request(&mut req).await {
                             Ok(v) => v,
                             Err(runtime_error) => {
-                                return runtime_error.into_response().map($serverCrate::body::boxed);
+                                $runtimeErrorResponse
                             }
                         };
                         $callImpl
@@ -118,6 +140,7 @@ open class ServerOperationHandlerGenerator(
                         response.extensions_mut().insert(
                             #{SmithyHttpServer}::extension::OperationExtension::new("${operation.id.namespace}", "$operationName")
                         );
+                        $insertSensitivity
                         response.map(#{SmithyHttpServer}::body::boxed)
                     }
                     """,
@@ -127,6 +150,49 @@ open class ServerOperationHandlerGenerator(
         }
     }
 
+    /**
+     * Generates the `SensitivityExtension` of an operation, which tells layers that log requests which path labels
+     * and query parameters bind input members marked with the `@sensitive` trait. It is a constant expression, so the
+     * extension is not rebuilt for every request. Returns `null` if there are none.
+     */
+    private fun sensitivityExtension(operation: OperationShape): String? {
+        val httpTrait = operation.getTrait<HttpTrait>() ?: return null
+        val sensitiveMembers = operation.inputShape(model).members().filter {
+            it.getMemberTrait(model, SensitiveTrait::class.java).isPresent
+        }
+        val sensitiveMemberNames = sensitiveMembers.map { it.memberName }.toSet()
+        val labels = mutableListOf<Int>()
+        var greedyLabel: Int? = null
+        httpTrait.uri.segments.forEachIndexed { index, segment ->
+            if (segment.isLabel && sensitiveMemberNames.contains(segment.content)) {
+                if (segment.isGreedyLabel) greedyLabel = index else labels += index
+            }
+        }
+        val queryParams = sensitiveMembers.mapNotNull { it.getTrait<HttpQueryTrait>()?.value }
+        val allQueryParams = sensitiveMembers.any { it.hasTrait<HttpQueryParamsTrait>() }
+        if (labels.isEmpty() && greedyLabel == null && queryParams.isEmpty() && !allQueryParams) {
+            return null
+        }
+        return "$serverCrate::extension::SensitivityExtension::from_static(" +
+            "&[${labels.joinToString(", ")}], " +
+            "${greedyLabel?.let { "Some($it)" } ?: "None"}, " +
+            "&[${queryParams.joinToString(", ") { rustStringLiteral(it) }}], " +
+            "$allQueryParams)"
+    }
+
+    /**
+     * Renders [value] as a Rust string literal. Characters that would end the literal, start an escape sequence or be
+     * interpreted by the code writer, and characters outside of printable ASCII, are written as Unicode escapes.
+     */
+    private fun rustStringLiteral(value: String): String =
+        value.codePoints().toArray().joinToString("", "\"", "\"") { codePoint ->
+            if (codePoint in 0x20..0x7e && codePoint.toChar() !in "\"\\#$") {
+                codePoint.toChar().toString()
+            } else {
+                "\\u{${Integer.toHexString(codePoint)}}"
+            }
+        }
+
     /**
      * Generates the trait bounds of the `Handler` trait implementation, depending on:
      *     - the presence of state; and
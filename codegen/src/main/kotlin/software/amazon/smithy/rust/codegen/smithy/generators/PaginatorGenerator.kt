@@ -159,6 +159,10 @@ class PaginatorGenerator private constructor(
                             Err(e) => { let _ = tx.send(Err(e)).await; return; }
                         };
                         loop {
+                            if let Err(e) = handle.client.before_serialization(&mut input) {
+                                let _ = tx.send(Err(e)).await;
+                                return;
+                            }
                             let op = match input.make_operation(&handle.conf)
                                 .await
                                 .map_err(|err| #{SdkError}::ConstructionFailure(err.into())) {
@@ -505,7 +505,9 @@ class FluentClientGenerator(
                         /// set when configuring the client.
                         pub async fn send(self) -> std::result::Result<#{ok}, #{sdk_err}<#{operation_err}>>
                         #{send_bounds:W} {
-                            let op = self.inner.build().map_err(|err|#{sdk_err}::ConstructionFailure(err.into()))?
+                            let mut input = self.inner.build().map_err(|err|#{sdk_err}::ConstructionFailure(err.into()))?;
+                            self.handle.client.before_serialization(&mut input)?;
+                            let op = input
                                 .make_operation(&self.handle.conf)
                                 .await
                                 .map_err(|err|#{sdk_err}::ConstructionFailure(err.into()))?;
@@ -6,14 +6,17 @@
 package software.amazon.smithy.rust.codegen.smithy.generators.http
 
 import software.amazon.smithy.model.shapes.OperationShape
+import software.amazon.smithy.model.traits.EndpointTrait
 import software.amazon.smithy.rust.codegen.rustlang.CargoDependency
 import software.amazon.smithy.rust.codegen.rustlang.Writable
 import software.amazon.smithy.rust.codegen.rustlang.asType
+import software.amazon.smithy.rust.codegen.rustlang.rust
 import software.amazon.smithy.rust.codegen.rustlang.rustTemplate
 import software.amazon.smithy.rust.codegen.rustlang.withBlock
 import software.amazon.smithy.rust.codegen.rustlang.writable
 import software.amazon.smithy.rust.codegen.smithy.RuntimeType
 import software.amazon.smithy.rust.codegen.smithy.protocols.HttpBindingResolver
+import software.amazon.smithy.rust.codegen.util.getTrait
 
 /**
  * [RestRequestSpecGenerator] generates a restJson1 or restXml specific `RequestSpec`. Both protocols are routed the same.
@@ -36,12 +39,30 @@ class RestRequestSpecGenerator(
                 "PathSpec",
                 "QuerySpec",
                 "PathSegment",
-                "QuerySegment"
+                "QuerySegment",
+                "HostPrefixSegment"
             ).map {
                 it to requestSpecModule.member(it)
             }.toTypedArray()
 
-        // TODO(https://github.com/awslabs/smithy-rs/issues/950): Support the `endpoint` trait.
+        val hostPrefixSegmentsVec = writable {
+            val endpointTrait = operationShape.getTrait<EndpointTrait>()
+            if (endpointTrait == null) {
+                rust("None")
+            } else {
+                withBlock("Some(vec![", "])") {
+                    for (segment in endpointTrait.hostPrefix.segments) {
+                        val variant = if (segment.isLabel) {
+                            """Label(String::from("${segment.content}"))"""
+                        } else {
+                            """Literal(String::from("${segment.content}"))"""
+                        }
+                        rustTemplate("#{HostPrefixSegment}::$variant,", *extraCodegenScope)
+                    }
+                }
+            }
+        }
+
         val pathSegmentsVec = writable {
             withBlock("vec![", "]") {
                 for (segment in httpTrait.uri.segments) {
@@ -77,6 +98,7 @@ class RestRequestSpecGenerator(
                 #{RequestSpec}::new(
                     #{Method}::${httpTrait.method},
                     #{UriSpec}::new(
+                        #{HostPrefixSegmentsVec:W},
                         #{PathAndQuerySpec}::new(
                             #{PathSpec}::from_vector_unchecked(#{PathSegmentsVec:W}),
                             #{QuerySpec}::from_vector_unchecked(#{QuerySegmentsVec:W})
@@ -85,6 +107,7 @@ class RestRequestSpecGenerator(
                 )
                 """,
                 *extraCodegenScope,
+                "HostPrefixSegmentsVec" to hostPrefixSegmentsVec,
                 "PathSegmentsVec" to pathSegmentsVec,
                 "QuerySegmentsVec" to querySegmentsVec,
                 "Method" to CargoDependency.Http.asType().member("Method"),
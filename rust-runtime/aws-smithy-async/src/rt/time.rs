@@ -0,0 +1,106 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Provides a [`TimeSource`] trait that returns the current time, and implementations of
+//! `TimeSource` for different async runtimes.
+//!
+//! Code that measures elapsed time alongside an [`AsyncSleep`](crate::rt::sleep::AsyncSleep)
+//! implementation should read the time from the matching `TimeSource`, so that both agree when the
+//! runtime's clock is paused or advanced manually, as in tests.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Trait with a `now` function that returns the current time.
+pub trait TimeSource: std::fmt::Debug + Send + Sync {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+}
+
+impl<T> TimeSource for Box<T>
+where
+    T: TimeSource,
+    T: ?Sized,
+{
+    fn now(&self) -> Instant {
+        T::now(self)
+    }
+}
+
+impl<T> TimeSource for Arc<T>
+where
+    T: TimeSource,
+    T: ?Sized,
+{
+    fn now(&self) -> Instant {
+        T::now(self)
+    }
+}
+
+#[cfg(feature = "rt-tokio")]
+/// Returns a default time source based on the features enabled
+pub fn default_time_source() -> Arc<dyn TimeSource> {
+    Arc::new(TokioTimeSource::new())
+}
+
+#[cfg(not(feature = "rt-tokio"))]
+/// Returns a default time source based on the features enabled
+pub fn default_time_source() -> Arc<dyn TimeSource> {
+    Arc::new(SystemTimeSource::new())
+}
+
+/// Implementation of [`TimeSource`] that reads the system's monotonic clock.
+#[non_exhaustive]
+#[derive(Debug, Default)]
+pub struct SystemTimeSource;
+
+impl SystemTimeSource {
+    /// Create a new [`TimeSource`] implementation that uses [`Instant::now`]
+    pub fn new() -> SystemTimeSource {
+        Default::default()
+    }
+}
+
+impl TimeSource for SystemTimeSource {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Implementation of [`TimeSource`] for Tokio.
+#[non_exhaustive]
+#[cfg(feature = "rt-tokio")]
+#[derive(Debug, Default)]
+pub struct TokioTimeSource;
+
+#[cfg(feature = "rt-tokio")]
+impl TokioTimeSource {
+    /// Create a new [`TimeSource`] implementation that follows the Tokio clock, which can be
+    /// paused and advanced in tests
+    pub fn new() -> TokioTimeSource {
+        Default::default()
+    }
+}
+
+#[cfg(feature = "rt-tokio")]
+impl TimeSource for TokioTimeSource {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+#[cfg(all(test, feature = "rt-tokio"))]
+mod test {
+    use super::{TimeSource, TokioTimeSource};
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn tokio_time_source_follows_paused_clock() {
+        let time_source = TokioTimeSource::new();
+        let start = time_source.now();
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(Duration::from_secs(5), time_source.now() - start);
+    }
+}
@@ -5,11 +5,16 @@
 
 use std::sync::Arc;
 
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConnector};
+use crate::rate_limit::RateLimiter;
+use crate::timeout::ResponseBodyTimeout;
 use crate::{bounds, erase, retry, Client, TriState, MISSING_SLEEP_IMPL_RECOMMENDATION};
 use aws_smithy_async::rt::sleep::{default_async_sleep, AsyncSleep};
 use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::interceptor::{Interceptor, Interceptors};
 use aws_smithy_http::result::ConnectorError;
 use aws_smithy_types::timeout;
+use tower::Layer;
 
 /// A builder that provides more customization options when constructing a [`Client`].
 ///
@@ -23,6 +28,9 @@ pub struct Builder<C = (), M = (), R = retry::Standard> {
     retry_policy: R,
     timeout_config: timeout::Config,
     sleep_impl: TriState<Arc<dyn AsyncSleep>>,
+    rate_limiter: Option<RateLimiter>,
+    interceptors: Interceptors,
+    response_body_timeout: ResponseBodyTimeout,
 }
 
 // It'd be nice to include R where R: Default here, but then the caller ends up always having to
@@ -62,6 +70,9 @@ impl<M, R> Builder<(), M, R> {
             middleware: self.middleware,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            rate_limiter: self.rate_limiter,
+            interceptors: self.interceptors,
+            response_body_timeout: self.response_body_timeout,
         }
     }
 
@@ -118,6 +129,9 @@ impl<C, R> Builder<C, (), R> {
             timeout_config: self.timeout_config,
             middleware,
             sleep_impl: self.sleep_impl,
+            rate_limiter: self.rate_limiter,
+            interceptors: self.interceptors,
+            response_body_timeout: self.response_body_timeout,
         }
     }
 
@@ -169,6 +183,9 @@ impl<C, M> Builder<C, M, retry::Standard> {
             timeout_config: self.timeout_config,
             middleware: self.middleware,
             sleep_impl: self.sleep_impl,
+            rate_limiter: self.rate_limiter,
+            interceptors: self.interceptors,
+            response_body_timeout: self.response_body_timeout,
         }
     }
 }
@@ -200,6 +217,50 @@ impl<C, M> Builder<C, M> {
         self.sleep_impl = TriState::or_unset(default_async_sleep());
         self
     }
+
+    /// Set the [`RateLimiter`] used to limit the rate of requests sent by the client.
+    ///
+    /// Rate limiting requires a sleep implementation.
+    pub fn set_rate_limiter(&mut self, rate_limiter: Option<RateLimiter>) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    /// Set the [`RateLimiter`] used to limit the rate of requests sent by the client.
+    ///
+    /// Rate limiting requires a sleep implementation.
+    pub fn rate_limiter(mut self, rate_limiter: Option<RateLimiter>) -> Self {
+        self.set_rate_limiter(rate_limiter);
+        self
+    }
+
+    /// Add an [`Interceptor`] that is called for every operation sent by the client.
+    ///
+    /// Interceptors are called in the order they were added, before any interceptors registered
+    /// on the operation itself.
+    pub fn interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Replace the [`Interceptors`] that are called for every operation sent by the client.
+    pub fn set_interceptors(&mut self, interceptors: Interceptors) {
+        self.interceptors = interceptors;
+    }
+
+    /// Set the timeouts for reading response bodies.
+    ///
+    /// Response body timeouts require a sleep implementation.
+    pub fn set_response_body_timeout(&mut self, response_body_timeout: ResponseBodyTimeout) {
+        self.response_body_timeout = response_body_timeout;
+    }
+
+    /// Set the timeouts for reading response bodies.
+    ///
+    /// Response body timeouts require a sleep implementation.
+    pub fn response_body_timeout(mut self, response_body_timeout: ResponseBodyTimeout) -> Self {
+        self.set_response_body_timeout(response_body_timeout);
+        self
+    }
 }
 
 impl<C, M, R> Builder<C, M, R> {
@@ -214,9 +275,22 @@ impl<C, M, R> Builder<C, M, R> {
             retry_policy: self.retry_policy,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            rate_limiter: self.rate_limiter,
+            interceptors: self.interceptors,
+            response_body_timeout: self.response_body_timeout,
         }
     }
 
+    /// Send requests through a [`CircuitBreaker`] that wraps the current connector.
+    ///
+    /// This must be called after the connector has been set.
+    pub fn circuit_breaker(
+        self,
+        circuit_breaker: CircuitBreaker,
+    ) -> Builder<CircuitBreakerConnector<C>, M, R> {
+        self.map_connector(|connector| circuit_breaker.layer(connector))
+    }
+
     /// Use a middleware that wraps the current middleware.
     pub fn map_middleware<F, M2>(self, map: F) -> Builder<C, M2, R>
     where
@@ -228,6 +302,9 @@ impl<C, M, R> Builder<C, M, R> {
             retry_policy: self.retry_policy,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            rate_limiter: self.rate_limiter,
+            interceptors: self.interceptors,
+            response_body_timeout: self.response_body_timeout,
         }
     }
 
@@ -256,6 +333,9 @@ impl<C, M, R> Builder<C, M, R> {
             middleware: self.middleware,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            rate_limiter: self.rate_limiter,
+            interceptors: self.interceptors,
+            response_body_timeout: self.response_body_timeout,
         }
     }
 }
@@ -0,0 +1,560 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Circuit breaking for failing endpoints
+//!
+//! A [`CircuitBreaker`] tracks the outcome of requests sent to each endpoint (scheme and
+//! authority of the request URI). Each endpoint has its own circuit:
+//! - **Closed**: requests are sent normally. After
+//!   [`failure_threshold`](CircuitBreakerConfig::with_failure_threshold) consecutive failures, the
+//!   circuit opens.
+//! - **Open**: requests fail immediately with
+//!   [`SdkError::CircuitBreakerOpen`](aws_smithy_http::result::SdkError::CircuitBreakerOpen),
+//!   without being sent. After [`open_duration`](CircuitBreakerConfig::with_open_duration) has
+//!   elapsed, the circuit becomes half-open.
+//! - **Half-open**: a single trial request is sent while other requests continue to fail
+//!   immediately. If the trial succeeds, the circuit closes; otherwise, it opens again.
+//!
+//! Connection errors and responses with a 5xx status code count as failures. Attempts that are
+//! cancelled before a response is received (for example, a hedged attempt that lost the race) aren't
+//! counted either way, and neither are outcomes of attempts that were sent before the circuit last
+//! changed state. Requests rejected by an open circuit are never retried, so a failing endpoint
+//! doesn't drain the retry quota.
+//!
+//! Endpoints are only tracked while their circuit isn't closed, has recent failures, or has requests
+//! in flight.
+//!
+//! A circuit breaker is installed with [`Builder::circuit_breaker`](crate::Builder::circuit_breaker).
+//! Clones of a `CircuitBreaker` share their state, so a clone can be kept to report the state of
+//! each circuit as a metric:
+//!
+//! ```no_run
+//! use aws_smithy_client::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig};
+//! use aws_smithy_client::erase::DynConnector;
+//! # fn wrap(connector: DynConnector) {
+//! let breaker = CircuitBreaker::new(CircuitBreakerConfig::new().with_failure_threshold(10));
+//! let client = aws_smithy_client::Builder::<_, ()>::new()
+//!     .connector(connector)
+//!     .circuit_breaker(breaker.clone());
+//! for (endpoint, state) in breaker.states() {
+//!     println!("{}: {:?}", endpoint, state);
+//! }
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use aws_smithy_async::rt::time::{default_time_source, TimeSource};
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::result::ConnectorError;
+use tower::{Layer, Service};
+
+/// Configuration for a [`CircuitBreaker`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CircuitBreakerConfig {
+    failure_threshold: u32,
+    open_duration: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Create a config with a failure threshold of 5 and an open duration of 30 seconds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of consecutive failures that opens a circuit
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+
+    /// Set how long a circuit stays open before a trial request is allowed
+    pub fn with_open_duration(mut self, open_duration: Duration) -> Self {
+        self.open_duration = open_duration;
+        self
+    }
+}
+
+/// State of the circuit for a single endpoint
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent normally
+    Closed,
+    /// Requests fail immediately
+    Open,
+    /// A single trial request is allowed to determine whether the endpoint has recovered
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+    trial_in_flight: bool,
+    /// Incremented whenever the state changes, so that outcomes of requests sent before the
+    /// change can be ignored
+    generation: u64,
+    /// Requests that have been sent but whose outcome hasn't been recorded yet
+    in_flight: usize,
+}
+
+impl Circuit {
+    fn new(now: Instant) -> Self {
+        Self {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: now,
+            trial_in_flight: false,
+            generation: 0,
+            in_flight: 0,
+        }
+    }
+
+    fn set_state(&mut self, state: CircuitState) {
+        self.state = state;
+        self.generation += 1;
+    }
+
+    /// A closed circuit without failures or requests in flight is equivalent to no circuit at all
+    fn is_idle(&self) -> bool {
+        self.state == CircuitState::Closed && self.consecutive_failures == 0 && self.in_flight == 0
+    }
+
+    fn current_state(&self, config: &CircuitBreakerConfig, now: Instant) -> CircuitState {
+        match self.state {
+            CircuitState::Open if now.duration_since(self.opened_at) >= config.open_duration => {
+                CircuitState::HalfOpen
+            }
+            state => state,
+        }
+    }
+
+    fn open(&mut self, now: Instant) {
+        self.set_state(CircuitState::Open);
+        self.opened_at = now;
+        self.trial_in_flight = false;
+    }
+
+    fn close(&mut self) {
+        if self.state != CircuitState::Closed {
+            self.set_state(CircuitState::Closed);
+        }
+        self.consecutive_failures = 0;
+        self.trial_in_flight = false;
+    }
+}
+
+/// Tracks the health of endpoints and rejects requests to endpoints that are failing
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    circuits: Arc<Mutex<HashMap<String, Circuit>>>,
+    time_source: Arc<dyn TimeSource>,
+}
+
+impl CircuitBreaker {
+    /// Create a circuit breaker where all circuits are initially closed
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            circuits: Default::default(),
+            time_source: default_time_source(),
+        }
+    }
+
+    /// Set the source of the current time used to decide when an open circuit becomes half-open
+    ///
+    /// This should follow the same clock as the client's sleep implementation. By default, the
+    /// time is read from Tokio when the `rt-tokio` feature is enabled.
+    pub fn with_time_source(mut self, time_source: impl TimeSource + 'static) -> Self {
+        self.time_source = Arc::new(time_source);
+        self
+    }
+
+    /// Returns the state of the circuit for `endpoint`, e.g. `https://example.com`
+    pub fn state(&self, endpoint: &str) -> CircuitState {
+        let now = self.time_source.now();
+        self.circuits
+            .lock()
+            .unwrap()
+            .get(endpoint)
+            .map(|circuit| circuit.current_state(&self.config, now))
+            .unwrap_or(CircuitState::Closed)
+    }
+
+    /// Returns the state of the circuit for every tracked endpoint
+    ///
+    /// Endpoints whose circuit is closed, without recent failures or requests in flight, are
+    /// omitted.
+    pub fn states(&self) -> HashMap<String, CircuitState> {
+        let now = self.time_source.now();
+        self.circuits
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(endpoint, circuit)| (endpoint.clone(), circuit.current_state(&self.config, now)))
+            .collect()
+    }
+
+    /// Acquire permission to send a request to `endpoint`
+    ///
+    /// Returns the generation of the circuit, which must be passed back to
+    /// [`record`](CircuitBreaker::record).
+    fn acquire(&self, endpoint: &str, now: Instant) -> Result<u64, CircuitBreakerOpenError> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits
+            .entry(endpoint.to_string())
+            .or_insert_with(|| Circuit::new(now));
+        match circuit.current_state(&self.config, now) {
+            CircuitState::Closed => {}
+            CircuitState::HalfOpen if !circuit.trial_in_flight => {
+                tracing::debug!(endpoint = %endpoint, "circuit is half-open; sending a trial request");
+                circuit.set_state(CircuitState::HalfOpen);
+                circuit.trial_in_flight = true;
+            }
+            _ => {
+                return Err(CircuitBreakerOpenError {
+                    endpoint: endpoint.to_string(),
+                })
+            }
+        }
+        circuit.in_flight += 1;
+        Ok(circuit.generation)
+    }
+
+    /// Record the outcome of a request sent to `endpoint` when the circuit was at `generation`
+    ///
+    /// An outcome of `None` means that the request was cancelled before it completed.
+    fn record(&self, endpoint: &str, generation: u64, success: Option<bool>, now: Instant) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = match circuits.get_mut(endpoint) {
+            Some(circuit) => circuit,
+            None => return,
+        };
+        circuit.in_flight = circuit.in_flight.saturating_sub(1);
+        if generation == circuit.generation {
+            match success {
+                Some(true) => {
+                    if circuit.state != CircuitState::Closed {
+                        tracing::debug!(endpoint = %endpoint, "closing circuit");
+                    }
+                    circuit.close();
+                }
+                Some(false) => {
+                    circuit.consecutive_failures += 1;
+                    let should_open = match circuit.state {
+                        CircuitState::HalfOpen => true,
+                        CircuitState::Closed => {
+                            circuit.consecutive_failures >= self.config.failure_threshold
+                        }
+                        CircuitState::Open => false,
+                    };
+                    if should_open {
+                        tracing::debug!(
+                            endpoint = %endpoint,
+                            failures = circuit.consecutive_failures,
+                            "opening circuit"
+                        );
+                        circuit.open(now);
+                    }
+                }
+                // a cancelled trial request lets another request try instead
+                None if circuit.state == CircuitState::HalfOpen => circuit.trial_in_flight = false,
+                None => {}
+            }
+        }
+        if circuit.is_idle() {
+            circuits.remove(endpoint);
+        }
+    }
+}
+
+impl<C> Layer<C> for CircuitBreaker {
+    type Service = CircuitBreakerConnector<C>;
+
+    fn layer(&self, inner: C) -> Self::Service {
+        CircuitBreakerConnector {
+            inner,
+            breaker: self.clone(),
+        }
+    }
+}
+
+/// Error returned for requests rejected by an open circuit
+#[derive(Debug)]
+pub struct CircuitBreakerOpenError {
+    endpoint: String,
+}
+
+impl CircuitBreakerOpenError {
+    /// The endpoint whose circuit is open
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+}
+
+impl fmt::Display for CircuitBreakerOpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the circuit for {} is open", self.endpoint)
+    }
+}
+
+impl Error for CircuitBreakerOpenError {}
+
+fn endpoint(request: &http::Request<SdkBody>) -> String {
+    let uri = request.uri();
+    match (uri.scheme_str(), uri.authority()) {
+        (Some(scheme), Some(authority)) => format!("{}://{}", scheme, authority),
+        (None, Some(authority)) => authority.to_string(),
+        _ => uri.to_string(),
+    }
+}
+
+/// A connector that sends requests through a [`CircuitBreaker`]
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerConnector<C> {
+    inner: C,
+    breaker: CircuitBreaker,
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+impl<C> Service<http::Request<SdkBody>> for CircuitBreakerConnector<C>
+where
+    C: Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+    C::Error: Into<ConnectorError>,
+    C::Future: Send + 'static,
+{
+    type Response = http::Response<SdkBody>;
+    type Error = ConnectorError;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
+        let endpoint = endpoint(&req);
+        let generation = match self
+            .breaker
+            .acquire(&endpoint, self.breaker.time_source.now())
+        {
+            Ok(generation) => generation,
+            Err(err) => {
+                return Box::pin(std::future::ready(Err(
+                    ConnectorError::circuit_breaker_open(err.into()),
+                )))
+            }
+        };
+        let outcome = PendingOutcome {
+            breaker: self.breaker.clone(),
+            endpoint: Some(endpoint),
+            generation,
+        };
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let result = future.await.map_err(Into::into);
+            outcome.record(matches!(&result, Ok(response) if !response.status().is_server_error()));
+            result
+        })
+    }
+}
+
+/// Records the outcome of a request, or that it was cancelled if it is dropped before completing
+struct PendingOutcome {
+    breaker: CircuitBreaker,
+    endpoint: Option<String>,
+    generation: u64,
+}
+
+impl PendingOutcome {
+    fn record(mut self, success: bool) {
+        if let Some(endpoint) = self.endpoint.take() {
+            let now = self.breaker.time_source.now();
+            self.breaker
+                .record(&endpoint, self.generation, Some(success), now);
+        }
+    }
+}
+
+impl Drop for PendingOutcome {
+    fn drop(&mut self) {
+        if let Some(endpoint) = self.endpoint.take() {
+            let now = self.breaker.time_source.now();
+            self.breaker.record(&endpoint, self.generation, None, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::result::ConnectorError;
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tower::{Layer, ServiceExt};
+
+    const ENDPOINT: &str = "https://example.com";
+
+    fn config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig::new()
+            .with_failure_threshold(2)
+            .with_open_duration(Duration::from_secs(10))
+    }
+
+    /// Send a request to `ENDPOINT` and record its outcome
+    fn send(breaker: &CircuitBreaker, success: bool, now: Instant) {
+        let generation = breaker.acquire(ENDPOINT, now).unwrap();
+        breaker.record(ENDPOINT, generation, Some(success), now);
+    }
+
+    #[test]
+    fn circuit_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(config());
+        let now = Instant::now();
+        for _ in 0..2 {
+            assert_eq!(breaker.state(ENDPOINT), CircuitState::Closed);
+            send(&breaker, false, now);
+        }
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Open);
+        let err = breaker.acquire(ENDPOINT, now).unwrap_err();
+        assert_eq!(err.endpoint(), ENDPOINT);
+        // other endpoints are unaffected
+        assert!(breaker.acquire("https://other.example.com", now).is_ok());
+    }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(config());
+        let now = Instant::now();
+        for success in [false, true, false, true] {
+            send(&breaker, success, now);
+        }
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Closed);
+    }
+
+    #[test]
+    fn half_open_circuit_allows_a_single_trial_request() {
+        let breaker = CircuitBreaker::new(config());
+        let now = Instant::now();
+        for _ in 0..2 {
+            send(&breaker, false, now);
+        }
+
+        let later = now + Duration::from_secs(10);
+        let trial = breaker.acquire(ENDPOINT, later).unwrap();
+        assert!(breaker.acquire(ENDPOINT, later).is_err());
+        // the trial failed, so the circuit opens again
+        breaker.record(ENDPOINT, trial, Some(false), later);
+        assert!(breaker.acquire(ENDPOINT, later).is_err());
+
+        let even_later = later + Duration::from_secs(10);
+        send(&breaker, true, even_later);
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Closed);
+        assert!(breaker.acquire(ENDPOINT, even_later).is_ok());
+    }
+
+    #[test]
+    fn cancelled_requests_are_not_counted() {
+        let breaker = CircuitBreaker::new(config());
+        let now = Instant::now();
+        for _ in 0..3 {
+            let generation = breaker.acquire(ENDPOINT, now).unwrap();
+            breaker.record(ENDPOINT, generation, None, now);
+        }
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Closed);
+
+        for _ in 0..2 {
+            send(&breaker, false, now);
+        }
+        // a cancelled trial request lets another trial request be sent
+        let later = now + Duration::from_secs(10);
+        let trial = breaker.acquire(ENDPOINT, later).unwrap();
+        breaker.record(ENDPOINT, trial, None, later);
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::HalfOpen);
+        assert!(breaker.acquire(ENDPOINT, later).is_ok());
+    }
+
+    #[test]
+    fn stale_outcomes_are_ignored() {
+        let breaker = CircuitBreaker::new(config());
+        let now = Instant::now();
+        let slow_request = breaker.acquire(ENDPOINT, now).unwrap();
+        for _ in 0..2 {
+            send(&breaker, false, now);
+        }
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Open);
+        // the request was sent before the circuit opened, so its success doesn't close it
+        breaker.record(ENDPOINT, slow_request, Some(true), now);
+        assert_eq!(breaker.state(ENDPOINT), CircuitState::Open);
+    }
+
+    #[test]
+    fn healthy_endpoints_are_pruned() {
+        let breaker = CircuitBreaker::new(config());
+        let now = Instant::now();
+        let generation = breaker.acquire(ENDPOINT, now).unwrap();
+        assert_eq!(breaker.states().len(), 1, "requests in flight are tracked");
+        breaker.record(ENDPOINT, generation, Some(false), now);
+        assert_eq!(breaker.states().len(), 1, "failures are tracked");
+        send(&breaker, true, now);
+        assert!(breaker.states().is_empty());
+    }
+
+    #[tokio::test]
+    async fn connector_fails_fast_when_circuit_is_open() {
+        let status = Arc::new(AtomicU16::new(503));
+        let connector = {
+            let status = status.clone();
+            tower::service_fn(move |_: http::Request<SdkBody>| {
+                let response = http::Response::builder()
+                    .status(status.load(Ordering::SeqCst))
+                    .body(SdkBody::empty())
+                    .unwrap();
+                async move { Ok::<_, ConnectorError>(response) }
+            })
+        };
+        let breaker = CircuitBreaker::new(config());
+        let connector = breaker.layer(connector);
+        let request = || {
+            http::Request::builder()
+                .uri("https://example.com/foo")
+                .body(SdkBody::empty())
+                .unwrap()
+        };
+
+        for _ in 0..2 {
+            let response = connector.clone().oneshot(request()).await.unwrap();
+            assert_eq!(response.status(), 503);
+        }
+        status.store(200, Ordering::SeqCst);
+        let err = connector.clone().oneshot(request()).await.unwrap_err();
+        assert!(err.is_circuit_breaker_open());
+        assert_eq!(
+            breaker.states().get(ENDPOINT).copied(),
+            Some(CircuitState::Open)
+        );
+    }
+}
@@ -15,11 +15,19 @@ use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
 use aws_smithy_types::base64;
+pub use cassette::{
+    Cassette, CassetteConnection, Interaction, DEFAULT_INTERACTION, RECORD_ENV_VAR,
+};
+pub use matcher::RequestMatcher;
 pub use record::RecordingConnection;
 pub use replay::ReplayingConnection;
+pub use sanitize::{Sanitizer, REDACTED};
 
+mod cassette;
+mod matcher;
 mod record;
 mod replay;
+mod sanitize;
 
 /// A complete traffic recording
 ///
@@ -43,6 +51,9 @@ impl NetworkTraffic {
 pub enum Version {
     /// Initial network traffic version
     V0,
+
+    /// Multiple named interactions per file, see [`Cassette`]
+    V1,
 }
 
 /// A network traffic recording may contain multiple different connections occurring simultaneously
@@ -235,9 +246,15 @@ mod tests {
     use aws_smithy_http::body::SdkBody;
     use aws_smithy_http::byte_stream::ByteStream;
 
-    use crate::dvr::{NetworkTraffic, RecordingConnection, ReplayingConnection};
+    use crate::dvr::{
+        Action, BodyData, ConnectionId, Direction, Event, NetworkTraffic, RecordingConnection,
+        ReplayingConnection, Request, RequestMatcher, Response, Sanitizer, Version, REDACTED,
+    };
+    use crate::test_connection::test_util;
     use bytes::Bytes;
     use http::Uri;
+    use std::collections::HashMap;
+    use tower::{BoxError, Service};
 
     #[tokio::test]
     async fn turtles_all_the_way_down() -> Result<(), Box<dyn Error>> {
@@ -250,7 +267,6 @@ mod tests {
         let req = http::Request::post("https://www.example.com")
             .body(SdkBody::from("hello world"))
             .unwrap();
-        use tower::Service;
         let mut resp = connection.call(req).await.expect("ok");
         let body = std::mem::replace(resp.body_mut(), SdkBody::taken());
         let data = ByteStream::new(body).collect().await.unwrap().into_bytes();
@@ -273,4 +289,179 @@ mod tests {
         );
         Ok(())
     }
+
+    fn exchange(id: usize, uri: &str, request_body: &str, response_body: &str) -> Vec<Event> {
+        let event = |action| Event {
+            connection_id: ConnectionId(id),
+            action,
+        };
+        vec![
+            event(Action::Request {
+                request: Request {
+                    uri: uri.to_string(),
+                    headers: HashMap::from([(
+                        "authorization".to_string(),
+                        vec![format!("signature-{}", id)],
+                    )]),
+                    method: "POST".to_string(),
+                },
+            }),
+            event(Action::Data {
+                data: BodyData::Utf8(request_body.to_string()),
+                direction: Direction::Request,
+            }),
+            event(Action::Eof {
+                ok: true,
+                direction: Direction::Request,
+            }),
+            event(Action::Response {
+                response: Ok(Response {
+                    status: 200,
+                    version: "HTTP/1.1".to_string(),
+                    headers: HashMap::new(),
+                }),
+            }),
+            event(Action::Data {
+                data: BodyData::Utf8(response_body.to_string()),
+                direction: Direction::Response,
+            }),
+            event(Action::Eof {
+                ok: true,
+                direction: Direction::Response,
+            }),
+        ]
+    }
+
+    async fn send<S>(
+        connection: &mut S,
+        uri: &str,
+        body: &'static str,
+    ) -> Result<String, Box<dyn Error>>
+    where
+        S: Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+        S::Error: Into<BoxError>,
+    {
+        let req = http::Request::post(uri)
+            .header("authorization", "a-new-signature")
+            .body(SdkBody::from(body))
+            .unwrap();
+        test_util::send(connection, req)
+            .await
+            .map_err(|err| err as Box<dyn Error>)
+    }
+
+    #[tokio::test]
+    async fn matching_replay_is_order_independent() -> Result<(), Box<dyn Error>> {
+        let mut events = exchange(0, "https://example.com/a?X-Amz-Date=1", "first", "one");
+        events.extend(exchange(1, "https://example.com/b", "second", "two"));
+        events.extend(exchange(
+            2,
+            "https://example.com/a?X-Amz-Date=1",
+            "third",
+            "three",
+        ));
+        let mut connection = ReplayingConnection::new(events).with_matcher(
+            RequestMatcher::new()
+                .with_all_headers_except(&["authorization"])
+                .ignore_query_params(&["X-Amz-Date"]),
+        );
+
+        let uri = "https://example.com/a?X-Amz-Date=2";
+        assert_eq!(send(&mut connection, uri, "third").await?, "three");
+        assert_eq!(
+            send(&mut connection, "https://example.com/b", "second").await?,
+            "two"
+        );
+        assert_eq!(send(&mut connection, uri, "first").await?, "one");
+
+        let requests = connection.take_requests().await;
+        let bodies: Vec<_> = requests.iter().map(|req| req.body().as_ref()).collect();
+        assert_eq!(bodies, vec![&b"first"[..], b"second", b"third"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn matching_replay_reports_the_closest_candidate() {
+        let mut events = exchange(0, "https://example.com/a", "first", "one");
+        events.extend(exchange(1, "https://example.com/b", "second", "two"));
+        let mut connection = ReplayingConnection::new(events).with_matcher(RequestMatcher::new());
+
+        let err = send(&mut connection, "https://example.com/b", "other")
+            .await
+            .expect_err("no recorded request has this body");
+        assert_eq!(
+            err.to_string(),
+            "other: no recorded request matched POST https://example.com/b. \
+            The closest recorded request (connection 1) differs:\n  \
+            body: expected `second`, found `other`"
+        );
+
+        assert_eq!(
+            send(&mut connection, "https://example.com/a", "first")
+                .await
+                .unwrap(),
+            "one"
+        );
+        let err = send(&mut connection, "https://example.com/a", "first")
+            .await
+            .expect_err("each recording is only replayed once");
+        assert!(err.to_string().contains("connection 1"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn sanitized_recordings_can_be_replayed() -> Result<(), Box<dyn Error>> {
+        let credentials = r#"{"AccessKeyId":"AKID","SecretAccessKey":"secret"}"#;
+        let inner = ReplayingConnection::new(exchange(0, "https://example.com/", "", credentials));
+        let mut connection = RecordingConnection::new(inner)
+            .with_sanitizer(Sanitizer::aws_credentials().redact_body_field("SecretAccessKey"));
+        let _ = send(&mut connection, "https://example.com/", "").await?;
+
+        let recording = serde_json::to_string(&connection.network_traffic())?;
+        assert!(!recording.contains("a-new-signature"), "{}", recording);
+        assert!(!recording.contains("AKID"), "{}", recording);
+        assert!(!recording.contains("secret"), "{}", recording);
+
+        let traffic: NetworkTraffic = serde_json::from_str(&recording)?;
+        assert_eq!(traffic.version, Version::V0);
+        let mut replay = ReplayingConnection::new(traffic.events);
+        assert_eq!(
+            send(&mut replay, "https://example.com/", "").await?,
+            format!(
+                r#"{{"AccessKeyId":"{0}","SecretAccessKey":"{0}"}}"#,
+                REDACTED
+            )
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_bodies_are_not_recorded_unsanitized() {
+        let inner = tower::service_fn(|_req: http::Request<SdkBody>| async {
+            let (mut sender, body) = hyper::Body::channel();
+            tokio::spawn(async move {
+                sender
+                    .send_data(Bytes::from_static(br#"{"AccessKeyId":"AKID","#))
+                    .await
+                    .unwrap();
+                sender.abort();
+            });
+            Ok::<_, std::convert::Infallible>(http::Response::new(body))
+        });
+        let mut connection =
+            RecordingConnection::new(inner).with_sanitizer(Sanitizer::aws_credentials());
+        send(&mut connection, "https://example.com/", "")
+            .await
+            .expect_err("response body was aborted");
+
+        let events = connection.events().clone();
+        assert!(events.iter().any(|event| matches!(
+            event.action,
+            Action::Eof {
+                ok: false,
+                direction: Direction::Response
+            }
+        )));
+        let recording = serde_json::to_string(&events).unwrap();
+        assert!(!recording.contains("AKID"), "{}", recording);
+    }
 }
@@ -0,0 +1,431 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::dvr::{
+    Event, NetworkTraffic, RecordingConnection, ReplayingConnection, RequestMatcher, Sanitizer,
+    Version,
+};
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::result::ConnectorError;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::Display;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tower::Service;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Set this environment variable to `true` or `1` to re-record cassettes that already exist
+pub const RECORD_ENV_VAR: &str = "SMITHY_DVR_RECORD";
+
+/// The name of the interaction that a [`Version::V0`] recording is loaded as
+pub const DEFAULT_INTERACTION: &str = "default";
+
+/// A file of recorded traffic, made of named interactions
+///
+/// Each interaction is the traffic recorded for one test or scenario. A [`Version::V0`]
+/// [`NetworkTraffic`] file is loaded as a cassette with a single interaction named
+/// [`DEFAULT_INTERACTION`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cassette {
+    version: Version,
+    docs: Option<String>,
+    interactions: Vec<Interaction>,
+}
+
+/// Traffic recorded under a name in a [`Cassette`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Interaction {
+    name: String,
+    events: Vec<Event>,
+}
+
+impl Interaction {
+    /// The name of the interaction
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Network events
+    pub fn events(&self) -> &Vec<Event> {
+        &self.events
+    }
+}
+
+impl Default for Cassette {
+    fn default() -> Self {
+        Self {
+            version: Version::V1,
+            docs: None,
+            interactions: Vec::new(),
+        }
+    }
+}
+
+impl From<NetworkTraffic> for Cassette {
+    fn from(traffic: NetworkTraffic) -> Self {
+        Self {
+            version: Version::V1,
+            docs: traffic.docs,
+            interactions: vec![Interaction {
+                name: DEFAULT_INTERACTION.to_string(),
+                events: traffic.events,
+            }],
+        }
+    }
+}
+
+impl Cassette {
+    /// Create an empty cassette
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a cassette, or a [`NetworkTraffic`] recording, from a JSON file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let data = std::fs::read_to_string(path)?;
+        let value: serde_json::Value = serde_json::from_str(&data)?;
+        match serde_json::from_value(value["version"].clone())? {
+            Version::V0 => Ok(serde_json::from_value::<NetworkTraffic>(value)?.into()),
+            Version::V1 => Ok(serde_json::from_value(value)?),
+        }
+    }
+
+    /// Write the cassette to a JSON file, creating the parent directories if needed
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The interactions in this cassette, in the order they were first recorded
+    pub fn interactions(&self) -> &[Interaction] {
+        &self.interactions
+    }
+
+    /// The interaction named `name`, if it was recorded
+    pub fn interaction(&self, name: &str) -> Option<&Interaction> {
+        self.interactions.iter().find(|i| i.name == name)
+    }
+
+    /// Add an interaction, replacing any interaction with the same name
+    pub fn insert(&mut self, name: impl Into<String>, events: Vec<Event>) {
+        let name = name.into();
+        match self.interactions.iter_mut().find(|i| i.name == name) {
+            Some(interaction) => interaction.events = events,
+            None => self.interactions.push(Interaction { name, events }),
+        }
+    }
+}
+
+/// A connection that replays an interaction from a cassette, or records it if it doesn't exist
+///
+/// If the cassette file contains the interaction, its traffic is replayed with a
+/// [`ReplayingConnection`] and the inner connection is never used. Otherwise, traffic is recorded
+/// through the inner connection with a [`RecordingConnection`], and the interaction is written to
+/// the cassette file by [`finish`](CassetteConnection::finish). Other interactions in the file are
+/// kept.
+///
+/// Set the [`RECORD_ENV_VAR`] environment variable to re-record interactions that already exist.
+///
+/// ```no_run
+/// use aws_smithy_client::dvr::{CassetteConnection, Sanitizer};
+/// # async fn example<S>(real_connection: S) -> Result<(), Box<dyn std::error::Error>> {
+/// let connection =
+///     CassetteConnection::new("test-data/cassettes/s3.json", "list_buckets", real_connection)?
+///         .with_sanitizer(Sanitizer::aws_credentials());
+/// // send requests through a client that uses `connection.clone()`
+/// connection.finish().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CassetteConnection<S> {
+    mode: Mode<S>,
+}
+
+#[derive(Clone, Debug)]
+enum Mode<S> {
+    Replay(ReplayingConnection),
+    Record {
+        connection: RecordingConnection<S>,
+        writer: Arc<CassetteWriter>,
+    },
+}
+
+/// Writes the recorded interaction to the cassette
+///
+/// If the interaction was never written when the writer is dropped, a warning is logged instead:
+/// `Drop` can't wait for the bodies that are still being recorded.
+#[derive(Debug)]
+struct CassetteWriter {
+    path: PathBuf,
+    name: String,
+    events: Arc<Mutex<Vec<Event>>>,
+    written: AtomicBool,
+}
+
+impl CassetteWriter {
+    /// Wait for `connection` to finish recording bodies, then write the interaction
+    async fn finish<S>(&self, connection: &RecordingConnection<S>) -> Result<(), Box<dyn Error>> {
+        connection.wait_for_bodies().await?;
+        let mut cassette = if self.path.exists() {
+            Cassette::load(&self.path)?
+        } else {
+            Cassette::new()
+        };
+        cassette.insert(&self.name, self.events.lock().unwrap().clone());
+        cassette.save(&self.path)?;
+        self.written.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Drop for CassetteWriter {
+    fn drop(&mut self) {
+        if !self.written.load(Ordering::Relaxed) {
+            tracing::warn!(
+                "interaction `{}` was recorded but never written to cassette {}; \
+                call `CassetteConnection::finish` when recording is done",
+                self.name,
+                self.path.display()
+            );
+        }
+    }
+}
+
+fn force_record() -> bool {
+    matches!(
+        std::env::var(RECORD_ENV_VAR).as_deref(),
+        Ok("1") | Ok("true")
+    )
+}
+
+impl<S> CassetteConnection<S> {
+    /// Replay the interaction `name` from the cassette at `path` if it exists, or record it through `inner`
+    pub fn new(
+        path: impl Into<PathBuf>,
+        name: impl Into<String>,
+        inner: S,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (path, name) = (path.into(), name.into());
+        if !force_record() && path.exists() {
+            if let Some(interaction) = Cassette::load(&path)?.interaction(&name) {
+                return Ok(Self {
+                    mode: Mode::Replay(ReplayingConnection::new(interaction.events.clone())),
+                });
+            }
+        }
+        Ok(Self::record(path, name, inner))
+    }
+
+    /// Record the interaction `name` through `inner`, replacing it in the cassette at `path` if it exists
+    pub fn record(path: impl Into<PathBuf>, name: impl Into<String>, inner: S) -> Self {
+        let connection = RecordingConnection::new(inner);
+        let writer = CassetteWriter {
+            path: path.into(),
+            name: name.into(),
+            events: connection.data.clone(),
+            written: AtomicBool::new(false),
+        };
+        Self {
+            mode: Mode::Record {
+                connection,
+                writer: Arc::new(writer),
+            },
+        }
+    }
+
+    /// Write the recorded interaction to the cassette
+    ///
+    /// This waits until the bodies of all the requests and responses sent so far have been
+    /// recorded, so response bodies must have been read or dropped. Does nothing when replaying.
+    pub async fn finish(&self) -> Result<(), Box<dyn Error>> {
+        match &self.mode {
+            Mode::Replay(_) => Ok(()),
+            Mode::Record { connection, writer } => writer.finish(connection).await,
+        }
+    }
+
+    /// Returns true if traffic is being recorded rather than replayed
+    pub fn is_recording(&self) -> bool {
+        matches!(self.mode, Mode::Record { .. })
+    }
+
+    /// Sanitize traffic with `sanitizer` when recording
+    pub fn with_sanitizer(mut self, sanitizer: Sanitizer) -> Self {
+        if let Mode::Record { connection, .. } = &mut self.mode {
+            connection.sanitizer = Some(Arc::new(sanitizer));
+        }
+        self
+    }
+
+    /// Replay the recorded connection whose request matches each request when replaying
+    ///
+    /// See [`ReplayingConnection::with_matcher`].
+    pub fn with_matcher(self, matcher: RequestMatcher) -> Self {
+        match self.mode {
+            Mode::Replay(connection) => Self {
+                mode: Mode::Replay(connection.with_matcher(matcher)),
+            },
+            mode => Self { mode },
+        }
+    }
+}
+
+impl<S, ResponseBody> Service<http::Request<SdkBody>> for CassetteConnection<S>
+where
+    S: Service<http::Request<SdkBody>, Response = http::Response<ResponseBody>>
+        + Send
+        + Clone
+        + 'static,
+    S::Error: Into<BoxError> + Display + Send + Sync + 'static,
+    S::Future: Send + 'static,
+    ResponseBody: Into<SdkBody>,
+{
+    type Response = http::Response<SdkBody>;
+    type Error = ConnectorError;
+    #[allow(clippy::type_complexity)]
+    type Future =
+        Pin<Box<dyn Future<Output = Result<http::Response<SdkBody>, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match &mut self.mode {
+            Mode::Replay(connection) => connection.poll_ready(cx),
+            Mode::Record { connection, .. } => {
+                connection.poll_ready(cx).map_err(into_connector_error)
+            }
+        }
+    }
+
+    fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
+        match &mut self.mode {
+            Mode::Replay(connection) => connection.call(req),
+            Mode::Record { connection, .. } => {
+                let fut = connection.call(req);
+                Box::pin(async move { fut.await.map_err(into_connector_error) })
+            }
+        }
+    }
+}
+
+fn into_connector_error(err: impl Into<BoxError>) -> ConnectorError {
+    match err.into().downcast::<ConnectorError>() {
+        Ok(err) => *err,
+        Err(err) => ConnectorError::other(err, None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cassette, CassetteConnection, DEFAULT_INTERACTION};
+    use crate::dvr::{Action, Direction, Sanitizer, Version, REDACTED};
+    use crate::test_connection::test_util::{self, call, connection, request};
+    use aws_smithy_http::body::SdkBody;
+    use http::HeaderValue;
+    use std::path::PathBuf;
+    use tower::{BoxError, Service};
+
+    fn cassette_path(test: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("dvr-cassette-{}-{}.json", test, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    async fn send<S>(connection: &mut S) -> String
+    where
+        S: Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+        S::Error: Into<BoxError>,
+    {
+        let mut req = request();
+        req.headers_mut()
+            .insert("authorization", HeaderValue::from_static("signature"));
+        test_util::send(connection, req).await.expect("response")
+    }
+
+    #[tokio::test]
+    async fn records_missing_interactions_and_replays_existing_ones() {
+        let path = cassette_path("record-or-replay");
+
+        let mut recording = CassetteConnection::new(&path, "first", connection(["one"]))
+            .unwrap()
+            .with_sanitizer(Sanitizer::aws_credentials());
+        assert!(recording.is_recording());
+        assert_eq!(send(&mut recording).await, "one");
+        recording.finish().await.unwrap();
+
+        let mut recording = CassetteConnection::new(&path, "second", connection(["two"])).unwrap();
+        assert!(recording.is_recording());
+        assert_eq!(send(&mut recording).await, "two");
+        recording.finish().await.unwrap();
+
+        let cassette = Cassette::load(&path).unwrap();
+        assert_eq!(cassette.version, Version::V1);
+        let names: Vec<_> = cassette.interactions().iter().map(|i| i.name()).collect();
+        assert_eq!(names, vec!["first", "second"]);
+        let recorded = serde_json::to_string(cassette.interaction("first").unwrap()).unwrap();
+        assert!(recorded.contains(REDACTED), "{}", recorded);
+
+        // the inner connection has no more responses, so these must be replayed
+        let mut replaying = CassetteConnection::new(&path, "first", connection([])).unwrap();
+        assert!(!replaying.is_recording());
+        assert_eq!(send(&mut replaying).await, "one");
+        let mut replaying = CassetteConnection::new(&path, "second", connection([])).unwrap();
+        assert_eq!(send(&mut replaying).await, "two");
+
+        // re-recording replaces the interaction
+        let mut recording = CassetteConnection::record(&path, "first", connection(["uno"]));
+        assert_eq!(send(&mut recording).await, "uno");
+        recording.finish().await.unwrap();
+        let mut replaying = CassetteConnection::new(&path, "first", connection([])).unwrap();
+        assert_eq!(send(&mut replaying).await, "uno");
+        assert_eq!(Cassette::load(&path).unwrap().interactions().len(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn finish_waits_for_bodies_and_drop_doesnt_write() {
+        let path = cassette_path("finish");
+
+        let mut recording = CassetteConnection::record(&path, "dropped", connection(["one"]));
+        assert_eq!(send(&mut recording).await, "one");
+        drop(recording);
+        assert!(!path.exists());
+
+        // the response body is never read, so it's only recorded once `finish` waits for it
+        let mut recording = CassetteConnection::record(&path, "unread", connection(["two"]));
+        drop(call(&mut recording, request()).await.unwrap());
+        recording.finish().await.unwrap();
+        let cassette = Cassette::load(&path).unwrap();
+        let events = cassette.interaction("unread").unwrap().events();
+        assert!(matches!(
+            events.last().unwrap().action,
+            Action::Eof {
+                ok: true,
+                direction: Direction::Response
+            }
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn v0_recordings_are_loaded_as_a_default_interaction() {
+        let cassette = Cassette::load("test-data/example.com.json").unwrap();
+        assert_eq!(cassette.interactions().len(), 1);
+        let interaction = cassette.interaction(DEFAULT_INTERACTION).unwrap();
+        assert!(!interaction.events().is_empty());
+    }
+}
@@ -0,0 +1,327 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use bytes::Bytes;
+use http::Request;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+type BoxError = Box<dyn Error>;
+type Rule = Arc<dyn Fn(&Request<Bytes>, &Request<Bytes>) -> Result<(), BoxError> + Send + Sync>;
+
+/// Decides which recorded request an actual request corresponds to during replay
+///
+/// A `RequestMatcher` is made of rules that each compare one aspect of the recorded (expected)
+/// request with the actual request. A recorded request matches when every rule passes. By default,
+/// requests match when their method, URI and body are equal, where JSON bodies are equal when they
+/// hold the same values regardless of key order and whitespace; headers are only compared when
+/// selected with [`with_headers`](RequestMatcher::with_headers) or
+/// [`with_all_headers_except`](RequestMatcher::with_all_headers_except).
+///
+/// ```no_run
+/// use aws_smithy_client::dvr::RequestMatcher;
+/// let matcher = RequestMatcher::new()
+///     .with_headers(&["content-type"])
+///     .ignore_query_params(&["X-Amz-Date", "X-Amz-Signature"]);
+/// ```
+#[derive(Clone)]
+pub struct RequestMatcher {
+    headers: HeaderSelection,
+    ignored_query_params: HashSet<String>,
+    body: Rule,
+    rules: Vec<(&'static str, Rule)>,
+}
+
+#[derive(Clone, Debug)]
+enum HeaderSelection {
+    Only(Vec<String>),
+    AllExcept(HashSet<String>),
+}
+
+impl fmt::Debug for RequestMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestMatcher")
+            .field("headers", &self.headers)
+            .field("ignored_query_params", &self.ignored_query_params)
+            .field(
+                "rules",
+                &self.rules.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Default for RequestMatcher {
+    fn default() -> Self {
+        Self {
+            headers: HeaderSelection::Only(Vec::new()),
+            ignored_query_params: HashSet::new(),
+            body: Arc::new(|expected, actual| {
+                if expected.body() == actual.body() || json_eq(expected.body(), actual.body()) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "expected `{}`, found `{}`",
+                        String::from_utf8_lossy(expected.body()),
+                        String::from_utf8_lossy(actual.body())
+                    )
+                    .into())
+                }
+            }),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl RequestMatcher {
+    /// Create a matcher that compares the method, URI and body of requests
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare the values of the headers named `headers`
+    pub fn with_headers(mut self, headers: &[&str]) -> Self {
+        self.headers = HeaderSelection::Only(headers.iter().map(|h| h.to_lowercase()).collect());
+        self
+    }
+
+    /// Compare the values of all the headers of the recorded request, except for `ignored`
+    ///
+    /// Use this to skip headers that change from run to run, such as `authorization` and
+    /// `x-amz-date`.
+    pub fn with_all_headers_except(mut self, ignored: &[&str]) -> Self {
+        self.headers =
+            HeaderSelection::AllExcept(ignored.iter().map(|h| h.to_lowercase()).collect());
+        self
+    }
+
+    /// Ignore the query parameters named `params` when comparing URIs
+    pub fn ignore_query_params(mut self, params: &[&str]) -> Self {
+        self.ignored_query_params
+            .extend(params.iter().map(|p| p.to_string()));
+        self
+    }
+
+    /// Replace the body comparison
+    ///
+    /// `body_comparer` is called with the expected and actual body, like the body comparer passed
+    /// to [`ReplayingConnection::validate`](super::ReplayingConnection::validate).
+    pub fn with_body_matcher(
+        mut self,
+        body_comparer: impl Fn(&[u8], &[u8]) -> Result<(), BoxError> + Send + Sync + 'static,
+    ) -> Self {
+        self.body = Arc::new(move |expected, actual| body_comparer(expected.body(), actual.body()));
+        self
+    }
+
+    /// Add a rule that must pass for requests to match
+    ///
+    /// `rule` is called with the expected and actual request. `name` identifies the rule when
+    /// reporting mismatches.
+    pub fn with_rule(
+        mut self,
+        name: &'static str,
+        rule: impl Fn(&Request<Bytes>, &Request<Bytes>) -> Result<(), BoxError> + Send + Sync + 'static,
+    ) -> Self {
+        self.rules.push((name, Arc::new(rule)));
+        self
+    }
+
+    /// Compare an expected request with an actual request, returning a description of each mismatch
+    pub(super) fn mismatches(
+        &self,
+        expected: &Request<Bytes>,
+        actual: &Request<Bytes>,
+    ) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if expected.method() != actual.method() {
+            mismatches.push(format!(
+                "method: expected `{}`, found `{}`",
+                expected.method(),
+                actual.method()
+            ));
+        }
+        if !self.uris_match(expected, actual) {
+            mismatches.push(format!(
+                "uri: expected `{}`, found `{}`",
+                expected.uri(),
+                actual.uri()
+            ));
+        }
+        for name in self.compared_headers(expected) {
+            let expected_values = header_values(expected, &name);
+            let actual_values = header_values(actual, &name);
+            if expected_values != actual_values {
+                mismatches.push(format!(
+                    "header `{}`: expected {:?}, found {:?}",
+                    name, expected_values, actual_values
+                ));
+            }
+        }
+        if let Err(err) = (self.body)(expected, actual) {
+            mismatches.push(format!("body: {}", err));
+        }
+        for (name, rule) in &self.rules {
+            if let Err(err) = rule(expected, actual) {
+                mismatches.push(format!("{}: {}", name, err));
+            }
+        }
+        mismatches
+    }
+
+    fn uris_match(&self, expected: &Request<Bytes>, actual: &Request<Bytes>) -> bool {
+        let (expected, actual) = (expected.uri(), actual.uri());
+        expected.scheme() == actual.scheme()
+            && expected.authority() == actual.authority()
+            && expected.path() == actual.path()
+            && self.query_params(expected.query()) == self.query_params(actual.query())
+    }
+
+    fn query_params<'a>(&self, query: Option<&'a str>) -> Vec<&'a str> {
+        let mut params: Vec<_> = query
+            .unwrap_or_default()
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .filter(|param| {
+                let name = param.split('=').next().unwrap_or_default();
+                !self.ignored_query_params.contains(name)
+            })
+            .collect();
+        params.sort_unstable();
+        params
+    }
+
+    fn compared_headers(&self, expected: &Request<Bytes>) -> Vec<String> {
+        match &self.headers {
+            HeaderSelection::Only(headers) => headers.clone(),
+            HeaderSelection::AllExcept(ignored) => {
+                let mut headers: Vec<_> = expected
+                    .headers()
+                    .keys()
+                    .map(|name| name.as_str().to_string())
+                    .filter(|name| !ignored.contains(name))
+                    .collect();
+                headers.dedup();
+                headers
+            }
+        }
+    }
+}
+
+/// Returns true if both bodies are JSON documents with the same values
+fn json_eq(expected: &[u8], actual: &[u8]) -> bool {
+    match (
+        serde_json::from_slice::<serde_json::Value>(expected),
+        serde_json::from_slice::<serde_json::Value>(actual),
+    ) {
+        (Ok(expected), Ok(actual)) => expected == actual,
+        _ => false,
+    }
+}
+
+fn header_values(request: &Request<Bytes>, name: &str) -> Vec<String> {
+    request
+        .headers()
+        .get_all(name)
+        .iter()
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::RequestMatcher;
+    use bytes::Bytes;
+    use http::Request;
+
+    fn request(uri: &str, auth: &str, body: &'static str) -> Request<Bytes> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .header("authorization", auth)
+            .body(Bytes::from_static(body.as_bytes()))
+            .unwrap()
+    }
+
+    #[test]
+    fn default_matcher_compares_method_uri_and_body() {
+        let matcher = RequestMatcher::new();
+        let expected = request("https://example.com/?a=1&b=2", "sig-1", "{}");
+        assert!(matcher
+            .mismatches(
+                &expected,
+                &request("https://example.com/?b=2&a=1", "sig-2", "{}")
+            )
+            .is_empty());
+        assert_eq!(
+            matcher.mismatches(
+                &expected,
+                &request("https://example.com/?a=2&b=2", "sig-1", "[]")
+            ),
+            vec![
+                "uri: expected `https://example.com/?a=1&b=2`, found `https://example.com/?a=2&b=2`",
+                "body: expected `{}`, found `[]`"
+            ]
+        );
+    }
+
+    #[test]
+    fn json_bodies_are_compared_regardless_of_key_order() {
+        let matcher = RequestMatcher::new();
+        let expected = request(
+            "https://example.com/",
+            "sig-1",
+            r#"{"b":1,"a":[true,null]}"#,
+        );
+        assert!(matcher
+            .mismatches(
+                &expected,
+                &request(
+                    "https://example.com/",
+                    "sig-1",
+                    r#"{ "a": [true, null], "b": 1 }"#
+                )
+            )
+            .is_empty());
+        assert_eq!(
+            matcher
+                .mismatches(
+                    &expected,
+                    &request(
+                        "https://example.com/",
+                        "sig-1",
+                        r#"{"a":[true,null],"b":2}"#
+                    )
+                )
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn ignore_rules_are_applied() {
+        let matcher = RequestMatcher::new()
+            .with_all_headers_except(&["Authorization"])
+            .ignore_query_params(&["X-Amz-Date"])
+            .with_body_matcher(|_, _| Ok(()));
+        let expected = request("https://example.com/?X-Amz-Date=1", "sig-1", "{}");
+        assert!(matcher
+            .mismatches(
+                &expected,
+                &request("https://example.com/?X-Amz-Date=2", "sig-2", "[]")
+            )
+            .is_empty());
+
+        let mut actual = request("https://example.com/", "sig-2", "{}");
+        actual
+            .headers_mut()
+            .insert("content-type", "text/plain".parse().unwrap());
+        assert_eq!(
+            matcher.mismatches(&expected, &actual),
+            vec![r#"header `content-type`: expected ["application/json"], found ["text/plain"]"#]
+        );
+    }
+}
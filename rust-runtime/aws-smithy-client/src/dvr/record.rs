@@ -10,12 +10,14 @@ use std::sync::{Arc, Mutex, MutexGuard};
 use std::task::{Context, Poll};
 
 use http_body::Body;
-use tokio::task::JoinHandle;
+use tokio::task::{JoinError, JoinHandle};
 use tower::Service;
 
 use aws_smithy_http::body::SdkBody;
 
-use crate::dvr::{self, Action, BodyData, ConnectionId, Direction, Error, NetworkTraffic, Version};
+use crate::dvr::{
+    self, Action, BodyData, ConnectionId, Direction, Error, NetworkTraffic, Sanitizer, Version,
+};
 
 use super::Event;
 use std::fmt::Display;
@@ -23,11 +25,15 @@ use std::fmt::Display;
 /// Recording Connection Wrapper
 ///
 /// RecordingConnection wraps an inner connection and records all traffic, enabling traffic replay.
+/// Use [`with_sanitizer`](RecordingConnection::with_sanitizer) to remove secrets from the traffic
+/// as it's recorded.
 #[derive(Clone, Debug)]
 pub struct RecordingConnection<S> {
     pub(crate) data: Arc<Mutex<Vec<Event>>>,
     pub(crate) num_events: Arc<AtomicUsize>,
     pub(crate) inner: S,
+    pub(crate) sanitizer: Option<Arc<Sanitizer>>,
+    pub(crate) body_tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
 }
 
 impl RecordingConnection<crate::conns::Https> {
@@ -38,6 +44,8 @@ impl RecordingConnection<crate::conns::Https> {
             data: Default::default(),
             inner: crate::conns::https(),
             num_events: Arc::new(AtomicUsize::new(0)),
+            sanitizer: None,
+            body_tasks: Default::default(),
         }
     }
 }
@@ -49,9 +57,22 @@ impl<S> RecordingConnection<S> {
             data: Default::default(),
             inner: connection,
             num_events: Arc::new(AtomicUsize::new(0)),
+            sanitizer: None,
+            body_tasks: Default::default(),
         }
     }
 
+    /// Sanitize traffic with `sanitizer` as it's recorded
+    ///
+    /// Headers and URIs are sanitized as soon as they are recorded. Bodies are sanitized once
+    /// they have been read completely, so their data only appears in
+    /// [`events`](RecordingConnection::events) at that point. The data of bodies that fail, or
+    /// that are dropped before they are read completely, is not recorded.
+    pub fn with_sanitizer(mut self, sanitizer: Sanitizer) -> Self {
+        self.sanitizer = Some(Arc::new(sanitizer));
+        self
+    }
+
     /// Return the traffic recorded by this connection
     pub fn events(&self) -> MutexGuard<'_, Vec<Event>> {
         self.data.lock().unwrap()
@@ -66,33 +87,66 @@ impl<S> RecordingConnection<S> {
         }
     }
 
+    /// Wait until the bodies of all the requests and responses sent so far have been recorded
+    pub(crate) async fn wait_for_bodies(&self) -> Result<(), JoinError> {
+        loop {
+            let tasks = std::mem::take(&mut *self.body_tasks.lock().unwrap());
+            if tasks.is_empty() {
+                return Ok(());
+            }
+            for task in tasks {
+                task.await?;
+            }
+        }
+    }
+
     fn next_id(&self) -> ConnectionId {
         ConnectionId(self.num_events.fetch_add(1, Ordering::Relaxed))
     }
 }
 
+fn record_event(event_bus: &Mutex<Vec<Event>>, sanitizer: Option<&Sanitizer>, mut event: Event) {
+    if let Some(sanitizer) = sanitizer {
+        sanitizer.sanitize_event(&mut event);
+    }
+    event_bus.lock().unwrap().push(event);
+}
+
+/// Record the data of `body` as it's read, followed by an EOF event
+///
+/// Without a sanitizer, data events are recorded as soon as they are read. With a sanitizer,
+/// data events are held back until the body has been read completely, and are sanitized before
+/// they're recorded. The data of a body that fails or isn't read to the end is never recorded,
+/// since it can't be sanitized.
 fn record_body(
     body: &mut SdkBody,
     event_id: ConnectionId,
     direction: Direction,
     event_bus: Arc<Mutex<Vec<Event>>>,
+    sanitizer: Option<Arc<Sanitizer>>,
 ) -> JoinHandle<()> {
     let (sender, output_body) = hyper::Body::channel();
     let real_body = std::mem::replace(body, SdkBody::from(output_body));
     tokio::spawn(async move {
         let mut real_body = real_body;
         let mut sender = sender;
+        let mut pending_data = Vec::new();
         loop {
             let data = real_body.data().await;
             match data {
                 Some(Ok(data)) => {
-                    event_bus.lock().unwrap().push(Event {
+                    let event = Event {
                         connection_id: event_id,
                         action: Action::Data {
                             data: BodyData::from(data.clone()),
                             direction,
                         },
-                    });
+                    };
+                    if sanitizer.is_some() {
+                        pending_data.push(event);
+                    } else {
+                        event_bus.lock().unwrap().push(event);
+                    }
                     // This happens if the real connection is closed during recording.
                     // Need to think more carefully if this is the correct thing to log in this
                     // case.
@@ -107,7 +161,12 @@ fn record_body(
                     };
                 }
                 None => {
-                    event_bus.lock().unwrap().push(Event {
+                    let mut events = event_bus.lock().unwrap();
+                    if let Some(sanitizer) = &sanitizer {
+                        events.append(&mut pending_data);
+                        sanitizer.sanitize_body(&mut events, event_id, direction);
+                    }
+                    events.push(Event {
                         connection_id: event_id,
                         action: Action::Eof {
                             ok: true,
@@ -162,23 +221,31 @@ where
         // the channel should be closed.
 
         // Phase 1: the initial http request
-        self.data.lock().unwrap().push(Event {
-            connection_id: event_id,
-            action: Action::Request {
-                request: dvr::Request::from(&req),
+        record_event(
+            &self.data,
+            self.sanitizer.as_deref(),
+            Event {
+                connection_id: event_id,
+                action: Action::Request {
+                    request: dvr::Request::from(&req),
+                },
             },
-        });
+        );
 
         // Phase 2: Swap out the real request body for one that will log all traffic that passes
         // through it
         // This will also handle phase three when the request body runs out of data.
-        record_body(
+        let task = record_body(
             req.body_mut(),
             event_id,
             Direction::Request,
             self.data.clone(),
+            self.sanitizer.clone(),
         );
+        self.body_tasks.lock().unwrap().push(task);
         let events = self.data.clone();
+        let sanitizer = self.sanitizer.clone();
+        let body_tasks = self.body_tasks.clone();
         // create a channel we'll use to stream the data while reading it
         let resp_fut = self.inner.call(req);
         let fut = async move {
@@ -189,15 +256,26 @@ where
                     let mut resp = resp.map(|body| body.into());
 
                     // push the initial response event
-                    events.lock().unwrap().push(Event {
-                        connection_id: event_id,
-                        action: Action::Response {
-                            response: Ok(dvr::Response::from(&resp)),
+                    record_event(
+                        &events,
+                        sanitizer.as_deref(),
+                        Event {
+                            connection_id: event_id,
+                            action: Action::Response {
+                                response: Ok(dvr::Response::from(&resp)),
+                            },
                         },
-                    });
+                    );
 
                     // instrument the body and record traffic
-                    record_body(resp.body_mut(), event_id, Direction::Response, events);
+                    let task = record_body(
+                        resp.body_mut(),
+                        event_id,
+                        Direction::Response,
+                        events,
+                        sanitizer,
+                    );
+                    body_tasks.lock().unwrap().push(task);
                     Ok(resp)
                 }
                 Err(e) => {
@@ -3,7 +3,7 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
-use crate::dvr::{Action, ConnectionId, Direction, Event};
+use crate::dvr::{Action, ConnectionId, Direction, Event, RequestMatcher};
 use aws_smithy_http::body::SdkBody;
 use aws_smithy_http::result::ConnectorError;
 use bytes::{Bytes, BytesMut};
@@ -42,12 +42,17 @@ impl<T> Waitable<T> {
 }
 
 /// Replay traffic recorded by a [`RecordingConnection`](super::RecordingConnection)
+///
+/// By default, recorded connections are replayed in the order they were recorded, regardless of
+/// the requests that are sent. Use [`with_matcher`](ReplayingConnection::with_matcher) to replay
+/// the recorded connection whose request matches each request instead.
 #[derive(Clone, Debug)]
 pub struct ReplayingConnection {
     live_events: Arc<Mutex<HashMap<ConnectionId, VecDeque<Event>>>>,
     verifiable_events: Arc<HashMap<ConnectionId, Request<Bytes>>>,
     num_events: Arc<AtomicUsize>,
     recorded_requests: Arc<Mutex<HashMap<ConnectionId, Waitable<http::Request<Bytes>>>>>,
+    matcher: Option<Arc<RequestMatcher>>,
 }
 
 impl ReplayingConnection {
@@ -99,11 +104,13 @@ impl ReplayingConnection {
     /// Return all the recorded requests for further analysis
     pub async fn take_requests(self) -> Vec<http::Request<Bytes>> {
         let mut recorded_requests = self.recorded_requests.lock().unwrap();
-        let mut out = Vec::with_capacity(recorded_requests.len());
-        for conn_id in 0..recorded_requests.len() {
+        let mut conn_ids: Vec<_> = recorded_requests.keys().copied().collect();
+        conn_ids.sort_unstable_by_key(|conn_id| conn_id.0);
+        let mut out = Vec::with_capacity(conn_ids.len());
+        for conn_id in conn_ids {
             out.push(
                 recorded_requests
-                    .remove(&ConnectionId(conn_id))
+                    .remove(&conn_id)
                     .expect("should exist")
                     .take()
                     .await,
@@ -112,6 +119,17 @@ impl ReplayingConnection {
         out
     }
 
+    /// Replay the recorded connection whose request matches each request, instead of replaying
+    /// connections in the order they were recorded
+    ///
+    /// Each recorded connection is replayed at most once. When several unused recorded requests
+    /// match, the one recorded first is replayed. When none match, the request fails with an
+    /// error describing how the closest recorded request differs from it.
+    pub fn with_matcher(mut self, matcher: RequestMatcher) -> Self {
+        self.matcher = Some(Arc::new(matcher));
+        self
+    }
+
     /// Build a replay connection from a sequence of events
     pub fn new(events: Vec<Event>) -> Self {
         let mut event_map: HashMap<_, VecDeque<_>> = HashMap::new();
@@ -149,7 +167,53 @@ impl ReplayingConnection {
             num_events: Arc::new(AtomicUsize::new(0)),
             recorded_requests: Default::default(),
             verifiable_events,
+            matcher: None,
+        }
+    }
+
+    /// Remove the events of the first unused recorded connection whose request matches `request`
+    fn take_matching_events(
+        &self,
+        matcher: &RequestMatcher,
+        request: &Request<Bytes>,
+    ) -> Result<(ConnectionId, VecDeque<Event>), ConnectorError> {
+        let mut live_events = self.live_events.lock().unwrap();
+        let mut candidates: Vec<_> = live_events.keys().copied().collect();
+        candidates.sort_unstable_by_key(|conn_id| conn_id.0);
+        let mut closest: Option<(ConnectionId, Vec<String>)> = None;
+        for conn_id in candidates {
+            let expected = self
+                .verifiable_events
+                .get(&conn_id)
+                .expect("every connection has a request");
+            let mismatches = matcher.mismatches(expected, request);
+            if mismatches.is_empty() {
+                let events = live_events.remove(&conn_id).expect("candidate exists");
+                return Ok((conn_id, events));
+            }
+            if closest
+                .as_ref()
+                .map(|(_, closest)| mismatches.len() < closest.len())
+                .unwrap_or(true)
+            {
+                closest = Some((conn_id, mismatches));
+            }
         }
+        let message = match closest {
+            Some((conn_id, mismatches)) => format!(
+                "no recorded request matched {} {}. The closest recorded request (connection {}) differs:\n  {}",
+                request.method(),
+                request.uri(),
+                conn_id.0,
+                mismatches.join("\n  ")
+            ),
+            None => format!(
+                "no recorded requests left to match {} {}",
+                request.method(),
+                request.uri()
+            ),
+        };
+        Err(ConnectorError::other(message.into(), None))
     }
 }
 
@@ -216,9 +280,24 @@ impl tower::Service<http::Request<SdkBody>> for ReplayingConnection {
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, mut req: Request<SdkBody>) -> Self::Future {
+    fn call(&mut self, req: Request<SdkBody>) -> Self::Future {
+        if let Some(matcher) = self.matcher.clone() {
+            let this = self.clone();
+            return Box::pin(async move {
+                let request = read_body(req).await;
+                let (event_id, events) = this.take_matching_events(&matcher, &request)?;
+                let mut recorded_request = Waitable::Value(request);
+                let resp = replay_response(events, &mut recorded_request).await;
+                this.recorded_requests
+                    .lock()
+                    .unwrap()
+                    .insert(event_id, recorded_request);
+                resp
+            });
+        }
+
         let event_id = self.next_id();
-        let mut events = match self.live_events.lock().unwrap().remove(&event_id) {
+        let events = match self.live_events.lock().unwrap().remove(&event_id) {
             Some(traffic) => traffic,
             None => {
                 return Box::pin(std::future::ready(Err(ConnectorError::other(
@@ -228,75 +307,84 @@ impl tower::Service<http::Request<SdkBody>> for ReplayingConnection {
             }
         };
 
-        let _initial_request = events.pop_front().unwrap();
-        let (sender, response_body) = hyper::Body::channel();
-        let body = SdkBody::from(response_body);
         let recording = self.recorded_requests.clone();
-        let recorded_request = tokio::spawn(async move {
-            let mut data_read = vec![];
-            while let Some(data) = req.body_mut().data().await {
-                data_read
-                    .extend_from_slice(data.expect("in memory request should not fail").as_ref())
-            }
-            req.map(|_| Bytes::from(data_read))
-        });
+        let recorded_request = tokio::spawn(read_body(req));
         let mut recorded_request = Waitable::Loading(recorded_request);
         let fut = async move {
-            let resp = loop {
-                let event = events
-                    .pop_front()
-                    .expect("no events, needed a response event");
-                match event.action {
-                    // to ensure deterministic behavior if the request EOF happens first in the log,
-                    // wait for the request body to be done before returning a response.
-                    Action::Eof {
-                        direction: Direction::Request,
-                        ..
-                    } => {
-                        recorded_request.wait().await;
-                    }
-                    Action::Request { .. } => panic!("invalid"),
-                    Action::Response {
-                        response: Err(error),
-                    } => break Err(ConnectorError::other(error.0.into(), None)),
-                    Action::Response {
-                        response: Ok(response),
-                    } => {
-                        let mut builder = http::Response::builder()
-                            .status(response.status)
-                            .version(convert_version(&response.version));
-                        for (name, values) in response.headers {
-                            for value in values {
-                                builder = builder.header(&name, &value);
-                            }
-                        }
-                        tokio::spawn(async move {
-                            replay_body(events, sender).await;
-                            // insert the finalized body into
-                        });
-                        break Ok(builder.body(body).expect("valid builder"));
-                    }
-
-                    Action::Data {
-                        direction: Direction::Request,
-                        data: _data,
-                    } => {
-                        tracing::info!("get request data");
-                    }
-                    Action::Eof {
-                        direction: Direction::Response,
-                        ..
-                    } => panic!("got eof before response"),
-
-                    Action::Data {
-                        data: _,
-                        direction: Direction::Response,
-                    } => panic!("got response data before response"),
-                }
-            };
+            let resp = replay_response(events, &mut recorded_request).await;
             recording.lock().unwrap().insert(event_id, recorded_request);
             resp
         };
         Box::pin(fut)
     }
 }
+
+async fn read_body(mut req: Request<SdkBody>) -> Request<Bytes> {
+    let mut data_read = vec![];
+    while let Some(data) = req.body_mut().data().await {
+        data_read.extend_from_slice(data.expect("in memory request should not fail").as_ref())
+    }
+    req.map(|_| Bytes::from(data_read))
+}
+
+/// Replay the response recorded in `events`, the events of a single connection
+async fn replay_response(
+    mut events: VecDeque<Event>,
+    recorded_request: &mut Waitable<Request<Bytes>>,
+) -> Result<http::Response<SdkBody>, ConnectorError> {
+    let _initial_request = events.pop_front().unwrap();
+    let (sender, response_body) = hyper::Body::channel();
+    let body = SdkBody::from(response_body);
+    loop {
+        let event = events
+            .pop_front()
+            .expect("no events, needed a response event");
+        match event.action {
+            // to ensure deterministic behavior if the request EOF happens first in the log,
+            // wait for the request body to be done before returning a response.
+            Action::Eof {
+                direction: Direction::Request,
+                ..
+            } => {
+                recorded_request.wait().await;
+            }
+            Action::Request { .. } => panic!("invalid"),
+            Action::Response {
+                response: Err(error),
+            } => break Err(ConnectorError::other(error.0.into(), None)),
+            Action::Response {
+                response: Ok(response),
+            } => {
+                let mut builder = http::Response::builder()
+                    .status(response.status)
+                    .version(convert_version(&response.version));
+                for (name, values) in response.headers {
+                    for value in values {
+                        builder = builder.header(&name, &value);
+                    }
+                }
+                tokio::spawn(async move {
+                    replay_body(events, sender).await;
+                    // insert the finalized body into
+                });
+                break Ok(builder.body(body).expect("valid builder"));
+            }
+
+            Action::Data {
+                direction: Direction::Request,
+                data: _data,
+            } => {
+                tracing::info!("get request data");
+            }
+            Action::Eof {
+                direction: Direction::Response,
+                ..
+            } => panic!("got eof before response"),
+
+            Action::Data {
+                data: _,
+                direction: Direction::Response,
+            } => panic!("got response data before response"),
+        }
+    }
+}
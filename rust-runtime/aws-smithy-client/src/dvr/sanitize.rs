@@ -0,0 +1,412 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::dvr::{Action, BodyData, ConnectionId, Direction, Event, NetworkTraffic};
+use std::collections::{HashMap, HashSet};
+
+/// The value that redacted headers, query parameters and body fields are replaced with
+pub const REDACTED: &str = "**REDACTED**";
+
+/// Removes secrets from recorded traffic
+///
+/// A `Sanitizer` replaces the values of selected headers, URI query parameters and body fields
+/// with [`REDACTED`]. Body fields are redacted in JSON bodies (object keys, at any depth) and in
+/// XML bodies (element names). When a body is changed, the recorded `content-length` header is
+/// updated so that the sanitized traffic can still be replayed. Sanitized JSON bodies are
+/// re-serialized, which may reorder their keys; the default [`RequestMatcher`](super::RequestMatcher)
+/// body comparison ignores key order.
+///
+/// Pass a sanitizer to [`RecordingConnection::with_sanitizer`](super::RecordingConnection::with_sanitizer)
+/// to sanitize traffic as it's recorded, or call [`sanitize`](Sanitizer::sanitize) on traffic
+/// that was already recorded. When replaying sanitized traffic with a
+/// [`RequestMatcher`](super::RequestMatcher), the redacted headers and query parameters should be
+/// ignored by the matcher.
+///
+/// ```no_run
+/// use aws_smithy_client::dvr::Sanitizer;
+/// let sanitizer = Sanitizer::aws_credentials()
+///     .redact_header("x-api-key")
+///     .redact_body_field("Password");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Sanitizer {
+    headers: HashSet<String>,
+    query_params: HashSet<String>,
+    body_fields: HashSet<String>,
+}
+
+impl Sanitizer {
+    /// Create a sanitizer that doesn't redact anything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a sanitizer that redacts AWS credentials and request signatures
+    ///
+    /// This redacts:
+    /// - the `authorization` and `x-amz-security-token` headers
+    /// - the `X-Amz-Signature`, `X-Amz-Credential` and `X-Amz-Security-Token` query parameters
+    ///   of presigned requests
+    /// - the credential fields returned by STS, SSO and IMDS: `AccessKeyId`, `SecretAccessKey`,
+    ///   `SessionToken` and `Token`, as well as their camel case equivalents
+    pub fn aws_credentials() -> Self {
+        let mut sanitizer = Self::new()
+            .redact_header("authorization")
+            .redact_header("x-amz-security-token")
+            .redact_query_param("X-Amz-Signature")
+            .redact_query_param("X-Amz-Credential")
+            .redact_query_param("X-Amz-Security-Token");
+        for field in [
+            "AccessKeyId",
+            "SecretAccessKey",
+            "SessionToken",
+            "Token",
+            "accessKeyId",
+            "secretAccessKey",
+            "sessionToken",
+        ] {
+            sanitizer = sanitizer.redact_body_field(field);
+        }
+        sanitizer
+    }
+
+    /// Redact the values of the header named `name`
+    pub fn redact_header(mut self, name: impl Into<String>) -> Self {
+        self.headers.insert(name.into().to_lowercase());
+        self
+    }
+
+    /// Redact the values of the URI query parameter named `name`
+    pub fn redact_query_param(mut self, name: impl Into<String>) -> Self {
+        self.query_params.insert(name.into());
+        self
+    }
+
+    /// Redact the values of the JSON fields and XML elements named `name`
+    pub fn redact_body_field(mut self, name: impl Into<String>) -> Self {
+        self.body_fields.insert(name.into());
+        self
+    }
+
+    /// Sanitize traffic that was already recorded
+    pub fn sanitize(&self, traffic: &mut NetworkTraffic) {
+        let mut bodies = Vec::new();
+        for event in traffic.events.iter_mut() {
+            self.sanitize_event(event);
+            if let Action::Data { direction, .. } = event.action {
+                if !bodies.contains(&(event.connection_id, direction)) {
+                    bodies.push((event.connection_id, direction));
+                }
+            }
+        }
+        for (connection_id, direction) in bodies {
+            self.sanitize_body(&mut traffic.events, connection_id, direction);
+        }
+    }
+
+    /// Sanitize the headers and URI of a request or response event
+    pub(super) fn sanitize_event(&self, event: &mut Event) {
+        match &mut event.action {
+            Action::Request { request } => {
+                self.sanitize_headers(&mut request.headers);
+                request.uri = self.sanitize_uri(&request.uri);
+            }
+            Action::Response {
+                response: Ok(response),
+            } => self.sanitize_headers(&mut response.headers),
+            _ => {}
+        }
+    }
+
+    /// Sanitize the complete body sent by `connection_id` in `direction`
+    ///
+    /// If the body is changed, its data events are replaced by a single data event.
+    pub(super) fn sanitize_body(
+        &self,
+        events: &mut Vec<Event>,
+        connection_id: ConnectionId,
+        direction: Direction,
+    ) {
+        if self.body_fields.is_empty() {
+            return;
+        }
+        let is_body_data = |event: &Event| {
+            event.connection_id == connection_id
+                && matches!(&event.action, Action::Data { direction: d, .. } if *d == direction)
+        };
+        let mut body = Vec::new();
+        for event in events.iter().filter(|event| is_body_data(event)) {
+            if let Action::Data { data, .. } = &event.action {
+                body.extend_from_slice(&data.copy_to_vec());
+            }
+        }
+        let sanitized = match self.sanitize_body_data(&body) {
+            Some(sanitized) => sanitized,
+            None => return,
+        };
+
+        let first = events
+            .iter()
+            .position(is_body_data)
+            .expect("body contains data");
+        let content_length = sanitized.len();
+        events[first].action = Action::Data {
+            data: BodyData::from(bytes::Bytes::from(sanitized)),
+            direction,
+        };
+        let mut index = 0;
+        events.retain(|event| {
+            index += 1;
+            index - 1 == first || !is_body_data(event)
+        });
+        for event in events
+            .iter_mut()
+            .filter(|event| event.connection_id == connection_id)
+        {
+            let headers = match (&mut event.action, direction) {
+                (Action::Request { request }, Direction::Request) => &mut request.headers,
+                (
+                    Action::Response {
+                        response: Ok(response),
+                    },
+                    Direction::Response,
+                ) => &mut response.headers,
+                _ => continue,
+            };
+            if let Some(values) = headers.get_mut("content-length") {
+                *values = vec![content_length.to_string()];
+            }
+        }
+    }
+
+    fn sanitize_headers(&self, headers: &mut HashMap<String, Vec<String>>) {
+        for (name, values) in headers.iter_mut() {
+            if self.headers.contains(&name.to_lowercase()) {
+                for value in values.iter_mut() {
+                    *value = REDACTED.to_string();
+                }
+            }
+        }
+    }
+
+    fn sanitize_uri(&self, uri: &str) -> String {
+        let (path, query) = match uri.split_once('?') {
+            Some(split) => split,
+            None => return uri.to_string(),
+        };
+        let query: Vec<_> = query
+            .split('&')
+            .map(|param| match param.split_once('=') {
+                Some((name, _)) if self.query_params.contains(name) => {
+                    format!("{}={}", name, REDACTED)
+                }
+                _ => param.to_string(),
+            })
+            .collect();
+        format!("{}?{}", path, query.join("&"))
+    }
+
+    /// Returns the sanitized body, or `None` if nothing was redacted
+    fn sanitize_body_data(&self, body: &[u8]) -> Option<Vec<u8>> {
+        if let Ok(mut json) = serde_json::from_slice::<serde_json::Value>(body) {
+            return if self.sanitize_json(&mut json) {
+                Some(serde_json::to_vec(&json).expect("values can be serialized"))
+            } else {
+                None
+            };
+        }
+        let body = std::str::from_utf8(body).ok()?;
+        if body.trim_start().starts_with('<') {
+            return self.sanitize_xml(body).map(String::into_bytes);
+        }
+        None
+    }
+
+    /// Returns true if a field was redacted
+    fn sanitize_json(&self, value: &mut serde_json::Value) -> bool {
+        let mut redacted = false;
+        match value {
+            serde_json::Value::Object(fields) => {
+                for (name, value) in fields.iter_mut() {
+                    if self.body_fields.contains(name) {
+                        *value = serde_json::Value::String(REDACTED.to_string());
+                        redacted = true;
+                    } else {
+                        redacted |= self.sanitize_json(value);
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => {
+                for value in values {
+                    redacted |= self.sanitize_json(value);
+                }
+            }
+            _ => {}
+        }
+        redacted
+    }
+
+    /// Replace the contents of `<Field>...</Field>` elements
+    ///
+    /// Elements with attributes and self-closing elements are left as-is.
+    fn sanitize_xml(&self, body: &str) -> Option<String> {
+        let mut body = body.to_string();
+        let mut redacted = false;
+        for field in &self.body_fields {
+            let (open, close) = (format!("<{}>", field), format!("</{}>", field));
+            let mut search_from = 0;
+            while let Some(start) = body[search_from..].find(&open) {
+                let content_start = search_from + start + open.len();
+                let content_end = match body[content_start..].find(&close) {
+                    Some(end) => content_start + end,
+                    None => break,
+                };
+                body.replace_range(content_start..content_end, REDACTED);
+                search_from = content_start + REDACTED.len() + close.len();
+                redacted = true;
+            }
+        }
+        if redacted {
+            Some(body)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Sanitizer, REDACTED};
+    use crate::dvr::{
+        Action, BodyData, ConnectionId, Direction, Event, NetworkTraffic, Request, Response,
+        Version,
+    };
+    use std::collections::HashMap;
+
+    fn event(action: Action) -> Event {
+        Event {
+            connection_id: ConnectionId(0),
+            action,
+        }
+    }
+
+    fn data(data: &str, direction: Direction) -> Event {
+        event(Action::Data {
+            data: BodyData::Utf8(data.to_string()),
+            direction,
+        })
+    }
+
+    fn traffic(request_body: &[&str], response_body: &[&str]) -> NetworkTraffic {
+        let mut events = vec![event(Action::Request {
+            request: Request {
+                uri: "https://example.com/?X-Amz-Signature=abc&list-type=2".to_string(),
+                headers: HashMap::from([
+                    ("authorization".to_string(), vec!["secret".to_string()]),
+                    ("content-type".to_string(), vec!["text/plain".to_string()]),
+                ]),
+                method: "POST".to_string(),
+            },
+        })];
+        events.extend(request_body.iter().map(|d| data(d, Direction::Request)));
+        events.push(event(Action::Response {
+            response: Ok(Response {
+                status: 200,
+                version: "HTTP/1.1".to_string(),
+                headers: HashMap::from([(
+                    "content-length".to_string(),
+                    vec![response_body.concat().len().to_string()],
+                )]),
+            }),
+        }));
+        events.extend(response_body.iter().map(|d| data(d, Direction::Response)));
+        NetworkTraffic {
+            events,
+            docs: None,
+            version: Version::V0,
+        }
+    }
+
+    fn body(traffic: &NetworkTraffic, direction: Direction) -> String {
+        traffic
+            .events
+            .iter()
+            .filter_map(|event| match &event.action {
+                Action::Data { data, direction: d } if *d == direction => {
+                    Some(String::from_utf8(data.copy_to_vec()).unwrap())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn redacts_headers_and_query_params() {
+        let mut traffic = traffic(&["hello"], &["world"]);
+        Sanitizer::aws_credentials().sanitize(&mut traffic);
+        match &traffic.events[0].action {
+            Action::Request { request } => {
+                assert_eq!(
+                    request.uri,
+                    format!(
+                        "https://example.com/?X-Amz-Signature={}&list-type=2",
+                        REDACTED
+                    )
+                );
+                assert_eq!(request.headers["authorization"], vec![REDACTED]);
+                assert_eq!(request.headers["content-type"], vec!["text/plain"]);
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+        // bodies without redacted fields are left untouched
+        assert_eq!(traffic.events.len(), 4);
+        assert_eq!(body(&traffic, Direction::Response), "world");
+    }
+
+    #[test]
+    fn redacts_json_fields_split_across_data_events() {
+        let mut traffic = traffic(
+            &[],
+            &[
+                r#"{"Credentials": {"AccessKeyId": "AKID", "Secret"#,
+                r#"AccessKey": "secret", "Expiration": 1}}"#,
+            ],
+        );
+        Sanitizer::aws_credentials().sanitize(&mut traffic);
+        let expected = format!(
+            r#"{{"Credentials":{{"AccessKeyId":"{0}","Expiration":1,"SecretAccessKey":"{0}"}}}}"#,
+            REDACTED
+        );
+        assert_eq!(body(&traffic, Direction::Response), expected);
+        assert_eq!(traffic.events.len(), 3);
+        match &traffic.events[1].action {
+            Action::Response {
+                response: Ok(response),
+            } => assert_eq!(
+                response.headers["content-length"],
+                vec![expected.len().to_string()]
+            ),
+            other => panic!("unexpected action: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn redacts_xml_elements() {
+        let mut traffic = traffic(
+            &[],
+            &["<Credentials><SessionToken>token</SessionToken><SessionTokenExpiry>1</SessionTokenExpiry></Credentials>"],
+        );
+        Sanitizer::new()
+            .redact_body_field("SessionToken")
+            .sanitize(&mut traffic);
+        assert_eq!(
+            body(&traffic, Direction::Response),
+            format!(
+                "<Credentials><SessionToken>{}</SessionToken><SessionTokenExpiry>1</SessionTokenExpiry></Credentials>",
+                REDACTED
+            )
+        );
+    }
+}
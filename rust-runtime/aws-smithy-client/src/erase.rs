@@ -58,6 +58,9 @@ where
             retry_policy: self.retry_policy,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            rate_limiter: self.rate_limiter,
+            interceptors: self.interceptors,
+            response_body_timeout: self.response_body_timeout,
         }
     }
 }
@@ -98,6 +101,9 @@ where
             retry_policy: self.retry_policy,
             timeout_config: self.timeout_config,
             sleep_impl: self.sleep_impl,
+            rate_limiter: self.rate_limiter,
+            interceptors: self.interceptors,
+            response_body_timeout: self.response_body_timeout,
         }
     }
 
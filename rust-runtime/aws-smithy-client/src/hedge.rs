@@ -0,0 +1,490 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Hedged requests
+//!
+//! A hedged request sends a second, speculative attempt if the first attempt hasn't completed
+//! after a configurable delay. Whichever attempt completes first is returned, and the other one
+//! is cancelled. Choosing a delay close to the operation's p99 latency cuts off the latency tail at
+//! the cost of a small number of additional requests.
+//!
+//! Hedging is opt-in for each operation: insert a [`HedgeConfig`] into the operation's property
+//! bag. Since both attempts may reach the service, only idempotent operations should be hedged.
+//! Operations that the retry policy can't clone (such as operations with streaming bodies) are
+//! never hedged. If one attempt fails while the other is still in flight, the request waits for the
+//! other attempt.
+//!
+//! Hedged attempts are paid for from the client's [retry quota](crate::retry::HedgeQuota). The quota
+//! is returned if the hedged attempt succeeds or is cancelled because the initial attempt won, but
+//! it is kept if the hedged attempt fails, like the quota of a failed retry. If no quota is
+//! available, the request proceeds with only its initial attempt.
+//!
+//! ```no_run
+//! use aws_smithy_client::hedge::HedgeConfig;
+//! use aws_smithy_http::body::SdkBody;
+//! use aws_smithy_http::operation::{Operation, Request};
+//! use std::time::Duration;
+//! let mut op = Operation::new(Request::new(http::Request::new(SdkBody::empty())), ());
+//! op.properties_mut()
+//!     .insert(HedgeConfig::new(Duration::from_millis(250)));
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::retry::HedgeQuota;
+use aws_smithy_async::rt::sleep::{AsyncSleep, Sleep};
+use aws_smithy_http::operation::Operation;
+use aws_smithy_http::result::SdkError;
+use pin_project_lite::pin_project;
+use tower::{Layer, Service};
+
+/// Per-operation configuration for hedged requests
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub struct HedgeConfig {
+    delay: Duration,
+}
+
+impl HedgeConfig {
+    /// Send a hedged attempt if the initial attempt hasn't completed after `delay`
+    pub fn new(delay: Duration) -> Self {
+        Self { delay }
+    }
+
+    /// How long to wait for an attempt before sending a hedged attempt
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+}
+
+/// A layer that wraps services in a [`HedgeService`]
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct HedgeLayer<P> {
+    policy: P,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+    quota: Option<HedgeQuota>,
+}
+
+impl<P> HedgeLayer<P> {
+    /// Create a new `HedgeLayer`
+    ///
+    /// Hedged attempts are cloned from the original request with the retry `policy`'s
+    /// [`clone_request`](tower::retry::Policy::clone_request), so only requests that could be
+    /// retried can be hedged. Requests are never hedged without a sleep implementation. When `quota`
+    /// is `None`, hedged attempts are sent without limit.
+    pub fn new(
+        policy: P,
+        sleep_impl: Option<Arc<dyn AsyncSleep>>,
+        quota: Option<HedgeQuota>,
+    ) -> Self {
+        Self {
+            policy,
+            sleep_impl,
+            quota,
+        }
+    }
+}
+
+impl<P, S> Layer<S> for HedgeLayer<P>
+where
+    P: Clone,
+{
+    type Service = HedgeService<P, S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HedgeService {
+            inner,
+            policy: self.policy.clone(),
+            sleep_impl: self.sleep_impl.clone(),
+            quota: self.quota.clone(),
+        }
+    }
+}
+
+/// A service that sends a hedged attempt for operations configured with a [`HedgeConfig`]
+#[derive(Clone, Debug)]
+pub struct HedgeService<P, S> {
+    inner: S,
+    policy: P,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+    quota: Option<HedgeQuota>,
+}
+
+#[derive(Debug)]
+enum HedgeState<S, H, R> {
+    /// Waiting for the hedge delay to elapse
+    Waiting {
+        delay: Sleep,
+        service: S,
+        request: Operation<H, R>,
+    },
+    /// Waiting for the hedged attempt's service to become ready
+    Readying {
+        service: S,
+        request: Operation<H, R>,
+    },
+    /// The hedged attempt has been sent, or will never be sent
+    Done,
+}
+
+/// Retry quota acquired for a hedged attempt, which is returned when dropped unless it was spent
+#[derive(Debug)]
+struct AcquiredQuota {
+    quota: HedgeQuota,
+    amount: usize,
+}
+
+impl AcquiredQuota {
+    /// Keep the quota because the hedged attempt failed
+    fn spend(mut self) {
+        self.amount = 0;
+    }
+}
+
+impl Drop for AcquiredQuota {
+    fn drop(&mut self) {
+        if self.amount > 0 {
+            self.quota.release(self.amount);
+        }
+    }
+}
+
+pin_project! {
+    /// Future returned by [`HedgeService`]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct HedgeFuture<S, H, R, F, E> {
+        #[pin]
+        primary: Option<F>,
+        #[pin]
+        hedged: Option<F>,
+        state: HedgeState<S, H, R>,
+        quota: Option<HedgeQuota>,
+        acquired_quota: Option<AcquiredQuota>,
+        // The error of the first attempt to fail, returned if the other attempt fails too
+        error: Option<SdkError<E>>,
+    }
+}
+
+impl<S, H, R, F, E> HedgeFuture<S, H, R, F, E> {
+    fn new(primary: F) -> Self {
+        Self {
+            primary: Some(primary),
+            hedged: None,
+            state: HedgeState::Done,
+            quota: None,
+            acquired_quota: None,
+            error: None,
+        }
+    }
+}
+
+impl<S, H, R, F, T, E> Future for HedgeFuture<S, H, R, F, E>
+where
+    S: Service<Operation<H, R>, Future = F>,
+    F: Future<Output = Result<T, SdkError<E>>>,
+{
+    type Output = Result<T, SdkError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            match std::mem::replace(this.state, HedgeState::Done) {
+                HedgeState::Waiting {
+                    mut delay,
+                    service,
+                    request,
+                } => match Pin::new(&mut delay).poll(cx) {
+                    Poll::Ready(()) => {
+                        if let Some(quota) = this.quota.as_ref() {
+                            match quota.acquire() {
+                                Some(amount) => {
+                                    *this.acquired_quota = Some(AcquiredQuota {
+                                        quota: quota.clone(),
+                                        amount,
+                                    })
+                                }
+                                None => {
+                                    tracing::debug!("not sending hedged attempt: no retry quota");
+                                    break;
+                                }
+                            }
+                        }
+                        *this.state = HedgeState::Readying { service, request };
+                    }
+                    Poll::Pending => {
+                        *this.state = HedgeState::Waiting {
+                            delay,
+                            service,
+                            request,
+                        };
+                        break;
+                    }
+                },
+                HedgeState::Readying {
+                    mut service,
+                    request,
+                } => match service.poll_ready(cx) {
+                    Poll::Ready(Ok(())) => {
+                        tracing::debug!("sending hedged attempt");
+                        this.hedged.set(Some(service.call(request)));
+                        break;
+                    }
+                    Poll::Ready(Err(_)) => {
+                        tracing::debug!("not sending hedged attempt: service is unavailable");
+                        break;
+                    }
+                    Poll::Pending => {
+                        *this.state = HedgeState::Readying { service, request };
+                        break;
+                    }
+                },
+                HedgeState::Done => break,
+            }
+        }
+
+        if let Some(Poll::Ready(result)) = this.primary.as_mut().as_pin_mut().map(|f| f.poll(cx)) {
+            this.primary.set(None);
+            match result {
+                Ok(response) => {
+                    this.acquired_quota.take();
+                    return Poll::Ready(Ok(response));
+                }
+                Err(err) => {
+                    // A hedged attempt that hasn't been sent yet never will be: the failure is
+                    // left to the retry policy instead
+                    *this.state = HedgeState::Done;
+                    *this.error = Some(err);
+                }
+            }
+        }
+        if let Some(Poll::Ready(result)) = this.hedged.as_mut().as_pin_mut().map(|f| f.poll(cx)) {
+            this.hedged.set(None);
+            match result {
+                Ok(response) => {
+                    this.acquired_quota.take();
+                    return Poll::Ready(Ok(response));
+                }
+                Err(err) => {
+                    if let Some(acquired_quota) = this.acquired_quota.take() {
+                        acquired_quota.spend();
+                    }
+                    *this.error = Some(err);
+                }
+            }
+        }
+        // Wait for the outstanding attempt, if any, before giving up on the request
+        if this.primary.is_some() || this.hedged.is_some() {
+            return Poll::Pending;
+        }
+        this.acquired_quota.take();
+        Poll::Ready(Err(this
+            .error
+            .take()
+            .expect("an attempt failed if none are outstanding")))
+    }
+}
+
+impl<P, H, R, S, T, E> Service<Operation<H, R>> for HedgeService<P, S>
+where
+    P: tower::retry::Policy<Operation<H, R>, T, SdkError<E>>,
+    S: Service<Operation<H, R>, Response = T, Error = SdkError<E>> + Clone,
+{
+    type Response = T;
+    type Error = SdkError<E>;
+    type Future = HedgeFuture<S, H, R, S::Future, E>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Operation<H, R>) -> Self::Future {
+        let config = req.properties().get::<HedgeConfig>().cloned();
+        let hedged_request = match (&config, &self.sleep_impl) {
+            (Some(_), Some(_)) => self.policy.clone_request(&req),
+            _ => None,
+        };
+        let service = self.inner.clone();
+        let mut future = HedgeFuture::new(self.inner.call(req));
+        if let (Some(config), Some(sleep_impl), Some(request)) =
+            (config, &self.sleep_impl, hedged_request)
+        {
+            future.state = HedgeState::Waiting {
+                delay: sleep_impl.sleep(config.delay),
+                service,
+                request,
+            };
+            future.quota = self.quota.clone();
+        }
+        future
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HedgeConfig, HedgeLayer};
+    use crate::retry::{NewRequestPolicy, RetryHandler, Standard};
+    use crate::SdkError;
+    use aws_smithy_async::assert_elapsed;
+    use aws_smithy_async::rt::sleep::{AsyncSleep, TokioSleep};
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::operation::{self, Operation, Request};
+    use aws_smithy_http::result::SdkSuccess;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    fn operation(hedge_delay: Option<Duration>) -> Operation<(), ()> {
+        let mut op = Operation::new(Request::new(http::Request::new(SdkBody::empty())), ());
+        if let Some(delay) = hedge_delay {
+            op.properties_mut().insert(HedgeConfig::new(delay));
+        }
+        op
+    }
+
+    fn success(attempt: usize) -> SdkSuccess<usize> {
+        SdkSuccess {
+            raw: operation::Response::new(http::Response::new(SdkBody::empty())),
+            parsed: attempt,
+        }
+    }
+
+    /// A service where the first attempt takes ten seconds and subsequent attempts take two seconds
+    ///
+    /// If `first_attempt_fails` is set, the first attempt fails after three seconds instead.
+    fn slow_first_attempt(
+        attempts: Arc<AtomicUsize>,
+        first_attempt_fails: bool,
+    ) -> impl tower::Service<
+        Operation<(), ()>,
+        Response = SdkSuccess<usize>,
+        Error = SdkError<()>,
+        Future = impl Send,
+    > + Clone {
+        service_fn(move |_: Operation<(), ()>| {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match (attempt, first_attempt_fails) {
+                    (0, true) => {
+                        tokio::time::sleep(Duration::from_secs(3)).await;
+                        return Err(SdkError::TimeoutError("first attempt failed".into()));
+                    }
+                    (0, false) => tokio::time::sleep(Duration::from_secs(10)).await,
+                    _ => tokio::time::sleep(Duration::from_secs(2)).await,
+                }
+                Ok(success(attempt))
+            }
+        })
+    }
+
+    fn sleep_impl() -> Option<Arc<dyn AsyncSleep>> {
+        Some(Arc::new(TokioSleep::new()))
+    }
+
+    fn layer(policy: &Standard) -> HedgeLayer<RetryHandler> {
+        HedgeLayer::new(
+            policy.new_request_policy(None),
+            sleep_impl(),
+            policy.hedge_quota(),
+        )
+    }
+
+    #[tokio::test]
+    async fn hedged_attempt_wins_when_first_attempt_is_slow() {
+        tokio::time::pause();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let policy = Standard::default();
+        let svc = ServiceBuilder::new()
+            .layer(layer(&policy))
+            .service(slow_first_attempt(attempts.clone(), false));
+
+        let start = tokio::time::Instant::now();
+        let winner = svc
+            .oneshot(operation(Some(Duration::from_secs(2))))
+            .await
+            .unwrap();
+        assert_eq!(winner.parsed, 1);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_elapsed!(start, Duration::from_secs(4));
+        // the request completed, so the quota of the hedged attempt was returned
+        assert_eq!(policy.hedge_quota().unwrap().available(), 500);
+    }
+
+    #[tokio::test]
+    async fn failed_attempt_waits_for_outstanding_attempt() {
+        tokio::time::pause();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let policy = Standard::default();
+        let svc = ServiceBuilder::new()
+            .layer(layer(&policy))
+            .service(slow_first_attempt(attempts.clone(), true));
+
+        let start = tokio::time::Instant::now();
+        let winner = svc
+            .oneshot(operation(Some(Duration::from_secs(2))))
+            .await
+            .unwrap();
+        // the first attempt fails after three seconds, the hedged attempt succeeds after four
+        assert_eq!(winner.parsed, 1);
+        assert_elapsed!(start, Duration::from_secs(4));
+        assert_eq!(policy.hedge_quota().unwrap().available(), 500);
+    }
+
+    #[tokio::test]
+    async fn quota_is_kept_when_hedged_attempt_fails() {
+        tokio::time::pause();
+        let policy = Standard::default();
+        let svc = ServiceBuilder::new()
+            .layer(layer(&policy))
+            .service(service_fn(|_: Operation<(), ()>| async {
+                tokio::time::sleep(Duration::from_secs(3)).await;
+                Err::<SdkSuccess<usize>, _>(SdkError::<()>::TimeoutError("failed".into()))
+            }));
+
+        let err = svc
+            .oneshot(operation(Some(Duration::from_secs(2))))
+            .await
+            .expect_err("both attempts fail");
+        assert!(matches!(err, SdkError::TimeoutError(_)), "{:?}", err);
+        // the hedged attempt failed, so its quota is spent like the quota of a failed retry
+        assert_eq!(policy.hedge_quota().unwrap().available(), 495);
+    }
+
+    #[tokio::test]
+    async fn requests_are_not_hedged_unless_configured() {
+        tokio::time::pause();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let svc = ServiceBuilder::new()
+            .layer(layer(&Standard::default()))
+            .service(slow_first_attempt(attempts.clone(), false));
+
+        assert_eq!(svc.oneshot(operation(None)).await.unwrap().parsed, 0);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn hedged_attempts_require_retry_quota() {
+        tokio::time::pause();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let policy = Standard::default();
+        let quota = policy.hedge_quota().unwrap();
+        while quota.acquire().is_some() {}
+        let svc = ServiceBuilder::new()
+            .layer(layer(&policy))
+            .service(slow_first_attempt(attempts.clone(), false));
+
+        let winner = svc
+            .oneshot(operation(Some(Duration::from_secs(2))))
+            .await
+            .unwrap();
+        assert_eq!(winner.parsed, 0);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -38,6 +38,34 @@
 //! // once you have a connector, use it to construct a Smithy client:
 //! let client = Client::<DynConnector, MyMiddleware>::new(DynConnector::new(connector));
 //! ```
+//!
+//! ### Configure the connection pool and record connection metrics
+//! [`PoolConfig`] controls how connections are pooled, and a [`ConnectionMetrics`] implementation
+//! receives pool hits and misses, as well as connection establishment and TLS handshake latencies.
+//! ```no_run
+//! use std::time::Duration;
+//! use aws_smithy_client::{conns, hyper_ext};
+//! use aws_smithy_client::hyper_ext::{ConnectionMetrics, PoolConfig};
+//! use aws_smithy_types::tristate::TriState;
+//!
+//! #[derive(Debug)]
+//! struct LogMetrics;
+//!
+//! impl ConnectionMetrics for LogMetrics {
+//!     fn connection_established(&self, uri: &http::Uri, duration: Duration) {
+//!         println!("connected to {} in {:?}", uri, duration);
+//!     }
+//! }
+//!
+//! let pool_config = PoolConfig::new()
+//!     .with_max_idle_per_host(8)
+//!     .with_idle_timeout(TriState::Set(Duration::from_secs(30)))
+//!     .with_max_connections_per_host(32);
+//! let connector = hyper_ext::Adapter::builder()
+//!     .pool_config(pool_config)
+//!     .metrics(LogMetrics)
+//!     .build(conns::https());
+//! ```
 
 use std::error::Error;
 use std::sync::Arc;
@@ -59,15 +87,23 @@ use crate::erase::DynConnector;
 use crate::never::stream::EmptyStream;
 use crate::Builder as ClientBuilder;
 
+use self::pool::PoolConnector;
 use self::timeout_middleware::{ConnectTimeout, HttpReadTimeout, HttpTimeoutError};
 
+mod pool;
+
+pub use self::pool::{ConnectionMetrics, PoolConfig, PooledStream, TcpConnectTimer, TimedStream};
+
 /// Adapter from a [`hyper::Client`](hyper::Client) to a connector usable by a Smithy [`Client`](crate::Client).
 ///
 /// This adapter also enables TCP `CONNECT` and HTTP `READ` timeouts via [`Adapter::builder`]. For examples
 /// see [the module documentation](crate::hyper_ext).
 #[derive(Clone, Debug)]
 #[non_exhaustive]
-pub struct Adapter<C>(HttpReadTimeout<hyper::Client<ConnectTimeout<C>, SdkBody>>);
+pub struct Adapter<C> {
+    client: HttpReadTimeout<hyper::Client<PoolConnector<ConnectTimeout<C>>, SdkBody>>,
+    metrics: Option<Arc<dyn ConnectionMetrics>>,
+}
 
 impl<C> Service<http::Request<SdkBody>> for Adapter<C>
 where
@@ -89,12 +125,19 @@ where
         &mut self,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Result<(), Self::Error>> {
-        self.0.poll_ready(cx).map_err(downcast_error)
+        self.client.poll_ready(cx).map_err(downcast_error)
     }
 
     fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
-        let fut = self.0.call(req);
-        Box::pin(async move { Ok(fut.await.map_err(downcast_error)?.map(SdkBody::from)) })
+        let fut = self.client.call(req);
+        let metrics = self.metrics.clone();
+        Box::pin(async move {
+            let response = fut.await.map_err(downcast_error)?;
+            if let Some(metrics) = metrics {
+                pool::record_response(metrics.as_ref(), response.extensions());
+            }
+            Ok(response.map(SdkBody::from))
+        })
     }
 }
 
@@ -185,6 +228,8 @@ pub struct Builder {
     http_timeout_config: timeout::Http,
     sleep: Option<Arc<dyn AsyncSleep>>,
     client_builder: hyper::client::Builder,
+    pool_config: PoolConfig,
+    metrics: Option<Arc<dyn ConnectionMetrics>>,
 }
 
 impl Builder {
@@ -210,7 +255,14 @@ impl Builder {
             // Some day, we could provide a default timeout if none is set. Today is not that day.
             TriState::Unset | TriState::Disabled => ConnectTimeout::no_timeout(connector),
         };
-        let base = self.client_builder.build(connector);
+        let connector = PoolConnector::new(
+            connector,
+            self.pool_config.max_connections_per_host(),
+            self.metrics.clone(),
+        );
+        let mut client_builder = self.client_builder;
+        self.pool_config.apply(&mut client_builder);
+        let base = client_builder.build(connector);
         let http_timeout = match self.http_timeout_config.read_timeout() {
             TriState::Set(duration) => HttpReadTimeout::new(
                 base,
@@ -222,7 +274,10 @@ impl Builder {
             // Some day, we could provide a default timeout if none is set. Today is not that day.
             TriState::Unset | TriState::Disabled => HttpReadTimeout::no_timeout(base),
         };
-        Adapter(http_timeout)
+        Adapter {
+            client: http_timeout,
+            metrics: self.metrics,
+        }
     }
 
     /// Set the async sleep implementation used for timeouts
@@ -255,6 +310,25 @@ impl Builder {
             ..self
         }
     }
+
+    /// Configure the connection pool
+    ///
+    /// Settings in the [`PoolConfig`] take precedence over the equivalent settings of a Hyper
+    /// builder passed to [`hyper_builder`](Builder::hyper_builder).
+    pub fn pool_config(self, pool_config: PoolConfig) -> Self {
+        Self {
+            pool_config,
+            ..self
+        }
+    }
+
+    /// Report connection pool and connection establishment metrics to `metrics`
+    pub fn metrics(self, metrics: impl ConnectionMetrics + 'static) -> Self {
+        Self {
+            metrics: Some(Arc::new(metrics)),
+            ..self
+        }
+    }
 }
 
 #[cfg(any(feature = "rustls", feature = "native-tls"))]
@@ -631,18 +705,21 @@ impl Connection for EmptyStream {
 mod test {
     use std::io::{Error, ErrorKind};
     use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
     use std::task::{Context, Poll};
+    use std::time::Duration;
 
     use http::Uri;
     use hyper::client::connect::{Connected, Connection};
-    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-    use tower::BoxError;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf};
+    use tower::{BoxError, Service, ServiceExt};
 
     use aws_smithy_http::body::SdkBody;
 
     use super::ClientBuilder;
     use crate::erase::DynConnector;
-    use crate::hyper_ext::Adapter;
+    use crate::hyper_ext::{Adapter, ConnectionMetrics, PoolConfig, TcpConnectTimer};
 
     #[test]
     fn builder_connection_helpers_are_dyn() {
@@ -658,7 +735,6 @@ mod test {
             inner: HangupStream,
         };
         let mut adapter = Adapter::builder().build(connector);
-        use tower::Service;
         let err = adapter
             .call(
                 http::Request::builder()
@@ -717,6 +793,209 @@ mod test {
         inner: T,
     }
 
+    #[derive(Clone, Debug, Default)]
+    struct RecordingMetrics {
+        hits: Arc<AtomicUsize>,
+        misses: Arc<AtomicUsize>,
+        connections: Arc<Mutex<Vec<Duration>>>,
+        tls_handshakes: Arc<Mutex<Vec<Duration>>>,
+    }
+
+    impl ConnectionMetrics for RecordingMetrics {
+        fn pool_hit(&self, _uri: &Uri) {
+            self.hits.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn pool_miss(&self, _uri: &Uri) {
+            self.misses.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn connection_established(&self, _uri: &Uri, duration: Duration) {
+            self.connections.lock().unwrap().push(duration);
+        }
+
+        fn tls_handshake(&self, _uri: &Uri, duration: Duration) {
+            self.tls_handshakes.lock().unwrap().push(duration);
+        }
+    }
+
+    fn request() -> http::Request<SdkBody> {
+        http::Request::builder()
+            .uri("http://amazon.com")
+            .body(SdkBody::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn pool_hits_and_misses_are_reported() {
+        let connector = EchoConnector::default();
+        let metrics = RecordingMetrics::default();
+        let mut adapter = Adapter::builder()
+            .metrics(metrics.clone())
+            .build(connector.clone());
+        for _ in 0..2 {
+            let response = adapter
+                .ready()
+                .await
+                .unwrap()
+                .call(request())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+        }
+        assert_eq!(connector.connects.load(Ordering::SeqCst), 1);
+        assert_eq!(metrics.misses.load(Ordering::SeqCst), 1);
+        assert_eq!(metrics.hits.load(Ordering::SeqCst), 1);
+        assert_eq!(metrics.connections.lock().unwrap().len(), 1);
+        assert!(metrics.tls_handshakes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn max_connections_per_host_is_enforced() {
+        let connector = EchoConnector::default();
+        let adapter = Adapter::builder()
+            .pool_config(PoolConfig::new().with_max_connections_per_host(1))
+            .build(connector.clone());
+        let requests: Vec<_> = (0..4)
+            .map(|_| tokio::spawn(adapter.clone().oneshot(request())))
+            .collect();
+        for request in requests {
+            assert_eq!(request.await.unwrap().unwrap().status(), 200);
+        }
+        assert_eq!(connector.connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn tls_handshake_duration_is_reported() {
+        let connector = SlowHandshake(TcpConnectTimer::new(EchoConnector::default()));
+        let metrics = RecordingMetrics::default();
+        let mut adapter = Adapter::builder().metrics(metrics.clone()).build(connector);
+        adapter.call(request()).await.unwrap();
+        assert!(
+            metrics.tls_handshakes.lock().unwrap().is_empty(),
+            "plain HTTP connections don't have a TLS handshake"
+        );
+        let https_request = http::Request::builder()
+            .uri("https://amazon.com")
+            .body(SdkBody::empty())
+            .unwrap();
+        adapter.call(https_request).await.unwrap();
+        let handshakes = metrics.tls_handshakes.lock().unwrap().clone();
+        assert_eq!(handshakes.len(), 1);
+        assert!(handshakes[0] >= HANDSHAKE_DURATION, "{:?}", handshakes);
+        assert!(metrics.connections.lock().unwrap()[1] >= handshakes[0]);
+    }
+
+    // ---- machinery to make a Hyper connector that responds to every request with a 200
+    #[derive(Clone, Debug, Default)]
+    struct EchoConnector {
+        connects: Arc<AtomicUsize>,
+    }
+
+    impl tower::Service<Uri> for EchoConnector {
+        type Response = EchoStream;
+        type Error = BoxError;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: Uri) -> Self::Future {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            let (client, mut server) = tokio::io::duplex(1024);
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while let Ok(n) = server.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                    while let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        request.drain(..end + 4);
+                        let response = b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n";
+                        if server.write_all(response).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+            std::future::ready(Ok(EchoStream(client)))
+        }
+    }
+
+    #[derive(Debug)]
+    struct EchoStream(DuplexStream);
+
+    impl Connection for EchoStream {
+        fn connected(&self) -> Connected {
+            Connected::new()
+        }
+    }
+
+    impl AsyncRead for EchoStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for EchoStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<Result<usize, Error>> {
+            Pin::new(&mut self.0).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            Pin::new(&mut self.0).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Error>> {
+            Pin::new(&mut self.0).poll_shutdown(cx)
+        }
+    }
+
+    const HANDSHAKE_DURATION: Duration = Duration::from_millis(50);
+
+    /// Stands in for a TLS connector: takes `HANDSHAKE_DURATION` after the TCP connection is made
+    #[derive(Clone)]
+    struct SlowHandshake<C>(C);
+
+    impl<C> tower::Service<Uri> for SlowHandshake<C>
+    where
+        C: tower::Service<Uri, Error = BoxError> + Clone + Send + 'static,
+        C::Future: Send,
+        C::Response: Send,
+    {
+        type Response = C::Response;
+        type Error = BoxError;
+        type Future =
+            Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.0.poll_ready(cx)
+        }
+
+        fn call(&mut self, req: Uri) -> Self::Future {
+            let connect = self.0.call(req);
+            Box::pin(async move {
+                let stream = connect.await?;
+                tokio::time::sleep(HANDSHAKE_DURATION).await;
+                Ok(stream)
+            })
+        }
+    }
+
     impl<T> tower::Service<Uri> for TestConnection<T>
     where
         T: Clone + hyper::client::connect::Connection,
@@ -0,0 +1,452 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Connection pool configuration and metrics for [`Adapter`](super::Adapter)
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use http::Uri;
+use hyper::client::connect::{Connected, Connection};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower::BoxError;
+
+use aws_smithy_types::tristate::TriState;
+
+/// Connection pool settings for a [`hyper_ext::Adapter`](super::Adapter)
+///
+/// Settings that are left unset use Hyper's defaults.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default)]
+pub struct PoolConfig {
+    max_idle_per_host: Option<usize>,
+    idle_timeout: TriState<Duration>,
+    max_connections_per_host: Option<usize>,
+    #[cfg(feature = "http2-keep-alive")]
+    http2_keep_alive_interval: Option<Duration>,
+    #[cfg(feature = "http2-keep-alive")]
+    http2_keep_alive_timeout: Option<Duration>,
+    #[cfg(feature = "http2-keep-alive")]
+    http2_keep_alive_while_idle: Option<bool>,
+}
+
+impl PoolConfig {
+    /// Create a pool config that uses Hyper's defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of idle connections kept open for each host
+    pub fn with_max_idle_per_host(mut self, max_idle_per_host: usize) -> Self {
+        self.max_idle_per_host = Some(max_idle_per_host);
+        self
+    }
+
+    /// Set how long idle connections are kept open
+    ///
+    /// When disabled, idle connections are never closed by the pool.
+    pub fn with_idle_timeout(mut self, idle_timeout: TriState<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Set the maximum number of connections (idle or in use) open to each host
+    ///
+    /// When the limit has been reached, requests that need a new connection wait for an existing
+    /// connection to be closed or to become available in the pool.
+    pub fn with_max_connections_per_host(mut self, max_connections_per_host: usize) -> Self {
+        self.max_connections_per_host = Some(max_connections_per_host.max(1));
+        self
+    }
+
+    /// Set the interval at which HTTP/2 PING frames are sent to keep connections alive
+    ///
+    /// Requires the `http2-keep-alive` feature.
+    #[cfg(feature = "http2-keep-alive")]
+    pub fn with_http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.http2_keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Set how long to wait for an acknowledgement of an HTTP/2 keep-alive PING before closing
+    /// the connection
+    ///
+    /// Requires the `http2-keep-alive` feature.
+    #[cfg(feature = "http2-keep-alive")]
+    pub fn with_http2_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.http2_keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Set whether HTTP/2 keep-alive PINGs are sent while there are no open streams
+    ///
+    /// Requires the `http2-keep-alive` feature.
+    #[cfg(feature = "http2-keep-alive")]
+    pub fn with_http2_keep_alive_while_idle(mut self, enabled: bool) -> Self {
+        self.http2_keep_alive_while_idle = Some(enabled);
+        self
+    }
+
+    pub(super) fn max_connections_per_host(&self) -> Option<usize> {
+        self.max_connections_per_host
+    }
+
+    pub(super) fn apply(&self, builder: &mut hyper::client::Builder) {
+        if let Some(max_idle_per_host) = self.max_idle_per_host {
+            builder.pool_max_idle_per_host(max_idle_per_host);
+        }
+        match self.idle_timeout {
+            TriState::Set(idle_timeout) => {
+                builder.pool_idle_timeout(idle_timeout);
+            }
+            TriState::Disabled => {
+                builder.pool_idle_timeout(None);
+            }
+            TriState::Unset => {}
+        }
+        #[cfg(feature = "http2-keep-alive")]
+        self.apply_http2_keep_alive(builder);
+    }
+
+    #[cfg(feature = "http2-keep-alive")]
+    fn apply_http2_keep_alive(&self, builder: &mut hyper::client::Builder) {
+        if let Some(interval) = self.http2_keep_alive_interval {
+            builder.http2_keep_alive_interval(interval);
+        }
+        if let Some(timeout) = self.http2_keep_alive_timeout {
+            builder.http2_keep_alive_timeout(timeout);
+        }
+        if let Some(enabled) = self.http2_keep_alive_while_idle {
+            builder.http2_keep_alive_while_idle(enabled);
+        }
+    }
+}
+
+/// Receives connection metrics from a [`hyper_ext::Adapter`](super::Adapter)
+///
+/// `uri` identifies the host that the connection is made to. All methods do nothing by default.
+pub trait ConnectionMetrics: Debug + Send + Sync {
+    /// A request was sent over a connection that had been used before
+    fn pool_hit(&self, uri: &Uri) {
+        let _ = uri;
+    }
+
+    /// A request was sent over a newly established connection
+    fn pool_miss(&self, uri: &Uri) {
+        let _ = uri;
+    }
+
+    /// A new connection was established (including the TLS handshake, if any) after `duration`
+    fn connection_established(&self, uri: &Uri, duration: Duration) {
+        let _ = (uri, duration);
+    }
+
+    /// The TLS handshake of a new connection took `duration`
+    ///
+    /// This is only reported for connectors that wrap their TCP connector in a [`TcpConnectTimer`],
+    /// like the default [`conns::https`](crate::conns::https) and
+    /// [`conns::native_tls`](crate::conns::native_tls) connectors do. It is reported when the first
+    /// request is sent over the connection.
+    fn tls_handshake(&self, uri: &Uri, duration: Duration) {
+        let _ = (uri, duration);
+    }
+}
+
+/// Records when the TCP connection underneath a TLS connection was established
+///
+/// Wrap the TCP connector of a TLS connector (for example, the `HttpConnector` inside a
+/// `hyper_rustls::HttpsConnector`) in a `TcpConnectTimer` to report TLS handshake durations to
+/// [`ConnectionMetrics::tls_handshake`]. The default connectors in [`conns`](crate::conns) already
+/// do this.
+#[derive(Clone, Debug)]
+pub struct TcpConnectTimer<C> {
+    inner: C,
+}
+
+impl<C> TcpConnectTimer<C> {
+    /// Wrap a TCP connector
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TcpConnectedAt(Instant);
+
+impl<C> tower::Service<Uri> for TcpConnectTimer<C>
+where
+    C: tower::Service<Uri>,
+    C::Future: Send + 'static,
+{
+    type Response = TimedStream<C::Response>;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let future = self.inner.call(uri);
+        Box::pin(async move {
+            let stream = future.await?;
+            Ok(TimedStream {
+                inner: stream,
+                connected_at: TcpConnectedAt(Instant::now()),
+            })
+        })
+    }
+}
+
+/// Stream returned by [`TcpConnectTimer`]
+#[derive(Debug)]
+pub struct TimedStream<S> {
+    inner: S,
+    connected_at: TcpConnectedAt,
+}
+
+impl<S: Connection> Connection for TimedStream<S> {
+    fn connected(&self) -> Connected {
+        self.inner.connected().extra(self.connected_at)
+    }
+}
+
+/// Metadata attached to each connection, available from the extensions of every response
+#[derive(Clone, Debug)]
+struct ConnectionInfo {
+    uri: Uri,
+    established_at: Instant,
+    requests_sent: Arc<AtomicUsize>,
+}
+
+/// Report the metrics for a response received by an [`Adapter`](super::Adapter)
+pub(super) fn record_response(metrics: &dyn ConnectionMetrics, extensions: &http::Extensions) {
+    let info = match extensions.get::<ConnectionInfo>() {
+        Some(info) => info,
+        None => return,
+    };
+    if info.requests_sent.fetch_add(1, Ordering::SeqCst) > 0 {
+        metrics.pool_hit(&info.uri);
+        return;
+    }
+    metrics.pool_miss(&info.uri);
+    // TLS connectors pass plain HTTP connections through, so they don't have a handshake
+    if info.uri.scheme_str() != Some("https") {
+        return;
+    }
+    if let Some(TcpConnectedAt(tcp_connected_at)) = extensions.get::<TcpConnectedAt>() {
+        metrics.tls_handshake(
+            &info.uri,
+            info.established_at
+                .saturating_duration_since(*tcp_connected_at),
+        );
+    }
+}
+
+/// Connector that enforces [`PoolConfig::with_max_connections_per_host`] and reports
+/// [`ConnectionMetrics`] for new connections
+#[derive(Clone, Debug)]
+pub(super) struct PoolConnector<C> {
+    inner: C,
+    max_connections_per_host: Option<usize>,
+    /// Connection permits of each host that has connections open or being established
+    permits: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+    metrics: Option<Arc<dyn ConnectionMetrics>>,
+}
+
+impl<C> PoolConnector<C> {
+    pub(super) fn new(
+        inner: C,
+        max_connections_per_host: Option<usize>,
+        metrics: Option<Arc<dyn ConnectionMetrics>>,
+    ) -> Self {
+        Self {
+            inner,
+            max_connections_per_host,
+            permits: Default::default(),
+            metrics,
+        }
+    }
+
+    fn semaphore(&self, uri: &Uri) -> Option<Arc<Semaphore>> {
+        let max_connections = self.max_connections_per_host?;
+        let host = format!(
+            "{}://{}",
+            uri.scheme_str().unwrap_or_default(),
+            uri.authority().map(|a| a.as_str()).unwrap_or_default()
+        );
+        let mut permits = self.permits.lock().unwrap();
+        // Every outstanding permit holds a reference to its semaphore, so a semaphore that is only
+        // referenced by the map belongs to a host without open connections and can be dropped
+        permits.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+        Some(
+            permits
+                .entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(max_connections)))
+                .clone(),
+        )
+    }
+}
+
+impl<C> tower::Service<Uri> for PoolConnector<C>
+where
+    C: tower::Service<Uri> + Clone + Send + 'static,
+    C::Response: Connection,
+    C::Error: Into<BoxError>,
+    C::Future: Send + 'static,
+{
+    type Response = PooledStream<C::Response>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        // take the service that was driven to readiness, leaving a clone in its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let semaphore = self.semaphore(&uri);
+        let metrics = self.metrics.clone();
+        Box::pin(async move {
+            let permit = match semaphore {
+                Some(semaphore) => Some(semaphore.acquire_owned().await?),
+                None => None,
+            };
+            let start = Instant::now();
+            let stream = inner.call(uri.clone()).await.map_err(Into::into)?;
+            let established_at = Instant::now();
+            if let Some(metrics) = &metrics {
+                metrics.connection_established(&uri, established_at - start);
+            }
+            Ok(PooledStream {
+                inner: stream,
+                info: metrics.map(|_| ConnectionInfo {
+                    uri,
+                    established_at,
+                    requests_sent: Default::default(),
+                }),
+                _permit: permit,
+            })
+        })
+    }
+}
+
+/// Stream returned by [`PoolConnector`]
+///
+/// The stream holds its host's connection permit (if any) until the connection is closed.
+#[derive(Debug)]
+pub struct PooledStream<S> {
+    inner: S,
+    info: Option<ConnectionInfo>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<S: Connection> Connection for PooledStream<S> {
+    fn connected(&self) -> Connected {
+        let connected = self.inner.connected();
+        match &self.info {
+            Some(info) => connected.extra(info.clone()),
+            None => connected,
+        }
+    }
+}
+
+macro_rules! delegate_io {
+    ($stream:ident) => {
+        impl<S: AsyncRead + Unpin> AsyncRead for $stream<S> {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                Pin::new(&mut self.inner).poll_read(cx, buf)
+            }
+        }
+
+        impl<S: AsyncWrite + Unpin> AsyncWrite for $stream<S> {
+            fn poll_write(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<std::io::Result<usize>> {
+                Pin::new(&mut self.inner).poll_write(cx, buf)
+            }
+
+            fn poll_write_vectored(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                bufs: &[IoSlice<'_>],
+            ) -> Poll<std::io::Result<usize>> {
+                Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+            }
+
+            fn is_write_vectored(&self) -> bool {
+                self.inner.is_write_vectored()
+            }
+
+            fn poll_flush(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                Pin::new(&mut self.inner).poll_flush(cx)
+            }
+
+            fn poll_shutdown(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                Pin::new(&mut self.inner).poll_shutdown(cx)
+            }
+        }
+    };
+}
+
+delegate_io!(TimedStream);
+delegate_io!(PooledStream);
+
+#[cfg(test)]
+mod test {
+    use super::PoolConnector;
+    use http::Uri;
+
+    #[tokio::test]
+    async fn permits_of_hosts_without_connections_are_pruned() {
+        let connector = PoolConnector::new((), Some(1), None);
+        let first: Uri = "https://first.amazon.com".parse().unwrap();
+        let second: Uri = "https://second.amazon.com".parse().unwrap();
+
+        let permit = connector
+            .semaphore(&first)
+            .unwrap()
+            .acquire_owned()
+            .await
+            .unwrap();
+        drop(connector.semaphore(&second));
+        assert_eq!(connector.permits.lock().unwrap().len(), 2);
+
+        // the second host has no open connections, but the first host's permits are kept
+        drop(connector.semaphore(&first));
+        assert_eq!(connector.permits.lock().unwrap().len(), 1);
+
+        drop(permit);
+        drop(connector.semaphore(&second));
+        assert_eq!(connector.permits.lock().unwrap().len(), 1);
+        assert!(connector
+            .permits
+            .lock()
+            .unwrap()
+            .contains_key("https://second.amazon.com"));
+    }
+}
@@ -22,7 +22,10 @@
 )]
 
 pub mod bounds;
+pub mod circuit_breaker;
 pub mod erase;
+pub mod hedge;
+pub mod rate_limit;
 pub mod retry;
 
 // https://github.com/rust-lang/rust/issues/72081
@@ -54,15 +57,48 @@ pub use timeout::TimeoutLayer;
 #[cfg(feature = "client-hyper")]
 #[allow(missing_docs)]
 pub mod conns {
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    use crate::hyper_ext::TcpConnectTimer;
+
+    /// The TCP connector underneath the default TLS connectors
+    ///
+    /// It records when the TCP connection was established, so that the duration of the TLS
+    /// handshake is reported to [`ConnectionMetrics`](crate::hyper_ext::ConnectionMetrics).
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub type Tcp = TcpConnectTimer<hyper::client::HttpConnector>;
+
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    fn tcp() -> Tcp {
+        let mut http = hyper::client::HttpConnector::new();
+        http.enforce_http(false);
+        TcpConnectTimer::new(http)
+    }
+
     #[cfg(feature = "rustls")]
-    pub type Https = hyper_rustls::HttpsConnector<hyper::client::HttpConnector>;
+    pub type Https = hyper_rustls::HttpsConnector<Tcp>;
 
     // Creating a `with_native_roots` HTTP client takes 300ms on OS X. Cache this so that we
     // don't need to repeatedly incur that cost.
     #[cfg(feature = "rustls")]
     lazy_static::lazy_static! {
         static ref HTTPS_NATIVE_ROOTS: Https = {
-            hyper_rustls::HttpsConnector::with_native_roots()
+            // This matches the configuration of `HttpsConnector::with_native_roots`, which can't
+            // be used because it doesn't accept a TCP connector
+            let mut config = rustls_crate::ClientConfig::new();
+            config.root_store = match rustls_native_certs::load_native_certs() {
+                Ok(store) => store,
+                Err((Some(store), err)) => {
+                    tracing::warn!("could not load all certificates: {:?}", err);
+                    store
+                }
+                Err((None, err)) => panic!("cannot access native cert store: {:?}", err),
+            };
+            if config.root_store.is_empty() {
+                panic!("no CA certificates found");
+            }
+            config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+            config.ct_logs = Some(&ct_logs::LOGS);
+            hyper_rustls::HttpsConnector::from((tcp(), config))
         };
     }
 
@@ -73,26 +109,33 @@ pub mod conns {
 
     #[cfg(feature = "native-tls")]
     pub fn native_tls() -> NativeTls {
-        hyper_tls::HttpsConnector::new()
+        hyper_tls::HttpsConnector::new_with_connector(tcp())
     }
 
     #[cfg(feature = "native-tls")]
-    pub type NativeTls = hyper_tls::HttpsConnector<hyper::client::HttpConnector>;
+    pub type NativeTls = hyper_tls::HttpsConnector<Tcp>;
 
     #[cfg(feature = "rustls")]
-    pub type Rustls =
-        crate::hyper_ext::Adapter<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>;
+    pub type Rustls = crate::hyper_ext::Adapter<Https>;
 }
 
+use std::any::Any;
 use std::error::Error;
 use std::sync::Arc;
 use tower::{Layer, Service, ServiceBuilder, ServiceExt};
 
-use crate::timeout::generate_timeout_service_params_from_timeout_config;
+use crate::hedge::HedgeLayer;
+use crate::rate_limit::{InitialAttemptDelayLayer, RateLimitLayer};
+use crate::timeout::{
+    generate_timeout_service_params_from_timeout_config, ResponseBodyTimeout,
+    ResponseBodyTimeoutInterceptor, TimeoutConfigOverride,
+};
 use aws_smithy_async::rt::sleep::AsyncSleep;
 use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::interceptor::{Interceptors, OperationInput};
 use aws_smithy_http::operation::Operation;
 use aws_smithy_http::response::ParseHttpResponse;
+use aws_smithy_http::result::AttemptHistory;
 pub use aws_smithy_http::result::{SdkError, SdkSuccess};
 use aws_smithy_http::retry::ClassifyResponse;
 use aws_smithy_http_tower::dispatch::DispatchLayer;
@@ -130,6 +173,9 @@ pub struct Client<
     retry_policy: RetryPolicy,
     timeout_config: aws_smithy_types::timeout::Config,
     sleep_impl: TriState<Arc<dyn AsyncSleep>>,
+    rate_limiter: Option<rate_limit::RateLimiter>,
+    interceptors: Interceptors,
+    response_body_timeout: timeout::ResponseBodyTimeout,
 }
 
 // Quick-create for people who just want "the default".
@@ -189,6 +235,38 @@ impl<C, M, R> Client<C, M, R> {
         self.set_sleep_impl(Some(sleep_impl));
         self
     }
+
+    /// Set the [`RateLimiter`](rate_limit::RateLimiter) used to limit the rate of requests sent by the client.
+    pub fn set_rate_limiter(&mut self, rate_limiter: Option<rate_limit::RateLimiter>) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    /// Set the [`RateLimiter`](rate_limit::RateLimiter) used to limit the rate of requests sent by the client.
+    pub fn with_rate_limiter(mut self, rate_limiter: rate_limit::RateLimiter) -> Self {
+        self.set_rate_limiter(Some(rate_limiter));
+        self
+    }
+
+    /// Call the [`before_serialization`](aws_smithy_http::interceptor::Interceptor::before_serialization)
+    /// hook of the client's interceptors with the input of an operation
+    ///
+    /// Generated clients call this before the input is serialized into an [`Operation`]. If an
+    /// interceptor fails, the error is reported to the interceptors' `on_error` hook and returned
+    /// as a construction failure.
+    // `SdkError` is what generated clients return from `send`, so it isn't worth boxing here
+    #[allow(clippy::result_large_err)]
+    pub fn before_serialization<I, E>(&self, input: &mut I) -> Result<(), SdkError<E>>
+    where
+        I: Any + Send,
+    {
+        self.interceptors
+            .before_serialization(&mut OperationInput::new(input))
+            .map_err(|err| {
+                let err = SdkError::ConstructionFailure(err);
+                self.interceptors.on_error(&err);
+                err
+            })
+    }
 }
 
 fn check_send_sync<T: Send + Sync>(t: T) -> T {
@@ -222,7 +300,7 @@ where
     /// implementing unsupported features.
     pub async fn call_raw<O, T, E, Retry>(
         &self,
-        input: Operation<O, Retry>,
+        mut input: Operation<O, Retry>,
     ) -> Result<SdkSuccess<T>, SdkError<E>>
     where
         O: Send + Sync,
@@ -240,24 +318,88 @@ where
         if matches!(&self.sleep_impl, TriState::Unset) {
             // during requests, debug log (a warning is emitted during client construction)
             tracing::debug!(
-                "Client does not have a sleep implementation. Timeouts and retry \
-                will not work without this. {}",
+                "Client does not have a sleep implementation. Timeouts, retry, and client-side \
+                rate limiting will not work without this. {}",
                 MISSING_SLEEP_IMPL_RECOMMENDATION
             );
         }
+        if input.properties().get::<AttemptHistory>().is_none() {
+            input.properties_mut().insert(AttemptHistory::new());
+        }
+        // Without a sleep implementation the request can't be delayed, so it must not take a
+        // token from the rate limiter either. The delay itself is applied inside the service
+        // stack so that it counts towards the API call timeout.
+        let initial_delay = match &self.sleep_impl {
+            TriState::Set(_) => self.retry_policy.acquire_initial_attempt(),
+            _ => None,
+        };
+        if let Some(delay) = initial_delay {
+            tracing::debug!(
+                "client is being rate limited; delaying request by {:?}",
+                delay
+            );
+            if let Some(history) = input.properties().get::<AttemptHistory>() {
+                history.record_rate_limit_delay(delay);
+            }
+        }
+        // client interceptors are called before the operation's own interceptors
+        let mut interceptors = Interceptors::new();
+        let response_body_timeout = match input.properties().get::<ResponseBodyTimeout>() {
+            Some(operation_timeout) => operation_timeout.clone(),
+            None => self.response_body_timeout.clone(),
+        };
+        if let (true, TriState::Set(sleep_impl)) =
+            (response_body_timeout.has_timeouts(), &self.sleep_impl)
+        {
+            interceptors.push(ResponseBodyTimeoutInterceptor::new(
+                response_body_timeout,
+                sleep_impl.clone(),
+            ));
+        }
+        interceptors.extend(&self.interceptors);
+        if let Some(operation_interceptors) = input.properties().get::<Interceptors>() {
+            interceptors.extend(operation_interceptors);
+        }
+        if !interceptors.is_empty() {
+            input.properties_mut().insert(interceptors.clone());
+        }
+        let (mut request, parts) = input.into_request_response();
+        if let Err(err) = interceptors.before_first_attempt(&mut request) {
+            let err = SdkError::ConstructionFailure(err);
+            interceptors.on_error(&err);
+            return Err(err);
+        }
+        let input = Operation::from_parts(request, parts);
         let connector = self.connector.clone();
 
+        let api_timeout_config = match input.properties().get::<TimeoutConfigOverride>() {
+            Some(timeout_override) => timeout_override.apply_to(&self.timeout_config.api),
+            None => self.timeout_config.api.clone(),
+        };
         let timeout_service_params = generate_timeout_service_params_from_timeout_config(
-            &self.timeout_config.api,
+            &api_timeout_config,
             self.sleep_impl.clone().into(),
         );
 
+        let retry_policy = self
+            .retry_policy
+            .new_request_policy(self.sleep_impl.clone().into());
         let svc = ServiceBuilder::new()
             .layer(TimeoutLayer::new(timeout_service_params.api_call))
-            .retry(
-                self.retry_policy
-                    .new_request_policy(self.sleep_impl.clone().into()),
-            )
+            .layer(InitialAttemptDelayLayer::new(
+                initial_delay,
+                self.sleep_impl.clone().into(),
+            ))
+            .retry(retry_policy.clone())
+            .layer(HedgeLayer::new(
+                retry_policy,
+                self.sleep_impl.clone().into(),
+                self.retry_policy.hedge_quota(),
+            ))
+            .layer(RateLimitLayer::new(
+                self.rate_limiter.clone(),
+                self.sleep_impl.clone().into(),
+            ))
             .layer(TimeoutLayer::new(timeout_service_params.api_call_attempt))
             .layer(ParseResponseLayer::<O, Retry>::new())
             // These layers can be considered as occurring in order. That is, first invoke the
@@ -266,7 +408,11 @@ where
             .layer(DispatchLayer::new())
             .service(connector);
 
-        check_send_sync(svc).ready().await?.call(input).await
+        let result = async { check_send_sync(svc).ready().await?.call(input).await }.await;
+        if let Err(err) = &result {
+            interceptors.on_error(err);
+        }
+        result
     }
 
     /// Statically check the validity of a `Client` without a request to send.
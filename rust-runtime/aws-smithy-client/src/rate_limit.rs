@@ -0,0 +1,542 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Client-side rate limiting
+//!
+//! A [`RateLimiter`] caps the rate at which a client sends requests with token buckets. Limits can
+//! apply to every request sent by the client, to requests for a single operation (identified by the
+//! name in the operation's [`Metadata`](aws_smithy_http::operation::Metadata)), or both. Requests
+//! that exceed the limit are delayed until a token is available, rather than failing.
+//!
+//! Rate limiting is applied to each attempt, so retries and hedged attempts also consume tokens.
+//!
+//! ```no_run
+//! use aws_smithy_client::rate_limit::{RateLimit, RateLimiter};
+//! use aws_smithy_client::{Builder, erase::DynConnector};
+//!
+//! let rate_limiter = RateLimiter::new()
+//!     .with_global_limit(RateLimit::new(100.0))
+//!     .with_operation_limit("PutItem", RateLimit::new(10.0).with_burst(20));
+//! // Replace this with your middleware type
+//! type MyMiddleware = tower::layer::util::Identity;
+//! let client = Builder::<DynConnector, MyMiddleware>::dyn_https()
+//!     .rate_limiter(Some(rate_limiter))
+//!     .build();
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use aws_smithy_async::rt::sleep::{AsyncSleep, Sleep};
+use aws_smithy_async::rt::time::{default_time_source, TimeSource};
+use aws_smithy_http::operation::Operation;
+use pin_project_lite::pin_project;
+use tower::{Layer, Service};
+
+/// The rate and burst size of a token bucket
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    requests_per_second: f64,
+    burst: u32,
+}
+
+impl RateLimit {
+    /// Allow `requests_per_second` requests per second on average
+    ///
+    /// The burst size defaults to `requests_per_second`, rounded up.
+    ///
+    /// # Panics
+    /// Panics if `requests_per_second` is not a positive, finite number.
+    pub fn new(requests_per_second: f64) -> Self {
+        assert!(
+            requests_per_second.is_finite() && requests_per_second > 0.0,
+            "requests_per_second must be a positive number"
+        );
+        Self {
+            requests_per_second,
+            burst: requests_per_second.ceil() as u32,
+        }
+    }
+
+    /// Set the number of requests that can be sent at once after the limiter has been idle
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// The average number of requests per second
+    pub fn requests_per_second(&self) -> f64 {
+        self.requests_per_second
+    }
+
+    /// The maximum number of requests that can be sent at once
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    /// Available tokens. This is negative when requests are waiting for tokens.
+    tokens: f64,
+    /// When tokens were last added to the bucket, or `None` if the bucket has never been used
+    last_refill: Option<Instant>,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(BucketState {
+                tokens: limit.burst as f64,
+                last_refill: None,
+            }),
+        }
+    }
+
+    /// Take a token at `now`, returning how long the caller must wait before the token is available
+    ///
+    /// Tokens are reserved in the order that callers arrive, so waiting callers are served
+    /// first-come, first-served.
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let refill = match state.last_refill {
+            Some(last_refill) => {
+                now.saturating_duration_since(last_refill).as_secs_f64()
+                    * self.limit.requests_per_second
+            }
+            None => 0.0,
+        };
+        state.tokens = (state.tokens + refill).min(self.limit.burst as f64) - 1.0;
+        state.last_refill = Some(now);
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.limit.requests_per_second)
+        }
+    }
+
+    /// Return a token that was reserved but never used
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.tokens = (state.tokens + 1.0).min(self.limit.burst as f64);
+    }
+}
+
+/// Tokens reserved for a delayed request
+///
+/// The tokens are returned to their buckets if the reservation is dropped before the request is
+/// sent, so that cancelled requests don't delay later ones.
+#[derive(Debug)]
+struct Reservation {
+    buckets: Vec<Arc<TokenBucket>>,
+}
+
+impl Reservation {
+    /// Keep the reserved tokens because the request was sent
+    fn commit(mut self) {
+        self.buckets.clear();
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        for bucket in &self.buckets {
+            bucket.release();
+        }
+    }
+}
+
+/// Limits the rate of requests sent by a client, globally and per operation
+///
+/// Clones of a `RateLimiter` share their token buckets, so a single limiter can be shared
+/// between clients.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    global: Option<Arc<TokenBucket>>,
+    operations: HashMap<String, Arc<TokenBucket>>,
+    time_source: Arc<dyn TimeSource>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            global: None,
+            operations: HashMap::new(),
+            time_source: default_time_source(),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Create a rate limiter without any limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the source of the current time used to refill the token buckets
+    ///
+    /// This should follow the same clock as the client's sleep implementation. By default, the
+    /// time is read from Tokio when the `rt-tokio` feature is enabled.
+    pub fn with_time_source(mut self, time_source: impl TimeSource + 'static) -> Self {
+        self.time_source = Arc::new(time_source);
+        self
+    }
+
+    /// Limit the rate of all requests
+    pub fn with_global_limit(mut self, limit: RateLimit) -> Self {
+        self.global = Some(Arc::new(TokenBucket::new(limit)));
+        self
+    }
+
+    /// Limit the rate of requests for the operation named `operation`
+    ///
+    /// Requests for the operation are subject to both this limit and the global limit, if any.
+    pub fn with_operation_limit(mut self, operation: impl Into<String>, limit: RateLimit) -> Self {
+        self.operations
+            .insert(operation.into(), Arc::new(TokenBucket::new(limit)));
+        self
+    }
+
+    /// Reserve tokens for a request, returning how long the request must be delayed
+    fn reserve(&self, operation: Option<&str>) -> (Duration, Reservation) {
+        let now = self.time_source.now();
+        let buckets: Vec<_> = self
+            .global
+            .iter()
+            .chain(operation.and_then(|name| self.operations.get(name)))
+            .cloned()
+            .collect();
+        let delay = buckets
+            .iter()
+            .map(|bucket| bucket.reserve(now))
+            .max()
+            .unwrap_or_default();
+        (delay, Reservation { buckets })
+    }
+}
+
+/// A layer that wraps services in a [`RateLimitService`]
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct RateLimitLayer {
+    limiter: Option<RateLimiter>,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+}
+
+impl RateLimitLayer {
+    /// Create a new `RateLimitLayer`
+    ///
+    /// Requests are never delayed without a sleep implementation.
+    pub fn new(limiter: Option<RateLimiter>, sleep_impl: Option<Arc<dyn AsyncSleep>>) -> Self {
+        Self {
+            limiter,
+            sleep_impl,
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+            sleep_impl: self.sleep_impl.clone(),
+        }
+    }
+}
+
+/// A service that delays requests that exceed the limits of a [`RateLimiter`]
+#[derive(Clone, Debug)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Option<RateLimiter>,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+}
+
+pin_project! {
+    /// Future returned by [`RateLimitService`]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    // This allow is needed because otherwise Clippy will get mad we didn't document the
+    // generated RateLimitFutureProj
+    #[allow(missing_docs)]
+    #[project = RateLimitFutureProj]
+    pub enum RateLimitFuture<S, H, R, F> {
+        /// The request is waiting for a token
+        Delayed {
+            delay: Sleep,
+            reservation: Option<Reservation>,
+            service: Option<S>,
+            request: Option<Operation<H, R>>,
+        },
+        /// The request has been sent
+        Sent {
+            #[pin]
+            future: F,
+        },
+    }
+}
+
+impl<S, H, R, F> Future for RateLimitFuture<S, H, R, F>
+where
+    S: Service<Operation<H, R>, Future = F>,
+    F: Future<Output = Result<S::Response, S::Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            let future = match self.as_mut().project() {
+                RateLimitFutureProj::Delayed {
+                    delay,
+                    reservation,
+                    service,
+                    request,
+                } => {
+                    if Pin::new(delay).poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                    if let Some(reservation) = reservation.take() {
+                        reservation.commit();
+                    }
+                    let mut service = service.take().expect("polled after completion");
+                    service.call(request.take().expect("polled after completion"))
+                }
+                RateLimitFutureProj::Sent { future } => return future.poll(cx),
+            };
+            self.set(RateLimitFuture::Sent { future });
+        }
+    }
+}
+
+impl<H, R, S> Service<Operation<H, R>> for RateLimitService<S>
+where
+    S: Service<Operation<H, R>> + Clone,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = RateLimitFuture<S, H, R, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Operation<H, R>) -> Self::Future {
+        let reservation = match (&self.limiter, &self.sleep_impl) {
+            (Some(limiter), Some(_)) => {
+                Some(limiter.reserve(req.metadata().map(|metadata| metadata.name())))
+            }
+            _ => None,
+        };
+        match (&self.sleep_impl, reservation) {
+            (Some(sleep_impl), Some((delay, reservation))) if delay > Duration::ZERO => {
+                tracing::debug!(
+                    "request exceeds the client rate limit; delaying by {:?}",
+                    delay
+                );
+                // take the service that was driven to readiness, leaving a clone in its place
+                let clone = self.inner.clone();
+                let service = std::mem::replace(&mut self.inner, clone);
+                RateLimitFuture::Delayed {
+                    delay: sleep_impl.sleep(delay),
+                    reservation: Some(reservation),
+                    service: Some(service),
+                    request: Some(req),
+                }
+            }
+            (_, reservation) => {
+                if let Some((_, reservation)) = reservation {
+                    reservation.commit();
+                }
+                RateLimitFuture::Sent {
+                    future: self.inner.call(req),
+                }
+            }
+        }
+    }
+}
+
+/// A layer that delays the first attempt of a request by a fixed duration
+///
+/// This is used to apply the delay required by the retry policy's adaptive rate limiter. Unlike
+/// [`RateLimitLayer`], it is placed outside of the retry layer, so that retries aren't delayed twice.
+#[derive(Debug)]
+pub(crate) struct InitialAttemptDelayLayer {
+    delay: Option<Duration>,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+}
+
+impl InitialAttemptDelayLayer {
+    pub(crate) fn new(delay: Option<Duration>, sleep_impl: Option<Arc<dyn AsyncSleep>>) -> Self {
+        Self { delay, sleep_impl }
+    }
+}
+
+impl<S> Layer<S> for InitialAttemptDelayLayer {
+    type Service = InitialAttemptDelayService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InitialAttemptDelayService {
+            inner,
+            delay: self.delay,
+            sleep_impl: self.sleep_impl.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct InitialAttemptDelayService<S> {
+    inner: S,
+    delay: Option<Duration>,
+    sleep_impl: Option<Arc<dyn AsyncSleep>>,
+}
+
+impl<H, R, S> Service<Operation<H, R>> for InitialAttemptDelayService<S>
+where
+    S: Service<Operation<H, R>> + Clone,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = RateLimitFuture<S, H, R, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Operation<H, R>) -> Self::Future {
+        match (&self.sleep_impl, self.delay.take()) {
+            (Some(sleep_impl), Some(delay)) if delay > Duration::ZERO => {
+                // take the service that was driven to readiness, leaving a clone in its place
+                let clone = self.inner.clone();
+                let service = std::mem::replace(&mut self.inner, clone);
+                RateLimitFuture::Delayed {
+                    delay: sleep_impl.sleep(delay),
+                    reservation: None,
+                    service: Some(service),
+                    request: Some(req),
+                }
+            }
+            _ => RateLimitFuture::Sent {
+                future: self.inner.call(req),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RateLimit, RateLimitLayer, RateLimiter};
+    use aws_smithy_async::rt::sleep::{AsyncSleep, Sleep};
+    use aws_smithy_async::rt::time::TokioTimeSource;
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::operation::{Metadata, Operation, Request};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tower::{service_fn, Layer, Service, ServiceExt};
+
+    /// Records requested delays without waiting
+    #[derive(Debug, Default)]
+    struct RecordingSleep(Mutex<Vec<Duration>>);
+
+    impl AsyncSleep for RecordingSleep {
+        fn sleep(&self, duration: Duration) -> Sleep {
+            self.0.lock().unwrap().push(duration);
+            Sleep::new(std::future::ready(()))
+        }
+    }
+
+    fn operation(name: &'static str) -> Operation<(), ()> {
+        Operation::new(Request::new(http::Request::new(SdkBody::empty())), ())
+            .with_metadata(Metadata::new(name, "test-service"))
+    }
+
+    async fn send(limiter: &RateLimiter, sleep: &Arc<RecordingSleep>, name: &'static str) {
+        let layer = RateLimitLayer::new(Some(limiter.clone()), Some(sleep.clone() as _));
+        let svc = layer.layer(service_fn(|_: Operation<(), ()>| async { Ok::<_, ()>(()) }));
+        svc.oneshot(operation(name)).await.unwrap();
+    }
+
+    fn assert_delays(sleep: &RecordingSleep, expected: &[Duration]) {
+        assert_eq!(sleep.0.lock().unwrap().as_slice(), expected);
+    }
+
+    /// A limiter that follows the paused Tokio clock, so that time only passes when advanced
+    fn limiter() -> RateLimiter {
+        RateLimiter::new().with_time_source(TokioTimeSource::new())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_over_the_limit_are_delayed() {
+        let limiter = limiter().with_global_limit(RateLimit::new(1.0).with_burst(2));
+        let sleep = Arc::new(RecordingSleep::default());
+        for _ in 0..4 {
+            send(&limiter, &sleep, "A").await;
+        }
+        // the burst is sent immediately, then one request per second
+        assert_delays(&sleep, &[Duration::from_secs(1), Duration::from_secs(2)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn operation_limits_only_apply_to_their_operation() {
+        let limiter = limiter().with_operation_limit("A", RateLimit::new(2.0));
+        let sleep = Arc::new(RecordingSleep::default());
+        for _ in 0..3 {
+            send(&limiter, &sleep, "A").await;
+            send(&limiter, &sleep, "B").await;
+        }
+        assert_delays(&sleep, &[Duration::from_millis(500)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn global_and_operation_limits_are_combined() {
+        let limiter = limiter()
+            .with_global_limit(RateLimit::new(1.0))
+            .with_operation_limit("A", RateLimit::new(0.5));
+        let sleep = Arc::new(RecordingSleep::default());
+        send(&limiter, &sleep, "A").await;
+        send(&limiter, &sleep, "B").await;
+        send(&limiter, &sleep, "A").await;
+        assert_delays(&sleep, &[Duration::from_secs(1), Duration::from_secs(2)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn buckets_are_refilled_with_the_time_source() {
+        let limiter = limiter().with_global_limit(RateLimit::new(1.0));
+        let sleep = Arc::new(RecordingSleep::default());
+        send(&limiter, &sleep, "A").await;
+        tokio::time::advance(Duration::from_millis(500)).await;
+        send(&limiter, &sleep, "A").await;
+        assert_delays(&sleep, &[Duration::from_millis(500)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_requests_return_their_tokens() {
+        let limiter = limiter().with_global_limit(RateLimit::new(1.0));
+        let sleep = Arc::new(RecordingSleep::default());
+        send(&limiter, &sleep, "A").await;
+
+        let layer = RateLimitLayer::new(Some(limiter.clone()), Some(sleep.clone() as _));
+        let mut svc = layer.layer(service_fn(|_: Operation<(), ()>| async { Ok::<_, ()>(()) }));
+        let cancelled = svc.ready().await.unwrap().call(operation("A"));
+        drop(cancelled);
+
+        // the cancelled request's token is available again, so this request only waits for one
+        send(&limiter, &sleep, "A").await;
+        assert_delays(&sleep, &[Duration::from_secs(1), Duration::from_secs(1)]);
+    }
+}
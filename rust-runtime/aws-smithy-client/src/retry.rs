@@ -15,7 +15,21 @@
 //! - [`RetryHandler`]: A request-scoped retry policy, backed by request-local state and shared
 //!   state contained within [`Standard`].
 //! - [`Config`]: Static configuration (max attempts, max backoff etc.)
+//! - [`Backoff`](backoff::Backoff): Strategy for how long to wait between attempts. Some common
+//!   strategies are provided in the [`backoff`] module.
+//! - [`RetryClassifier`]: Client-wide retry classification that supplements the classifier of each
+//!   individual operation.
+//! - [`RetryConfigOverride`]: Per-operation overrides of the client's [`Config`], stored in the
+//!   operation's property bag.
+//! - [`HedgeQuota`]: Handle to the retry quota of a [`Standard`] policy, consumed by
+//!   [hedged attempts](crate::hedge).
+//!
+//! When the [adaptive retry mode](RetryMode::Adaptive) is configured, [`Standard`] additionally
+//! maintains a client-side rate limiter. Once a throttling error has been received, every attempt
+//! (including the initial attempt of a request) must first acquire a token from that rate limiter,
+//! which reduces the rate at which requests are sent to a throttled service.
 
+use std::fmt::Debug;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
@@ -24,12 +38,19 @@ use std::time::Duration;
 use crate::{SdkError, SdkSuccess};
 
 use aws_smithy_async::rt::sleep::AsyncSleep;
-use aws_smithy_http::operation::Operation;
-use aws_smithy_http::retry::ClassifyResponse;
-use aws_smithy_types::retry::{ErrorKind, RetryKind};
-
+use aws_smithy_async::rt::time::{default_time_source, TimeSource};
+use aws_smithy_http::operation::{self, Operation};
+use aws_smithy_http::result::{Attempt, AttemptHistory};
+use aws_smithy_http::retry::{raw_response, ClassifyResponse, HttpStatusCodeClassifier};
+use aws_smithy_types::retry::{ErrorKind, RetryKind, RetryMode};
+
+use backoff::Backoff;
+use rate_limiter::ClientRateLimiter;
 use tracing::Instrument;
 
+pub mod backoff;
+mod rate_limiter;
+
 /// A policy instantiator.
 ///
 /// Implementors are essentially "policy factories" that can produce a new instance of a retry
@@ -44,6 +65,40 @@ where
 
     /// Create a new policy mechanism instance.
     fn new_request_policy(&self, sleep_impl: Option<Arc<dyn AsyncSleep>>) -> Self::Policy;
+
+    /// Acquire permission to send the initial attempt of a new request.
+    ///
+    /// Policies that perform client-side rate limiting return the duration that the client must
+    /// wait before sending the request. By default, requests are sent immediately.
+    fn acquire_initial_attempt(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns the quota that [hedged attempts](crate::hedge) must acquire before being sent.
+    ///
+    /// Policies without a retry quota return `None`, which allows hedged attempts to be sent
+    /// without limit.
+    fn hedge_quota(&self) -> Option<HedgeQuota> {
+        None
+    }
+}
+
+/// A retry classifier that applies to every request made by a client
+///
+/// Client-wide classifiers are consulted for failed attempts that the operation's own
+/// [`ClassifyResponse`] implementation doesn't retry; the first classifier to return `Some` decides
+/// how the attempt is retried. Since they apply to every operation, client-wide classifiers only have
+/// access to the raw response (if one was received). To classify modeled errors, add a classifier to
+/// the individual operation with [`Operation::with_retry_classifier`].
+pub trait RetryClassifier: Debug + Send + Sync {
+    /// Classify a failed attempt, returning `None` to defer to the next classifier
+    fn classify_retry(&self, response: Option<&operation::Response>) -> Option<RetryKind>;
+}
+
+impl RetryClassifier for HttpStatusCodeClassifier {
+    fn classify_retry(&self, response: Option<&operation::Response>) -> Option<RetryKind> {
+        self.classify_raw(response?)
+    }
 }
 
 /// Retry Policy Configuration
@@ -53,6 +108,7 @@ where
 /// Currently these fields are private and no setters provided. As needed, this configuration will become user-modifiable in the future..
 #[derive(Clone, Debug)]
 pub struct Config {
+    mode: RetryMode,
     initial_retry_tokens: usize,
     retry_cost: usize,
     no_retry_increment: usize,
@@ -61,6 +117,9 @@ pub struct Config {
     initial_backoff: Duration,
     max_backoff: Duration,
     base: fn() -> f64,
+    backoff: Option<Arc<dyn Backoff>>,
+    retry_classifiers: Vec<Arc<dyn RetryClassifier>>,
+    time_source: Arc<dyn TimeSource>,
 }
 
 impl Config {
@@ -102,11 +161,74 @@ impl Config {
         self.initial_backoff = initial_backoff;
         self
     }
+
+    /// Override the retry mode
+    ///
+    /// In [adaptive](RetryMode::Adaptive) mode, the client will delay requests after it gets
+    /// throttled in order to reduce the rate at which requests are sent to the service.
+    pub fn with_retry_mode(mut self, mode: RetryMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Override the maximum backoff of 20 seconds
+    ///
+    /// The backoff before each retry will never exceed this duration, regardless of the
+    /// [backoff strategy](Config::with_backoff).
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Override the backoff strategy
+    ///
+    /// By default, exponential backoff with jitter is used. When a custom strategy is set,
+    /// [`with_base`](Config::with_base) and [`with_initial_backoff`](Config::with_initial_backoff)
+    /// have no effect.
+    ///
+    /// ```no_run
+    /// use aws_smithy_client::retry::Config;
+    /// use aws_smithy_client::retry::backoff::ConstantBackoff;
+    /// use std::time::Duration;
+    /// let conf = Config::default().with_backoff(ConstantBackoff::new(Duration::from_millis(100)));
+    /// ```
+    pub fn with_backoff(mut self, backoff: impl Backoff + 'static) -> Self {
+        self.backoff = Some(Arc::new(backoff));
+        self
+    }
+
+    /// Add a client-wide [retry classifier](RetryClassifier)
+    ///
+    /// Classifiers are consulted in the order they were added.
+    ///
+    /// ```no_run
+    /// use aws_smithy_client::retry::Config;
+    /// use aws_smithy_http::retry::HttpStatusCodeClassifier;
+    /// use aws_smithy_types::retry::ErrorKind;
+    /// let conf = Config::default().with_retry_classifier(
+    ///     HttpStatusCodeClassifier::new().retry_on(409, ErrorKind::ThrottlingError),
+    /// );
+    /// ```
+    pub fn with_retry_classifier(mut self, classifier: impl RetryClassifier + 'static) -> Self {
+        self.retry_classifiers.push(Arc::new(classifier));
+        self
+    }
+
+    /// Override the time source used by the [adaptive](RetryMode::Adaptive) retry mode
+    ///
+    /// The client-side rate limiter measures the time between requests with this time source. It
+    /// should follow the same clock as the client's sleep implementation, which is the case for the
+    /// default time source when the `rt-tokio` feature is enabled.
+    pub fn with_time_source(mut self, time_source: impl TimeSource + 'static) -> Self {
+        self.time_source = Arc::new(time_source);
+        self
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            mode: RetryMode::Standard,
             initial_retry_tokens: INITIAL_RETRY_TOKENS,
             retry_cost: RETRY_COST,
             no_retry_increment: 1,
@@ -116,6 +238,9 @@ impl Default for Config {
             // by default, use a random base for exponential backoff
             base: fastrand::f64,
             initial_backoff: Duration::from_secs(1),
+            backoff: None,
+            retry_classifiers: Vec::new(),
+            time_source: default_time_source(),
         }
     }
 }
@@ -123,22 +248,87 @@ impl Default for Config {
 impl From<aws_smithy_types::retry::RetryConfig> for Config {
     fn from(conf: aws_smithy_types::retry::RetryConfig) -> Self {
         Self::default()
+            .with_retry_mode(conf.mode())
             .with_max_attempts(conf.max_attempts())
             .with_initial_backoff(conf.initial_backoff())
     }
 }
 
+/// Per-operation overrides of the client's retry [`Config`]
+///
+/// Insert a `RetryConfigOverride` into an operation's property bag to change how that operation is
+/// retried without affecting other requests made by the same client. Settings that aren't overridden
+/// fall back to the client's configuration.
+///
+/// ```no_run
+/// use aws_smithy_client::retry::RetryConfigOverride;
+/// use aws_smithy_http::body::SdkBody;
+/// use aws_smithy_http::operation::{Operation, Request};
+/// use std::time::Duration;
+/// let mut op = Operation::new(Request::new(http::Request::new(SdkBody::empty())), ());
+/// op.properties_mut().insert(
+///     RetryConfigOverride::new()
+///         .with_max_attempts(5)
+///         .with_initial_backoff(Duration::from_millis(50)),
+/// );
+/// ```
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RetryConfigOverride {
+    max_attempts: Option<u32>,
+    initial_backoff: Option<Duration>,
+}
+
+impl RetryConfigOverride {
+    /// Create an override that doesn't override anything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the maximum number of attempts (including the initial attempt)
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Override the initial backoff. See [`Config::with_initial_backoff`].
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = Some(initial_backoff);
+        self
+    }
+
+    /// Returns the overridden maximum number of attempts, if any
+    pub fn max_attempts(&self) -> Option<u32> {
+        self.max_attempts
+    }
+
+    /// Returns the overridden initial backoff, if any
+    pub fn initial_backoff(&self) -> Option<Duration> {
+        self.initial_backoff
+    }
+
+    fn apply_to(&self, mut config: Config) -> Config {
+        if let Some(max_attempts) = self.max_attempts {
+            config.max_attempts = max_attempts;
+        }
+        if let Some(initial_backoff) = self.initial_backoff {
+            config.initial_backoff = initial_backoff;
+        }
+        config
+    }
+}
+
 const MAX_ATTEMPTS: u32 = 3;
 const INITIAL_RETRY_TOKENS: usize = 500;
 const RETRY_COST: usize = 5;
 
 /// Manage retries for a service
 ///
-/// An implementation of the `standard` AWS retry strategy as specified in the SEP. A `Strategy` is scoped to a client.
-/// For an individual request, call [`Standard::new_request_policy()`](Standard::new_request_policy)
+/// An implementation of the `standard` and `adaptive` AWS retry strategies as specified in the SEP. A `Strategy` is
+/// scoped to a client. For an individual request, call [`Standard::new_request_policy()`](Standard::new_request_policy)
 ///
-/// In the future, adding support for the adaptive retry strategy will be added by adding a `TokenBucket` to
-/// `CrossRequestRetryState`
+/// The adaptive retry strategy is selected with [`Config::with_retry_mode`]. It is implemented by a client-side
+/// rate limiter stored in `CrossRequestRetryState`.
 /// Its main functionality is via `new_request_policy` which creates a `RetryHandler` to manage the retry for
 /// an individual request.
 #[derive(Debug, Clone)]
@@ -151,7 +341,10 @@ impl Standard {
     /// Construct a new standard retry policy from the given policy configuration.
     pub fn new(config: Config) -> Self {
         Self {
-            shared_state: CrossRequestRetryState::new(config.initial_retry_tokens),
+            shared_state: CrossRequestRetryState::new(
+                config.initial_retry_tokens,
+                config.time_source.clone(),
+            ),
             config,
         }
     }
@@ -174,6 +367,19 @@ impl NewRequestPolicy for Standard {
             sleep_impl,
         }
     }
+
+    fn acquire_initial_attempt(&self) -> Option<Duration> {
+        self.shared_state
+            .rate_limiter(&self.config)?
+            .acquire_permission_to_send_request()
+    }
+
+    fn hedge_quota(&self) -> Option<HedgeQuota> {
+        Some(HedgeQuota {
+            shared: self.shared_state.clone(),
+            config: self.config.clone(),
+        })
+    }
 }
 
 impl Default for Standard {
@@ -186,6 +392,7 @@ impl Default for Standard {
 struct RequestLocalRetryState {
     attempts: u32,
     last_quota_usage: Option<usize>,
+    last_backoff: Option<Duration>,
 }
 
 impl Default for RequestLocalRetryState {
@@ -194,6 +401,7 @@ impl Default for RequestLocalRetryState {
             // Starts at one to account for the initial request that failed and warranted a retry
             attempts: 1,
             last_quota_usage: None,
+            last_backoff: None,
         }
     }
 }
@@ -214,15 +422,25 @@ struct RetryPartition(Cow<'static, str>); */
 #[derive(Clone, Debug)]
 struct CrossRequestRetryState {
     quota_available: Arc<Mutex<usize>>,
+    rate_limiter: ClientRateLimiter,
 }
 
 // clippy is upset that we didn't use AtomicUsize here, but doing so makes the code
 // significantly more complicated for negligible benefit.
 #[allow(clippy::mutex_atomic)]
 impl CrossRequestRetryState {
-    pub fn new(initial_quota: usize) -> Self {
+    pub fn new(initial_quota: usize, time_source: Arc<dyn TimeSource>) -> Self {
         Self {
             quota_available: Arc::new(Mutex::new(initial_quota)),
+            rate_limiter: ClientRateLimiter::new(time_source),
+        }
+    }
+
+    /// Returns the client-side rate limiter if the adaptive retry mode is enabled
+    fn rate_limiter(&self, config: &Config) -> Option<&ClientRateLimiter> {
+        match config.mode {
+            RetryMode::Adaptive => Some(&self.rate_limiter),
+            _ => None,
         }
     }
 
@@ -236,12 +454,16 @@ impl CrossRequestRetryState {
     /// If quota is available, the amount of quota consumed is returned
     /// If no quota is available, `None` is returned.
     fn quota_acquire(&self, err: &ErrorKind, config: &Config) -> Option<usize> {
-        let mut quota = self.quota_available.lock().unwrap();
         let retry_cost = if err == &ErrorKind::TransientError {
             config.timeout_retry_cost
         } else {
             config.retry_cost
         };
+        self.quota_take(retry_cost)
+    }
+
+    fn quota_take(&self, retry_cost: usize) -> Option<usize> {
+        let mut quota = self.quota_available.lock().unwrap();
         if retry_cost > *quota {
             None
         } else {
@@ -251,6 +473,33 @@ impl CrossRequestRetryState {
     }
 }
 
+/// Handle to the retry quota shared by all requests made with a [`Standard`] retry policy
+///
+/// Hedged attempts are paid for from the same quota as retries, so that a degraded service can't
+/// cause hedging to multiply the load placed on it.
+#[derive(Clone, Debug)]
+pub struct HedgeQuota {
+    shared: CrossRequestRetryState,
+    config: Config,
+}
+
+impl HedgeQuota {
+    /// Acquire quota for a hedged attempt, returning the amount acquired
+    pub(crate) fn acquire(&self) -> Option<usize> {
+        self.shared.quota_take(self.config.retry_cost)
+    }
+
+    /// Return quota acquired for a hedged attempt once the request has succeeded
+    pub(crate) fn release(&self, amount: usize) {
+        self.shared.quota_release(Some(amount), &self.config);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn available(&self) -> usize {
+        *self.shared.quota_available.lock().unwrap()
+    }
+}
+
 type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 
 /// RetryHandler
@@ -293,25 +542,30 @@ impl RetryHandler {
     /// If no retry is specified, this function returns None
     fn should_retry_error(&self, error_kind: &ErrorKind) -> Option<(Self, Duration)> {
         let quota_used = {
-            if self.local.attempts == self.config.max_attempts {
+            if self.local.attempts >= self.config.max_attempts {
                 return None;
             }
             self.shared.quota_acquire(error_kind, &self.config)?
         };
-        let backoff = calculate_exponential_backoff(
-            // Generate a random base multiplier to create jitter
-            (self.config.base)(),
-            // Get the backoff time multiplier in seconds (with fractional seconds)
-            self.config.initial_backoff.as_secs_f64(),
-            // `self.local.attempts` tracks number of requests made including the initial request
-            // The initial attempt shouldn't count towards backoff calculations so we subtract it
-            self.local.attempts - 1,
-        );
-        let backoff = Duration::from_secs_f64(backoff).min(self.config.max_backoff);
+        let backoff = match &self.config.backoff {
+            // `self.local.attempts` tracks number of requests made including the initial request,
+            // which is also the number of the retry attempt we're about to make
+            Some(backoff) => backoff.backoff(self.local.attempts, self.local.last_backoff),
+            None => Duration::from_secs_f64(calculate_exponential_backoff(
+                // Generate a random base multiplier to create jitter
+                (self.config.base)(),
+                // Get the backoff time multiplier in seconds (with fractional seconds)
+                self.config.initial_backoff.as_secs_f64(),
+                // The initial attempt shouldn't count towards backoff calculations so we subtract it
+                self.local.attempts - 1,
+            )),
+        };
+        let backoff = backoff.min(self.config.max_backoff);
         let next = RetryHandler {
             local: RequestLocalRetryState {
                 attempts: self.local.attempts + 1,
                 last_quota_usage: Some(quota_used),
+                last_backoff: Some(backoff),
             },
             shared: self.shared.clone(),
             config: self.config.clone(),
@@ -335,7 +589,57 @@ impl RetryHandler {
         }
     }
 
-    fn retry_for(&self, retry_kind: RetryKind) -> Option<BoxFuture<Self>> {
+    /// Classify an attempt with the operation's classifier, falling back to the client-wide
+    /// [retry classifiers](RetryClassifier) if the operation doesn't retry it
+    fn classify<T, E>(
+        &self,
+        classifier: &impl ClassifyResponse<SdkSuccess<T>, SdkError<E>>,
+        result: Result<&SdkSuccess<T>, &SdkError<E>>,
+    ) -> RetryKind {
+        match classifier.classify(result) {
+            RetryKind::UnretryableFailure => self
+                .classify_with_client_classifiers(result)
+                .unwrap_or(RetryKind::UnretryableFailure),
+            retry_kind => retry_kind,
+        }
+    }
+
+    /// Classify a failed attempt with the client-wide [retry classifiers](RetryClassifier)
+    fn classify_with_client_classifiers<T, E>(
+        &self,
+        result: Result<&SdkSuccess<T>, &SdkError<E>>,
+    ) -> Option<RetryKind> {
+        if result.is_ok() {
+            return None;
+        }
+        let raw = raw_response(result);
+        self.config
+            .retry_classifiers
+            .iter()
+            .find_map(|classifier| classifier.classify_retry(raw))
+    }
+
+    /// Adjust the client's send rate according to the outcome of the last attempt
+    ///
+    /// This is a no-op unless the adaptive retry mode is enabled.
+    fn update_rate_limiter(&self, retry_kind: &RetryKind) {
+        if let Some(rate_limiter) = self.shared.rate_limiter(&self.config) {
+            rate_limiter.update_rate_limiter(matches!(
+                retry_kind,
+                RetryKind::Error(ErrorKind::ThrottlingError)
+            ));
+        }
+    }
+
+    /// Returns a future that resolves to the handler for the next attempt, along with how long the
+    /// future backs off for, if the request should be retried
+    ///
+    /// Delays imposed by the adaptive rate limiter are recorded into `attempt_history`.
+    fn retry_for(
+        &self,
+        retry_kind: RetryKind,
+        attempt_history: Option<AttemptHistory>,
+    ) -> Option<(BoxFuture<Self>, Duration)> {
         let (next, dur) = self.should_retry(&retry_kind)?;
 
         let sleep = match &self.sleep_impl {
@@ -355,12 +659,28 @@ impl RetryHandler {
             dur
         );
         let sleep_future = sleep.sleep(dur);
+        let sleep = sleep.clone();
+        let rate_limiter = self.shared.rate_limiter(&self.config).cloned();
         let fut = async move {
             sleep_future.await;
+            // The rate limiter must be consulted after backing off since tokens are refilled
+            // while the backoff elapses.
+            if let Some(delay) =
+                rate_limiter.and_then(|limiter| limiter.acquire_permission_to_send_request())
+            {
+                tracing::debug!(
+                    "client is being rate limited; delaying retry by {:?}",
+                    delay
+                );
+                if let Some(history) = attempt_history {
+                    history.record_rate_limit_delay(delay);
+                }
+                sleep.sleep(delay).await;
+            }
             next
         }
         .instrument(tracing::info_span!("retry", kind = &debug(retry_kind)));
-        Some(check_send(Box::pin(fut)))
+        Some((check_send(Box::pin(fut)), dur))
     }
 }
 
@@ -377,9 +697,25 @@ where
         req: &Operation<Handler, R>,
         result: Result<&SdkSuccess<T>, &SdkError<E>>,
     ) -> Option<Self::Future> {
-        let policy = req.retry_policy();
-        let retry_kind = policy.classify(result);
-        self.retry_for(retry_kind)
+        let retry_kind = self.classify(req.retry_policy(), result);
+        self.update_rate_limiter(&retry_kind);
+        let attempt_history = req.properties().get::<AttemptHistory>().cloned();
+        let retry = match req.properties().get::<RetryConfigOverride>() {
+            Some(config_override) => RetryHandler {
+                config: config_override.apply_to(self.config.clone()),
+                ..self.clone()
+            }
+            .retry_for(retry_kind.clone(), attempt_history.clone()),
+            None => self.retry_for(retry_kind.clone(), attempt_history.clone()),
+        };
+        if let Some(history) = attempt_history {
+            let attempt = Attempt::new(result, retry_kind);
+            history.record_attempt(match &retry {
+                Some((_, backoff)) => attempt.with_backoff(*backoff),
+                None => attempt,
+            });
+        }
+        retry.map(|(future, _)| future)
     }
 
     fn clone_request(&self, req: &Operation<Handler, R>) -> Option<Operation<Handler, R>> {
@@ -393,16 +729,37 @@ fn check_send<T: Send>(t: T) -> T {
 
 #[cfg(test)]
 mod test {
-    use super::{calculate_exponential_backoff, Config, NewRequestPolicy, RetryHandler, Standard};
-
-    use aws_smithy_types::retry::{ErrorKind, RetryKind};
-
+    use super::backoff::ConstantBackoff;
+    use super::{
+        calculate_exponential_backoff, Config, NewRequestPolicy, RetryConfigOverride, RetryHandler,
+        Standard,
+    };
+
+    use aws_smithy_async::rt::sleep::{AsyncSleep, TokioSleep};
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::operation;
+    use aws_smithy_http::result::{AttemptHistory, SdkError, SdkSuccess};
+    use aws_smithy_http::retry::{ClassifyResponse, HttpStatusCodeClassifier};
+    use aws_smithy_types::retry::{ErrorKind, RetryKind, RetryMode};
+
+    use std::sync::Arc;
     use std::time::Duration;
+    use tower::retry::Policy;
 
     fn test_config() -> Config {
         Config::default().with_base(|| 1_f64)
     }
 
+    /// Classifies every attempt as the given kind
+    #[derive(Clone)]
+    struct ClassifyAs(RetryKind);
+
+    impl<T, E> ClassifyResponse<T, E> for ClassifyAs {
+        fn classify(&self, _: Result<&T, &E>) -> RetryKind {
+            self.0.clone()
+        }
+    }
+
     #[test]
     fn retry_handler_send_sync() {
         fn must_be_send_sync<T: Send + Sync>() {}
@@ -566,6 +923,146 @@ mod test {
         assert_eq!(policy.retry_quota(), 480);
     }
 
+    #[test]
+    fn custom_backoff_is_capped_by_max_backoff() {
+        let conf = test_config()
+            .with_max_attempts(4)
+            .with_max_backoff(Duration::from_secs(3))
+            .with_backoff(ConstantBackoff::new(Duration::from_secs(2)));
+        let policy = Standard::new(conf).new_request_policy(None);
+        let (_, dur) = policy
+            .should_retry(&RetryKind::Error(ErrorKind::ServerError))
+            .expect("should retry");
+        assert_eq!(dur, Duration::from_secs(2));
+
+        let conf = test_config()
+            .with_max_backoff(Duration::from_secs(1))
+            .with_backoff(ConstantBackoff::new(Duration::from_secs(2)));
+        let policy = Standard::new(conf).new_request_policy(None);
+        let (_, dur) = policy
+            .should_retry(&RetryKind::Error(ErrorKind::ServerError))
+            .expect("should retry");
+        assert_eq!(dur, Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn config_override_changes_max_attempts_and_backoff() {
+        let policy = Standard::new(test_config())
+            .new_request_policy(Some(Arc::new(TokioSleep::new()) as Arc<dyn AsyncSleep>));
+        let mut op = operation::Operation::new(
+            operation::Request::new(http::Request::new(SdkBody::empty())),
+            (),
+        )
+        .with_retry_policy(ClassifyAs(RetryKind::Error(ErrorKind::ServerError)));
+        op.properties_mut().insert(
+            RetryConfigOverride::new()
+                .with_max_attempts(2)
+                .with_initial_backoff(Duration::from_millis(100)),
+        );
+        let history = AttemptHistory::new();
+        op.properties_mut().insert(history.clone());
+        let error = SdkError::<()>::ServiceError {
+            err: (),
+            raw: operation::Response::new(http::Response::new(SdkBody::empty())),
+        };
+
+        let policy = Policy::<_, SdkSuccess<()>, _>::retry(&policy, &op, Err(&error))
+            .expect("should retry")
+            .await;
+        assert_eq!(
+            history.attempts()[0].backoff(),
+            Some(Duration::from_millis(100))
+        );
+
+        let no_retry = Policy::<_, SdkSuccess<()>, _>::retry(&policy, &op, Err(&error));
+        assert!(no_retry.is_none(), "only two attempts are allowed");
+    }
+
+    #[test]
+    fn client_classifiers_are_a_fallback() {
+        let conf = test_config().with_retry_classifier(
+            HttpStatusCodeClassifier::new().retry_on(409, ErrorKind::ThrottlingError),
+        );
+        let policy = Standard::new(conf).new_request_policy(None);
+        let error = |status: u16| -> SdkError<()> {
+            SdkError::ServiceError {
+                err: (),
+                raw: operation::Response::new(
+                    http::Response::builder()
+                        .status(status)
+                        .body(SdkBody::empty())
+                        .unwrap(),
+                ),
+            }
+        };
+        assert_eq!(
+            policy.classify_with_client_classifiers::<(), ()>(Err(&error(409))),
+            Some(RetryKind::Error(ErrorKind::ThrottlingError))
+        );
+        assert_eq!(
+            policy.classify_with_client_classifiers::<(), ()>(Err(&error(400))),
+            None
+        );
+        let success = SdkSuccess {
+            raw: operation::Response::new(
+                http::Response::builder()
+                    .status(409)
+                    .body(SdkBody::empty())
+                    .unwrap(),
+            ),
+            parsed: (),
+        };
+        assert_eq!(
+            policy.classify_with_client_classifiers::<(), ()>(Ok(&success)),
+            None,
+            "successful attempts are never classified by client classifiers"
+        );
+
+        let server_error = ClassifyAs(RetryKind::Error(ErrorKind::ServerError));
+        assert_eq!(
+            policy.classify::<(), ()>(&server_error, Err(&error(409))),
+            RetryKind::Error(ErrorKind::ServerError),
+            "the operation's classifier is consulted first"
+        );
+        let unretryable = ClassifyAs(RetryKind::UnretryableFailure);
+        assert_eq!(
+            policy.classify::<(), ()>(&unretryable, Err(&error(409))),
+            RetryKind::Error(ErrorKind::ThrottlingError)
+        );
+        assert_eq!(
+            policy.classify::<(), ()>(&unretryable, Err(&error(400))),
+            RetryKind::UnretryableFailure
+        );
+    }
+
+    #[test]
+    fn adaptive_mode_rate_limits_after_throttling() {
+        let standard = Standard::new(test_config().with_retry_mode(RetryMode::Adaptive));
+        let policy = standard.new_request_policy(None);
+        policy.update_rate_limiter(&RetryKind::Unnecessary);
+        assert_eq!(
+            standard.acquire_initial_attempt(),
+            None,
+            "requests should not be delayed before the client is throttled"
+        );
+
+        policy.update_rate_limiter(&RetryKind::Error(ErrorKind::ThrottlingError));
+        assert!(
+            standard.acquire_initial_attempt().is_some(),
+            "requests should be delayed after the client is throttled"
+        );
+    }
+
+    #[test]
+    fn standard_mode_does_not_rate_limit() {
+        let standard = Standard::new(test_config());
+        let policy = standard.new_request_policy(None);
+        for _ in 0..10 {
+            policy.update_rate_limiter(&RetryKind::Error(ErrorKind::ThrottlingError));
+        }
+        assert_eq!(standard.acquire_initial_attempt(), None);
+    }
+
     #[test]
     fn calculate_exponential_backoff_where_initial_backoff_is_one() {
         let initial_backoff = 1.0;
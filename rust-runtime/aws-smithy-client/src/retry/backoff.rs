@@ -0,0 +1,169 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Backoff strategies for retries
+//!
+//! A [`Backoff`] strategy determines how long to wait before each retry attempt. Regardless of
+//! the strategy, backoff durations are capped at the configured
+//! [maximum backoff](crate::retry::Config::with_max_backoff).
+
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// A strategy for computing how long to wait before retrying a request
+pub trait Backoff: Debug + Send + Sync {
+    /// Returns how long to wait before making the next attempt.
+    ///
+    /// `retry_attempt` is `1` before the first retry, `2` before the second retry, and so on.
+    /// `previous_backoff` is the duration waited before the previous retry, if there was one.
+    fn backoff(&self, retry_attempt: u32, previous_backoff: Option<Duration>) -> Duration;
+}
+
+/// Wait for the same duration before every retry
+#[derive(Clone, Debug)]
+pub struct ConstantBackoff {
+    delay: Duration,
+}
+
+impl ConstantBackoff {
+    /// Create a backoff strategy that always waits for `delay`
+    pub fn new(delay: Duration) -> Self {
+        Self { delay }
+    }
+}
+
+impl Backoff for ConstantBackoff {
+    fn backoff(&self, _retry_attempt: u32, _previous_backoff: Option<Duration>) -> Duration {
+        self.delay
+    }
+}
+
+/// Exponential backoff with optional "full jitter"
+///
+/// Before retry attempt `n`, this waits for `initial_backoff * 2^(n - 1)`. With jitter enabled (the
+/// default), the duration is multiplied by a random value between 0 and 1.
+#[derive(Clone, Debug)]
+pub struct ExponentialBackoff {
+    initial_backoff: Duration,
+    base: fn() -> f64,
+}
+
+impl ExponentialBackoff {
+    /// Create an exponential backoff strategy with jitter
+    pub fn new(initial_backoff: Duration) -> Self {
+        Self {
+            initial_backoff,
+            base: fastrand::f64,
+        }
+    }
+
+    /// Disable jitter so that backoff durations are deterministic
+    pub fn without_jitter(mut self) -> Self {
+        self.base = || 1_f64;
+        self
+    }
+}
+
+impl Backoff for ExponentialBackoff {
+    fn backoff(&self, retry_attempt: u32, _previous_backoff: Option<Duration>) -> Duration {
+        Duration::from_secs_f64(super::calculate_exponential_backoff(
+            (self.base)(),
+            self.initial_backoff.as_secs_f64(),
+            retry_attempt.saturating_sub(1),
+        ))
+    }
+}
+
+/// "Decorrelated jitter" backoff
+///
+/// Each backoff is a random duration between `base_delay` and three times the previous backoff.
+/// This spreads out retries from many clients more evenly than exponential backoff while still
+/// growing the delay between attempts.
+#[derive(Clone, Debug)]
+pub struct DecorrelatedJitterBackoff {
+    base_delay: Duration,
+    rand: fn() -> f64,
+}
+
+impl DecorrelatedJitterBackoff {
+    /// Create a decorrelated jitter backoff strategy that waits at least `base_delay`
+    pub fn new(base_delay: Duration) -> Self {
+        Self {
+            base_delay,
+            rand: fastrand::f64,
+        }
+    }
+
+    #[cfg(test)]
+    fn with_rand(mut self, rand: fn() -> f64) -> Self {
+        self.rand = rand;
+        self
+    }
+}
+
+impl Backoff for DecorrelatedJitterBackoff {
+    fn backoff(&self, _retry_attempt: u32, previous_backoff: Option<Duration>) -> Duration {
+        let base = self.base_delay.as_secs_f64();
+        let upper = previous_backoff
+            .map(|previous| previous.as_secs_f64() * 3.0)
+            .unwrap_or(base)
+            .max(base);
+        Duration::from_secs_f64(base + (self.rand)() * (upper - base))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Backoff, ConstantBackoff, DecorrelatedJitterBackoff, ExponentialBackoff};
+    use std::time::Duration;
+
+    #[test]
+    fn constant_backoff() {
+        let backoff = ConstantBackoff::new(Duration::from_millis(250));
+        for attempt in 1..5 {
+            assert_eq!(
+                backoff.backoff(attempt, Some(Duration::from_secs(10))),
+                Duration::from_millis(250)
+            );
+        }
+    }
+
+    #[test]
+    fn exponential_backoff_without_jitter() {
+        let backoff = ExponentialBackoff::new(Duration::from_millis(100)).without_jitter();
+        let delays: Vec<_> = (1..5)
+            .map(|attempt| backoff.backoff(attempt, None))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(400),
+                Duration::from_millis(800)
+            ]
+        );
+    }
+
+    #[test]
+    fn decorrelated_jitter_grows_from_previous_backoff() {
+        let backoff = DecorrelatedJitterBackoff::new(Duration::from_secs(1)).with_rand(|| 1_f64);
+        assert_eq!(backoff.backoff(1, None), Duration::from_secs(1));
+        assert_eq!(
+            backoff.backoff(2, Some(Duration::from_secs(1))),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            backoff.backoff(3, Some(Duration::from_secs(3))),
+            Duration::from_secs(9)
+        );
+
+        let backoff = DecorrelatedJitterBackoff::new(Duration::from_secs(1)).with_rand(|| 0_f64);
+        assert_eq!(
+            backoff.backoff(3, Some(Duration::from_secs(3))),
+            Duration::from_secs(1)
+        );
+    }
+}
@@ -0,0 +1,310 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Client-side rate limiting for the adaptive retry mode
+//!
+//! The [`ClientRateLimiter`] is a token bucket whose fill rate is adjusted with the CUBIC congestion
+//! control algorithm: when a throttling error is received, the allowed send rate is reduced
+//! multiplicatively; as requests succeed, it recovers along a cubic curve towards (and then beyond)
+//! the rate at which the client was last throttled.
+//!
+//! The token bucket is only enabled after the first throttling error. Until then, requests are
+//! never delayed.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use aws_smithy_async::rt::time::TimeSource;
+
+/// The minimum rate at which the token bucket is refilled (tokens per second)
+const MIN_FILL_RATE: f64 = 0.5;
+/// The minimum capacity of the token bucket
+const MIN_CAPACITY: f64 = 1.0;
+/// Weight of the most recently measured send rate in the smoothed send rate
+const SMOOTH: f64 = 0.8;
+/// How much to scale back the send rate when throttled
+const BETA: f64 = 0.7;
+/// How aggressively the send rate recovers after being throttled
+const SCALE_CONSTANT: f64 = 0.4;
+
+/// Send-rate limiter shared between all requests made with the same retry policy
+///
+/// Cloning a `ClientRateLimiter` produces a handle to the same underlying state.
+#[derive(Clone, Debug)]
+pub(crate) struct ClientRateLimiter {
+    inner: Arc<Mutex<Inner>>,
+    time_source: Arc<dyn TimeSource>,
+    start: Instant,
+}
+
+impl ClientRateLimiter {
+    pub(crate) fn new(time_source: Arc<dyn TimeSource>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner::new(0.0))),
+            start: time_source.now(),
+            time_source,
+        }
+    }
+
+    /// Seconds elapsed since this rate limiter was created
+    fn now(&self) -> f64 {
+        self.time_source
+            .now()
+            .saturating_duration_since(self.start)
+            .as_secs_f64()
+    }
+
+    /// Acquire permission to send a single request attempt
+    ///
+    /// If the request may be sent immediately, `None` is returned. Otherwise, the returned duration
+    /// is how long the caller must wait before sending it.
+    pub(crate) fn acquire_permission_to_send_request(&self) -> Option<Duration> {
+        let now = self.now();
+        self.inner
+            .lock()
+            .unwrap()
+            .acquire(now, 1.0)
+            .map(Duration::from_secs_f64)
+    }
+
+    /// Update the allowed send rate after receiving a response
+    pub(crate) fn update_rate_limiter(&self, is_throttling_error: bool) {
+        let now = self.now();
+        self.inner
+            .lock()
+            .unwrap()
+            .update_sending_rate(now, is_throttling_error);
+    }
+}
+
+/// Rate limiter state
+///
+/// All timestamps are expressed in seconds relative to an arbitrary, fixed starting point.
+#[derive(Debug)]
+struct Inner {
+    /// Rate at which tokens are added to the bucket (tokens per second)
+    fill_rate: f64,
+    /// Maximum number of tokens the bucket can hold
+    max_capacity: f64,
+    /// Number of tokens currently in the bucket. May go negative when a caller was told to wait.
+    current_capacity: f64,
+    /// When the bucket was last refilled
+    last_timestamp: Option<f64>,
+    /// Whether the bucket is enabled. The bucket is enabled by the first throttling error.
+    enabled: bool,
+    /// Smoothed measurement of the rate at which requests are being sent
+    measured_tx_rate: f64,
+    /// Start of the half-second bucket currently used to measure the send rate
+    last_tx_rate_bucket: f64,
+    /// Number of requests sent within the current measurement bucket
+    request_count: u64,
+    /// Send rate at the time of the last throttling error
+    last_max_rate: f64,
+    /// When the last throttling error was received
+    last_throttle_time: f64,
+    /// Time it will take for the send rate to recover to `last_max_rate` after being throttled
+    time_window: f64,
+}
+
+impl Inner {
+    fn new(now: f64) -> Self {
+        Self {
+            fill_rate: 0.0,
+            max_capacity: 0.0,
+            current_capacity: 0.0,
+            last_timestamp: None,
+            enabled: false,
+            measured_tx_rate: 0.0,
+            last_tx_rate_bucket: now.floor(),
+            request_count: 0,
+            last_max_rate: 0.0,
+            last_throttle_time: now,
+            time_window: 0.0,
+        }
+    }
+
+    /// Take `amount` tokens from the bucket, returning how many seconds to wait if the bucket did
+    /// not have enough tokens available.
+    fn acquire(&mut self, now: f64, amount: f64) -> Option<f64> {
+        if !self.enabled {
+            return None;
+        }
+        self.refill(now);
+        let delay = if amount <= self.current_capacity {
+            None
+        } else {
+            Some((amount - self.current_capacity) / self.fill_rate)
+        };
+        self.current_capacity -= amount;
+        delay
+    }
+
+    fn refill(&mut self, now: f64) {
+        if let Some(last_timestamp) = self.last_timestamp {
+            let fill_amount = (now - last_timestamp) * self.fill_rate;
+            self.current_capacity = self.max_capacity.min(self.current_capacity + fill_amount);
+        }
+        self.last_timestamp = Some(now);
+    }
+
+    fn update_bucket_rate(&mut self, now: f64, new_rps: f64) {
+        self.refill(now);
+        self.fill_rate = new_rps.max(MIN_FILL_RATE);
+        self.max_capacity = new_rps.max(MIN_CAPACITY);
+        self.current_capacity = self.current_capacity.min(self.max_capacity);
+    }
+
+    fn update_sending_rate(&mut self, now: f64, is_throttling_error: bool) {
+        self.update_measured_rate(now);
+        let calculated_rate = if is_throttling_error {
+            let rate_to_use = if self.enabled {
+                self.measured_tx_rate.min(self.fill_rate)
+            } else {
+                self.measured_tx_rate
+            };
+            self.last_max_rate = rate_to_use;
+            self.calculate_time_window();
+            self.last_throttle_time = now;
+            self.enabled = true;
+            cubic_throttle(rate_to_use)
+        } else {
+            self.calculate_time_window();
+            self.cubic_success(now)
+        };
+        let new_rate = calculated_rate.min(2.0 * self.measured_tx_rate);
+        self.update_bucket_rate(now, new_rate);
+    }
+
+    fn calculate_time_window(&mut self) {
+        self.time_window = (self.last_max_rate * (1.0 - BETA) / SCALE_CONSTANT).cbrt();
+    }
+
+    fn cubic_success(&self, now: f64) -> f64 {
+        let dt = now - self.last_throttle_time;
+        SCALE_CONSTANT * (dt - self.time_window).powi(3) + self.last_max_rate
+    }
+
+    fn update_measured_rate(&mut self, now: f64) {
+        let time_bucket = (now * 2.0).floor() / 2.0;
+        self.request_count += 1;
+        if time_bucket > self.last_tx_rate_bucket {
+            let current_rate = self.request_count as f64 / (time_bucket - self.last_tx_rate_bucket);
+            self.measured_tx_rate = current_rate * SMOOTH + self.measured_tx_rate * (1.0 - SMOOTH);
+            self.request_count = 0;
+            self.last_tx_rate_bucket = time_bucket;
+        }
+    }
+}
+
+fn cubic_throttle(rate_to_use: f64) -> f64 {
+    rate_to_use * BETA
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cubic_throttle, ClientRateLimiter, Inner};
+    use aws_smithy_async::rt::time::TokioTimeSource;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn assert_approx_eq(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-9,
+            "expected {} but got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn bucket_is_disabled_until_throttled() {
+        let mut state = Inner::new(0.0);
+        for i in 0..100 {
+            assert_eq!(state.acquire(i as f64 / 100.0, 1.0), None);
+            state.update_sending_rate(i as f64 / 100.0, false);
+        }
+        assert!(!state.enabled);
+
+        state.update_sending_rate(1.0, true);
+        assert!(state.enabled);
+    }
+
+    #[test]
+    fn cubic_success_recovers_towards_last_max_rate() {
+        let mut state = Inner::new(0.0);
+        state.last_max_rate = 10.0;
+        state.last_throttle_time = 5.0;
+        state.calculate_time_window();
+
+        let expected = [
+            (5.0, 7.0),
+            (6.0, 9.64893600966),
+            (7.0, 10.000030849917364),
+            (8.0, 10.453284520772092),
+            (9.0, 13.408697022224185),
+            (10.0, 21.26626835427364),
+            (11.0, 36.425998516920465),
+        ];
+        for (timestamp, rate) in expected {
+            assert!(
+                (state.cubic_success(timestamp) - rate).abs() < 1e-6,
+                "at {}: expected {} but got {}",
+                timestamp,
+                rate,
+                state.cubic_success(timestamp)
+            );
+        }
+    }
+
+    #[test]
+    fn cubic_throttle_scales_back_rate() {
+        assert_approx_eq(7.0, cubic_throttle(10.0));
+        assert_approx_eq(0.0, cubic_throttle(0.0));
+    }
+
+    #[test]
+    fn measured_rate_is_smoothed_per_half_second() {
+        let mut state = Inner::new(0.0);
+        // four requests in the first half-second bucket
+        for timestamp in [0.1, 0.2, 0.3, 0.4] {
+            state.update_measured_rate(timestamp);
+        }
+        assert_approx_eq(0.0, state.measured_tx_rate);
+        // the fifth request closes the bucket: 5 requests / 0.5 seconds, smoothed
+        state.update_measured_rate(0.5);
+        assert_approx_eq(10.0 * 0.8, state.measured_tx_rate);
+        assert_eq!(0, state.request_count);
+    }
+
+    #[test]
+    fn throttled_bucket_delays_requests() {
+        let mut state = Inner::new(0.0);
+        state.update_sending_rate(0.3, true);
+        assert!(state.enabled);
+        // no send rate has been measured yet, so the fill rate is clamped to the minimum
+        assert_approx_eq(0.5, state.fill_rate);
+        assert_approx_eq(1.0, state.max_capacity);
+
+        // the bucket starts empty: a single token takes two seconds to refill
+        assert_eq!(state.acquire(0.3, 1.0), Some(2.0));
+        // the previous caller borrowed a token, so the next caller waits for two tokens
+        assert_eq!(state.acquire(0.3, 1.0), Some(4.0));
+        // after waiting long enough, the bucket is full again and no delay is necessary
+        assert_eq!(state.acquire(10.0, 1.0), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_follows_time_source() {
+        let limiter = ClientRateLimiter::new(Arc::new(TokioTimeSource::new()));
+        limiter.update_rate_limiter(true);
+        assert_eq!(
+            limiter.acquire_permission_to_send_request(),
+            Some(Duration::from_secs(2))
+        );
+        // the paused clock only moves when advanced, so the bucket refills in exactly 4 seconds
+        tokio::time::advance(Duration::from_secs(4)).await;
+        assert_eq!(limiter.acquire_permission_to_send_request(), None);
+    }
+}
@@ -48,6 +48,12 @@ impl CaptureRequestReceiver {
 #[doc(inline)]
 pub use crate::never;
 
+pub use fault::{Fault, FaultInjectingConnection};
+pub use mock::{CallCounter, MockConnection, Route};
+
+mod fault;
+mod mock;
+
 impl tower::Service<http::Request<SdkBody>> for CaptureRequestHandler {
     type Response = http::Response<SdkBody>;
     type Error = ConnectorError;
@@ -254,6 +260,69 @@ where
     }
 }
 
+/// Helpers shared by the tests of the connections in this crate
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::TestConnection;
+    use aws_smithy_http::body::SdkBody;
+    use aws_smithy_http::byte_stream::ByteStream;
+    use tower::{BoxError, Service, ServiceExt};
+
+    /// A connection that responds to `GET https://example.com/` with each of `bodies` in turn
+    pub(crate) fn connection(
+        bodies: impl IntoIterator<Item = &'static str>,
+    ) -> TestConnection<&'static str> {
+        TestConnection::new(
+            bodies
+                .into_iter()
+                .map(|body| {
+                    (
+                        request(),
+                        http::Response::builder().status(200).body(body).unwrap(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// A request that [`connection`] responds to
+    pub(crate) fn request() -> http::Request<SdkBody> {
+        http::Request::get("https://example.com/")
+            .body(SdkBody::empty())
+            .unwrap()
+    }
+
+    /// Send `request` once `connection` is ready
+    pub(crate) async fn call<S>(
+        connection: &mut S,
+        request: http::Request<SdkBody>,
+    ) -> Result<S::Response, S::Error>
+    where
+        S: Service<http::Request<SdkBody>>,
+    {
+        connection.ready().await?.call(request).await
+    }
+
+    /// Read the body of `response` as a string
+    pub(crate) async fn read_body(response: http::Response<SdkBody>) -> Result<String, BoxError> {
+        let data = ByteStream::new(response.into_body()).collect().await?;
+        Ok(String::from_utf8(data.into_bytes().to_vec())?)
+    }
+
+    /// Send `request` and read the body of the response
+    pub(crate) async fn send<S>(
+        connection: &mut S,
+        request: http::Request<SdkBody>,
+    ) -> Result<String, BoxError>
+    where
+        S: Service<http::Request<SdkBody>, Response = http::Response<SdkBody>>,
+        S::Error: Into<BoxError>,
+    {
+        let response = call(connection, request).await.map_err(Into::into)?;
+        read_body(response).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::bounds::SmithyConnector;
@@ -0,0 +1,361 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Connector that injects faults into the traffic of another connector
+
+use aws_smithy_async::rt::sleep::AsyncSleep;
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::result::ConnectorError;
+use bytes::Bytes;
+use http_body::Body;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A fault that can be injected by a [`FaultInjectingConnection`]
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail with an IO error, as if the connection could not be established
+    ///
+    /// The request is not sent to the inner connection.
+    ConnectError,
+
+    /// Wait for the given duration, then fail with a timeout error
+    ///
+    /// The request is not sent to the inner connection.
+    Timeout(Duration),
+
+    /// Respond with an empty body and the given status, e.g. `500` or `503`
+    ///
+    /// If `retry_after` is set, the response has an `x-amz-retry-after` header with its value in
+    /// milliseconds. The request is not sent to the inner connection.
+    ErrorResponse {
+        /// HTTP status code of the response
+        status: u16,
+        /// Value of the `x-amz-retry-after` header
+        retry_after: Option<Duration>,
+    },
+
+    /// Send the request, but fail the response body after `after_bytes` bytes have been read
+    TruncatedBody {
+        /// Number of bytes of the response body that are read before it fails
+        after_bytes: usize,
+    },
+
+    /// Send the request, but stream the response body `chunk_size` bytes at a time, waiting
+    /// `delay` before each chunk
+    SlowBody {
+        /// Size of each chunk of the response body
+        chunk_size: usize,
+        /// Delay before each chunk
+        delay: Duration,
+    },
+}
+
+/// Wraps a connector and injects faults into its traffic
+///
+/// Faults are either scheduled for specific requests, or injected at random with a given
+/// probability. Scheduled faults take precedence over random faults, and random faults are
+/// evaluated in the order they were added. The random number generator is seeded (with `0`
+/// unless [`with_seed`](FaultInjectingConnection::with_seed) is called), so the same requests
+/// always get the same faults. All delays are driven by the given [`AsyncSleep`] implementation.
+///
+/// ```no_run
+/// use aws_smithy_async::rt::sleep::TokioSleep;
+/// use aws_smithy_client::test_connection::{Fault, FaultInjectingConnection, TestConnection};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// # let inner = TestConnection::<&str>::new(vec![]);
+/// // fail the first request, then throttle a quarter of the others
+/// let connection = FaultInjectingConnection::new(inner, Arc::new(TokioSleep::new()))
+///     .with_scheduled_fault(0, Fault::ConnectError)
+///     .with_random_fault(
+///         Fault::ErrorResponse { status: 503, retry_after: Some(Duration::from_secs(1)) },
+///         0.25,
+///     );
+/// ```
+#[derive(Clone, Debug)]
+pub struct FaultInjectingConnection<S> {
+    inner: S,
+    sleep_impl: Arc<dyn AsyncSleep>,
+    scheduled: HashMap<usize, Fault>,
+    random: Vec<(Fault, f64)>,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    rng: fastrand::Rng,
+    requests: usize,
+    injected: Vec<(usize, Fault)>,
+}
+
+impl<S> FaultInjectingConnection<S> {
+    /// Wrap `inner`, without injecting any faults yet
+    pub fn new(inner: S, sleep_impl: Arc<dyn AsyncSleep>) -> Self {
+        Self {
+            inner,
+            sleep_impl,
+            scheduled: HashMap::new(),
+            random: Vec::new(),
+            state: Arc::new(Mutex::new(State {
+                rng: fastrand::Rng::with_seed(0),
+                requests: 0,
+                injected: Vec::new(),
+            })),
+        }
+    }
+
+    /// Inject `fault` into the request at `request_index` (starting at `0`)
+    pub fn with_scheduled_fault(mut self, request_index: usize, fault: Fault) -> Self {
+        self.scheduled.insert(request_index, fault);
+        self
+    }
+
+    /// Inject `fault` into requests with the given `probability`, between `0.0` and `1.0`
+    pub fn with_random_fault(mut self, fault: Fault, probability: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&probability),
+            "probability must be between 0.0 and 1.0"
+        );
+        self.random.push((fault, probability));
+        self
+    }
+
+    /// Seed the random number generator that decides which random faults are injected
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.state.lock().unwrap().rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    /// The faults that were injected so far, with the index of the request they were injected into
+    pub fn injected_faults(&self) -> Vec<(usize, Fault)> {
+        self.state.lock().unwrap().injected.clone()
+    }
+
+    fn next_fault(&self) -> Option<Fault> {
+        let mut state = self.state.lock().unwrap();
+        let request_index = state.requests;
+        state.requests += 1;
+        let fault = match self.scheduled.get(&request_index) {
+            Some(fault) => Some(fault.clone()),
+            None => {
+                let rng = &state.rng;
+                self.random
+                    .iter()
+                    .find(|(_, probability)| rng.f64() < *probability)
+                    .map(|(fault, _)| fault.clone())
+            }
+        };
+        if let Some(fault) = &fault {
+            state.injected.push((request_index, fault.clone()));
+        }
+        fault
+    }
+}
+
+impl<S> tower::Service<http::Request<SdkBody>> for FaultInjectingConnection<S>
+where
+    S: tower::Service<
+        http::Request<SdkBody>,
+        Response = http::Response<SdkBody>,
+        Error = ConnectorError,
+    >,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<SdkBody>;
+    type Error = ConnectorError;
+    #[allow(clippy::type_complexity)]
+    type Future =
+        Pin<Box<dyn Future<Output = Result<http::Response<SdkBody>, ConnectorError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
+        let sleep_impl = self.sleep_impl.clone();
+        match self.next_fault() {
+            Some(Fault::ConnectError) => Box::pin(std::future::ready(Err(ConnectorError::io(
+                "injected connection error".into(),
+            )))),
+            Some(Fault::Timeout(duration)) => Box::pin(async move {
+                sleep_impl.sleep(duration).await;
+                Err(ConnectorError::timeout(
+                    format!("injected timeout after {:?}", duration).into(),
+                ))
+            }),
+            Some(Fault::ErrorResponse {
+                status,
+                retry_after,
+            }) => {
+                let mut builder = http::Response::builder().status(status);
+                if let Some(retry_after) = retry_after {
+                    builder =
+                        builder.header("x-amz-retry-after", retry_after.as_millis().to_string());
+                }
+                let response = builder.body(SdkBody::empty()).map_err(|err| {
+                    ConnectorError::other(
+                        format!("invalid injected response: {}", err).into(),
+                        None,
+                    )
+                });
+                Box::pin(std::future::ready(response))
+            }
+            Some(fault) => {
+                let response = self.inner.call(req);
+                Box::pin(async move {
+                    let response = response.await?;
+                    Ok(response.map(|body| faulty_body(body, fault, sleep_impl)))
+                })
+            }
+            None => Box::pin(self.inner.call(req)),
+        }
+    }
+}
+
+/// Stream `body` through a channel, applying a `TruncatedBody` or `SlowBody` fault
+fn faulty_body(mut body: SdkBody, fault: Fault, sleep_impl: Arc<dyn AsyncSleep>) -> SdkBody {
+    let (mut sender, output) = hyper::Body::channel();
+    tokio::spawn(async move {
+        let mut sent = 0;
+        while let Some(data) = body.data().await {
+            let mut data = match data {
+                Ok(data) => data,
+                Err(_) => return sender.abort(),
+            };
+            while !data.is_empty() {
+                let chunk: Bytes = match &fault {
+                    Fault::TruncatedBody { after_bytes } if sent + data.len() > *after_bytes => {
+                        let chunk = data.split_to(*after_bytes - sent);
+                        if !chunk.is_empty() {
+                            let _ = sender.send_data(chunk).await;
+                        }
+                        return sender.abort();
+                    }
+                    Fault::SlowBody { chunk_size, delay } => {
+                        sleep_impl.sleep(*delay).await;
+                        data.split_to((*chunk_size).clamp(1, data.len()))
+                    }
+                    _ => data.split_to(data.len()),
+                };
+                sent += chunk.len();
+                if sender.send_data(chunk).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    SdkBody::from(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Fault, FaultInjectingConnection};
+    use crate::test_connection::test_util::{call, connection, read_body, request};
+    use crate::test_connection::TestConnection;
+    use aws_smithy_async::assert_elapsed;
+    use aws_smithy_async::rt::sleep::TokioSleep;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn faulty(responses: usize) -> FaultInjectingConnection<TestConnection<&'static str>> {
+        FaultInjectingConnection::new(
+            connection(vec!["0123456789"; responses]),
+            Arc::new(TokioSleep::new()),
+        )
+    }
+
+    #[tokio::test]
+    async fn scheduled_faults_are_injected() {
+        let mut connection = faulty(1)
+            .with_scheduled_fault(0, Fault::ConnectError)
+            .with_scheduled_fault(
+                1,
+                Fault::ErrorResponse {
+                    status: 503,
+                    retry_after: Some(Duration::from_millis(1500)),
+                },
+            );
+
+        let err = call(&mut connection, request())
+            .await
+            .expect_err("connect error");
+        assert!(err.is_io(), "{:?}", err);
+
+        let response = call(&mut connection, request()).await.unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(response.headers()["x-amz-retry-after"], "1500");
+
+        let response = call(&mut connection, request()).await.unwrap();
+        assert_eq!(read_body(response).await.unwrap(), "0123456789");
+        assert_eq!(connection.injected_faults().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn timeouts_use_the_sleep_impl() {
+        tokio::time::pause();
+        let mut connection =
+            faulty(0).with_scheduled_fault(0, Fault::Timeout(Duration::from_secs(10)));
+        let now = tokio::time::Instant::now();
+        let err = call(&mut connection, request()).await.expect_err("timeout");
+        assert!(err.is_timeout(), "{:?}", err);
+        assert_elapsed!(now, Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn truncated_bodies_fail_after_some_bytes() {
+        let mut connection =
+            faulty(1).with_scheduled_fault(0, Fault::TruncatedBody { after_bytes: 4 });
+        let response = call(&mut connection, request()).await.unwrap();
+        read_body(response).await.expect_err("body is truncated");
+    }
+
+    #[tokio::test]
+    async fn slow_bodies_are_streamed_in_chunks() {
+        tokio::time::pause();
+        let mut connection = faulty(1).with_scheduled_fault(
+            0,
+            Fault::SlowBody {
+                chunk_size: 4,
+                delay: Duration::from_secs(1),
+            },
+        );
+        let response = call(&mut connection, request()).await.unwrap();
+        let now = tokio::time::Instant::now();
+        assert_eq!(read_body(response).await.unwrap(), "0123456789");
+        assert_elapsed!(now, Duration::from_secs(3));
+    }
+
+    #[tokio::test]
+    async fn random_faults_are_deterministic() {
+        async fn injected(seed: u64) -> Vec<usize> {
+            let mut connection = faulty(100)
+                .with_random_fault(Fault::ConnectError, 0.5)
+                .with_seed(seed);
+            for _ in 0..100 {
+                let _ = call(&mut connection, request()).await;
+            }
+            connection
+                .injected_faults()
+                .into_iter()
+                .map(|(index, _)| index)
+                .collect()
+        }
+        let faults = injected(1234).await;
+        assert!(faults.len() > 25 && faults.len() < 75, "{:?}", faults);
+        assert_eq!(faults, injected(1234).await);
+        assert_ne!(faults, injected(4321).await);
+
+        let mut never = faulty(1).with_random_fault(Fault::ConnectError, 0.0);
+        assert!(call(&mut never, request()).await.is_ok());
+        let mut always = faulty(1).with_random_fault(Fault::ConnectError, 1.0);
+        assert!(call(&mut always, request()).await.is_err());
+    }
+}
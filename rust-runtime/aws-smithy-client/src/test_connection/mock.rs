@@ -0,0 +1,507 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Mock connector that routes requests to handlers
+
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::result::ConnectorError;
+use bytes::Bytes;
+use http::Method;
+use http_body::Body;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+type Handler = Arc<dyn Fn(&http::Request<Bytes>) -> http::Response<SdkBody> + Send + Sync>;
+
+/// A request handler registered with a [`MockConnection`]
+///
+/// A route matches requests by method, path pattern and, optionally, `X-Amz-Target` header and
+/// query parameters. Path patterns use Smithy's label syntax: `{label}` matches a single path
+/// segment and `{label+}` matches one or more segments.
+///
+/// ```no_run
+/// use aws_smithy_client::test_connection::Route;
+/// use aws_smithy_http::body::SdkBody;
+/// let route = Route::get("/buckets/{bucket}/objects/{key+}")
+///     .times(1)
+///     .respond_with(|_req| http::Response::new(SdkBody::from("hello")));
+/// ```
+#[derive(Clone)]
+pub struct Route {
+    method: Option<Method>,
+    path: Vec<Segment>,
+    pattern: String,
+    target: Option<String>,
+    query: Vec<(String, String)>,
+    expected_calls: Option<usize>,
+    handler: Handler,
+    calls: CallCounter,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Label,
+    GreedyLabel,
+}
+
+impl fmt::Debug for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.method {
+            Some(method) => write!(f, "{} {}", method, self.pattern)?,
+            None => write!(f, "* {}", self.pattern)?,
+        }
+        if let Some(target) = &self.target {
+            write!(f, " (X-Amz-Target: {})", target)?;
+        }
+        Ok(())
+    }
+}
+
+impl Route {
+    /// Match requests with `method` whose path matches `path_pattern`
+    pub fn new(method: Method, path_pattern: &str) -> Self {
+        Self::with_method(Some(method), path_pattern)
+    }
+
+    /// Match requests with any method whose path matches `path_pattern`
+    pub fn any(path_pattern: &str) -> Self {
+        Self::with_method(None, path_pattern)
+    }
+
+    /// Match `GET` requests whose path matches `path_pattern`
+    pub fn get(path_pattern: &str) -> Self {
+        Self::new(Method::GET, path_pattern)
+    }
+
+    /// Match `POST` requests whose path matches `path_pattern`
+    pub fn post(path_pattern: &str) -> Self {
+        Self::new(Method::POST, path_pattern)
+    }
+
+    /// Match `PUT` requests whose path matches `path_pattern`
+    pub fn put(path_pattern: &str) -> Self {
+        Self::new(Method::PUT, path_pattern)
+    }
+
+    /// Match `DELETE` requests whose path matches `path_pattern`
+    pub fn delete(path_pattern: &str) -> Self {
+        Self::new(Method::DELETE, path_pattern)
+    }
+
+    fn with_method(method: Option<Method>, path_pattern: &str) -> Self {
+        let path = path_segments(path_pattern)
+            .map(|segment| match segment {
+                s if s.starts_with('{') && s.ends_with("+}") => Segment::GreedyLabel,
+                s if s.starts_with('{') && s.ends_with('}') => Segment::Label,
+                s => Segment::Literal(s.to_string()),
+            })
+            .collect();
+        Self {
+            method,
+            path,
+            pattern: path_pattern.to_string(),
+            target: None,
+            query: Vec::new(),
+            expected_calls: None,
+            handler: Arc::new(|_| {
+                http::Response::builder()
+                    .status(200)
+                    .body(SdkBody::empty())
+                    .unwrap()
+            }),
+            calls: CallCounter::default(),
+        }
+    }
+
+    /// Only match requests whose `X-Amz-Target` header is `target`
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Only match requests that have the query parameter `name` set to `value`
+    pub fn with_query_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    /// Expect this route to be called exactly `count` times
+    ///
+    /// Once the route has been called `count` times, it stops matching requests so that a later
+    /// route can handle them. This makes it possible to register a sequence of responses for the
+    /// same request, e.g. the pages of a paginated operation.
+    pub fn times(mut self, count: usize) -> Self {
+        self.expected_calls = Some(count);
+        self
+    }
+
+    /// Produce responses with `handler`
+    ///
+    /// Routes respond with an empty `200 OK` response by default.
+    pub fn respond_with(
+        mut self,
+        handler: impl Fn(&http::Request<Bytes>) -> http::Response<SdkBody> + Send + Sync + 'static,
+    ) -> Self {
+        self.handler = Arc::new(handler);
+        self
+    }
+
+    /// Counts the calls to this route
+    pub fn calls(&self) -> CallCounter {
+        self.calls.clone()
+    }
+
+    fn is_exhausted(&self) -> bool {
+        matches!(self.expected_calls, Some(expected) if self.calls.count() >= expected)
+    }
+
+    fn matches(&self, request: &http::Request<Bytes>) -> bool {
+        if let Some(method) = &self.method {
+            if method != request.method() {
+                return false;
+            }
+        }
+        if let Some(target) = &self.target {
+            if request.headers().get("x-amz-target").map(|t| t.as_bytes())
+                != Some(target.as_bytes())
+            {
+                return false;
+            }
+        }
+        let query: Vec<_> = request
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .map(|param| param.split_once('=').unwrap_or((param, "")))
+            .collect();
+        if !self
+            .query
+            .iter()
+            .all(|(name, value)| query.contains(&(name.as_str(), value.as_str())))
+        {
+            return false;
+        }
+        path_matches(&self.path, path_segments(request.uri().path()))
+    }
+}
+
+fn path_segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn path_matches<'a>(pattern: &[Segment], mut path: impl Iterator<Item = &'a str>) -> bool {
+    for (index, segment) in pattern.iter().enumerate() {
+        match segment {
+            // greedy labels consume at least one segment, then the rest of the pattern must match
+            // the end of the path
+            Segment::GreedyLabel => {
+                let rest: Vec<_> = path.collect();
+                let suffix = &pattern[index + 1..];
+                return rest.len() > suffix.len()
+                    && path_matches(suffix, rest[rest.len() - suffix.len()..].iter().copied());
+            }
+            Segment::Label => {
+                if path.next().is_none() {
+                    return false;
+                }
+            }
+            Segment::Literal(literal) => {
+                if path.next() != Some(literal.as_str()) {
+                    return false;
+                }
+            }
+        }
+    }
+    path.next().is_none()
+}
+
+/// Number of calls to a [`Route`]
+#[derive(Clone, Debug, Default)]
+pub struct CallCounter(Arc<AtomicUsize>);
+
+impl CallCounter {
+    /// Number of requests handled by the route so far
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// A connector that routes requests to handlers
+///
+/// Requests are handled by the first registered [`Route`] that matches them and isn't exhausted.
+/// Requests that don't match any route fail with a [`ConnectorError`] and are reported by
+/// [`assert_expectations`](MockConnection::assert_expectations).
+///
+/// ```no_run
+/// use aws_smithy_client::test_connection::{MockConnection, Route};
+/// use aws_smithy_http::body::SdkBody;
+/// let connection = MockConnection::new()
+///     .with_route(
+///         Route::post("/")
+///             .with_target("DynamoDB_20120810.Scan")
+///             .times(1)
+///             .respond_with(|_| http::Response::new(SdkBody::from(r#"{"LastEvaluatedKey":{}}"#))),
+///     )
+///     .with_route(
+///         Route::post("/")
+///             .with_target("DynamoDB_20120810.Scan")
+///             .times(1)
+///             .respond_with(|_| http::Response::new(SdkBody::from("{}"))),
+///     );
+/// // make requests...
+/// connection.assert_expectations();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MockConnection {
+    routes: Arc<Mutex<Vec<Route>>>,
+    unexpected: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockConnection {
+    /// Create a connection without any routes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `route`, after the routes that were already registered
+    pub fn with_route(self, route: Route) -> Self {
+        self.route(route);
+        self
+    }
+
+    /// Register `route`, after the routes that were already registered, and return its call counter
+    pub fn route(&self, route: Route) -> CallCounter {
+        let calls = route.calls();
+        self.routes.lock().unwrap().push(route);
+        calls
+    }
+
+    /// Check that every request matched a route and that every route was called as many times as
+    /// expected, returning a description of each problem
+    pub fn check_expectations(&self) -> Result<(), Vec<String>> {
+        let mut problems: Vec<_> = self
+            .unexpected
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| format!("unexpected request: {}", request))
+            .collect();
+        for route in self.routes.lock().unwrap().iter() {
+            if let Some(expected) = route.expected_calls {
+                let actual = route.calls.count();
+                if actual != expected {
+                    problems.push(format!(
+                        "expected {} calls to {} but there were {}",
+                        expected, route, actual
+                    ));
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Panic if a request didn't match any route or a route wasn't called as many times as expected
+    #[track_caller]
+    pub fn assert_expectations(&self) {
+        if let Err(problems) = self.check_expectations() {
+            panic!("mock expectations were not met:\n{}", problems.join("\n"));
+        }
+    }
+
+    fn handle(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<SdkBody>, ConnectorError> {
+        // the lock is released before calling the handler so that handlers can register routes
+        let handler = self
+            .routes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|route| !route.is_exhausted() && route.matches(&request))
+            .map(|route| {
+                route.calls.0.fetch_add(1, Ordering::SeqCst);
+                route.handler.clone()
+            });
+        match handler {
+            Some(handler) => Ok(handler(&request)),
+            None => {
+                let description = match request.headers().get("x-amz-target") {
+                    Some(target) => format!(
+                        "{} {} (X-Amz-Target: {})",
+                        request.method(),
+                        request.uri(),
+                        String::from_utf8_lossy(target.as_bytes())
+                    ),
+                    None => format!("{} {}", request.method(), request.uri()),
+                };
+                self.unexpected.lock().unwrap().push(description.clone());
+                Err(ConnectorError::other(
+                    format!("no route matched {}", description).into(),
+                    None,
+                ))
+            }
+        }
+    }
+}
+
+impl tower::Service<http::Request<SdkBody>> for MockConnection {
+    type Response = http::Response<SdkBody>;
+    type Error = ConnectorError;
+    #[allow(clippy::type_complexity)]
+    type Future =
+        Pin<Box<dyn Future<Output = Result<http::Response<SdkBody>, ConnectorError>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut req: http::Request<SdkBody>) -> Self::Future {
+        let this = self.clone();
+        Box::pin(async move {
+            let mut body = Vec::new();
+            while let Some(data) = req.body_mut().data().await {
+                body.extend_from_slice(&data.map_err(ConnectorError::user)?);
+            }
+            this.handle(req.map(|_| Bytes::from(body)))
+        })
+    }
+}
+
+impl From<MockConnection> for crate::Client<MockConnection, tower::layer::util::Identity> {
+    fn from(connection: MockConnection) -> Self {
+        crate::Builder::new()
+            .middleware(tower::layer::util::Identity::new())
+            .connector(connection)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MockConnection, Route};
+    use crate::test_connection::test_util;
+    use aws_smithy_http::body::SdkBody;
+
+    async fn send(
+        connection: &mut MockConnection,
+        request: http::request::Builder,
+    ) -> Result<String, String> {
+        let request = request.body(SdkBody::from("request")).unwrap();
+        test_util::send(connection, request)
+            .await
+            .map_err(|err| err.to_string())
+    }
+
+    fn respond(
+        body: &'static str,
+    ) -> impl Fn(&http::Request<bytes::Bytes>) -> http::Response<SdkBody> {
+        move |_| http::Response::new(SdkBody::from(body))
+    }
+
+    #[tokio::test]
+    async fn routes_by_target_in_registration_order() {
+        let scan = || {
+            http::Request::post("https://dynamodb.us-east-1.amazonaws.com/")
+                .header("x-amz-target", "DynamoDB_20120810.Scan")
+        };
+        let mut connection = MockConnection::new();
+        let first_page = connection.route(
+            Route::post("/")
+                .with_target("DynamoDB_20120810.Scan")
+                .times(1)
+                .respond_with(respond("page 1")),
+        );
+        let last_page = connection.route(
+            Route::post("/")
+                .with_target("DynamoDB_20120810.Scan")
+                .respond_with(|req| {
+                    assert_eq!(req.body().as_ref(), b"request");
+                    http::Response::new(SdkBody::from("page 2"))
+                }),
+        );
+
+        assert_eq!(send(&mut connection, scan()).await.unwrap(), "page 1");
+        assert_eq!(send(&mut connection, scan()).await.unwrap(), "page 2");
+        assert_eq!(send(&mut connection, scan()).await.unwrap(), "page 2");
+        assert_eq!(first_page.count(), 1);
+        assert_eq!(last_page.count(), 2);
+        connection.assert_expectations();
+    }
+
+    #[tokio::test]
+    async fn routes_by_path_pattern_and_query() {
+        let mut connection = MockConnection::new()
+            .with_route(
+                Route::get("/{bucket}")
+                    .with_query_param("list-type", "2")
+                    .respond_with(respond("list")),
+            )
+            .with_route(Route::get("/{bucket}/{key+}/acl").respond_with(respond("acl")))
+            .with_route(Route::get("/{bucket}/{key+}").respond_with(respond("object")));
+
+        let get = |uri| http::Request::get(uri);
+        assert_eq!(
+            send(
+                &mut connection,
+                get("https://s3/bucket?list-type=2&prefix=a")
+            )
+            .await
+            .unwrap(),
+            "list"
+        );
+        assert_eq!(
+            send(&mut connection, get("https://s3/bucket/a/b/acl"))
+                .await
+                .unwrap(),
+            "acl"
+        );
+        assert_eq!(
+            send(&mut connection, get("https://s3/bucket/a/b"))
+                .await
+                .unwrap(),
+            "object"
+        );
+        assert_eq!(
+            send(&mut connection, get("https://s3/bucket")).await,
+            Err("other: no route matched GET https://s3/bucket".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_unmet_expectations() {
+        let mut connection = MockConnection::new()
+            .with_route(Route::put("/a").times(2))
+            .with_route(Route::delete("/a").with_target("Delete").times(0));
+        send(&mut connection, http::Request::put("https://host/a"))
+            .await
+            .unwrap();
+        let _ = send(&mut connection, http::Request::post("https://host/a")).await;
+
+        assert_eq!(
+            connection.check_expectations(),
+            Err(vec![
+                "unexpected request: POST https://host/a".to_string(),
+                "expected 2 calls to PUT /a but there were 1".to_string(),
+            ])
+        );
+    }
+}
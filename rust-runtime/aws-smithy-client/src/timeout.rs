@@ -19,32 +19,156 @@ use crate::SdkError;
 use aws_smithy_async::future::timeout::Timeout;
 use aws_smithy_async::rt::sleep::{AsyncSleep, Sleep};
 use aws_smithy_http::operation::Operation;
+use aws_smithy_http::result::{AttemptHistory, RequestTimeoutError};
+use aws_smithy_types::timeout;
+use aws_smithy_types::tristate::TriState;
 use pin_project_lite::pin_project;
 use tower::Layer;
 
-#[derive(Debug)]
-struct RequestTimeoutError {
-    kind: &'static str,
-    duration: Duration,
+mod body;
+
+pub(crate) use body::ResponseBodyTimeoutInterceptor;
+
+/// Per-operation overrides of the client's API timeouts
+///
+/// Insert a `TimeoutConfigOverride` into an operation's property bag to change its timeouts without
+/// affecting other requests made by the same client. Timeouts that are set (or explicitly disabled)
+/// here take precedence over the client's [timeout config](aws_smithy_types::timeout::Config);
+/// timeouts that are left unset fall back to it.
+///
+/// ```no_run
+/// use aws_smithy_client::timeout::TimeoutConfigOverride;
+/// use aws_smithy_http::body::SdkBody;
+/// use aws_smithy_http::operation::{Operation, Request};
+/// use aws_smithy_types::tristate::TriState;
+/// use std::time::Duration;
+/// let mut op = Operation::new(Request::new(http::Request::new(SdkBody::empty())), ());
+/// op.properties_mut().insert(
+///     TimeoutConfigOverride::new()
+///         .with_call_timeout(TriState::Set(Duration::from_secs(300)))
+///         .with_call_attempt_timeout(TriState::Disabled),
+/// );
+/// ```
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TimeoutConfigOverride {
+    api: timeout::Api,
 }
 
-impl RequestTimeoutError {
-    pub fn new_boxed(kind: &'static str, duration: Duration) -> Box<Self> {
-        Box::new(Self { kind, duration })
+impl TimeoutConfigOverride {
+    /// Create an override that doesn't override any timeouts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the API call timeout (all attempts including retries)
+    pub fn with_call_timeout(mut self, timeout: TriState<Duration>) -> Self {
+        self.api = self.api.with_call_timeout(timeout);
+        self
+    }
+
+    /// Override the API call attempt timeout (a single attempt)
+    pub fn with_call_attempt_timeout(mut self, timeout: TriState<Duration>) -> Self {
+        self.api = self.api.with_call_attempt_timeout(timeout);
+        self
+    }
+
+    /// Apply this override to the client's API timeout config
+    pub fn apply_to(&self, api_timeout_config: &timeout::Api) -> timeout::Api {
+        self.api.clone().take_unset_from(api_timeout_config.clone())
     }
 }
 
-impl std::fmt::Display for RequestTimeoutError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{} timeout occurred after {:?}",
-            self.kind, self.duration
-        )
+/// Timeouts for reading a response body
+///
+/// The [API call timeouts](aws_smithy_types::timeout::Api) end once the response headers have been
+/// received, so they don't cover reading streaming response bodies. These timeouts fail reads of
+/// the response body with a timeout error instead of letting stalled downloads hang forever.
+///
+/// Set them for every request with [`Builder::response_body_timeout`](crate::Builder::response_body_timeout),
+/// or for a single operation by inserting a `ResponseBodyTimeout` into the operation's property bag.
+///
+/// ```no_run
+/// use aws_smithy_client::timeout::{MinimumThroughput, ResponseBodyTimeout};
+/// use std::time::Duration;
+/// let body_timeout = ResponseBodyTimeout::new()
+///     .with_read_timeout(Duration::from_secs(600))
+///     .with_minimum_throughput(MinimumThroughput::new(1024, Duration::from_secs(10)));
+/// ```
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResponseBodyTimeout {
+    read_timeout: Option<Duration>,
+    minimum_throughput: Option<MinimumThroughput>,
+}
+
+impl ResponseBodyTimeout {
+    /// Create a config without any response body timeouts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail if the response body hasn't been read completely within `timeout` of receiving the
+    /// response headers
+    ///
+    /// This includes the time spent by the caller between reads of the body.
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
     }
+
+    /// Fail if the response body is received slower than `minimum_throughput`
+    pub fn with_minimum_throughput(mut self, minimum_throughput: MinimumThroughput) -> Self {
+        self.minimum_throughput = Some(minimum_throughput);
+        self
+    }
+
+    /// The maximum time allowed for reading the response body
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    /// The minimum rate at which the response body must be received
+    pub fn minimum_throughput(&self) -> Option<MinimumThroughput> {
+        self.minimum_throughput
+    }
+
+    /// Returns true if any timeouts are set
+    pub fn has_timeouts(&self) -> bool {
+        self.read_timeout.is_some() || self.minimum_throughput.is_some()
+    }
+}
+
+/// A minimum rate at which a response body must be received, averaged over a window
+///
+/// The throughput is only checked while the body is waiting for data from the network, so callers
+/// that read the body slowly don't cause it to fail.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinimumThroughput {
+    bytes_per_second: u64,
+    window: Duration,
 }
 
-impl std::error::Error for RequestTimeoutError {}
+impl MinimumThroughput {
+    /// Require at least `bytes_per_second` on average over every `window`
+    pub fn new(bytes_per_second: u64, window: Duration) -> Self {
+        Self {
+            bytes_per_second,
+            window,
+        }
+    }
+
+    /// The minimum number of bytes per second
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second
+    }
+
+    /// The window that the throughput is averaged over
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+}
 
 #[derive(Clone, Debug)]
 /// A struct containing everything needed to create a new [`TimeoutService`]
@@ -161,6 +285,7 @@ pin_project! {
             future: Timeout<F, Sleep>,
             kind: &'static str,
             duration: Duration,
+            attempt_history: Option<AttemptHistory>,
         },
         /// A thin wrapper around an inner future that will never time out
         NoTimeout {
@@ -179,9 +304,22 @@ impl<F> TimeoutServiceFuture<F> {
             future: Timeout::new(future, params.async_sleep.sleep(params.duration)),
             kind: params.kind,
             duration: params.duration,
+            attempt_history: None,
         }
     }
 
+    /// Record the timeout into `attempt_history` if it occurs
+    pub fn with_attempt_history(mut self, attempt_history: Option<AttemptHistory>) -> Self {
+        if let Self::Timeout {
+            attempt_history: history,
+            ..
+        } = &mut self
+        {
+            *history = attempt_history;
+        }
+        self
+    }
+
     /// Create a [`TimeoutServiceFuture`] that will never time out.
     pub fn no_timeout(future: F) -> Self {
         Self::NoTimeout { future }
@@ -195,19 +333,25 @@ where
     type Output = Result<T, SdkError<E>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let (future, kind, duration) = match self.project() {
+        let (future, kind, duration, attempt_history) = match self.project() {
             TimeoutServiceFutureProj::NoTimeout { future } => return future.poll(cx),
             TimeoutServiceFutureProj::Timeout {
                 future,
                 kind,
                 duration,
-            } => (future, kind, duration),
+                attempt_history,
+            } => (future, kind, duration, attempt_history),
         };
         match future.poll(cx) {
             Poll::Ready(Ok(response)) => Poll::Ready(response),
-            Poll::Ready(Err(_timeout)) => Poll::Ready(Err(SdkError::TimeoutError(
-                RequestTimeoutError::new_boxed(kind, *duration),
-            ))),
+            Poll::Ready(Err(_timeout)) => {
+                let err = RequestTimeoutError::new(kind, *duration);
+                if let Some(attempt_history) = attempt_history {
+                    attempt_history.record_timeout(err.to_string());
+                }
+                let err = err.with_attempt_history(attempt_history.clone());
+                Poll::Ready(Err(SdkError::TimeoutError(Box::new(err))))
+            }
             Poll::Pending => Poll::Pending,
         }
     }
@@ -226,10 +370,11 @@ where
     }
 
     fn call(&mut self, req: Operation<H, R>) -> Self::Future {
+        let attempt_history = req.properties().get::<AttemptHistory>().cloned();
         let future = self.inner.call(req);
 
         if let Some(params) = &self.params {
-            Self::Future::new(future, params)
+            Self::Future::new(future, params).with_attempt_history(attempt_history)
         } else {
             Self::Future::no_timeout(future)
         }
@@ -242,7 +387,9 @@ mod test {
     use std::time::Duration;
 
     use crate::never::NeverService;
-    use crate::timeout::generate_timeout_service_params_from_timeout_config;
+    use crate::timeout::{
+        generate_timeout_service_params_from_timeout_config, TimeoutConfigOverride,
+    };
     use crate::{SdkError, TimeoutLayer};
 
     use aws_smithy_async::assert_elapsed;
@@ -276,4 +423,24 @@ mod test {
         assert_eq!(format!("{:?}", err), "TimeoutError(RequestTimeoutError { kind: \"API call (all attempts including retries)\", duration: 250ms })");
         assert_elapsed!(now, Duration::from_secs_f32(0.25));
     }
+
+    #[test]
+    fn timeout_config_override_takes_precedence_over_client_config() {
+        let client_config = aws_smithy_types::timeout::Api::new()
+            .with_call_timeout(TriState::Set(Duration::from_secs(10)))
+            .with_call_attempt_timeout(TriState::Set(Duration::from_secs(2)));
+
+        let unchanged = TimeoutConfigOverride::new().apply_to(&client_config);
+        assert_eq!(unchanged, client_config);
+
+        let overridden = TimeoutConfigOverride::new()
+            .with_call_attempt_timeout(TriState::Disabled)
+            .apply_to(&client_config);
+        assert_eq!(
+            overridden,
+            aws_smithy_types::timeout::Api::new()
+                .with_call_timeout(TriState::Set(Duration::from_secs(10)))
+                .with_call_attempt_timeout(TriState::Disabled)
+        );
+    }
 }
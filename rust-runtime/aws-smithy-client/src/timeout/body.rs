@@ -0,0 +1,283 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Timeouts for reading response bodies
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use aws_smithy_async::rt::sleep::{AsyncSleep, Sleep};
+use aws_smithy_http::body::{BoxBody, Error, SdkBody};
+use aws_smithy_http::interceptor::Interceptor;
+use aws_smithy_http::operation;
+use aws_smithy_http::result::RequestTimeoutError;
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue};
+use http_body::{Body, SizeHint};
+use pin_project_lite::pin_project;
+
+use super::{MinimumThroughput, ResponseBodyTimeout};
+
+/// Wraps the body of every response in a [`TimedBody`]
+#[derive(Debug)]
+pub(crate) struct ResponseBodyTimeoutInterceptor {
+    config: ResponseBodyTimeout,
+    sleep_impl: Arc<dyn AsyncSleep>,
+}
+
+impl ResponseBodyTimeoutInterceptor {
+    pub(crate) fn new(config: ResponseBodyTimeout, sleep_impl: Arc<dyn AsyncSleep>) -> Self {
+        Self { config, sleep_impl }
+    }
+}
+
+impl Interceptor for ResponseBodyTimeoutInterceptor {
+    fn after_receive(&self, response: &mut operation::Response) -> Result<(), Error> {
+        let body = response.http_mut().body_mut();
+        let inner = std::mem::replace(body, SdkBody::taken());
+        *body = SdkBody::from_dyn(BoxBody::new(TimedBody::new(
+            inner,
+            &self.config,
+            self.sleep_impl.clone(),
+        )));
+        Ok(())
+    }
+}
+
+/// A `Sleep` that can be shared between threads
+///
+/// `Sleep` is only `Send`, but bodies must be `Sync` to be boxed into an `SdkBody`. The sleep is
+/// only ever polled through `&mut self`, so the lock is never contended.
+struct SyncSleep(Mutex<Sleep>);
+
+impl SyncSleep {
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        Pin::new(self.0.get_mut().unwrap()).poll(cx)
+    }
+}
+
+struct ThroughputGuard {
+    minimum: MinimumThroughput,
+    window: SyncSleep,
+    bytes_in_window: u64,
+}
+
+impl ThroughputGuard {
+    fn is_satisfied(&self) -> bool {
+        let required = self.minimum.bytes_per_second() as f64 * self.minimum.window().as_secs_f64();
+        self.bytes_in_window as f64 >= required
+    }
+
+    fn start_window(&mut self, sleep_impl: &dyn AsyncSleep) {
+        self.window = SyncSleep(Mutex::new(sleep_impl.sleep(self.minimum.window())));
+        self.bytes_in_window = 0;
+    }
+}
+
+pin_project! {
+    /// A body that fails with a timeout error if it isn't read quickly enough
+    struct TimedBody {
+        #[pin]
+        inner: SdkBody,
+        deadline: Option<(SyncSleep, Duration)>,
+        throughput: Option<ThroughputGuard>,
+        sleep_impl: Arc<dyn AsyncSleep>,
+    }
+}
+
+impl TimedBody {
+    fn new(inner: SdkBody, config: &ResponseBodyTimeout, sleep_impl: Arc<dyn AsyncSleep>) -> Self {
+        let deadline = config
+            .read_timeout()
+            .map(|timeout| (SyncSleep(Mutex::new(sleep_impl.sleep(timeout))), timeout));
+        let throughput = config.minimum_throughput().map(|minimum| ThroughputGuard {
+            minimum,
+            window: SyncSleep(Mutex::new(sleep_impl.sleep(minimum.window()))),
+            bytes_in_window: 0,
+        });
+        Self {
+            inner,
+            deadline,
+            throughput,
+            sleep_impl,
+        }
+    }
+}
+
+impl Body for TimedBody {
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let result = this.inner.poll_data(cx);
+        if let Poll::Ready(None) = result {
+            return result;
+        }
+        if let Some((deadline, duration)) = this.deadline {
+            if deadline.poll(cx).is_ready() {
+                return Poll::Ready(Some(Err(Box::new(RequestTimeoutError::new(
+                    "response body read",
+                    *duration,
+                )))));
+            }
+        }
+        if let Some(guard) = this.throughput {
+            if let Poll::Ready(Some(Ok(data))) = &result {
+                guard.bytes_in_window += data.len() as u64;
+            }
+            if guard.window.poll(cx).is_ready() {
+                // Only a window that ends while waiting for the network can fail. If data was
+                // immediately available, the caller is the one reading slowly.
+                if result.is_pending() && !guard.is_satisfied() {
+                    return Poll::Ready(Some(Err(Box::new(RequestTimeoutError::new(
+                        "response body minimum throughput",
+                        guard.minimum.window(),
+                    )))));
+                }
+                guard.start_window(this.sleep_impl.as_ref());
+                // poll the new window so that a stalled body is woken up when it ends
+                let _ = guard.window.poll(cx);
+            }
+        }
+        result
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap<HeaderValue>>, Self::Error>> {
+        self.project().inner.poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TimedBody;
+    use crate::timeout::{MinimumThroughput, ResponseBodyTimeout};
+    use aws_smithy_async::assert_elapsed;
+    use aws_smithy_async::rt::sleep::TokioSleep;
+    use aws_smithy_http::body::{BoxBody, Error, SdkBody};
+    use bytes::Bytes;
+    use http::{HeaderMap, HeaderValue};
+    use http_body::Body;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    /// A body that yields the chunks sent through a channel
+    struct ChannelBody(mpsc::UnboundedReceiver<Bytes>);
+
+    impl Body for ChannelBody {
+        type Data = Bytes;
+        type Error = Error;
+
+        fn poll_data(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+            self.0.poll_recv(cx).map(|chunk| chunk.map(Ok))
+        }
+
+        fn poll_trailers(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<Option<HeaderMap<HeaderValue>>, Self::Error>> {
+            Poll::Ready(Ok(None))
+        }
+    }
+
+    fn timed_body(config: ResponseBodyTimeout) -> (mpsc::UnboundedSender<Bytes>, TimedBody) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let inner = SdkBody::from_dyn(BoxBody::new(ChannelBody(rx)));
+        (
+            tx,
+            TimedBody::new(inner, &config, Arc::new(TokioSleep::new())),
+        )
+    }
+
+    async fn read_to_end(mut body: TimedBody) -> Result<usize, Error> {
+        let mut read = 0;
+        while let Some(chunk) = body.data().await {
+            read += chunk?.len();
+        }
+        Ok(read)
+    }
+
+    #[tokio::test]
+    async fn stalled_body_times_out() {
+        tokio::time::pause();
+        let config = ResponseBodyTimeout::new().with_read_timeout(Duration::from_secs(5));
+        let (tx, body) = timed_body(config);
+        tx.send(Bytes::from_static(b"hello")).unwrap();
+
+        let now = tokio::time::Instant::now();
+        let err = read_to_end(body)
+            .await
+            .expect_err("the body never finishes");
+        assert_eq!(
+            err.to_string(),
+            "response body read timeout occurred after 5s"
+        );
+        assert_elapsed!(now, Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn slow_body_fails_minimum_throughput() {
+        tokio::time::pause();
+        let config = ResponseBodyTimeout::new()
+            .with_minimum_throughput(MinimumThroughput::new(100, Duration::from_secs(1)));
+        let (tx, body) = timed_body(config);
+        tokio::spawn(async move {
+            loop {
+                if tx.send(Bytes::from_static(&[0; 10])).is_err() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        });
+
+        let now = tokio::time::Instant::now();
+        let err = read_to_end(body).await.expect_err("the body is too slow");
+        assert_eq!(
+            err.to_string(),
+            "response body minimum throughput timeout occurred after 1s"
+        );
+        assert_elapsed!(now, Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn body_that_keeps_up_is_read_completely() {
+        tokio::time::pause();
+        let config = ResponseBodyTimeout::new()
+            .with_read_timeout(Duration::from_secs(10))
+            .with_minimum_throughput(MinimumThroughput::new(100, Duration::from_secs(1)));
+        let (tx, body) = timed_body(config);
+        tokio::spawn(async move {
+            for _ in 0..10 {
+                tx.send(Bytes::from_static(&[0; 100])).unwrap();
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        });
+
+        assert_eq!(read_to_end(body).await.unwrap(), 1000);
+    }
+}
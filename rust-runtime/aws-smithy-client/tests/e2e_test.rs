@@ -4,17 +4,29 @@
  */
 
 use crate::test_operation::{TestOperationParser, TestPolicy};
+use aws_smithy_async::assert_elapsed;
 use aws_smithy_async::rt::sleep::TokioSleep;
+use aws_smithy_async::rt::time::TokioTimeSource;
 
-use aws_smithy_client::test_connection::TestConnection;
-use aws_smithy_client::Client;
+use aws_smithy_client::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+use aws_smithy_client::rate_limit::{RateLimit, RateLimiter};
+use aws_smithy_client::test_connection::{Fault, FaultInjectingConnection, TestConnection};
+use aws_smithy_client::timeout::ResponseBodyTimeout;
+use aws_smithy_client::{Builder, Client};
 use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::interceptor::{Interceptor, Interceptors, OperationError, OperationInput};
 use aws_smithy_http::operation;
 use aws_smithy_http::operation::Operation;
-use aws_smithy_http::result::SdkError;
-use std::sync::Arc;
+use aws_smithy_http::result::{AttemptHistory, ConnectorError, SdkError};
+use aws_smithy_types::retry::RetryMode;
+use aws_smithy_types::timeout::Api;
+use aws_smithy_types::tristate::TriState;
+use http::header::{HeaderName, HeaderValue};
+use http_body::Body;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tower::layer::util::Identity;
+use tower::BoxError;
 
 mod test_operation {
     use aws_smithy_http::operation;
@@ -25,6 +37,7 @@ mod test_operation {
     use bytes::Bytes;
     use std::error::Error;
     use std::fmt::{self, Debug, Display, Formatter};
+    use std::time::Duration;
 
     #[derive(Clone)]
     pub(super) struct TestOperationParser;
@@ -76,7 +89,18 @@ mod test_operation {
     {
         fn classify(&self, err: Result<&T, &SdkError<E>>) -> RetryKind {
             let kind = match err {
-                Err(SdkError::ServiceError { err, .. }) => err.retryable_error_kind(),
+                Err(SdkError::ServiceError { err, raw }) => {
+                    // honour `x-amz-retry-after` like the AWS retry policy does
+                    let retry_after = raw
+                        .http()
+                        .headers()
+                        .get("x-amz-retry-after")
+                        .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+                    if let Some(retry_after) = retry_after {
+                        return RetryKind::Explicit(Duration::from_millis(retry_after));
+                    }
+                    err.retryable_error_kind()
+                }
                 Ok(_) => return RetryKind::Unnecessary,
                 _ => panic!("test handler only handles modeled errors got: {:?}", err),
             };
@@ -172,6 +196,494 @@ async fn end_to_end_retry_test() {
     assert_time_passed(initial, Duration::from_secs(7));
 }
 
+#[tokio::test]
+async fn attempt_history_is_recorded() {
+    fn req() -> http::Request<SdkBody> {
+        http::Request::builder()
+            .body(SdkBody::from("request body"))
+            .unwrap()
+    }
+
+    fn response(status: u16) -> http::Response<&'static str> {
+        http::Response::builder()
+            .status(status)
+            .body("response body")
+            .unwrap()
+    }
+
+    let events = vec![
+        // First operation succeeds after two retries
+        (req(), response(500)),
+        (req(), response(500)),
+        (req(), response(200)),
+        // Second operation exhausts its attempts
+        (req(), response(500)),
+        (req(), response(500)),
+    ];
+    let conn = TestConnection::new(events);
+    let retry_config = aws_smithy_client::retry::Config::default()
+        .with_initial_backoff(Duration::from_secs(1))
+        .with_base(|| 1_f64);
+    let client = Client::<TestConnection<_>, Identity>::new(conn.clone())
+        .with_retry_config(retry_config)
+        .with_sleep_impl(Arc::new(TokioSleep::new()));
+    tokio::time::pause();
+
+    let resp = client
+        .call_raw(test_operation())
+        .await
+        .expect("successful operation");
+    let history = resp.attempt_history().expect("history is always recorded");
+    assert_eq!(history.attempt_count(), 3);
+    assert_eq!(history.total_backoff(), Duration::from_secs(3));
+    assert_eq!(
+        history.retried_errors(),
+        vec!["service error (HTTP 500)", "service error (HTTP 500)"]
+    );
+    assert_eq!(history.attempts()[2].status(), Some(200));
+
+    let mut operation = test_operation();
+    let history = AttemptHistory::new();
+    operation.properties_mut().insert(history.clone());
+    let err = client
+        .with_retry_config(
+            aws_smithy_client::retry::Config::default()
+                .with_max_attempts(2)
+                .with_base(|| 1_f64),
+        )
+        .call_raw(operation)
+        .await
+        .expect_err("all responses failed");
+    assert_eq!(err.attempt_history().unwrap().attempt_count(), 2);
+    assert_eq!(history.attempt_count(), 2);
+    assert_eq!(history.retried_errors().len(), 1);
+}
+
+#[tokio::test]
+async fn retries_consume_rate_limit_tokens() {
+    fn req() -> http::Request<SdkBody> {
+        http::Request::builder()
+            .body(SdkBody::from("request body"))
+            .unwrap()
+    }
+
+    fn response(status: u16) -> http::Response<&'static str> {
+        http::Response::builder()
+            .status(status)
+            .body("response body")
+            .unwrap()
+    }
+
+    let events = vec![
+        (req(), response(500)),
+        (req(), response(200)),
+        (req(), response(200)),
+    ];
+    let conn = TestConnection::new(events);
+    let retry_config = aws_smithy_client::retry::Config::default()
+        .with_initial_backoff(Duration::from_secs(1))
+        .with_base(|| 1_f64);
+    let rate_limiter =
+        RateLimiter::new().with_operation_limit("TestOperation", RateLimit::new(1.0));
+    let client = Client::<TestConnection<_>, Identity>::new(conn.clone())
+        .with_retry_config(retry_config)
+        .with_rate_limiter(rate_limiter)
+        .with_sleep_impl(Arc::new(TokioSleep::new()));
+    tokio::time::pause();
+
+    let operation =
+        || test_operation().with_metadata(operation::Metadata::new("TestOperation", "test"));
+    let initial = tokio::time::Instant::now();
+    let resp = client
+        .call(operation())
+        .await
+        .expect("successful operation");
+    assert_eq!(resp, "Hello!");
+    assert_eq!(conn.requests().len(), 2);
+    // the bucket is refilled during the backoff, so the retry doesn't wait for a token
+    assert_time_passed(initial, Duration::from_secs(1));
+
+    let initial = tokio::time::Instant::now();
+    client
+        .call(operation())
+        .await
+        .expect("successful operation");
+    assert_eq!(conn.requests().len(), 3);
+    // the retry took the token, so the next request waits for the bucket to be refilled
+    assert_time_passed(initial, Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn adaptive_rate_limit_delay_counts_towards_api_call_timeout() {
+    let requests = Arc::new(Mutex::new(0));
+    let conn = tower::service_fn({
+        let requests = requests.clone();
+        move |_request: http::Request<SdkBody>| {
+            *requests.lock().unwrap() += 1;
+            async move {
+                Ok::<_, ConnectorError>(
+                    http::Response::builder()
+                        .status(500)
+                        .body(SdkBody::from("response body"))
+                        .unwrap(),
+                )
+            }
+        }
+    });
+    let retry_config = aws_smithy_client::retry::Config::default()
+        .with_retry_mode(RetryMode::Adaptive)
+        .with_max_attempts(1);
+    let timeout_config = aws_smithy_types::timeout::Config::new()
+        .with_api_timeouts(Api::new().with_call_timeout(TriState::Set(Duration::from_millis(100))));
+    let client = Builder::new()
+        .connector(conn)
+        .middleware(Identity::new())
+        .sleep_impl(Some(Arc::new(TokioSleep::new())))
+        .build()
+        .with_retry_config(retry_config)
+        .with_timeout_config(timeout_config);
+    tokio::time::pause();
+
+    // every response is a throttling error, so the client slows down until requests are delayed
+    // for longer than the API call timeout
+    let mut sent = 0;
+    let err = loop {
+        assert!(sent < 20, "requests were never delayed past the timeout");
+        match client.call(test_operation()).await {
+            Err(SdkError::TimeoutError(err)) => break err,
+            Err(SdkError::ServiceError { .. }) => sent += 1,
+            other => panic!("unexpected result: {:?}", other),
+        }
+    };
+    assert!(
+        err.to_string()
+            .contains("API call (all attempts including retries)"),
+        "{}",
+        err
+    );
+    assert_eq!(
+        *requests.lock().unwrap(),
+        sent,
+        "the delayed request is never sent"
+    );
+}
+
+#[tokio::test]
+async fn open_circuit_fails_fast_until_it_becomes_half_open() {
+    let status = Arc::new(Mutex::new(503));
+    let requests = Arc::new(Mutex::new(0));
+    let conn = tower::service_fn({
+        let (status, requests) = (status.clone(), requests.clone());
+        move |_request: http::Request<SdkBody>| {
+            *requests.lock().unwrap() += 1;
+            let response = http::Response::builder()
+                .status(*status.lock().unwrap())
+                .body(SdkBody::from("response body"))
+                .unwrap();
+            async move { Ok::<_, ConnectorError>(response) }
+        }
+    });
+    let breaker = CircuitBreaker::new(
+        CircuitBreakerConfig::new()
+            .with_failure_threshold(2)
+            .with_open_duration(Duration::from_secs(10)),
+    )
+    .with_time_source(TokioTimeSource::new());
+    let client = Builder::new()
+        .connector(conn)
+        .circuit_breaker(breaker.clone())
+        .middleware(Identity::new())
+        .sleep_impl(Some(Arc::new(TokioSleep::new())))
+        .build();
+    tokio::time::pause();
+    // the operation isn't retried, so that each call sends at most one request
+    let operation = || test_operation().with_retry_policy(());
+    const ENDPOINT: &str = "https://test-service.test-region.amazonaws.com";
+
+    for _ in 0..2 {
+        let err = client
+            .call(operation())
+            .await
+            .expect_err("the service fails");
+        assert!(matches!(err, SdkError::ServiceError { .. }), "{:?}", err);
+    }
+    assert_eq!(breaker.state(ENDPOINT), CircuitState::Open);
+
+    *status.lock().unwrap() = 200;
+    let err = client
+        .call(operation())
+        .await
+        .expect_err("the circuit is open");
+    assert!(matches!(err, SdkError::CircuitBreakerOpen(_)), "{:?}", err);
+    assert_eq!(*requests.lock().unwrap(), 2, "the request was not sent");
+
+    tokio::time::advance(Duration::from_secs(10)).await;
+    assert_eq!(breaker.state(ENDPOINT), CircuitState::HalfOpen);
+    client
+        .call(operation())
+        .await
+        .expect("the trial request succeeds");
+    assert_eq!(breaker.state(ENDPOINT), CircuitState::Closed);
+}
+
+#[derive(Debug)]
+struct RecordingInterceptor {
+    name: &'static str,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingInterceptor {
+    fn record(&self, hook: &str) -> Result<(), BoxError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("{}:{}", self.name, hook));
+        Ok(())
+    }
+}
+
+impl Interceptor for RecordingInterceptor {
+    fn before_serialization(&self, input: &mut OperationInput<'_>) -> Result<(), BoxError> {
+        if let Some(input) = input.downcast_mut::<Vec<&'static str>>() {
+            input.push(self.name);
+        }
+        self.record("before_serialization")
+    }
+
+    fn before_first_attempt(&self, _request: &mut operation::Request) -> Result<(), BoxError> {
+        self.record("before_first_attempt")
+    }
+
+    fn before_signing(&self, _request: &mut operation::Request) -> Result<(), BoxError> {
+        self.record("before_signing")
+    }
+
+    fn before_transmit(&self, request: &mut operation::Request) -> Result<(), BoxError> {
+        request.http_mut().headers_mut().insert(
+            HeaderName::from_static(self.name),
+            HeaderValue::from_static("intercepted"),
+        );
+        self.record("before_transmit")
+    }
+
+    fn after_receive(&self, _response: &mut operation::Response) -> Result<(), BoxError> {
+        self.record("after_receive")
+    }
+
+    fn before_deserialization(&self, _response: &mut operation::Response) -> Result<(), BoxError> {
+        self.record("before_deserialization")
+    }
+
+    fn on_error(&self, _error: &OperationError<'_>) {
+        self.record("on_error").unwrap();
+    }
+}
+
+#[tokio::test]
+async fn interceptors_are_called_for_each_phase() {
+    fn req() -> http::Request<SdkBody> {
+        http::Request::builder()
+            .body(SdkBody::from("request body"))
+            .unwrap()
+    }
+
+    fn response(status: u16) -> http::Response<&'static str> {
+        http::Response::builder()
+            .status(status)
+            .body("response body")
+            .unwrap()
+    }
+
+    let events = vec![
+        // First operation succeeds after one retry
+        (req(), response(500)),
+        (req(), response(200)),
+        // Second operation exhausts its attempts
+        (req(), response(500)),
+        (req(), response(500)),
+    ];
+    let conn = TestConnection::new(events);
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let client = Builder::new()
+        .connector(conn.clone())
+        .middleware(Identity::new())
+        .interceptor(RecordingInterceptor {
+            name: "client",
+            calls: calls.clone(),
+        })
+        .sleep_impl(Some(Arc::new(TokioSleep::new())))
+        .build()
+        .with_retry_config(
+            aws_smithy_client::retry::Config::default()
+                .with_max_attempts(2)
+                .with_base(|| 1_f64),
+        );
+    tokio::time::pause();
+
+    let mut input = vec!["input"];
+    client
+        .before_serialization::<_, ()>(&mut input)
+        .expect("input is valid");
+    assert_eq!(input, vec!["input", "client"]);
+
+    let mut operation = test_operation();
+    operation
+        .properties_mut()
+        .insert(Interceptors::new().with_interceptor(RecordingInterceptor {
+            name: "operation",
+            calls: calls.clone(),
+        }));
+    client.call(operation).await.expect("successful operation");
+
+    let attempt = |calls: &mut Vec<String>| {
+        for hook in [
+            "before_signing",
+            "before_transmit",
+            "after_receive",
+            "before_deserialization",
+        ] {
+            calls.push(format!("client:{}", hook));
+            calls.push(format!("operation:{}", hook));
+        }
+    };
+    let mut expected = vec![
+        "client:before_serialization".to_string(),
+        "client:before_first_attempt".to_string(),
+        "operation:before_first_attempt".to_string(),
+    ];
+    attempt(&mut expected);
+    attempt(&mut expected);
+    assert_eq!(*calls.lock().unwrap(), expected);
+    for request in conn.requests().iter() {
+        assert_eq!(request.actual.headers()["client"], "intercepted");
+        assert_eq!(request.actual.headers()["operation"], "intercepted");
+    }
+
+    calls.lock().unwrap().clear();
+    client
+        .call(test_operation())
+        .await
+        .expect_err("all responses failed");
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.first().unwrap(), "client:before_first_attempt");
+    assert_eq!(calls.last().unwrap(), "client:on_error");
+    assert_eq!(calls.iter().filter(|c| c.ends_with("on_error")).count(), 1);
+    assert!(!calls.iter().any(|c| c.starts_with("operation:")));
+}
+
+#[derive(Debug)]
+struct RejectInput;
+
+impl Interceptor for RejectInput {
+    fn before_serialization(&self, _input: &mut OperationInput<'_>) -> Result<(), BoxError> {
+        Err("input rejected".into())
+    }
+}
+
+#[test]
+fn before_serialization_errors_are_construction_failures() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let client = Builder::new()
+        .connector(TestConnection::<&'static str>::new(vec![]))
+        .middleware(Identity::new())
+        .interceptor(RejectInput)
+        .interceptor(RecordingInterceptor {
+            name: "client",
+            calls: calls.clone(),
+        })
+        .build();
+
+    let err = client
+        .before_serialization::<_, ()>(&mut ())
+        .expect_err("input is rejected");
+    match &err {
+        SdkError::ConstructionFailure(err) => assert_eq!(err.to_string(), "input rejected"),
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert_eq!(*calls.lock().unwrap(), vec!["client:on_error"]);
+}
+
+#[tokio::test]
+async fn stalled_response_body_times_out() {
+    // keep the senders alive so that the response bodies stall instead of ending
+    let senders = Arc::new(Mutex::new(Vec::new()));
+    let conn = tower::service_fn({
+        let senders = senders.clone();
+        move |_request: http::Request<SdkBody>| {
+            let (sender, body) = hyper::Body::channel();
+            senders.lock().unwrap().push(sender);
+            async move { Ok::<_, ConnectorError>(http::Response::new(SdkBody::from(body))) }
+        }
+    });
+    let client = Builder::new()
+        .connector(conn)
+        .middleware(Identity::new())
+        .response_body_timeout(ResponseBodyTimeout::new().with_read_timeout(Duration::from_secs(5)))
+        .sleep_impl(Some(Arc::new(TokioSleep::new())))
+        .build();
+    tokio::time::pause();
+
+    let mut resp = client
+        .call_raw(test_operation())
+        .await
+        .expect("the response headers were received");
+    let now = tokio::time::Instant::now();
+    let err = resp
+        .raw
+        .http_mut()
+        .body_mut()
+        .data()
+        .await
+        .expect("the body didn't end")
+        .expect_err("the body stalled");
+    assert_eq!(
+        err.to_string(),
+        "response body read timeout occurred after 5s"
+    );
+    assert_time_passed(now, Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn retries_recover_from_injected_faults() {
+    let conn = TestConnection::new(vec![(
+        http::Request::builder()
+            .body(SdkBody::from("request body"))
+            .unwrap(),
+        http::Response::builder()
+            .status(200)
+            .body("response body")
+            .unwrap(),
+    )]);
+    let throttled = Fault::ErrorResponse {
+        status: 503,
+        retry_after: Some(Duration::from_secs(5)),
+    };
+    let conn = FaultInjectingConnection::new(conn, Arc::new(TokioSleep::new()))
+        .with_scheduled_fault(0, throttled.clone())
+        .with_scheduled_fault(1, throttled);
+    let retry_config = aws_smithy_client::retry::Config::default()
+        .with_initial_backoff(Duration::from_secs(1))
+        .with_base(|| 1_f64);
+    let client = Client::<FaultInjectingConnection<TestConnection<_>>, Identity>::new(conn.clone())
+        .with_retry_config(retry_config)
+        .with_sleep_impl(Arc::new(TokioSleep::new()));
+    tokio::time::pause();
+
+    let now = tokio::time::Instant::now();
+    let resp = client
+        .call_raw(test_operation())
+        .await
+        .expect("the third attempt succeeds");
+    // both retries wait for the 5 second `retry_after` rather than the 1 second initial backoff
+    assert_elapsed!(now, Duration::from_secs(10));
+    let history = resp.attempt_history().unwrap();
+    assert_eq!(history.attempt_count(), 3);
+    for attempt in &history.attempts()[..2] {
+        assert_eq!(attempt.backoff(), Some(Duration::from_secs(5)));
+    }
+    assert_eq!(conn.injected_faults().len(), 2);
+}
+
 /// Validate that time has passed with a 5ms tolerance
 ///
 /// This is to account for some non-determinism in the Tokio timer
@@ -0,0 +1,136 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Measures how long a RestJson1 [`Router`] takes to route requests as the number of operations in
+//! the service grows.
+
+use aws_smithy_http_server::body::{boxed, Body, BoxBody};
+use aws_smithy_http_server::routing::request_spec::{
+    PathAndQuerySpec, PathSegment, PathSpec, QuerySegment, QuerySpec, RequestSpec, UriSpec,
+};
+use aws_smithy_http_server::Router;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures_util::FutureExt;
+use http::{Method, Request, Response};
+use std::convert::Infallible;
+use tower::util::BoxCloneService;
+use tower::{service_fn, Service};
+
+fn request_spec(method: Method, path: Vec<PathSegment>, query: Vec<QuerySegment>) -> RequestSpec {
+    RequestSpec::new(
+        method,
+        UriSpec::new(
+            None,
+            PathAndQuerySpec::new(
+                PathSpec::from_vector_unchecked(path),
+                QuerySpec::from_vector_unchecked(query),
+            ),
+        ),
+    )
+}
+
+/// A service that responds with the name of its operation in the `x-operation` header.
+fn operation(name: String) -> BoxCloneService<Request<()>, Response<BoxBody>, Infallible> {
+    BoxCloneService::new(service_fn(move |_req: Request<()>| {
+        let response = Response::builder()
+            .header("x-operation", name.as_str())
+            .body(boxed(Body::empty()))
+            .unwrap();
+        async move { Ok::<_, Infallible>(response) }
+    }))
+}
+
+/// A router for a service with `resources * 4` operations, similar to the CRUD operations of a
+/// RestJson1 service with one resource per `resource` index.
+fn router(resources: usize) -> Router<()> {
+    let mut routes = Vec::new();
+    for resource in 0..resources {
+        let literal = || PathSegment::Literal(format!("resource{}", resource));
+        let name = |operation: &str| format!("{}Resource{}", operation, resource);
+        routes.push((
+            operation(name("Create")),
+            request_spec(Method::POST, vec![literal()], vec![]),
+        ));
+        routes.push((
+            operation(name("Get")),
+            request_spec(Method::GET, vec![literal(), PathSegment::Label], vec![]),
+        ));
+        routes.push((
+            operation(name("Put")),
+            request_spec(
+                Method::PUT,
+                vec![literal(), PathSegment::Label, PathSegment::Greedy],
+                vec![QuerySegment::Key(String::from("versionId"))],
+            ),
+        ));
+        routes.push((
+            operation(name("Delete")),
+            request_spec(
+                Method::DELETE,
+                vec![literal(), PathSegment::Label],
+                vec![QuerySegment::KeyValue(String::from("force"), String::from("true"))],
+            ),
+        ));
+    }
+    Router::new_rest_json_router(routes)
+}
+
+/// Routes `req`, and returns the operation it was routed to.
+fn route(router: &mut Router<()>, req: Request<()>) -> Option<String> {
+    // The operations respond immediately, so the response future is always ready.
+    let response = router.call(req).now_or_never().unwrap().unwrap();
+    response
+        .headers()
+        .get("x-operation")
+        .map(|operation| operation.to_str().unwrap().to_string())
+}
+
+fn bench_group(c: &mut Criterion) {
+    let mut group = c.benchmark_group("routing");
+    for resources in [10, 75, 150] {
+        let mut router = router(resources);
+
+        // The last resource was the worst case for the linear scan the router used to perform.
+        let last = resources - 1;
+        let requests = move || {
+            [
+                Request::get(format!("/resource{}/id", last)).body(()).unwrap(),
+                Request::put(format!("/resource{}/id/a/b/c?versionId=1", last))
+                    .body(())
+                    .unwrap(),
+                Request::delete(format!("/resource{}/id?force=true", last))
+                    .body(())
+                    .unwrap(),
+                Request::get("/unknown/id").body(()).unwrap(),
+            ]
+        };
+        let routed: Vec<_> = requests().into_iter().map(|req| route(&mut router, req)).collect();
+        assert_eq!(
+            vec![
+                Some(format!("GetResource{}", last)),
+                Some(format!("PutResource{}", last)),
+                Some(format!("DeleteResource{}", last)),
+                None,
+            ],
+            routed
+        );
+
+        group.bench_with_input(BenchmarkId::new("router", resources * 4), &resources, |b, _| {
+            b.iter_batched(
+                requests,
+                |requests| {
+                    for req in requests {
+                        black_box(route(&mut router, req));
+                    }
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_group);
+criterion_main!(benches);
@@ -0,0 +1,415 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Access logs and per-operation metrics.
+//!
+//! [`AccessLogLayer`] hands an [`AccessLogRecord`] to an [`AccessLogSink`] for every response. The
+//! record has the operation that handled the request, the status and latency of the response, and
+//! the type of error that was returned, read from the [`OperationExtension`],
+//! [`ModeledErrorExtension`] and [`RuntimeErrorExtension`] of the response. This is enough to emit
+//! both access logs and latency, status and error metrics keyed by operation. [`TracingSink`]
+//! writes the records as structured [tracing] events; other backends can be plugged in by
+//! implementing [`AccessLogSink`], which is also implemented for closures.
+//!
+//! Values of path labels and query parameters that bind input members marked with the
+//! `@sensitive` trait are redacted from the URI of the record, as described by the
+//! [`SensitivityExtension`] of the response. Requests that do not reach an operation, for example
+//! because the URI matches none, are logged without redaction.
+//!
+//! See [`Router::layer`] for which requests are recorded when the layer is applied to the router.
+//! Records also have the [request ID](crate::request_id) of the request.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use aws_smithy_http_server::access_log::{AccessLogLayer, TracingSink};
+//! use aws_smithy_http_server::request_id::RequestIdLayer;
+//! use aws_smithy_http_server::Router;
+//! use tower::ServiceBuilder;
+//!
+//! # fn router() -> Router { unimplemented!() }
+//! let app = ServiceBuilder::new()
+//!     .layer(RequestIdLayer::new())
+//!     .layer(AccessLogLayer::new(TracingSink))
+//!     .service(router());
+//! ```
+//!
+//! [tracing]: https://docs.rs/tracing
+//! [`Router::layer`]: crate::routing::Router::layer
+
+use crate::extension::{ModeledErrorExtension, OperationExtension, RuntimeErrorExtension, SensitivityExtension};
+use crate::request_id::RequestId;
+use http::{Method, Request, Response, StatusCode, Uri};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+/// The type of error that a response represents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorType {
+    /// An error modeled in the Smithy model, returned by the operation handler.
+    Modeled(&'static str),
+    /// An unmodeled error returned by the framework, for example when the request fails to
+    /// deserialize (see [`crate::runtime_error::RuntimeErrorKind::name`]).
+    Runtime(String),
+}
+
+impl ErrorType {
+    /// Returns the name of the error.
+    pub fn name(&self) -> &str {
+        match self {
+            ErrorType::Modeled(name) => name,
+            ErrorType::Runtime(name) => name,
+        }
+    }
+}
+
+/// A request handled by the server, as recorded by [`AccessLogLayer`].
+#[derive(Debug, Clone)]
+pub struct AccessLogRecord {
+    method: Method,
+    uri: String,
+    status: StatusCode,
+    latency: Duration,
+    operation: Option<OperationExtension>,
+    error_type: Option<ErrorType>,
+    request_id: Option<RequestId>,
+}
+
+impl AccessLogRecord {
+    /// Returns the method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the path and query of the request, with sensitive values redacted.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the status of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the time from receiving the request to the response being ready to send, which does
+    /// not include the time taken to send a streaming response body.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Returns the operation that handled the request, or `None` if the request was not routed to
+    /// an operation.
+    pub fn operation(&self) -> Option<&OperationExtension> {
+        self.operation.as_ref()
+    }
+
+    /// Returns the type of error that the response represents, if any.
+    pub fn error_type(&self) -> Option<&ErrorType> {
+        self.error_type.as_ref()
+    }
+
+    /// Returns the ID of the request, if one was assigned. The [`Router`](crate::routing::Router)
+    /// assigns one to every request.
+    pub fn request_id(&self) -> Option<&RequestId> {
+        self.request_id.as_ref()
+    }
+}
+
+/// Receives the [`AccessLogRecord`]s of [`AccessLogLayer`].
+pub trait AccessLogSink: Send + Sync + 'static {
+    /// Records a request. This is called before the response is sent, so it should not block.
+    fn record(&self, record: &AccessLogRecord);
+}
+
+impl<F> AccessLogSink for F
+where
+    F: Fn(&AccessLogRecord) + Send + Sync + 'static,
+{
+    fn record(&self, record: &AccessLogRecord) {
+        self(record)
+    }
+}
+
+impl<A, B> AccessLogSink for (A, B)
+where
+    A: AccessLogSink,
+    B: AccessLogSink,
+{
+    fn record(&self, record: &AccessLogRecord) {
+        self.0.record(record);
+        self.1.record(record);
+    }
+}
+
+/// An [`AccessLogSink`] that emits every record as an `INFO` [tracing] event with the
+/// `aws_smithy_http_server::access_log` target.
+///
+/// [tracing]: https://docs.rs/tracing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingSink;
+
+impl AccessLogSink for TracingSink {
+    fn record(&self, record: &AccessLogRecord) {
+        let operation = record.operation().map(OperationExtension::operation);
+        tracing::info!(
+            target: "aws_smithy_http_server::access_log",
+            method = %record.method(),
+            uri = record.uri(),
+            status = record.status().as_u16(),
+            latency_ms = record.latency().as_secs_f64() * 1000.0,
+            operation = operation.as_deref(),
+            error_type = record.error_type().map(ErrorType::name),
+            request_id = record.request_id().map(RequestId::as_str),
+            "request completed"
+        );
+    }
+}
+
+/// A [`tower::Layer`] that records every request in an [`AccessLogSink`]. See the [module
+/// documentation] for details.
+///
+/// [module documentation]: crate::access_log
+pub struct AccessLogLayer<K> {
+    sink: Arc<K>,
+}
+
+impl<K> AccessLogLayer<K> {
+    /// Creates a layer that records requests in `sink`.
+    pub fn new(sink: K) -> Self {
+        Self { sink: Arc::new(sink) }
+    }
+}
+
+impl<K> Clone for AccessLogLayer<K> {
+    fn clone(&self) -> Self {
+        Self {
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+impl<K> fmt::Debug for AccessLogLayer<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLogLayer").finish()
+    }
+}
+
+impl<S, K> Layer<S> for AccessLogLayer<K> {
+    type Service = AccessLog<S, K>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AccessLog {
+            inner,
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+/// Middleware that records every request in an [`AccessLogSink`]. Created with [`AccessLogLayer`].
+pub struct AccessLog<S, K> {
+    inner: S,
+    sink: Arc<K>,
+}
+
+impl<S, K> Clone for AccessLog<S, K>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+impl<S, K> fmt::Debug for AccessLog<S, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLog").finish()
+    }
+}
+
+impl<B, ResBody, S, K> Service<Request<B>> for AccessLog<S, K>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    K: AccessLogSink,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let start = Instant::now();
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+        let request_id = req.extensions().get::<RequestId>().cloned();
+        let sink = self.sink.clone();
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let res = future.await?;
+            let extensions = res.extensions();
+            let error_type = match (
+                extensions.get::<ModeledErrorExtension>(),
+                extensions.get::<RuntimeErrorExtension>(),
+            ) {
+                (Some(modeled), _) => Some(ErrorType::Modeled(**modeled)),
+                (None, Some(runtime)) => Some(ErrorType::Runtime(runtime.to_string())),
+                (None, None) => None,
+            };
+            let record = AccessLogRecord {
+                method,
+                uri: redact_uri(&uri, extensions.get::<SensitivityExtension>()),
+                status: res.status(),
+                latency: start.elapsed(),
+                operation: extensions.get::<OperationExtension>().cloned(),
+                error_type,
+                request_id: request_id.or_else(|| extensions.get::<RequestId>().cloned()),
+            };
+            sink.record(&record);
+            Ok(res)
+        })
+    }
+}
+
+fn redact_uri(uri: &Uri, sensitivity: Option<&SensitivityExtension>) -> String {
+    match sensitivity {
+        Some(sensitivity) => sensitivity.redact_uri(uri),
+        None => uri
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or("/")
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{boxed, Body, BoxBody};
+    use crate::request_id::RequestIdLayer;
+    use crate::Router;
+    use std::convert::Infallible;
+    use std::sync::Mutex;
+    use tower::util::{service_fn, BoxCloneService};
+    use tower::{ServiceBuilder, ServiceExt};
+
+    /// Returns a sink that stores the records it receives.
+    fn sink() -> (impl AccessLogSink, Arc<Mutex<Vec<AccessLogRecord>>>) {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let records = records.clone();
+            move |record: &AccessLogRecord| records.lock().unwrap().push(record.clone())
+        };
+        (sink, records)
+    }
+
+    #[test]
+    fn redacts_sensitive_uri_parts() {
+        let uri: Uri = "/cities/seattle/forecast/2022/06?units=metric&apiKey=secret&flag"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            "/cities/seattle/forecast/2022/06?units=metric&apiKey=secret&flag",
+            redact_uri(&uri, None)
+        );
+        assert_eq!(
+            "/cities/**REDACTED**/forecast/2022/06?units=metric&apiKey=**REDACTED**&flag",
+            redact_uri(
+                &uri,
+                Some(&SensitivityExtension::new().with_label(1).with_query_param("apiKey"))
+            )
+        );
+        assert_eq!(
+            "/cities/seattle/forecast/**REDACTED**?units=**REDACTED**&apiKey=**REDACTED**&flag",
+            redact_uri(
+                &uri,
+                Some(&SensitivityExtension::new().with_greedy_label(3).with_all_query_params())
+            )
+        );
+        assert_eq!("/", SensitivityExtension::new().redact_uri(&"/".parse().unwrap()));
+
+        // Names are compared after they are decoded.
+        let uri: Uri = "/?apiK%65y=secret&api+key=secret&apikey=public".parse().unwrap();
+        const SENSITIVITY: SensitivityExtension =
+            SensitivityExtension::from_static(&[], None, &["apiKey", "api key"], false);
+        assert_eq!(
+            SensitivityExtension::new()
+                .with_query_param("apiKey")
+                .with_query_param("api key"),
+            SENSITIVITY
+        );
+        assert_eq!(
+            "/?apiK%65y=**REDACTED**&api+key=**REDACTED**&apikey=public",
+            redact_uri(&uri, Some(&SENSITIVITY))
+        );
+    }
+
+    #[tokio::test]
+    async fn records_operation_errors() {
+        let (sink, records) = sink();
+        let service = service_fn(|_req: Request<Body>| async {
+            let mut res = Response::new(boxed(Body::empty()));
+            *res.status_mut() = StatusCode::BAD_REQUEST;
+            res.extensions_mut()
+                .insert(OperationExtension::new("com.example", "GetCity"));
+            res.extensions_mut()
+                .insert(ModeledErrorExtension::new("NoSuchResource"));
+            res.extensions_mut().insert(SensitivityExtension::new().with_label(1));
+            Ok::<_, Infallible>(res)
+        });
+        let app = ServiceBuilder::new()
+            .layer(RequestIdLayer::new())
+            .layer(AccessLogLayer::new(sink))
+            .service(service);
+        let res = app
+            .oneshot(Request::get("/cities/seattle").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let records = records.lock().unwrap();
+        assert_eq!(1, records.len());
+        let record = &records[0];
+        assert_eq!(Method::GET, record.method());
+        assert_eq!("/cities/**REDACTED**", record.uri());
+        assert_eq!(StatusCode::BAD_REQUEST, record.status());
+        assert_eq!("com.example#GetCity", record.operation().unwrap().operation());
+        assert_eq!(Some(&ErrorType::Modeled("NoSuchResource")), record.error_type());
+        assert_eq!(res.extensions().get::<RequestId>(), record.request_id());
+    }
+
+    #[tokio::test]
+    async fn records_runtime_errors() {
+        let (sink, records) = sink();
+        let router: Router<Body> = Router::new_aws_json_11_router(std::iter::empty::<(
+            BoxCloneService<Request<Body>, Response<BoxBody>, Infallible>,
+            String,
+        )>());
+        AccessLogLayer::new((TracingSink, sink))
+            .layer(router)
+            .oneshot(Request::post("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let records = records.lock().unwrap();
+        let record = &records[0];
+        assert_eq!(StatusCode::NOT_FOUND, record.status());
+        assert!(record.operation().is_none());
+        assert_eq!(
+            Some(&ErrorType::Runtime(String::from("UnknownOperationException"))),
+            record.error_type()
+        );
+        assert!(record.request_id().is_some());
+    }
+}
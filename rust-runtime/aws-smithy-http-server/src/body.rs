@@ -14,6 +14,7 @@ pub use hyper::body::Body;
 use bytes::Bytes;
 
 use crate::error::{BoxError, Error};
+use crate::runtime_error::RuntimeErrorKind;
 
 pub type BoxBody = http_body::combinators::UnsyncBoxBody<Bytes, Error>;
 
@@ -45,6 +46,25 @@ pub(crate) fn empty() -> BoxBody {
     boxed(http_body::Empty::new())
 }
 
+/// Reads `body` into memory, failing with [`RuntimeErrorKind::PayloadTooLarge`] if it is larger
+/// than `max_body_size` bytes.
+pub(crate) async fn read_limited<B>(body: B, max_body_size: usize) -> Result<Bytes, RuntimeErrorKind>
+where
+    B: http_body::Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    futures_util::pin_mut!(body);
+    let mut buffered = bytes::BytesMut::new();
+    while let Some(data) = body.data().await {
+        let data = data.map_err(|err| RuntimeErrorKind::InternalFailure(Error::new(err)))?;
+        if buffered.len() + data.len() > max_body_size {
+            return Err(RuntimeErrorKind::PayloadTooLarge);
+        }
+        buffered.extend_from_slice(&data);
+    }
+    Ok(buffered.freeze())
+}
+
 /// Convert anything that can be converted into a [`hyper::body::Body`] into a [`BoxBody`].
 /// This simplifies codegen a little bit.
 #[doc(hidden)]
@@ -0,0 +1,349 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Event stream support.
+//!
+//! Operations with an input event stream extract an [`EventStreamInput`] from the request, and
+//! operations with an output event stream return an [`EventStreamOutput`] as their response. These
+//! wrap the [`Receiver`] and [`EventStreamSender`] of the stream, and implement [`FromRequest`] and
+//! [`IntoResponse`] on top of [`extract_event_stream`] and [`into_event_stream_body`]. Messages are
+//! framed and unframed with [`aws_smithy_eventstream`], using the same marshallers and unmarshallers
+//! that the generated clients use.
+//!
+//! Neither side buffers the stream: the receiver reads frames from the request body as they
+//! arrive, and the response body is polled for a new frame whenever the connection can take it.
+//! Over HTTP/2 this means that a handler can respond and keep sending events while the client is
+//! still sending its own, as required by duplex event streams. Layers that buffer the whole request
+//! body before calling the handler, like the SigV4 authentication layer, prevent this.
+//!
+//! Modeled errors of an output event stream are sent as frames with a `:message-type` of
+//! `exception` (see [`exception_message`]). After the error marshaller has written an exception,
+//! the handler is expected to end the stream.
+
+use crate::body::{boxed, Body, BoxBody, HttpBody};
+use crate::error::BoxError;
+use crate::protocols::check_event_stream_content_type;
+use crate::rejection::RequestRejection;
+use crate::request::{FromRequest, RequestParts};
+use crate::response::{IntoResponse, Response};
+use async_trait::async_trait;
+use aws_smithy_eventstream::frame::{Header, HeaderValue, Message, NoOpSigner};
+use aws_smithy_http::body::SdkBody;
+use bytes::Bytes;
+use std::error::Error as StdError;
+use std::fmt;
+
+#[doc(inline)]
+pub use aws_smithy_eventstream::frame::{MarshallMessage, UnmarshallMessage, UnmarshalledMessage};
+#[doc(inline)]
+pub use aws_smithy_http::event_stream::{EventStreamSender, RawMessage, Receiver};
+
+/// The `Content-Type` of request and response bodies that contain an event stream.
+pub const CONTENT_TYPE: &str = "application/vnd.amazon.eventstream";
+
+/// Takes the body out of the request and returns a [`Receiver`] that unmarshalls the messages of
+/// the input event stream as they arrive.
+///
+/// The request is rejected if its `Content-Type` is not [`CONTENT_TYPE`].
+pub fn extract_event_stream<T, E, B>(
+    req: &mut RequestParts<B>,
+    unmarshaller: impl UnmarshallMessage<Output = T, Error = E> + Send + 'static,
+) -> Result<Receiver<T, E>, RequestRejection>
+where
+    B: HttpBody<Data = Bytes> + Send + Sync + 'static,
+    B::Error: Into<BoxError>,
+{
+    check_event_stream_content_type(req)?;
+    let body = req.take_body().ok_or(RequestRejection::BodyAlreadyExtracted)?;
+    Ok(Receiver::new(unmarshaller, into_sdk_body(body)))
+}
+
+fn into_sdk_body<B>(body: B) -> SdkBody
+where
+    B: HttpBody<Data = Bytes> + Send + Sync + 'static,
+    B::Error: Into<BoxError>,
+{
+    crate::body::try_downcast::<Body, _>(body)
+        .map(SdkBody::from)
+        .unwrap_or_else(|body| SdkBody::from_dyn(http_body::combinators::BoxBody::new(body.map_err(Into::into))))
+}
+
+/// Converts the output event stream of an operation into a response body.
+///
+/// `marshaller` converts the events and `error_marshaller` the modeled errors of the stream into
+/// messages. If a message fails to marshall, the response body ends with an error, which aborts the
+/// response.
+pub fn into_event_stream_body<T, E>(
+    sender: EventStreamSender<T, E>,
+    marshaller: impl MarshallMessage<Input = T> + Send + Sync + 'static,
+    error_marshaller: impl MarshallMessage<Input = E> + Send + Sync + 'static,
+) -> BoxBody
+where
+    T: 'static,
+    E: StdError + Send + Sync + 'static,
+{
+    let stream = sender.into_body_stream(marshaller, error_marshaller, NoOpSigner {});
+    boxed(Body::wrap_stream(stream))
+}
+
+/// The input event stream of an operation, whose messages are unmarshalled with `U`.
+///
+/// It is extracted from the request with [`FromRequest`], which creates the unmarshaller with
+/// [`Default`] and rejects requests whose `Content-Type` is not [`CONTENT_TYPE`].
+pub struct EventStreamInput<U: UnmarshallMessage> {
+    receiver: Receiver<U::Output, U::Error>,
+}
+
+impl<U: UnmarshallMessage> EventStreamInput<U> {
+    /// Returns the [`Receiver`] of the stream.
+    pub fn into_inner(self) -> Receiver<U::Output, U::Error> {
+        self.receiver
+    }
+}
+
+impl<U: UnmarshallMessage> fmt::Debug for EventStreamInput<U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventStreamInput").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<B, U> FromRequest<B> for EventStreamInput<U>
+where
+    B: HttpBody<Data = Bytes> + Send + Sync + 'static,
+    B::Error: Into<BoxError>,
+    U: UnmarshallMessage + Default + Send + 'static,
+{
+    type Rejection = RequestRejection;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let receiver = extract_event_stream(req, U::default())?;
+        Ok(EventStreamInput { receiver })
+    }
+}
+
+/// The output event stream of an operation, whose events are marshalled with `M` and whose
+/// modeled errors are marshalled with `EM`.
+///
+/// It is converted into a `200 OK` response with a `Content-Type` of [`CONTENT_TYPE`] by
+/// [`IntoResponse`], which creates the marshallers with [`Default`].
+pub struct EventStreamOutput<M: MarshallMessage, EM: MarshallMessage> {
+    sender: EventStreamSender<M::Input, EM::Input>,
+}
+
+impl<M: MarshallMessage, EM: MarshallMessage> From<EventStreamSender<M::Input, EM::Input>>
+    for EventStreamOutput<M, EM>
+{
+    fn from(sender: EventStreamSender<M::Input, EM::Input>) -> Self {
+        EventStreamOutput { sender }
+    }
+}
+
+impl<M: MarshallMessage, EM: MarshallMessage> fmt::Debug for EventStreamOutput<M, EM> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventStreamOutput").finish_non_exhaustive()
+    }
+}
+
+impl<M, EM> IntoResponse for EventStreamOutput<M, EM>
+where
+    M: MarshallMessage + Default + Send + Sync + 'static,
+    EM: MarshallMessage + Default + Send + Sync + 'static,
+    EM::Input: StdError + Send + Sync + 'static,
+{
+    fn into_response(self) -> Response {
+        let body = into_event_stream_body(self.sender, M::default(), EM::default());
+        http::Response::builder()
+            .header(http::header::CONTENT_TYPE, CONTENT_TYPE)
+            .body(body)
+            .expect("the content type is a valid header value")
+    }
+}
+
+/// Creates an event message of type `event_type`, which is the name of the member of the event
+/// stream union that the message represents.
+pub fn event_message(event_type: &'static str, content_type: &'static str, payload: impl Into<Bytes>) -> Message {
+    Message::new(payload)
+        .add_header(Header::new(":message-type", HeaderValue::String("event".into())))
+        .add_header(Header::new(":event-type", HeaderValue::String(event_type.into())))
+        .add_header(Header::new(":content-type", HeaderValue::String(content_type.into())))
+}
+
+/// Creates an exception message for a modeled error of type `exception_type`, which is the name of
+/// the member of the event stream union that targets the error.
+pub fn exception_message(
+    exception_type: &'static str,
+    content_type: &'static str,
+    payload: impl Into<Bytes>,
+) -> Message {
+    Message::new(payload)
+        .add_header(Header::new(":message-type", HeaderValue::String("exception".into())))
+        .add_header(Header::new(
+            ":exception-type",
+            HeaderValue::String(exception_type.into()),
+        ))
+        .add_header(Header::new(":content-type", HeaderValue::String(content_type.into())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_smithy_eventstream::error::Error as EventStreamError;
+    use aws_smithy_eventstream::smithy::parse_response_headers;
+    use futures_util::stream;
+    use http::Request;
+
+    #[derive(Debug, PartialEq)]
+    struct Greeting(String);
+
+    #[derive(Debug, PartialEq)]
+    struct InvalidGreeting(String);
+
+    impl std::fmt::Display for InvalidGreeting {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "InvalidGreeting: {}", self.0)
+        }
+    }
+
+    impl StdError for InvalidGreeting {}
+
+    #[derive(Debug, Default)]
+    struct Marshaller;
+
+    impl MarshallMessage for Marshaller {
+        type Input = Greeting;
+
+        fn marshall(&self, input: Greeting) -> Result<Message, EventStreamError> {
+            Ok(event_message("greeting", "text/plain", input.0))
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct ErrorMarshaller;
+
+    impl MarshallMessage for ErrorMarshaller {
+        type Input = InvalidGreeting;
+
+        fn marshall(&self, input: InvalidGreeting) -> Result<Message, EventStreamError> {
+            Ok(exception_message("invalidGreeting", "text/plain", input.0))
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Unmarshaller;
+
+    impl UnmarshallMessage for Unmarshaller {
+        type Output = Greeting;
+        type Error = InvalidGreeting;
+
+        fn unmarshall(
+            &self,
+            message: &Message,
+        ) -> Result<UnmarshalledMessage<Greeting, InvalidGreeting>, EventStreamError> {
+            let payload = String::from_utf8(message.payload().to_vec()).unwrap();
+            let headers = parse_response_headers(message)?;
+            match headers.message_type.as_str() {
+                "event" => Ok(UnmarshalledMessage::Event(Greeting(payload))),
+                _ => Ok(UnmarshalledMessage::Error(InvalidGreeting(payload))),
+            }
+        }
+    }
+
+    fn frame(message: Message) -> Bytes {
+        let mut buffer = Vec::new();
+        message.write_to(&mut buffer).unwrap();
+        buffer.into()
+    }
+
+    async fn next_message(body: &mut BoxBody) -> Option<Message> {
+        let mut frame = body.data().await?.unwrap();
+        Some(Message::read_from(&mut frame).unwrap())
+    }
+
+    fn request<B>(content_type: &str, body: B) -> RequestParts<B> {
+        RequestParts::new(
+            Request::builder()
+                .header(http::header::CONTENT_TYPE, content_type)
+                .body(body)
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn duplex_echo() {
+        let (mut client, body) = Body::channel();
+        let mut req = request(CONTENT_TYPE, body);
+        let input = EventStreamInput::<Unmarshaller>::from_request(&mut req).await.unwrap();
+        assert!(matches!(
+            EventStreamInput::<Unmarshaller>::from_request(&mut req).await,
+            Err(RequestRejection::BodyAlreadyExtracted)
+        ));
+
+        // Echo every greeting back until the client ends its stream.
+        let echo = stream::unfold(input.into_inner(), |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(Some(Greeting(greeting))) => Some((Ok(Greeting(format!("echo: {}", greeting))), receiver)),
+                Ok(None) => None,
+                Err(_) => Some((Err(InvalidGreeting("bad".into())), receiver)),
+            }
+        });
+        let output = EventStreamOutput::<Marshaller, ErrorMarshaller>::from(EventStreamSender::from(echo));
+        let res = output.into_response();
+        assert_eq!(http::StatusCode::OK, res.status());
+        assert_eq!(CONTENT_TYPE, res.headers()[http::header::CONTENT_TYPE]);
+        let mut res = res.into_body();
+
+        // Each greeting is echoed before the next one is sent.
+        for greeting in ["hello", "hi"] {
+            client
+                .send_data(frame(event_message("greeting", "text/plain", greeting)))
+                .await
+                .unwrap();
+            let message = next_message(&mut res).await.unwrap();
+            assert_eq!(format!("echo: {}", greeting).as_bytes(), &message.payload()[..]);
+        }
+
+        drop(client);
+        assert!(next_message(&mut res).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn other_content_types_are_rejected() {
+        let mut req = request("application/json", Body::empty());
+        assert!(matches!(
+            EventStreamInput::<Unmarshaller>::from_request(&mut req).await,
+            Err(RequestRejection::MissingEventStreamContentType)
+        ));
+        assert!(matches!(
+            extract_event_stream(&mut req, Unmarshaller),
+            Err(RequestRejection::MissingEventStreamContentType)
+        ));
+        // The body is left for other extractors.
+        assert!(req.take_body().is_some());
+    }
+
+    #[tokio::test]
+    async fn modeled_errors_are_exceptions() {
+        let events = stream::iter(vec![
+            Ok(Greeting("hello".into())),
+            Err(InvalidGreeting("goodbye".into())),
+        ]);
+        let mut res = into_event_stream_body(EventStreamSender::from(events), Marshaller, ErrorMarshaller);
+
+        let event = next_message(&mut res).await.unwrap();
+        let headers = parse_response_headers(&event).unwrap();
+        assert_eq!("event", headers.message_type.as_str());
+        assert_eq!("greeting", headers.smithy_type.as_str());
+
+        let exception = next_message(&mut res).await.unwrap();
+        let headers = parse_response_headers(&exception).unwrap();
+        assert_eq!("exception", headers.message_type.as_str());
+        assert_eq!("invalidGreeting", headers.smithy_type.as_str());
+        assert_eq!(Some("text/plain"), headers.content_type());
+        assert_eq!(&b"goodbye"[..], &exception.payload()[..]);
+
+        assert!(next_message(&mut res).await.is_none());
+    }
+}
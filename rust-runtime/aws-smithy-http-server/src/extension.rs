@@ -37,17 +37,21 @@
 //! Extension types are types that are stored in and extracted from _both_ requests and
 //! responses.
 //!
-//! There is only one _generic_ extension type _for requests_, [`Extension`].
+//! There is only one _generic_ extension type _for requests_, [`Extension`]. The router also
+//! stores [`HostLabels`] in requests to operations with a host prefix, which handlers can extract
+//! as an `Extension<HostLabels>`.
 //!
 //! On the other hand, the server SDK uses multiple concrete extension types for responses in order
 //! to store a variety of information, like the operation that was executed, the operation error
-//! that got returned, or the runtime error that happened, among others. The information stored in
-//! these types may be useful to [`tower::Layer`]s that post-process the response: for instance, a
-//! particular metrics layer implementation might want to emit metrics about the number of times an
-//! an operation got executed.
+//! that got returned, the runtime error that happened, or which parts of the request URI are
+//! sensitive, among others. The information stored in these types may be useful to
+//! [`tower::Layer`]s that post-process the response: for instance, a particular metrics layer
+//! implementation might want to emit metrics about the number of times an an operation got
+//! executed. The [`crate::access_log`] layer is one such implementation.
 //!
 //! [extensions]: https://docs.rs/http/latest/http/struct.Extensions.html
 
+use std::borrow::Cow;
 use std::ops::Deref;
 
 use crate::request::RequestParts;
@@ -130,6 +134,150 @@ impl Deref for RuntimeErrorExtension {
     }
 }
 
+/// Extension type used to store which parts of the request URI of an operation bind input members
+/// that are marked with the [`@sensitive` trait], so that layers that log requests can redact them
+/// (see [`SensitivityExtension::redact_uri`]). Path labels are identified by the index of their
+/// segment in the URI pattern of the operation, and query parameters by their name.
+///
+/// [`@sensitive` trait]: https://awslabs.github.io/smithy/1.0/spec/core/documentation-traits.html#sensitive-trait
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SensitivityExtension {
+    labels: Cow<'static, [usize]>,
+    greedy_label: Option<usize>,
+    query_params: Cow<'static, [&'static str]>,
+    all_query_params: bool,
+}
+
+impl SensitivityExtension {
+    /// The value that sensitive parts of the URI are replaced with.
+    pub const REDACTED: &'static str = "**REDACTED**";
+
+    /// Creates a new `SensitivityExtension` for an operation without sensitive URI parts.
+    pub fn new() -> SensitivityExtension {
+        Self::default()
+    }
+
+    /// Creates a new `SensitivityExtension` in a `const` context, with the path segments at the indexes
+    /// in `labels`, the segments from `greedy_label` on, the query parameters named in `query_params`
+    /// and, if `all_query_params` is `true`, all query parameters marked as sensitive.
+    pub const fn from_static(
+        labels: &'static [usize],
+        greedy_label: Option<usize>,
+        query_params: &'static [&'static str],
+        all_query_params: bool,
+    ) -> SensitivityExtension {
+        SensitivityExtension {
+            labels: Cow::Borrowed(labels),
+            greedy_label,
+            query_params: Cow::Borrowed(query_params),
+            all_query_params,
+        }
+    }
+
+    /// Marks the path segment at `index` as sensitive.
+    pub fn with_label(mut self, index: usize) -> Self {
+        self.labels.to_mut().push(index);
+        self
+    }
+
+    /// Marks the path segment at `index` and all the segments after it as sensitive.
+    pub fn with_greedy_label(mut self, index: usize) -> Self {
+        self.greedy_label = Some(index);
+        self
+    }
+
+    /// Marks the query parameter `name` as sensitive.
+    pub fn with_query_param(mut self, name: &'static str) -> Self {
+        self.query_params.to_mut().push(name);
+        self
+    }
+
+    /// Marks all query parameters as sensitive, as needed when a sensitive member is bound with
+    /// `@httpQueryParams`.
+    pub fn with_all_query_params(mut self) -> Self {
+        self.all_query_params = true;
+        self
+    }
+
+    /// Returns the path and query of `uri` with the values of the sensitive labels and query
+    /// parameters replaced with [`SensitivityExtension::REDACTED`]. Query parameter names are decoded
+    /// before they are compared, as they are when the query is deserialized.
+    pub fn redact_uri(&self, uri: &http::Uri) -> String {
+        let segments = uri
+            .path()
+            .split('/')
+            .skip(1)
+            .enumerate()
+            .map(|(index, segment)| match self.greedy_label {
+                Some(greedy) if index > greedy => None,
+                Some(greedy) if index == greedy => Some(Self::REDACTED),
+                _ if self.labels.contains(&index) => Some(Self::REDACTED),
+                _ => Some(segment),
+            });
+        let mut redacted = String::new();
+        for segment in segments.flatten() {
+            redacted.push('/');
+            redacted.push_str(segment);
+        }
+        if redacted.is_empty() {
+            redacted.push('/');
+        }
+        if let Some(query) = uri.query() {
+            let params = query.split('&').map(|param| match param.split_once('=') {
+                Some((name, _)) if self.is_sensitive_query_param(name) => {
+                    format!("{}={}", name, Self::REDACTED)
+                }
+                _ => param.to_string(),
+            });
+            redacted.push('?');
+            redacted.push_str(&params.collect::<Vec<_>>().join("&"));
+        }
+        redacted
+    }
+
+    /// Returns `true` if the query parameter with the percent-encoded `name` is sensitive.
+    fn is_sensitive_query_param(&self, name: &str) -> bool {
+        if self.all_query_params {
+            return true;
+        }
+        let decoded = form_urlencoded::parse(name.as_bytes()).next().map(|(name, _)| name);
+        matches!(decoded, Some(name) if self.query_params.contains(&name.as_ref()))
+    }
+}
+
+/// Extension type used to store the labels bound by the `hostPrefix` of an operation's [endpoint
+/// trait]. The router stores it in the request when it routes a RestJson1 or RestXml request to an
+/// operation with a host prefix, so handlers can extract it as an `Extension<HostLabels>`.
+///
+/// [endpoint trait]: https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostLabels(Vec<(String, String)>);
+
+impl HostLabels {
+    /// Creates a new `HostLabels` from pairs of label names and values.
+    pub fn new(labels: Vec<(String, String)>) -> HostLabels {
+        HostLabels(labels)
+    }
+
+    /// Returns the value bound to the label `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(label, _value)| label == name)
+            .map(|(_label, value)| value.as_str())
+    }
+
+    /// Returns `true` if no labels are bound.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the label names and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(label, value)| (label.as_str(), value.as_str()))
+    }
+}
+
 /// Generic extension type stored in and extracted from [request extensions].
 ///
 /// This is commonly used to share state across handlers.
@@ -10,9 +10,13 @@
 #[macro_use]
 pub(crate) mod macros;
 
+pub mod access_log;
 pub mod body;
 pub(crate) mod error;
+#[cfg(feature = "event-stream")]
+pub mod event_stream;
 pub mod extension;
+pub mod request_id;
 pub mod routing;
 
 #[doc(hidden)]
@@ -25,6 +29,8 @@ pub mod request;
 pub mod response;
 #[doc(hidden)]
 pub mod runtime_error;
+#[cfg(feature = "sigv4")]
+pub mod sigv4;
 
 #[doc(inline)]
 pub(crate) use self::error::Error;
@@ -6,15 +6,19 @@
 //! Protocol helpers.
 use crate::rejection::RequestRejection;
 use crate::request::RequestParts;
+use aws_smithy_xml::encode::XmlWriter;
 use paste::paste;
 
 /// Supported protocols.
+#[non_exhaustive]
 #[derive(Debug, Clone, Copy)]
 pub enum Protocol {
     RestJson1,
     RestXml,
     AwsJson10,
     AwsJson11,
+    AwsQuery,
+    Ec2Query,
 }
 
 /// Implement the content-type header validation for a request.
@@ -70,6 +74,70 @@ impl_content_type_validation!(
     RequestRejection::MissingAwsJson11ContentType
 );
 
+impl_content_type_validation!(
+    "aws_query",
+    "application",
+    "x-www-form-urlencoded",
+    RequestRejection::MissingAwsQueryContentType
+);
+
+impl_content_type_validation!(
+    "ec2_query",
+    "application",
+    "x-www-form-urlencoded",
+    RequestRejection::MissingEc2QueryContentType
+);
+
+impl_content_type_validation!(
+    "event_stream",
+    "application",
+    "vnd.amazon.eventstream",
+    RequestRejection::MissingEventStreamContentType
+);
+
+/// Serializes an AwsQuery error response body.
+///
+/// The body has the `<ErrorResponse>` shape that clients parse with `rest_xml_wrapped_errors`.
+/// `sender_fault` sets the error `Type` to `Sender` for client errors, or `Receiver` for server
+/// errors.
+pub fn serialize_aws_query_error(code: &str, message: Option<&str>, sender_fault: bool) -> String {
+    let mut out = String::new();
+    let mut writer = XmlWriter::new(&mut out);
+    let mut response = writer.start_el("ErrorResponse").finish();
+    let mut error = response.start_el("Error").finish();
+    error
+        .start_el("Type")
+        .finish()
+        .data(if sender_fault { "Sender" } else { "Receiver" });
+    write_code_and_message(&mut error, code, message);
+    error.finish();
+    response.finish();
+    out
+}
+
+/// Serializes an Ec2Query error response body.
+///
+/// The body has the `<Response>` shape that clients parse with `ec2_query_errors`.
+pub fn serialize_ec2_query_error(code: &str, message: Option<&str>) -> String {
+    let mut out = String::new();
+    let mut writer = XmlWriter::new(&mut out);
+    let mut response = writer.start_el("Response").finish();
+    let mut errors = response.start_el("Errors").finish();
+    let mut error = errors.start_el("Error").finish();
+    write_code_and_message(&mut error, code, message);
+    error.finish();
+    errors.finish();
+    response.finish();
+    out
+}
+
+fn write_code_and_message(error: &mut aws_smithy_xml::encode::ScopeWriter, code: &str, message: Option<&str>) {
+    error.start_el("Code").finish().data(code);
+    if let Some(message) = message {
+        error.start_el("Message").finish().data(message);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,4 +276,54 @@ mod tests {
         // Check request with not parsable content-type header.
         validate_rejection_type!(check_aws_json_11_content_type(&req("123")), RequestRejection::MimeParse);
     }
+
+    #[test]
+    fn validate_query_content_type() {
+        let request = req("application/x-www-form-urlencoded");
+        assert!(check_aws_query_content_type(&request).is_ok());
+        assert!(check_ec2_query_content_type(&request).is_ok());
+
+        let invalid = vec![
+            req("application/x-www-form-urlencode"),
+            req("multipart/form-data"),
+            req("application/xml"),
+            req("text/xml"),
+            RequestParts::new(Request::builder().body("").unwrap()),
+        ];
+        for request in &invalid {
+            validate_rejection_type!(
+                check_aws_query_content_type(request),
+                RequestRejection::MissingAwsQueryContentType
+            );
+            validate_rejection_type!(
+                check_ec2_query_content_type(request),
+                RequestRejection::MissingEc2QueryContentType
+            );
+        }
+    }
+
+    #[test]
+    fn validate_event_stream_content_type() {
+        assert!(check_event_stream_content_type(&req("application/vnd.amazon.eventstream")).is_ok());
+        validate_rejection_type!(
+            check_event_stream_content_type(&req("application/octet-stream")),
+            RequestRejection::MissingEventStreamContentType
+        );
+    }
+
+    #[test]
+    fn query_error_bodies() {
+        assert_eq!(
+            "<ErrorResponse><Error><Type>Sender</Type><Code>InvalidGreeting</Code><Message>Hi &amp; bye</Message></Error></ErrorResponse>",
+            serialize_aws_query_error("InvalidGreeting", Some("Hi & bye"), true)
+        );
+        assert_eq!(
+            "<ErrorResponse><Error><Type>Receiver</Type><Code>InternalFailure</Code></Error></ErrorResponse>",
+            serialize_aws_query_error("InternalFailure", None, false)
+        );
+        assert_eq!(
+            "<Response><Errors><Error><Code>InvalidGreeting</Code><Message>Hi</Message></Error></Errors></Response>",
+            serialize_ec2_query_error("InvalidGreeting", Some("Hi"))
+        );
+    }
 }
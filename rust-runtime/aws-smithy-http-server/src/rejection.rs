@@ -138,6 +138,9 @@ pub enum RequestRejection {
     MissingAwsJson10ContentType,
     MissingAwsJson11ContentType,
     MissingRestXmlContentType,
+    MissingAwsQueryContentType,
+    MissingEc2QueryContentType,
+    MissingEventStreamContentType,
     MimeParse,
 
     /// Used when failing to deserialize the HTTP body's bytes into a JSON document conforming to
@@ -146,6 +149,9 @@ pub enum RequestRejection {
     /// Used when failing to deserialize the HTTP body's bytes into a XML conforming to the modeled
     /// input it should represent.
     XmlDeserialize(crate::Error),
+    /// Used when failing to deserialize the HTTP body's bytes into form-encoded AwsQuery or
+    /// Ec2Query parameters conforming to the modeled input it should represent.
+    QueryDeserialize(crate::Error),
 
     /// Used when attempting to take the request's headers, and they have already been taken (presumably
     /// by an outer `Service` that handled the request before us).
@@ -221,6 +227,7 @@ impl From<std::convert::Infallible> for RequestRejection {
 
 convert_to_request_rejection!(aws_smithy_json::deserialize::Error, JsonDeserialize);
 convert_to_request_rejection!(aws_smithy_xml::decode::XmlError, XmlDeserialize);
+convert_to_request_rejection!(aws_smithy_query::QueryError, QueryDeserialize);
 convert_to_request_rejection!(aws_smithy_http::operation::BuildError, Build);
 convert_to_request_rejection!(aws_smithy_http::header::ParseError, HeaderParse);
 convert_to_request_rejection!(aws_smithy_types::date_time::DateTimeParseError, DateTimeParse);
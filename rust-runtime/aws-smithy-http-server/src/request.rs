@@ -32,8 +32,22 @@
  * DEALINGS IN THE SOFTWARE.
  */
 
+use async_trait::async_trait;
 use http::{Extensions, HeaderMap, Request, Uri};
 
+/// Types that can be created from a request.
+///
+/// Extractors may take parts of the request, like its body, so that later extractors of the same
+/// request cannot use them anymore.
+#[async_trait]
+pub trait FromRequest<B>: Sized {
+    /// If the extractor fails it'll use this "rejection" type.
+    type Rejection;
+
+    /// Perform the extraction.
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection>;
+}
+
 #[doc(hidden)]
 #[derive(Debug)]
 pub struct RequestParts<B> {
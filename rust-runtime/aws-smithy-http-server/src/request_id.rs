@@ -0,0 +1,313 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Request IDs.
+//!
+//! [`RequestIdLayer`] assigns a [`RequestId`] to every request. The ID is stored in the request
+//! extensions, so handlers can extract it as an `Extension<RequestId>`, and in the response
+//! extensions, so outer layers can log it. It is returned to the client in the `x-amzn-requestid`
+//! header, and every event emitted while handling the request is recorded in a `request` [tracing
+//! span] with a `request_id` field.
+//!
+//! The [`Router`] applies a default [`RequestIdLayer`] to its operations and also sets the header on
+//! the [`RuntimeError`] responses it returns itself, so every response has a request ID. Applying a
+//! [`RequestIdLayer`] explicitly is only needed to configure it, or to record events in the `request`
+//! span before the router is reached; the ID it assigns is reused by the layers it wraps. See
+//! [`Router::layer`] for which requests a layer applied to the router processes.
+//!
+//! By default a new ID is generated for every request. If the server runs behind a proxy that
+//! already assigns request IDs, [`RequestIdLayer::with_trusted_header`] reuses the ID the proxy put
+//! in a header instead. Only configure a header that clients cannot set themselves.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use aws_smithy_http_server::request_id::RequestIdLayer;
+//! use aws_smithy_http_server::Router;
+//! use http::header::HeaderName;
+//! use tower::Layer;
+//!
+//! # fn router() -> Router { unimplemented!() }
+//! let layer = RequestIdLayer::new().with_trusted_header(HeaderName::from_static("x-proxy-request-id"));
+//! let app = layer.layer(router());
+//! ```
+//!
+//! [tracing span]: https://docs.rs/tracing/latest/tracing/struct.Span.html
+//! [`RuntimeError`]: crate::runtime_error::RuntimeError
+//! [`Router`]: crate::routing::Router
+//! [`Router::layer`]: crate::routing::Router::layer
+
+use http::header::HeaderName;
+use http::{HeaderValue, Request, Response};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use tracing::Instrument;
+
+/// The name of the response header that contains the request ID.
+pub const REQUEST_ID_HEADER: &str = "x-amzn-requestid";
+
+/// The longest inbound request ID that [`RequestIdLayer`] accepts.
+const MAX_TRUSTED_ID_LEN: usize = 256;
+
+/// The unique ID of a request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Generates a new random ID, formatted as a version 4 UUID.
+    pub fn generate() -> Self {
+        let (high, low) = (fastrand::u64(..), fastrand::u64(..));
+        // Set the version (4) and the variant (RFC 4122) bits.
+        let high = (high & !0xf000) | 0x4000;
+        let low = (low & !(0xc << 60)) | (0x8 << 60);
+        RequestId(format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xffff,
+            high & 0xffff,
+            low >> 48,
+            low & 0xffff_ffff_ffff
+        ))
+    }
+
+    /// Returns the ID as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the ID that was assigned to `req` by an outer [`RequestIdService`], or generates a new
+    /// one.
+    pub(crate) fn of<B>(req: &Request<B>) -> Self {
+        req.extensions()
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_else(RequestId::generate)
+    }
+
+    /// Returns the ID to the client in the `x-amzn-requestid` header of `res`, and stores it in the
+    /// extensions of `res`.
+    pub(crate) fn insert_into<B>(self, res: &mut Response<B>) {
+        let value = HeaderValue::from_str(self.as_str()).expect("request IDs only contain visible ASCII characters");
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+        res.extensions_mut().insert(self);
+    }
+
+    /// Parses an ID received from a trusted source. The ID must be a non-empty string of at most 256
+    /// visible ASCII characters.
+    fn from_trusted(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        if value.is_empty() || value.len() > MAX_TRUSTED_ID_LEN || !value.bytes().all(|b| b.is_ascii_graphic()) {
+            return None;
+        }
+        Some(RequestId(value.to_string()))
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A [`tower::Layer`] that assigns a [`RequestId`] to every request. See the [module documentation]
+/// for details.
+///
+/// [module documentation]: crate::request_id
+#[derive(Debug, Clone, Default)]
+pub struct RequestIdLayer {
+    trusted_header: Option<HeaderName>,
+}
+
+impl RequestIdLayer {
+    /// Creates a layer that generates a new ID for every request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reuses the ID in the `header` of the request, if there is a valid one. Only use a header that
+    /// is set by a trusted proxy in front of the server.
+    pub fn with_trusted_header(mut self, header: HeaderName) -> Self {
+        self.trusted_header = Some(header);
+        self
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService {
+            inner,
+            trusted_header: self.trusted_header.clone(),
+        }
+    }
+}
+
+/// Middleware that assigns a [`RequestId`] to every request. Created with [`RequestIdLayer`].
+#[derive(Debug, Clone)]
+pub struct RequestIdService<S> {
+    inner: S,
+    trusted_header: Option<HeaderName>,
+}
+
+impl<S> RequestIdService<S> {
+    /// Returns the ID in the trusted header of `req`, the ID that was assigned to `req` by an outer
+    /// `RequestIdService`, or a new ID, in this order. The ID is only new to this service in the
+    /// first and last cases.
+    fn request_id<B>(&self, req: &Request<B>) -> (RequestId, bool) {
+        let trusted = self
+            .trusted_header
+            .as_ref()
+            .and_then(|header| req.headers().get(header))
+            .and_then(RequestId::from_trusted);
+        match (trusted, req.extensions().get::<RequestId>()) {
+            (Some(request_id), _) => (request_id, true),
+            (None, Some(request_id)) => (request_id.clone(), false),
+            (None, None) => (RequestId::generate(), true),
+        }
+    }
+}
+
+impl<B, ResBody, S> Service<Request<B>> for RequestIdService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let (request_id, new) = self.request_id(&req);
+        req.extensions_mut().insert(request_id.clone());
+        // An outer service that assigned the ID already records events in a span with it.
+        let span = if new {
+            tracing::info_span!("request", request_id = %request_id)
+        } else {
+            tracing::Span::none()
+        };
+        let future = span.in_scope(|| self.inner.call(req)).instrument(span);
+        Box::pin(async move {
+            let mut res = future.await?;
+            request_id.insert_into(&mut res);
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{boxed, Body, BoxBody};
+    use crate::Router;
+    use std::convert::Infallible;
+    use tower::util::{service_fn, BoxCloneService};
+    use tower::ServiceExt;
+
+    fn generated(id: &RequestId) -> bool {
+        let parts = id.as_str().split('-').map(str::len).collect::<Vec<_>>();
+        parts == [8, 4, 4, 4, 12] && id.as_str().as_bytes()[14] == b'4'
+    }
+
+    /// Sends `req` through `layer` to a service that responds with the request ID it sees.
+    async fn call(layer: RequestIdLayer, req: Request<Body>) -> Response<BoxBody> {
+        let service = service_fn(|req: Request<Body>| async move {
+            let request_id = req.extensions().get::<RequestId>().unwrap();
+            Ok::<_, Infallible>(Response::new(boxed(Body::from(request_id.to_string()))))
+        });
+        layer.layer(service).oneshot(req).await.unwrap()
+    }
+
+    async fn assert_request_id(res: Response<BoxBody>) -> RequestId {
+        let request_id = res.extensions().get::<RequestId>().unwrap().clone();
+        assert_eq!(request_id.as_str(), res.headers()[REQUEST_ID_HEADER]);
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(request_id.as_str().as_bytes(), &body[..]);
+        request_id
+    }
+
+    #[tokio::test]
+    async fn generates_unique_ids() {
+        let first = assert_request_id(call(RequestIdLayer::new(), Request::new(Body::empty())).await).await;
+        let second = assert_request_id(call(RequestIdLayer::new(), Request::new(Body::empty())).await).await;
+        assert!(generated(&first), "{}", first);
+        assert!(generated(&second), "{}", second);
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn trusted_header() {
+        let req = |id: &str| Request::builder().header("x-proxy-id", id).body(Body::empty()).unwrap();
+        let layer = RequestIdLayer::new().with_trusted_header(HeaderName::from_static("x-proxy-id"));
+
+        let request_id = assert_request_id(call(layer.clone(), req("proxy-1")).await).await;
+        assert_eq!("proxy-1", request_id.as_str());
+
+        // Invalid IDs are replaced.
+        let request_id = assert_request_id(call(layer.clone(), req("has spaces")).await).await;
+        assert!(generated(&request_id), "{}", request_id);
+        let request_id = assert_request_id(call(layer, req(&"a".repeat(MAX_TRUSTED_ID_LEN + 1))).await).await;
+        assert!(generated(&request_id), "{}", request_id);
+
+        // Inbound IDs are ignored unless a trusted header is configured.
+        let request_id = assert_request_id(call(RequestIdLayer::new(), req("proxy-1")).await).await;
+        assert!(generated(&request_id), "{}", request_id);
+    }
+
+    #[tokio::test]
+    async fn runtime_errors_have_request_ids() {
+        let router: Router<Body> = Router::new_aws_json_11_router(std::iter::empty::<(
+            BoxCloneService<Request<Body>, Response<BoxBody>, Infallible>,
+            String,
+        )>());
+        let res = RequestIdLayer::new()
+            .layer(router)
+            .oneshot(Request::post("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(http::StatusCode::NOT_FOUND, res.status());
+        let request_id = res.extensions().get::<RequestId>().unwrap();
+        assert!(generated(request_id), "{}", request_id);
+        assert_eq!(request_id.as_str(), res.headers()[REQUEST_ID_HEADER]);
+    }
+
+    #[tokio::test]
+    async fn routers_assign_request_ids_by_default() {
+        let operation = BoxCloneService::new(service_fn(|req: Request<Body>| async move {
+            let request_id = req.extensions().get::<RequestId>().unwrap();
+            Ok::<_, Infallible>(Response::new(boxed(Body::from(request_id.to_string()))))
+        }));
+        let router: Router<Body> = Router::new_aws_json_11_router(vec![(operation, String::from("Service.Operation"))]);
+        let req = |target: &str| {
+            Request::post("/")
+                .header("x-amz-target", target)
+                .header("x-proxy-id", "proxy-1")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = router.clone().oneshot(req("Service.Operation")).await.unwrap();
+        let request_id = assert_request_id(res).await;
+        assert!(generated(&request_id), "{}", request_id);
+
+        let res = router.clone().oneshot(req("Service.Unknown")).await.unwrap();
+        assert_eq!(http::StatusCode::NOT_FOUND, res.status());
+        let request_id = res.extensions().get::<RequestId>().unwrap();
+        assert!(generated(request_id), "{}", request_id);
+        assert_eq!(request_id.as_str(), res.headers()[REQUEST_ID_HEADER]);
+
+        // The operations reuse the ID assigned by an explicitly applied layer.
+        let layer = RequestIdLayer::new().with_trusted_header(HeaderName::from_static("x-proxy-id"));
+        let res = router.layer(layer).oneshot(req("Service.Operation")).await.unwrap();
+        assert_eq!("proxy-1", assert_request_id(res).await.as_str());
+    }
+}
@@ -7,11 +7,14 @@
 //!
 //! [Smithy specification]: https://awslabs.github.io/smithy/1.0/spec/core/http-traits.html
 
+use self::query::{MaxBodySize, QueryRouter, DEFAULT_MAX_BODY_SIZE};
 use self::request_spec::RequestSpec;
 use self::tiny_map::TinyMap;
+use self::trie::{Lookup, RouteTrie};
 use crate::body::{boxed, Body, BoxBody, HttpBody};
 use crate::error::BoxError;
 use crate::protocols::Protocol;
+use crate::request_id::{RequestId, RequestIdLayer};
 use crate::runtime_error::{RuntimeError, RuntimeErrorKind};
 use http::{Request, Response, StatusCode};
 use std::{
@@ -25,12 +28,14 @@ use tower_http::map_response_body::MapResponseBodyLayer;
 
 mod future;
 mod into_make_service;
+mod query;
 
 #[doc(hidden)]
 pub mod request_spec;
 
 mod route;
 mod tiny_map;
+mod trie;
 
 pub use self::{future::RouterFuture, into_make_service::IntoMakeService, route::Route};
 
@@ -40,8 +45,9 @@ pub use self::{future::RouterFuture, into_make_service::IntoMakeService, route::
 /// the [AwsJson specification].
 ///
 /// The router is also [Protocol] aware and currently supports REST based protocols like [restJson1] or [restXml]
-/// and RPC based protocols like [awsJson1.0] or [awsJson1.1].
-/// It currently does not support Smithy's [endpoint trait].
+/// and RPC based protocols like [awsJson1.0], [awsJson1.1], [awsQuery] or [ec2Query].
+/// RestJson1 and RestXml routers also match the host prefix of Smithy's [endpoint trait] against the
+/// request's host, and store the bound labels as [`HostLabels`](crate::extension::HostLabels).
 ///
 /// You should not **instantiate** this router directly; it will be created for you from the
 /// code generated from your Smithy model by `smithy-rs`.
@@ -53,6 +59,8 @@ pub use self::{future::RouterFuture, into_make_service::IntoMakeService, route::
 /// [restXml]: https://awslabs.github.io/smithy/1.0/spec/aws/aws-restxml-protocol.html
 /// [awsJson1.0]: https://awslabs.github.io/smithy/1.0/spec/aws/aws-json-1_0-protocol.html
 /// [awsJson1.1]: https://awslabs.github.io/smithy/1.0/spec/aws/aws-json-1_1-protocol.html
+/// [awsQuery]: https://awslabs.github.io/smithy/1.0/spec/aws/aws-query-protocol.html
+/// [ec2Query]: https://awslabs.github.io/smithy/1.0/spec/aws/aws-ec2-query-protocol.html
 /// [endpoint trait]: https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
 #[derive(Debug)]
 pub struct Router<B = Body> {
@@ -66,17 +74,24 @@ const ROUTE_CUTOFF: usize = 15;
 
 /// Protocol-aware routes types.
 ///
-/// RestJson1 and RestXml routes are stored in a `Vec` because there can be multiple matches on the
-/// request URI and we thus need to iterate the whole list and use a ranking mechanism to choose.
+/// RestJson1 and RestXml routes are stored in a [`RouteTrie`] keyed by path segment. There can be
+/// multiple matches on the request URI, so the trie collects the routes that match the request's
+/// path and uses a ranking mechanism to choose.
 ///
 /// AwsJson 1.0 and 1.1 routes can be stored in a `HashMap` since the requested operation can be
 /// directly found in the `X-Amz-Target` HTTP header.
+///
+/// AwsQuery and Ec2Query name the requested operation in the `Action` parameter of the request body,
+/// so their routes are stored in a single [`QueryRouter`] route that reads the body to dispatch,
+/// along with the size of the largest body it reads.
 #[derive(Debug)]
 enum Routes<B = Body> {
-    RestXml(Vec<(Route<B>, RequestSpec)>),
-    RestJson1(Vec<(Route<B>, RequestSpec)>),
+    RestXml(RouteTrie<Route<B>>),
+    RestJson1(RouteTrie<Route<B>>),
     AwsJson10(TinyMap<String, Route<B>, ROUTE_CUTOFF>),
     AwsJson11(TinyMap<String, Route<B>, ROUTE_CUTOFF>),
+    AwsQuery(Route<B>, MaxBodySize),
+    Ec2Query(Route<B>, MaxBodySize),
 }
 
 impl<B> Clone for Router<B> {
@@ -94,6 +109,12 @@ impl<B> Clone for Router<B> {
             Routes::AwsJson11(routes) => Router {
                 routes: Routes::AwsJson11(routes.clone()),
             },
+            Routes::AwsQuery(route, max_body_size) => Router {
+                routes: Routes::AwsQuery(route.clone(), *max_body_size),
+            },
+            Routes::Ec2Query(route, max_body_size) => Router {
+                routes: Routes::Ec2Query(route.clone(), *max_body_size),
+            },
         }
     }
 }
@@ -103,27 +124,43 @@ where
     B: Send + 'static,
 {
     /// Return the correct, protocol-specific "Not Found" response for an unknown operation.
-    fn unknown_operation(&self) -> RouterFuture<B> {
+    fn unknown_operation(&self, req: &Request<B>) -> RouterFuture<B> {
         let protocol = match &self.routes {
             Routes::RestJson1(_) => Protocol::RestJson1,
             Routes::RestXml(_) => Protocol::RestXml,
             Routes::AwsJson10(_) => Protocol::AwsJson10,
             Routes::AwsJson11(_) => Protocol::AwsJson11,
+            Routes::AwsQuery(..) => Protocol::AwsQuery,
+            Routes::Ec2Query(..) => Protocol::Ec2Query,
         };
         let error = RuntimeError {
             protocol,
             kind: RuntimeErrorKind::UnknownOperation,
         };
-        RouterFuture::from_response(error.into_response())
+        Self::respond(req, error.into_response())
     }
 
     /// Return the HTTP error response for non allowed method.
-    fn method_not_allowed(&self) -> RouterFuture<B> {
-        RouterFuture::from_response({
-            let mut res = Response::new(crate::body::empty());
-            *res.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
-            res
-        })
+    fn method_not_allowed(&self, req: &Request<B>) -> RouterFuture<B> {
+        let mut res = Response::new(crate::body::empty());
+        *res.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+        Self::respond(req, res)
+    }
+
+    /// Return a response of the router itself, with the request ID of `req`.
+    fn respond(req: &Request<B>, mut res: Response<BoxBody>) -> RouterFuture<B> {
+        RequestId::of(req).insert_into(&mut res);
+        RouterFuture::from_response(res)
+    }
+
+    /// Wrap the service of an operation, or of a router that dispatches requests itself, in the
+    /// default [`RequestIdLayer`].
+    fn route<S>(svc: S) -> Route<B>
+    where
+        S: Service<Request<B>, Response = Response<BoxBody>, Error = Infallible> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        Route::new(RequestIdLayer::new().layer(svc))
     }
     /// Convert this router into a [`MakeService`], that is a [`Service`] whose
     /// response is another service.
@@ -139,10 +176,15 @@ where
 
     /// Apply a [`tower::Layer`] to the router.
     ///
-    /// All requests to the router will be processed by the layer's
-    /// corresponding middleware.
+    /// This can be used to add additional processing to all routes. The layer wraps each
+    /// operation, so it only processes the requests that are routed to an operation: the
+    /// [`RuntimeError`] responses that the router returns itself, for example when a request does
+    /// not match any operation, do not go through it. AwsQuery and Ec2Query routers dispatch
+    /// requests after reading their body, so there the layer wraps the whole router and also
+    /// processes requests to unknown operations.
     ///
-    /// This can be used to add additional processing to all routes.
+    /// To process _all_ requests, for example to log them, wrap the router itself with the layer
+    /// instead: `layer.layer(router)`.
     pub fn layer<L, NewReqBody, NewResBody>(self, layer: L) -> Router<NewReqBody>
     where
         L: Layer<Route<B>>,
@@ -157,24 +199,12 @@ where
             .layer(MapResponseBodyLayer::new(boxed))
             .layer(layer);
         match self.routes {
-            Routes::RestJson1(routes) => {
-                let routes = routes
-                    .into_iter()
-                    .map(|(route, request_spec)| (Layer::layer(&layer, route), request_spec))
-                    .collect();
-                Router {
-                    routes: Routes::RestJson1(routes),
-                }
-            }
-            Routes::RestXml(routes) => {
-                let routes = routes
-                    .into_iter()
-                    .map(|(route, request_spec)| (Layer::layer(&layer, route), request_spec))
-                    .collect();
-                Router {
-                    routes: Routes::RestXml(routes),
-                }
-            }
+            Routes::RestJson1(routes) => Router {
+                routes: Routes::RestJson1(routes.map(|route| Layer::layer(&layer, route))),
+            },
+            Routes::RestXml(routes) => Router {
+                routes: Routes::RestXml(routes.map(|route| Layer::layer(&layer, route))),
+            },
             Routes::AwsJson10(routes) => {
                 let routes = routes
                     .into_iter()
@@ -193,7 +223,25 @@ where
                     routes: Routes::AwsJson11(routes),
                 }
             }
+            Routes::AwsQuery(route, max_body_size) => Router {
+                routes: Routes::AwsQuery(Layer::layer(&layer, route), max_body_size),
+            },
+            Routes::Ec2Query(route, max_body_size) => Router {
+                routes: Routes::Ec2Query(Layer::layer(&layer, route), max_body_size),
+            },
+        }
+    }
+
+    /// Sets the size in bytes of the largest request body that AwsQuery and Ec2Query routers
+    /// read to find the requested operation. Larger requests are rejected with a
+    /// `413 Payload Too Large` response. Defaults to 10 MiB.
+    ///
+    /// Routers of the other protocols don't read the request body, so this has no effect on them.
+    pub fn with_max_query_body_size(mut self, max_body_size: usize) -> Self {
+        if let Routes::AwsQuery(_, limit) | Routes::Ec2Query(_, limit) = &mut self.routes {
+            *limit = MaxBodySize(max_body_size);
         }
+        self
     }
 
     /// Create a new RestJson1 `Router` from an iterator over pairs of [`RequestSpec`]s and services.
@@ -209,15 +257,11 @@ where
             ),
         >,
     {
-        let mut routes: Vec<(Route<B>, RequestSpec)> = routes
-            .into_iter()
-            .map(|(svc, request_spec)| (Route::from_box_clone_service(svc), request_spec))
-            .collect();
-
-        // Sort them once by specifity, with the more specific routes sorted before the less
-        // specific ones, so that when routing a request we can simply iterate through the routes
-        // and pick the first one that matches.
-        routes.sort_by_key(|(_route, request_spec)| std::cmp::Reverse(request_spec.rank()));
+        let routes = RouteTrie::new(
+            routes
+                .into_iter()
+                .map(|(svc, request_spec)| (Self::route(svc), request_spec)),
+        );
 
         Self {
             routes: Routes::RestJson1(routes),
@@ -237,15 +281,11 @@ where
             ),
         >,
     {
-        let mut routes: Vec<(Route<B>, RequestSpec)> = routes
-            .into_iter()
-            .map(|(svc, request_spec)| (Route::from_box_clone_service(svc), request_spec))
-            .collect();
-
-        // Sort them once by specifity, with the more specific routes sorted before the less
-        // specific ones, so that when routing a request we can simply iterate through the routes
-        // and pick the first one that matches.
-        routes.sort_by_key(|(_route, request_spec)| std::cmp::Reverse(request_spec.rank()));
+        let routes = RouteTrie::new(
+            routes
+                .into_iter()
+                .map(|(svc, request_spec)| (Self::route(svc), request_spec)),
+        );
 
         Self {
             routes: Routes::RestXml(routes),
@@ -267,7 +307,7 @@ where
     {
         let routes = routes
             .into_iter()
-            .map(|(svc, operation)| (operation, Route::from_box_clone_service(svc)))
+            .map(|(svc, operation)| (operation, Self::route(svc)))
             .collect();
 
         Self {
@@ -290,7 +330,7 @@ where
     {
         let routes = routes
             .into_iter()
-            .map(|(svc, operation)| (operation, Route::from_box_clone_service(svc)))
+            .map(|(svc, operation)| (operation, Self::route(svc)))
             .collect();
 
         Self {
@@ -299,6 +339,70 @@ where
     }
 }
 
+impl<B> Router<B>
+where
+    B: HttpBody<Data = bytes::Bytes> + From<bytes::Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    /// Create a new AwsQuery `Router` from an iterator over pairs of operation names and services.
+    ///
+    /// If the iterator is empty the router will respond `404 Not Found` to all requests.
+    #[doc(hidden)]
+    pub fn new_aws_query_router<T>(routes: T) -> Self
+    where
+        T: IntoIterator<
+            Item = (
+                tower::util::BoxCloneService<Request<B>, Response<BoxBody>, Infallible>,
+                String,
+            ),
+        >,
+    {
+        Self {
+            routes: Routes::AwsQuery(
+                Self::query_router(Protocol::AwsQuery, routes),
+                MaxBodySize(DEFAULT_MAX_BODY_SIZE),
+            ),
+        }
+    }
+
+    /// Create a new Ec2Query `Router` from an iterator over pairs of operation names and services.
+    ///
+    /// If the iterator is empty the router will respond `404 Not Found` to all requests.
+    #[doc(hidden)]
+    pub fn new_ec2_query_router<T>(routes: T) -> Self
+    where
+        T: IntoIterator<
+            Item = (
+                tower::util::BoxCloneService<Request<B>, Response<BoxBody>, Infallible>,
+                String,
+            ),
+        >,
+    {
+        Self {
+            routes: Routes::Ec2Query(
+                Self::query_router(Protocol::Ec2Query, routes),
+                MaxBodySize(DEFAULT_MAX_BODY_SIZE),
+            ),
+        }
+    }
+
+    fn query_router<T>(protocol: Protocol, routes: T) -> Route<B>
+    where
+        T: IntoIterator<
+            Item = (
+                tower::util::BoxCloneService<Request<B>, Response<BoxBody>, Infallible>,
+                String,
+            ),
+        >,
+    {
+        let routes = routes
+            .into_iter()
+            .map(|(svc, operation)| (operation, Route::from_box_clone_service(svc)))
+            .collect();
+        Self::route(QueryRouter::new(protocol, routes))
+    }
+}
+
 impl<B> Service<Request<B>> for Router<B>
 where
     B: Send + 'static,
@@ -313,31 +417,25 @@ where
     }
 
     #[inline]
-    fn call(&mut self, req: Request<B>) -> Self::Future {
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
         match &self.routes {
             // REST routes.
-            Routes::RestJson1(routes) | Routes::RestXml(routes) => {
-                let mut method_not_allowed = false;
-
-                // Loop through all the routes and validate if any of them matches. Routes are already ranked.
-                for (route, request_spec) in routes {
-                    match request_spec.matches(&req) {
-                        request_spec::Match::Yes => {
-                            return RouterFuture::from_oneshot(route.clone().oneshot(req));
-                        }
-                        request_spec::Match::MethodNotAllowed => method_not_allowed = true,
-                        // Continue looping to see if another route matches.
-                        request_spec::Match::No => continue,
+            Routes::RestJson1(routes) | Routes::RestXml(routes) => match routes.lookup(&req) {
+                Lookup::Found(route, host_labels) => {
+                    if !host_labels.is_empty() {
+                        req.extensions_mut().insert(host_labels);
                     }
+                    RouterFuture::from_oneshot(route.clone().oneshot(req))
                 }
-
-                if method_not_allowed {
-                    // The HTTP method is not correct.
-                    self.method_not_allowed()
-                } else {
-                    // In any other case return the `RuntimeError::UnknownOperation`.
-                    self.unknown_operation()
-                }
+                // The HTTP method is not correct.
+                Lookup::MethodNotAllowed => self.method_not_allowed(&req),
+                // In any other case return the `RuntimeError::UnknownOperation`.
+                Lookup::NotFound => self.unknown_operation(&req),
+            },
+            // AwsQuery and Ec2Query routes.
+            Routes::AwsQuery(route, max_body_size) | Routes::Ec2Query(route, max_body_size) => {
+                req.extensions_mut().insert(*max_body_size);
+                RouterFuture::from_oneshot(route.clone().oneshot(req))
             }
             // AwsJson routes.
             Routes::AwsJson10(routes) | Routes::AwsJson11(routes) => {
@@ -356,11 +454,11 @@ where
                         }
                     } else {
                         // The HTTP method is not POST.
-                        return self.method_not_allowed();
+                        return self.method_not_allowed(&req);
                     }
                 }
                 // In any other case return the `RuntimeError::UnknownOperation`.
-                self.unknown_operation()
+                self.unknown_operation(&req)
             }
         }
     }
@@ -591,6 +689,82 @@ mod rest_tests {
             assert_eq!(format!("{} :: {}", svc_name, uri), actual_body);
         }
     }
+    /// A service that returns its name and the value of the `TenantId` host label in the response body.
+    #[derive(Clone)]
+    struct NamedEchoHostLabelService(String);
+
+    impl<B> Service<Request<B>> for NamedEchoHostLabelService {
+        type Response = Response<BoxBody>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        #[inline]
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        #[inline]
+        fn call(&mut self, req: Request<B>) -> Self::Future {
+            let tenant = req
+                .extensions()
+                .get::<crate::extension::HostLabels>()
+                .and_then(|labels| labels.get("TenantId"))
+                .unwrap_or("none");
+            let body = boxed(Body::from(format!("{} :: {}", self.0, tenant)));
+            let fut = async { Ok(Response::builder().status(&http::StatusCode::OK).body(body).unwrap()) };
+            Box::pin(fut)
+        }
+    }
+
+    #[tokio::test]
+    async fn host_prefix_routing() {
+        let mut request_specs: Vec<(RequestSpec, &str)> = vec![
+            (
+                RequestSpec::from_parts_with_host_prefix(
+                    Method::GET,
+                    Some(vec![
+                        HostPrefixSegment::Label(String::from("TenantId")),
+                        HostPrefixSegment::Literal(String::from(".data.")),
+                    ]),
+                    vec![PathSegment::Literal(String::from("items"))],
+                    Vec::new(),
+                ),
+                "TenantItems",
+            ),
+            (
+                RequestSpec::from_parts(
+                    Method::GET,
+                    vec![PathSegment::Literal(String::from("items"))],
+                    Vec::new(),
+                ),
+                "Items",
+            ),
+        ];
+
+        // The operation with the host prefix is preferred regardless of the order the operations are
+        // registered in.
+        for _ in 0..2 {
+            let mut router = Router::new_rest_json_router(request_specs.iter().cloned().map(|(spec, svc_name)| {
+                (
+                    tower::util::BoxCloneService::new(NamedEchoHostLabelService(String::from(svc_name))),
+                    spec,
+                )
+            }));
+
+            let cases = vec![
+                ("tenant-1.data.example.com", "TenantItems :: tenant-1"),
+                ("example.com", "Items :: none"),
+            ];
+            for (host, expected) in cases {
+                let mut headers = HeaderMap::new();
+                headers.insert(http::header::HOST, http::HeaderValue::from_static(host));
+                let mut res = router.call(req(&Method::GET, "/items", Some(headers))).await.unwrap();
+                assert_eq!(expected, get_body_as_string(&mut res).await);
+            }
+
+            request_specs.reverse();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -678,3 +852,119 @@ mod awsjson_tests {
         }
     }
 }
+
+#[cfg(test)]
+mod query_tests {
+    use super::rest_tests::get_body_as_string;
+    use super::*;
+    use crate::body::boxed;
+    use futures_util::Future;
+    use http::Method;
+    use pretty_assertions::assert_eq;
+    use std::pin::Pin;
+
+    /// A service that returns its name and the request's body in the response body.
+    #[derive(Clone)]
+    struct NamedEchoOperationService(String);
+
+    impl Service<Request<Body>> for NamedEchoOperationService {
+        type Response = Response<BoxBody>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        #[inline]
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        #[inline]
+        fn call(&mut self, req: Request<Body>) -> Self::Future {
+            let name = self.0.clone();
+            Box::pin(async move {
+                let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                let body = boxed(Body::from(format!("{} :: {}", name, String::from_utf8_lossy(&body))));
+                Ok(Response::builder().status(&http::StatusCode::OK).body(body).unwrap())
+            })
+        }
+    }
+
+    fn req(method: &Method, uri: &str, body: &'static str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn simple_routing() {
+        let services = || {
+            vec![("Operation", "A")].into_iter().map(|(operation, svc_name)| {
+                (
+                    tower::util::BoxCloneService::new(NamedEchoOperationService(String::from(svc_name))),
+                    operation.to_string(),
+                )
+            })
+        };
+        let router_aws_query = Router::new_aws_query_router(services());
+        let router_ec2_query = Router::new_ec2_query_router(services());
+
+        for mut router in [router_aws_query, router_ec2_query] {
+            let body = "Action=Operation&Version=2020-01-08";
+
+            // Valid request, should return a valid body that still contains the request body.
+            let mut res = router.call(req(&Method::POST, "/", body)).await.unwrap();
+            let actual_body = get_body_as_string(&mut res).await;
+            assert_eq!(format!("{} :: {}", "A", body), actual_body);
+
+            // Unknown or missing action, should return NOT_FOUND.
+            let res = router
+                .call(req(&Method::POST, "/", "Action=Unknown&Version=2020-01-08"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+            let res = router
+                .call(req(&Method::POST, "/", "Version=2020-01-08"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+            // Wrong HTTP method, should return METHOD_NOT_ALLOWED.
+            let res = router.call(req(&Method::GET, "/", body)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+            // Wrong URI, should return NOT_FOUND.
+            let res = router.call(req(&Method::POST, "/something", body)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_rejected() {
+        let services = || {
+            vec![(
+                tower::util::BoxCloneService::new(NamedEchoOperationService(String::from("A"))),
+                String::from("Operation"),
+            )]
+        };
+        let body = "Action=Operation&Version=2020-01-08";
+        let routers = [
+            Router::new_aws_query_router(services()).with_max_query_body_size(body.len()),
+            // The limit can be set after the router has been wrapped in layers.
+            Router::new_ec2_query_router(services())
+                .layer(tower::layer::util::Identity::new())
+                .with_max_query_body_size(body.len()),
+        ];
+        for mut router in routers {
+            let res = router.call(req(&Method::POST, "/", body)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let res = router
+                .call(req(&Method::POST, "/", "Action=Operation&Version=2020-01-08&"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Routing for the AwsQuery and Ec2Query protocols.
+//!
+//! Both protocols send every operation as a `POST` to `/` with a form-encoded body, and name the
+//! operation in its `Action` parameter. Unlike the other protocols, the body has to be read in order
+//! to route the request, so routing happens inside a [`QueryRouter`] service instead of in
+//! [`Router::call`](super::Router). Bodies larger than the
+//! [maximum body size](super::Router::with_max_query_body_size) are rejected with a
+//! `413 Payload Too Large` response.
+
+use super::tiny_map::TinyMap;
+use super::{Route, ROUTE_CUTOFF};
+use crate::body::{empty, read_limited, BoxBody, HttpBody};
+use crate::error::BoxError;
+use crate::protocols::Protocol;
+use crate::runtime_error::{RuntimeError, RuntimeErrorKind};
+use aws_smithy_query::QueryReader;
+use bytes::Bytes;
+use http::{Request, Response, StatusCode};
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tower::{Service, ServiceExt};
+
+/// The default size of the largest request body a [`QueryRouter`] reads, i.e. 10 MiB.
+pub(super) const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// The size of the largest request body a [`QueryRouter`] reads. The [`Router`](super::Router)
+/// inserts it into the extensions of every request, because the `QueryRouter` may be wrapped in
+/// layers by the time it is configured.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct MaxBodySize(pub(super) usize);
+
+/// A [`Service`] that dispatches requests to the operation named by their `Action` parameter.
+pub(super) struct QueryRouter<B> {
+    protocol: Protocol,
+    // `Route`s are `Send` but not `Sync`, so they are behind a `Mutex` to share them across clones
+    // of the router. The lock is only held while cloning the matched route.
+    routes: Arc<Mutex<TinyMap<String, Route<B>, ROUTE_CUTOFF>>>,
+}
+
+impl<B> QueryRouter<B> {
+    pub(super) fn new(protocol: Protocol, routes: TinyMap<String, Route<B>, ROUTE_CUTOFF>) -> Self {
+        Self {
+            protocol,
+            routes: Arc::new(Mutex::new(routes)),
+        }
+    }
+
+    fn route(&self, action: &str) -> Option<Route<B>> {
+        self.routes
+            .lock()
+            .expect("the lock is never held across a panic")
+            .get(action)
+            .cloned()
+    }
+
+    fn runtime_error(&self, kind: RuntimeErrorKind) -> Response<BoxBody> {
+        RuntimeError {
+            protocol: self.protocol,
+            kind,
+        }
+        .into_response()
+    }
+}
+
+impl<B> Clone for QueryRouter<B> {
+    fn clone(&self) -> Self {
+        Self {
+            protocol: self.protocol,
+            routes: self.routes.clone(),
+        }
+    }
+}
+
+impl<B> fmt::Debug for QueryRouter<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryRouter").field("protocol", &self.protocol).finish()
+    }
+}
+
+impl<B> Service<Request<B>> for QueryRouter<B>
+where
+    B: HttpBody<Data = Bytes> + From<Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline]
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let router = self.clone();
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let max_body_size = parts
+                .extensions
+                .remove::<MaxBodySize>()
+                .map_or(DEFAULT_MAX_BODY_SIZE, |MaxBodySize(max_body_size)| max_body_size);
+            if parts.uri != "/" {
+                return Ok(router.runtime_error(RuntimeErrorKind::UnknownOperation));
+            }
+            if parts.method != http::Method::POST {
+                let mut res = Response::new(empty());
+                *res.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+                return Ok(res);
+            }
+
+            let body = match read_limited(body, max_body_size).await {
+                Ok(body) => body,
+                Err(kind) => return Ok(router.runtime_error(kind)),
+            };
+            let params = match std::str::from_utf8(&body) {
+                Ok(params) => QueryReader::new(params).map_err(crate::Error::new),
+                Err(err) => Err(crate::Error::new(err)),
+            };
+            let params = match params {
+                Ok(params) => params,
+                Err(err) => return Ok(router.runtime_error(RuntimeErrorKind::Serialization(err))),
+            };
+            match params.action().and_then(|action| router.route(action)) {
+                Some(route) => route.oneshot(Request::from_parts(parts, B::from(body))).await,
+                None => Ok(router.runtime_error(RuntimeErrorKind::UnknownOperation)),
+            }
+        })
+    }
+}
@@ -3,6 +3,7 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+use crate::extension::HostLabels;
 use http::Request;
 use regex::Regex;
 
@@ -19,10 +20,16 @@ pub enum QuerySegment {
     KeyValue(String, String),
 }
 
+/// A segment of the `hostPrefix` of an operation's [endpoint trait], e.g. `{TenantId}.data.` is
+/// made up of the label `TenantId` followed by the literal `.data.`.
+///
+/// [endpoint trait]: https://awslabs.github.io/smithy/1.0/spec/core/endpoint-traits.html#endpoint-trait
 #[derive(Debug, Clone)]
 pub enum HostPrefixSegment {
     Literal(String),
-    Label,
+    /// A label with the given name, bound to a non-empty host label made up of ASCII letters,
+    /// digits, and hyphens.
+    Label(String),
 }
 
 #[derive(Debug, Clone, Default)]
@@ -65,11 +72,9 @@ pub struct UriSpec {
 }
 
 impl UriSpec {
-    // TODO(https://github.com/awslabs/smithy-rs/issues/950): When we add support for the endpoint
-    // trait, this constructor will take in a first argument `host_prefix`.
-    pub fn new(path_and_query: PathAndQuerySpec) -> Self {
+    pub fn new(host_prefix: Option<Vec<HostPrefixSegment>>, path_and_query: PathAndQuerySpec) -> Self {
         UriSpec {
-            host_prefix: None,
+            host_prefix,
             path_and_query,
         }
     }
@@ -79,6 +84,8 @@ impl UriSpec {
 pub struct RequestSpec {
     method: http::Method,
     uri_spec: UriSpec,
+    /// Only used to check the trie against matching each spec in turn.
+    #[cfg(test)]
     uri_path_regex: Regex,
 }
 
@@ -119,11 +126,11 @@ impl From<&PathSpec> for Regex {
 
 impl RequestSpec {
     pub fn new(method: http::Method, uri_spec: UriSpec) -> Self {
-        let uri_path_regex = (&uri_spec.path_and_query.path_segments).into();
         RequestSpec {
             method,
+            #[cfg(test)]
+            uri_path_regex: (&uri_spec.path_and_query.path_segments).into(),
             uri_spec,
-            uri_path_regex,
         }
     }
 
@@ -160,15 +167,33 @@ impl RequestSpec {
         self.uri_spec.path_and_query.path_segments.0.len() + self.uri_spec.path_and_query.query_segments.0.len()
     }
 
+    /// Returns `true` if this spec only matches requests whose host matches a host prefix.
+    pub(super) fn has_host_prefix(&self) -> bool {
+        self.uri_spec.host_prefix.is_some()
+    }
+
+    pub(super) fn path_segments(&self) -> &[PathSegment] {
+        &self.uri_spec.path_and_query.path_segments.0
+    }
+
+    /// Matches the request against this spec, using a `Regex` compiled from the spec's
+    /// [`PathSpec`] to match the request's path.
+    #[cfg(test)]
     pub(super) fn matches<B>(&self, req: &Request<B>) -> Match {
-        if let Some(_host_prefix) = &self.uri_spec.host_prefix {
-            todo!("Look at host prefix");
+        if !self.uri_path_regex.is_match(req.uri().path()) {
+            return Match::No;
         }
 
-        if !self.uri_path_regex.is_match(req.uri().path()) {
+        if self.host_labels(req).is_none() {
             return Match::No;
         }
 
+        self.matches_method_and_query(req)
+    }
+
+    /// Matches the request's method and query string against this spec. The caller has already
+    /// matched the request's path and host.
+    pub(super) fn matches_method_and_query<B>(&self, req: &Request<B>) -> Match {
         if self.uri_spec.path_and_query.query_segments.0.is_empty() {
             if self.method == req.method() {
                 return Match::Yes;
@@ -221,17 +246,49 @@ impl RequestSpec {
         }
     }
 
+    /// Returns the labels bound by this spec's host prefix, or `None` if the request's host does
+    /// not start with the host prefix. If the spec has no host prefix, no labels are bound.
+    pub(super) fn host_labels<B>(&self, req: &Request<B>) -> Option<HostLabels> {
+        let host_prefix = match &self.uri_spec.host_prefix {
+            Some(host_prefix) => host_prefix,
+            None => return Some(HostLabels::default()),
+        };
+        // HTTP/1.1 requests carry the host in the `Host` header, HTTP/2 requests in the URI.
+        let host = req
+            .headers()
+            .get(http::header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .or_else(|| req.uri().host())?;
+        let mut labels = Vec::new();
+        if match_host_prefix(host_prefix, host, &mut labels) {
+            Some(HostLabels::new(labels))
+        } else {
+            None
+        }
+    }
+
     // Helper function to build a `RequestSpec`.
     #[cfg(test)]
     pub fn from_parts(
         method: http::Method,
         path_segments: Vec<PathSegment>,
         query_segments: Vec<QuerySegment>,
+    ) -> Self {
+        Self::from_parts_with_host_prefix(method, None, path_segments, query_segments)
+    }
+
+    // Helper function to build a `RequestSpec` with a host prefix.
+    #[cfg(test)]
+    pub fn from_parts_with_host_prefix(
+        method: http::Method,
+        host_prefix: Option<Vec<HostPrefixSegment>>,
+        path_segments: Vec<PathSegment>,
+        query_segments: Vec<QuerySegment>,
     ) -> Self {
         Self::new(
             method,
             UriSpec {
-                host_prefix: None,
+                host_prefix,
                 path_and_query: PathAndQuerySpec {
                     path_segments: PathSpec::from_vector_unchecked(path_segments),
                     query_segments: QuerySpec::from_vector_unchecked(query_segments),
@@ -241,11 +298,40 @@ impl RequestSpec {
     }
 }
 
+/// Matches `host` against the host prefix `segments`, pushing the value bound to each label onto
+/// `labels`. The rest of the host after the prefix is the service's endpoint and is not matched.
+fn match_host_prefix(segments: &[HostPrefixSegment], host: &str, labels: &mut Vec<(String, String)>) -> bool {
+    match segments.split_first() {
+        None => true,
+        Some((HostPrefixSegment::Literal(literal), rest)) => match host.get(..literal.len()) {
+            Some(prefix) if prefix.eq_ignore_ascii_case(literal) => {
+                match_host_prefix(rest, &host[literal.len()..], labels)
+            }
+            _ => false,
+        },
+        Some((HostPrefixSegment::Label(name), rest)) => {
+            let max_len = host
+                .bytes()
+                .take_while(|b| b.is_ascii_alphanumeric() || *b == b'-')
+                .count();
+            // Bind the longest value that lets the rest of the host prefix match.
+            for len in (1..=max_len).rev() {
+                labels.push((name.clone(), host[..len].to_string()));
+                if match_host_prefix(rest, &host[len..], labels) {
+                    return true;
+                }
+                labels.pop();
+            }
+            false
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::rest_tests::req;
     use super::*;
-    use http::Method;
+    use http::{HeaderMap, Method};
 
     #[test]
     fn path_spec_into_regex() {
@@ -469,4 +555,63 @@ mod tests {
             assert_eq!(Match::Yes, label_spec.matches(&req(method, uri, None)));
         }
     }
+
+    fn host_prefix_spec() -> RequestSpec {
+        RequestSpec::from_parts_with_host_prefix(
+            Method::GET,
+            Some(vec![
+                HostPrefixSegment::Label(String::from("TenantId")),
+                HostPrefixSegment::Literal(String::from(".data.")),
+            ]),
+            vec![PathSegment::Literal(String::from("a"))],
+            Vec::new(),
+        )
+    }
+
+    fn host(host: &str) -> Option<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::HOST, host.parse().unwrap());
+        Some(headers)
+    }
+
+    #[test]
+    fn host_prefix_must_match() {
+        let spec = host_prefix_spec();
+        let hits = vec!["tenant-1.data.example.com", "T1.DATA.example.com:8080"];
+        for hit in hits {
+            assert_eq!(Match::Yes, spec.matches(&req(&Method::GET, "/a", host(hit))), "{}", hit);
+        }
+
+        let misses = vec![
+            "data.example.com",
+            ".data.example.com",
+            "a.b.data.example.com",
+            "example.com",
+        ];
+        for miss in misses {
+            assert_eq!(
+                Match::No,
+                spec.matches(&req(&Method::GET, "/a", host(miss))),
+                "{}",
+                miss
+            );
+        }
+        assert_eq!(Match::No, spec.matches(&req(&Method::GET, "/a", None)));
+    }
+
+    #[test]
+    fn host_prefix_labels_are_extracted() {
+        let spec = host_prefix_spec();
+        let labels = spec
+            .host_labels(&req(&Method::GET, "/a", host("tenant-1.data.example.com")))
+            .unwrap();
+        assert_eq!(Some("tenant-1"), labels.get("TenantId"));
+        assert_eq!(None, labels.get("Missing"));
+
+        // HTTP/2 requests don't have a `Host` header.
+        let labels = spec
+            .host_labels(&req(&Method::GET, "http://tenant-2.data.example.com/a", None))
+            .unwrap();
+        assert_eq!(Some("tenant-2"), labels.get("TenantId"));
+    }
 }
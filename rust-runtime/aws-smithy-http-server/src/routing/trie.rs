@@ -0,0 +1,331 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! A trie of [`PathSpec`] segments used to route RestJson1 and RestXml requests.
+//!
+//! Matching a request against every [`RequestSpec`] in turn is linear in the number of operations
+//! in the service. The trie is keyed by path segment instead, so that a lookup only visits the
+//! specs whose path can match the request's path. The candidates are then checked in rank order,
+//! exactly like the linear scan did, so precedence and ambiguity handling are unchanged. Of the
+//! specs with the same rank, the ones with a host prefix are checked first.
+//!
+//! [`PathSpec`]: super::request_spec::PathSpec
+
+use super::request_spec::{Match, PathSegment, RequestSpec};
+use crate::extension::HostLabels;
+use http::Request;
+use std::collections::HashMap;
+
+/// The outcome of looking up a request in a [`RouteTrie`].
+#[derive(Debug)]
+pub enum Lookup<'a, T> {
+    /// The value of the highest-ranked route matching the request, and the labels bound by its
+    /// host prefix.
+    Found(&'a T, HostLabels),
+    /// A route matches the request's URI, but not its HTTP method. `405 Method Not Allowed`
+    /// should be returned in the response.
+    MethodNotAllowed,
+    /// No route matches the request's URI. `404 Not Found` should be returned in the response.
+    NotFound,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    /// Children for path segments that are literals.
+    literals: HashMap<String, Node>,
+    /// The child for a path segment that is a label.
+    label: Option<Box<Node>>,
+    /// Routes whose path spec continues with a greedy label at this node, with the remaining
+    /// segments of the path spec, starting with the greedy label.
+    greedy: Vec<(Vec<PathSegment>, usize)>,
+    /// Routes whose path spec ends at this node.
+    routes: Vec<usize>,
+}
+
+impl Node {
+    fn insert(&mut self, segments: &[PathSegment], index: usize) {
+        match segments.split_first() {
+            None => self.routes.push(index),
+            Some((PathSegment::Literal(literal), rest)) => {
+                self.literals.entry(literal.clone()).or_default().insert(rest, index)
+            }
+            Some((PathSegment::Label, rest)) => self.label.get_or_insert_with(Default::default).insert(rest, index),
+            Some((PathSegment::Greedy, _)) => self.greedy.push((segments.to_vec(), index)),
+        }
+    }
+
+    /// Collects the indices of all routes whose path spec matches `path`.
+    fn collect(&self, path: &[&str], candidates: &mut Vec<usize>) {
+        match path.split_first() {
+            None => candidates.extend(&self.routes),
+            Some((segment, rest)) => {
+                if let Some(child) = self.literals.get(*segment) {
+                    child.collect(rest, candidates);
+                }
+                if let Some(child) = &self.label {
+                    child.collect(rest, candidates);
+                }
+            }
+        }
+        for (segments, index) in &self.greedy {
+            if segments_match(segments, path) {
+                candidates.push(*index);
+            }
+        }
+    }
+}
+
+/// Whether `path` matches `segments`. A label binds exactly one (possibly empty) path segment,
+/// while a greedy label binds one or more path segments.
+fn segments_match(segments: &[PathSegment], path: &[&str]) -> bool {
+    match segments.split_first() {
+        None => path.is_empty(),
+        Some((PathSegment::Literal(literal), rest)) => {
+            path.first() == Some(&literal.as_str()) && segments_match(rest, &path[1..])
+        }
+        Some((PathSegment::Label, rest)) => !path.is_empty() && segments_match(rest, &path[1..]),
+        Some((PathSegment::Greedy, rest)) => (1..=path.len()).any(|bound| segments_match(rest, &path[bound..])),
+    }
+}
+
+/// Routes for RestJson1 and RestXml services, indexed by the path segments of their
+/// [`RequestSpec`]s.
+#[derive(Debug, Clone)]
+pub struct RouteTrie<T> {
+    routes: Vec<(T, RequestSpec)>,
+    root: Node,
+}
+
+impl<T> RouteTrie<T> {
+    /// Creates a trie from pairs of values and the [`RequestSpec`]s that route to them.
+    pub fn new(routes: impl IntoIterator<Item = (T, RequestSpec)>) -> Self {
+        let mut routes: Vec<_> = routes.into_iter().collect();
+
+        // Sort them once by specifity, with the more specific routes sorted before the less
+        // specific ones, so that the lowest-indexed candidate that matches is the one we pick.
+        // Routes with a host prefix only match requests to their host, so they are sorted before
+        // the routes without one of the same rank.
+        routes.sort_by_key(|(_value, request_spec)| {
+            std::cmp::Reverse((request_spec.rank(), request_spec.has_host_prefix()))
+        });
+
+        let mut root = Node::default();
+        for (index, (_value, request_spec)) in routes.iter().enumerate() {
+            // The empty path spec only matches `/`, i.e. a single empty path segment.
+            match request_spec.path_segments() {
+                [] => root.insert(&[PathSegment::Literal(String::new())], index),
+                segments => root.insert(segments, index),
+            }
+        }
+        Self { routes, root }
+    }
+
+    /// Finds the value of the highest-ranked route that matches the request.
+    pub fn lookup<B>(&self, req: &Request<B>) -> Lookup<'_, T> {
+        let path = match req.uri().path().strip_prefix('/') {
+            Some(path) => path,
+            None => return Lookup::NotFound,
+        };
+        let path: Vec<&str> = path.split('/').collect();
+        let mut candidates = Vec::new();
+        self.root.collect(&path, &mut candidates);
+        candidates.sort_unstable();
+
+        let mut method_not_allowed = false;
+        for index in candidates {
+            let (value, request_spec) = &self.routes[index];
+            let host_labels = match request_spec.host_labels(req) {
+                Some(host_labels) => host_labels,
+                None => continue,
+            };
+            match request_spec.matches_method_and_query(req) {
+                Match::Yes => return Lookup::Found(value, host_labels),
+                Match::MethodNotAllowed => method_not_allowed = true,
+                Match::No => continue,
+            }
+        }
+        if method_not_allowed {
+            Lookup::MethodNotAllowed
+        } else {
+            Lookup::NotFound
+        }
+    }
+
+    /// Applies `f` to every value in the trie.
+    pub(super) fn map<U>(self, f: impl FnMut(T) -> U) -> RouteTrie<U> {
+        let (values, request_specs): (Vec<_>, Vec<_>) = self.routes.into_iter().unzip();
+        RouteTrie {
+            routes: values.into_iter().map(f).zip(request_specs).collect(),
+            root: self.root,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rest_tests::req;
+    use super::*;
+    use crate::routing::request_spec::{HostPrefixSegment, QuerySegment};
+    use http::Method;
+
+    fn literal(literal: &str) -> PathSegment {
+        PathSegment::Literal(String::from(literal))
+    }
+
+    fn trie(specs: Vec<(&'static str, Method, Vec<PathSegment>, Vec<QuerySegment>)>) -> RouteTrie<&'static str> {
+        RouteTrie::new(
+            specs
+                .into_iter()
+                .map(|(name, method, path, query)| (name, RequestSpec::from_parts(method, path, query))),
+        )
+    }
+
+    #[test]
+    fn lookup_agrees_with_request_spec_matching() {
+        let specs = vec![
+            (Method::GET, vec![]),
+            (Method::GET, vec![PathSegment::Label]),
+            (Method::GET, vec![literal("a"), PathSegment::Label]),
+            (Method::GET, vec![literal("a"), PathSegment::Label, literal("b")]),
+            (Method::GET, vec![literal("a"), PathSegment::Greedy]),
+            (Method::GET, vec![literal("mg"), PathSegment::Greedy, literal("z")]),
+            (
+                Method::GET,
+                vec![literal("mg"), PathSegment::Greedy, PathSegment::Label, literal("z")],
+            ),
+        ];
+        let paths = vec![
+            "/",
+            "//",
+            "/a",
+            "/a/",
+            "/a//",
+            "/a/x",
+            "/a//b",
+            "/a/x/b",
+            "/a/x/b/",
+            "/a///b",
+            "/mg/z",
+            "/mg//z",
+            "/mg/a/z",
+            "/mg/a/z/z",
+            "/mg/a/b/c/z",
+            "/mg/a/z/b",
+            "/x/y",
+        ];
+        for (method, segments) in specs {
+            let spec = RequestSpec::from_parts(method.clone(), segments.clone(), Vec::new());
+            let trie = RouteTrie::new(vec![((), spec.clone())]);
+            for path in &paths {
+                let request = req(&method, path, None);
+                let expected = spec.matches(&request) == Match::Yes;
+                let actual = matches!(trie.lookup(&request), Lookup::Found(..));
+                assert_eq!(expected, actual, "{:?} {}", segments, path);
+            }
+        }
+    }
+
+    #[test]
+    fn more_specific_routes_take_precedence() {
+        let trie = trie(vec![
+            ("Root", Method::GET, vec![], vec![]),
+            ("Label", Method::GET, vec![PathSegment::Label], vec![]),
+            ("Greedy", Method::GET, vec![literal("b"), PathSegment::Greedy], vec![]),
+            (
+                "GreedyQuery",
+                Method::GET,
+                vec![literal("b"), PathSegment::Greedy],
+                vec![QuerySegment::Key(String::from("q"))],
+            ),
+            ("Literal", Method::PUT, vec![literal("b"), literal("c")], vec![]),
+        ]);
+
+        let cases = vec![
+            (Method::GET, "/", "Label"),
+            (Method::GET, "/x", "Label"),
+            (Method::GET, "/b/c", "Greedy"),
+            (Method::PUT, "/b/c", "Literal"),
+            (Method::GET, "/b/c?q", "GreedyQuery"),
+            (Method::DELETE, "/b/c", "MethodNotAllowed"),
+            (Method::GET, "/x/y", "NotFound"),
+            (Method::GET, "*", "NotFound"),
+        ];
+        for (method, uri, expected) in cases {
+            let actual = match trie.lookup(&req(&method, uri, None)) {
+                Lookup::Found(name, _host_labels) => *name,
+                Lookup::MethodNotAllowed => "MethodNotAllowed",
+                Lookup::NotFound => "NotFound",
+            };
+            assert_eq!(expected, actual, "{} {}", method, uri);
+        }
+    }
+
+    #[test]
+    fn host_prefix_breaks_ties_between_routes_of_the_same_rank() {
+        let host_prefix = || {
+            Some(vec![
+                HostPrefixSegment::Label(String::from("TenantId")),
+                HostPrefixSegment::Literal(String::from(".data.")),
+            ])
+        };
+        let trie = RouteTrie::new(vec![
+            (
+                "Items",
+                RequestSpec::from_parts(Method::GET, vec![literal("items"), PathSegment::Label], vec![]),
+            ),
+            (
+                "TenantItems",
+                RequestSpec::from_parts_with_host_prefix(Method::GET, host_prefix(), vec![PathSegment::Greedy], vec![]),
+            ),
+            (
+                "TenantItem",
+                RequestSpec::from_parts_with_host_prefix(
+                    Method::GET,
+                    host_prefix(),
+                    vec![literal("items"), PathSegment::Label],
+                    vec![],
+                ),
+            ),
+        ]);
+
+        let cases = vec![
+            ("tenant-1.data.example.com", "/items/1", "TenantItem"),
+            ("tenant-1.data.example.com", "/other", "TenantItems"),
+            ("example.com", "/items/1", "Items"),
+            ("example.com", "/other", "NotFound"),
+        ];
+        for (host, uri, expected) in cases {
+            let mut headers = http::HeaderMap::new();
+            headers.insert(http::header::HOST, host.parse().unwrap());
+            let actual = match trie.lookup(&req(&Method::GET, uri, Some(headers))) {
+                Lookup::Found(name, _host_labels) => *name,
+                Lookup::MethodNotAllowed => "MethodNotAllowed",
+                Lookup::NotFound => "NotFound",
+            };
+            assert_eq!(expected, actual, "{} {}", host, uri);
+        }
+
+        // A more specific route takes precedence over a route with a host prefix.
+        let trie = RouteTrie::new(vec![
+            (
+                "TenantItems",
+                RequestSpec::from_parts_with_host_prefix(Method::GET, host_prefix(), vec![PathSegment::Greedy], vec![]),
+            ),
+            (
+                "Item",
+                RequestSpec::from_parts(Method::GET, vec![literal("items"), PathSegment::Label], vec![]),
+            ),
+        ]);
+        let mut headers = http::HeaderMap::new();
+        headers.insert(http::header::HOST, "tenant-1.data.example.com".parse().unwrap());
+        match trie.lookup(&req(&Method::GET, "/items/1", Some(headers))) {
+            Lookup::Found(name, host_labels) => {
+                assert_eq!("Item", *name);
+                assert!(host_labels.is_empty());
+            }
+            _ => panic!("the request should be routed"),
+        }
+    }
+}
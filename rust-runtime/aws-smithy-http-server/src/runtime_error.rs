@@ -21,7 +21,10 @@
 //! and converts into the corresponding `RuntimeError`, and then it uses the its
 //! [`RuntimeError::into_response`] method to render and send a response.
 
-use crate::{protocols::Protocol, response::Response};
+use crate::{
+    protocols::Protocol,
+    response::{IntoResponse, Response},
+};
 
 #[derive(Debug)]
 pub enum RuntimeErrorKind {
@@ -34,6 +37,11 @@ pub enum RuntimeErrorKind {
     InternalFailure(crate::Error),
     // UnsupportedMediaType,
     NotAcceptable,
+    /// The request could not be authenticated. The first field is the error code returned to the
+    /// client, which depends on the authentication scheme: for example, `SignatureDoesNotMatch`.
+    Unauthenticated(&'static str, crate::Error),
+    /// The request body is larger than the server is willing to read.
+    PayloadTooLarge,
 }
 
 /// String representation of the runtime error type.
@@ -46,6 +54,8 @@ impl RuntimeErrorKind {
             RuntimeErrorKind::InternalFailure(_) => "InternalFailureException",
             RuntimeErrorKind::UnknownOperation => "UnknownOperationException",
             RuntimeErrorKind::NotAcceptable => "NotAcceptableException",
+            RuntimeErrorKind::Unauthenticated(code, _) => code,
+            RuntimeErrorKind::PayloadTooLarge => "PayloadTooLargeException",
         }
     }
 }
@@ -63,16 +73,27 @@ impl RuntimeError {
             RuntimeErrorKind::InternalFailure(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
             RuntimeErrorKind::UnknownOperation => http::StatusCode::NOT_FOUND,
             RuntimeErrorKind::NotAcceptable => http::StatusCode::NOT_ACCEPTABLE,
+            RuntimeErrorKind::Unauthenticated(..) => http::StatusCode::FORBIDDEN,
+            RuntimeErrorKind::PayloadTooLarge => http::StatusCode::PAYLOAD_TOO_LARGE,
         };
 
-        let body = crate::body::to_boxed(match self.protocol {
-            Protocol::RestJson1 => "{}",
-            Protocol::RestXml => "",
+        let body = match self.protocol {
+            Protocol::RestJson1 => crate::body::to_boxed("{}"),
+            Protocol::RestXml => crate::body::to_boxed(""),
             // See https://awslabs.github.io/smithy/1.0/spec/aws/aws-json-1_0-protocol.html#empty-body-serialization
-            Protocol::AwsJson10 => "",
+            Protocol::AwsJson10 => crate::body::to_boxed(""),
             // See https://awslabs.github.io/smithy/1.0/spec/aws/aws-json-1_1-protocol.html#empty-body-serialization
-            Protocol::AwsJson11 => "",
-        });
+            Protocol::AwsJson11 => crate::body::to_boxed(""),
+            // AwsQuery and Ec2Query clients read the error code from the XML body, not from a header.
+            Protocol::AwsQuery => crate::body::to_boxed(crate::protocols::serialize_aws_query_error(
+                self.kind.name(),
+                None,
+                status_code.is_client_error(),
+            )),
+            Protocol::Ec2Query => {
+                crate::body::to_boxed(crate::protocols::serialize_ec2_query_error(self.kind.name(), None))
+            }
+        };
 
         let mut builder = http::Response::builder();
         builder = builder.status(status_code);
@@ -86,6 +107,7 @@ impl RuntimeError {
             Protocol::RestXml => builder = builder.header("Content-Type", "application/xml"),
             Protocol::AwsJson10 => builder = builder.header("Content-Type", "application/x-amz-json-1.0"),
             Protocol::AwsJson11 => builder = builder.header("Content-Type", "application/x-amz-json-1.1"),
+            Protocol::AwsQuery | Protocol::Ec2Query => builder = builder.header("Content-Type", "text/xml"),
         }
 
         builder = builder.extension(crate::extension::RuntimeErrorExtension::new(String::from(
@@ -96,6 +118,12 @@ impl RuntimeError {
     }
 }
 
+impl IntoResponse for RuntimeError {
+    fn into_response(self) -> Response {
+        RuntimeError::into_response(self)
+    }
+}
+
 impl From<crate::rejection::RequestExtensionNotFoundRejection> for RuntimeErrorKind {
     fn from(err: crate::rejection::RequestExtensionNotFoundRejection) -> Self {
         RuntimeErrorKind::InternalFailure(crate::Error::new(err))
@@ -0,0 +1,790 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! SigV4 request authentication.
+//!
+//! [`SigV4AuthLayer`] verifies that requests are signed with [AWS Signature Version 4], either in
+//! the `Authorization` header or in the query string of a presigned URL, with
+//! [`aws_sigv4::http_request::RequestSignature`]. The secret access key of the signer is looked up
+//! in a [`CredentialsStore`].
+//!
+//! The request body is only buffered when its hash is part of the signature, and requests whose
+//! body is larger than the [maximum body size](SigV4AuthLayer::with_max_body_size) are rejected
+//! with a `413 Payload Too Large` response. Bodies signed as `UNSIGNED-PAYLOAD` are passed to the
+//! inner service as they arrive. Payloads signed in chunks (`STREAMING-*`) are rejected, since
+//! their chunk signatures can't be verified.
+//!
+//! Requests that are authenticated reach the inner service with an [`AuthenticatedPrincipal`] in
+//! their extensions, which handlers can extract as an `Extension<AuthenticatedPrincipal>`. All
+//! other requests are rejected with a protocol-specific `403 Forbidden`
+//! [`RuntimeError`](crate::runtime_error::RuntimeError) response, without calling the inner
+//! service.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use aws_smithy_http_server::protocols::Protocol;
+//! use aws_smithy_http_server::sigv4::{Credentials, SigV4AuthLayer};
+//! use aws_smithy_http_server::Router;
+//! use std::collections::HashMap;
+//!
+//! # fn router() -> Router { unimplemented!() }
+//! let mut store = HashMap::new();
+//! store.insert(
+//!     String::from("AKIDEXAMPLE"),
+//!     Credentials::new("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "arn:aws:iam::123456789012:user/alice"),
+//! );
+//! let app = router().layer(
+//!     SigV4AuthLayer::new(Protocol::RestJson1, store)
+//!         .with_region("us-east-1")
+//!         .with_service("weather"),
+//! );
+//! ```
+//!
+//! [AWS Signature Version 4]: https://docs.aws.amazon.com/general/latest/gr/signature-version-4.html
+
+use crate::body::{read_limited, BoxBody, HttpBody};
+use crate::error::BoxError;
+use crate::protocols::Protocol;
+use crate::runtime_error::{RuntimeError, RuntimeErrorKind};
+use async_trait::async_trait;
+use aws_sigv4::http_request::{
+    PercentEncodingMode, RequestSignature, SignableBody, SignableRequest, VerifyError, VerifyErrorKind,
+};
+use bytes::Bytes;
+use http::request::Parts;
+use http::{Request, Response};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tower::{Layer, Service};
+
+const X_AMZ_CONTENT_SHA_256: &str = "x-amz-content-sha256";
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+/// The default maximum size of a request body that is buffered to compute its hash, i.e. 10 MiB.
+const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+/// The longest validity of a presigned URL that SigV4 allows, i.e. 7 days.
+const MAX_EXPIRES: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The secret access key of an access key id, and the principal that owns it.
+#[derive(Clone)]
+pub struct Credentials {
+    secret_access_key: String,
+    session_token: Option<String>,
+    principal: String,
+}
+
+impl Credentials {
+    /// Creates credentials for long-term access keys, which are used without a session token.
+    pub fn new(secret_access_key: impl Into<String>, principal: impl Into<String>) -> Self {
+        Self {
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+            principal: principal.into(),
+        }
+    }
+
+    /// Requires requests to carry `session_token`, as temporary credentials do.
+    pub fn with_session_token(mut self, session_token: impl Into<String>) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("secret_access_key", &"** redacted **")
+            .field("session_token", &self.session_token.as_ref().map(|_| "** redacted **"))
+            .field("principal", &self.principal)
+            .finish()
+    }
+}
+
+/// A store of [`Credentials`], indexed by access key id.
+#[async_trait]
+pub trait CredentialsStore: Send + Sync + 'static {
+    /// Returns the credentials of `access_key_id`, or `None` if the access key id is unknown.
+    ///
+    /// An error means the store could not be queried: the request is rejected with a
+    /// `500 Internal Server Error` response.
+    async fn credentials(&self, access_key_id: &str) -> Result<Option<Credentials>, BoxError>;
+}
+
+#[async_trait]
+impl CredentialsStore for HashMap<String, Credentials> {
+    async fn credentials(&self, access_key_id: &str) -> Result<Option<Credentials>, BoxError> {
+        Ok(self.get(access_key_id).cloned())
+    }
+}
+
+#[async_trait]
+impl<T> CredentialsStore for Arc<T>
+where
+    T: CredentialsStore + ?Sized,
+{
+    async fn credentials(&self, access_key_id: &str) -> Result<Option<Credentials>, BoxError> {
+        (**self).credentials(access_key_id).await
+    }
+}
+
+/// Extension type used to store the principal that signed a request in the request's extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedPrincipal {
+    principal: String,
+    access_key_id: String,
+}
+
+impl AuthenticatedPrincipal {
+    /// Returns the principal that owns the access key, as returned by the [`CredentialsStore`].
+    pub fn principal(&self) -> &str {
+        &self.principal
+    }
+
+    /// Returns the access key id the request was signed with.
+    pub fn access_key_id(&self) -> &str {
+        &self.access_key_id
+    }
+}
+
+/// A [`tower::Layer`] that authenticates SigV4-signed requests. See the [module documentation]
+/// for details.
+///
+/// [module documentation]: crate::sigv4
+pub struct SigV4AuthLayer<C> {
+    config: Config<C>,
+}
+
+struct Config<C> {
+    protocol: Protocol,
+    store: Arc<C>,
+    region: Option<String>,
+    service: Option<String>,
+    max_clock_skew: Duration,
+    double_uri_encode: bool,
+    max_body_size: usize,
+}
+
+impl<C> SigV4AuthLayer<C> {
+    /// Creates a layer that looks up credentials in `store`, and renders authentication failures
+    /// as `protocol` errors.
+    pub fn new(protocol: Protocol, store: C) -> Self {
+        Self {
+            config: Config {
+                protocol,
+                store: Arc::new(store),
+                region: None,
+                service: None,
+                max_clock_skew: Duration::from_secs(5 * 60),
+                double_uri_encode: true,
+                max_body_size: DEFAULT_MAX_BODY_SIZE,
+            },
+        }
+    }
+
+    /// Requires requests to be signed for `region`.
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.config.region = Some(region.into());
+        self
+    }
+
+    /// Requires requests to be signed for `service`, the signing name of the service.
+    pub fn with_service(mut self, service: impl Into<String>) -> Self {
+        self.config.service = Some(service.into());
+        self
+    }
+
+    /// Sets how far the signing time of a request may be from the server's clock. Defaults to 5
+    /// minutes.
+    pub fn with_max_clock_skew(mut self, max_clock_skew: Duration) -> Self {
+        self.config.max_clock_skew = max_clock_skew;
+        self
+    }
+
+    /// Canonicalizes the path without re-encoding it, as S3 does.
+    pub fn single_uri_encoding(mut self) -> Self {
+        self.config.double_uri_encode = false;
+        self
+    }
+
+    /// Sets the size in bytes of the largest request body that is buffered to compute its hash.
+    /// Larger requests are rejected with a `413 Payload Too Large` response. Defaults to 10 MiB.
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.config.max_body_size = max_body_size;
+        self
+    }
+}
+
+impl<C> Clone for SigV4AuthLayer<C> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+        }
+    }
+}
+
+impl<C> Clone for Config<C> {
+    fn clone(&self) -> Self {
+        Self {
+            protocol: self.protocol,
+            store: self.store.clone(),
+            region: self.region.clone(),
+            service: self.service.clone(),
+            max_clock_skew: self.max_clock_skew,
+            double_uri_encode: self.double_uri_encode,
+            max_body_size: self.max_body_size,
+        }
+    }
+}
+
+impl<C> fmt::Debug for SigV4AuthLayer<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigV4AuthLayer").finish()
+    }
+}
+
+impl<S, C> Layer<S> for SigV4AuthLayer<C> {
+    type Service = SigV4Auth<S, C>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SigV4Auth {
+            inner,
+            config: Arc::new(self.config.clone()),
+        }
+    }
+}
+
+/// Middleware that authenticates SigV4-signed requests. Created with [`SigV4AuthLayer`].
+///
+/// The request body is buffered when its hash must be computed.
+pub struct SigV4Auth<S, C> {
+    inner: S,
+    config: Arc<Config<C>>,
+}
+
+impl<S, C> Clone for SigV4Auth<S, C>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            config: self.config.clone(),
+        }
+    }
+}
+
+impl<S, C> fmt::Debug for SigV4Auth<S, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigV4Auth").finish()
+    }
+}
+
+impl<B, S, C> Service<Request<B>> for SigV4Auth<S, C>
+where
+    B: HttpBody<Data = Bytes> + From<Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    C: CredentialsStore,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        // The inner service is ready, its clone might not be.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let mut payload = Payload::Streaming(body);
+            match config.authenticate(&parts, &mut payload).await {
+                Ok(principal) => {
+                    parts.extensions.insert(principal);
+                    inner.call(Request::from_parts(parts, payload.into_body())).await
+                }
+                Err(kind) => Ok(config.reject(kind)),
+            }
+        })
+    }
+}
+
+/// The body of a request being authenticated.
+enum Payload<B> {
+    /// The body has not been read.
+    Streaming(B),
+    /// The body was read to compute its hash.
+    Buffered(Bytes),
+}
+
+impl<B> Payload<B>
+where
+    B: HttpBody<Data = Bytes> + From<Bytes>,
+    B::Error: Into<BoxError>,
+{
+    /// Returns the body, reading it into memory if needed.
+    async fn buffer(&mut self, max_body_size: usize) -> Result<&Bytes, RuntimeErrorKind> {
+        if let Payload::Streaming(_) = self {
+            let body = match std::mem::replace(self, Payload::Buffered(Bytes::new())) {
+                Payload::Streaming(body) => body,
+                Payload::Buffered(_) => unreachable!("checked above"),
+            };
+            *self = Payload::Buffered(read_limited(body, max_body_size).await?);
+        }
+        match self {
+            Payload::Buffered(body) => Ok(body),
+            Payload::Streaming(_) => unreachable!("the body was buffered above"),
+        }
+    }
+
+    fn into_body(self) -> B {
+        match self {
+            Payload::Streaming(body) => body,
+            Payload::Buffered(body) => B::from(body),
+        }
+    }
+}
+
+impl<C> Config<C>
+where
+    C: CredentialsStore,
+{
+    async fn authenticate<B>(
+        &self,
+        parts: &Parts,
+        payload: &mut Payload<B>,
+    ) -> Result<AuthenticatedPrincipal, RuntimeErrorKind>
+    where
+        B: HttpBody<Data = Bytes> + From<Bytes>,
+        B::Error: Into<BoxError>,
+    {
+        let signature = RequestSignature::from_request(&parts.uri, &parts.headers).map_err(AuthError::from)?;
+        self.check_scope_and_time(&signature)?;
+
+        let credentials = match self.store.credentials(signature.access_key_id()).await {
+            Ok(Some(credentials)) => credentials,
+            Ok(None) => {
+                return Err(AuthError::unrecognized_client(format!(
+                    "unknown access key id `{}`",
+                    signature.access_key_id()
+                ))
+                .into())
+            }
+            Err(err) => return Err(RuntimeErrorKind::InternalFailure(crate::Error::new(err))),
+        };
+        if credentials.session_token.as_deref() != signature.security_token() {
+            return Err(AuthError::unrecognized_client("the security token included in the request is invalid").into());
+        }
+
+        let verify = |body: SignableBody<'_>| {
+            let request = SignableRequest::new(&parts.method, &parts.uri, &parts.headers, body);
+            let percent_encoding_mode = if self.double_uri_encode {
+                PercentEncodingMode::Double
+            } else {
+                PercentEncodingMode::Single
+            };
+            signature.verify(&request, &credentials.secret_access_key, percent_encoding_mode)
+        };
+
+        // A body that was signed is verified through the signature, so it is only read when its
+        // hash is part of the signature. Presigned URLs usually don't sign the body, but they can.
+        let unsigned_payload = matches!(
+            parts.headers.get(X_AMZ_CONTENT_SHA_256),
+            Some(value) if value == UNSIGNED_PAYLOAD
+        );
+        let verified = if unsigned_payload {
+            verify(SignableBody::UnsignedPayload)
+        } else {
+            match signature.expires_in() {
+                Some(_) if verify(SignableBody::UnsignedPayload).is_ok() => Ok(()),
+                _ => verify(SignableBody::Bytes(payload.buffer(self.max_body_size).await?)),
+            }
+        };
+        verified.map_err(AuthError::from)?;
+        Ok(AuthenticatedPrincipal {
+            principal: credentials.principal,
+            access_key_id: String::from(signature.access_key_id()),
+        })
+    }
+
+    /// Checks the credential scope and the signing time of `signature`.
+    fn check_scope_and_time(&self, signature: &RequestSignature) -> Result<(), AuthError> {
+        for (field, actual, expected) in [
+            ("region", signature.region(), &self.region),
+            ("service", signature.service(), &self.service),
+        ] {
+            if let Some(expected) = expected {
+                if actual != expected {
+                    return Err(AuthError::signature_does_not_match(format!(
+                        "the credential should be scoped to {} `{}`, not `{}`",
+                        field, expected, actual
+                    )));
+                }
+            }
+        }
+
+        let now = SystemTime::now();
+        let time = signature.time();
+        let skew = match now.duration_since(time) {
+            Ok(elapsed) => elapsed,
+            Err(err) => err.duration(),
+        };
+        match signature.expires_in() {
+            None if skew > self.max_clock_skew => Err(AuthError::new(
+                "RequestTimeTooSkewed",
+                format!(
+                    "the signing time is more than {}s away from the server time",
+                    self.max_clock_skew.as_secs()
+                ),
+            )),
+            None => Ok(()),
+            Some(expires_in) if expires_in.is_zero() || expires_in > MAX_EXPIRES => Err(AuthError::incomplete(
+                format!("invalid `X-Amz-Expires` `{}`", expires_in.as_secs()),
+            )),
+            Some(_) if time > now + self.max_clock_skew => Err(AuthError::new(
+                "RequestTimeTooSkewed",
+                "the presigned URL is not valid yet",
+            )),
+            Some(expires_in) if now > time + expires_in => {
+                Err(AuthError::new("RequestExpired", "the presigned URL has expired"))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn reject(&self, kind: RuntimeErrorKind) -> Response<BoxBody> {
+        RuntimeError {
+            protocol: self.protocol,
+            kind,
+        }
+        .into_response()
+    }
+}
+
+/// A request that failed authentication, with the error code returned to the client.
+#[derive(Debug)]
+struct AuthError {
+    code: &'static str,
+    message: String,
+}
+
+impl AuthError {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn incomplete(message: impl Into<String>) -> Self {
+        Self::new("IncompleteSignatureException", message)
+    }
+
+    fn unrecognized_client(message: impl Into<String>) -> Self {
+        Self::new("UnrecognizedClientException", message)
+    }
+
+    fn signature_does_not_match(message: impl Into<String>) -> Self {
+        Self::new("SignatureDoesNotMatch", message)
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<VerifyError> for AuthError {
+    fn from(err: VerifyError) -> Self {
+        match err.kind() {
+            VerifyErrorKind::MissingSignature => Self::new("MissingAuthenticationTokenException", err.to_string()),
+            VerifyErrorKind::SignatureDoesNotMatch => Self::signature_does_not_match(err.to_string()),
+            _ => Self::incomplete(err.to_string()),
+        }
+    }
+}
+
+impl From<AuthError> for RuntimeErrorKind {
+    fn from(err: AuthError) -> Self {
+        RuntimeErrorKind::Unauthenticated(err.code, crate::Error::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{boxed, Body};
+    use aws_sigv4::http_request::{
+        sign, PayloadChecksumKind, SignableBody, SignableRequest, SignatureLocation, SigningParams, SigningSettings,
+    };
+    use http::header::AUTHORIZATION;
+    use std::convert::Infallible;
+    use tower::{service_fn, ServiceExt};
+
+    const SECRET: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    fn store() -> HashMap<String, Credentials> {
+        let mut store = HashMap::new();
+        store.insert(String::from("AKIDEXAMPLE"), Credentials::new(SECRET, "alice"));
+        store.insert(
+            String::from("ASIAEXAMPLE"),
+            Credentials::new(SECRET, "bob").with_session_token("session-token"),
+        );
+        store
+    }
+
+    struct Signer {
+        access_key_id: &'static str,
+        secret: &'static str,
+        security_token: Option<&'static str>,
+        region: &'static str,
+        time: SystemTime,
+        expires_in: Option<Duration>,
+        /// Signs this payload hash instead of the hash of the body. It is sent in the
+        /// `x-amz-content-sha256` header, except for presigned URLs.
+        payload_hash: Option<&'static str>,
+    }
+
+    impl Default for Signer {
+        fn default() -> Self {
+            Self {
+                access_key_id: "AKIDEXAMPLE",
+                secret: SECRET,
+                security_token: None,
+                region: "us-east-1",
+                time: SystemTime::now(),
+                expires_in: None,
+                payload_hash: None,
+            }
+        }
+    }
+
+    impl Signer {
+        /// Signs a request the way the SDK does, then turns it into a request as the server sees it.
+        fn sign(&self, body: &'static str) -> Request<Body> {
+            let mut request = Request::put("https://weather.example.com/cities/a%20b?units=metric&q=1")
+                .header("content-type", "application/json")
+                .body(body)
+                .unwrap();
+            let mut settings = SigningSettings::default();
+            if let Some(expires_in) = self.expires_in {
+                settings.signature_location = SignatureLocation::QueryParams;
+                settings.expires_in = Some(expires_in);
+            } else if self.payload_hash.is_some() {
+                settings.payload_checksum_kind = PayloadChecksumKind::XAmzSha256;
+            }
+            let signable_body = match self.payload_hash {
+                Some(payload_hash) => SignableBody::Precomputed(String::from(payload_hash)),
+                None => SignableBody::Bytes(request.body().as_bytes()),
+            };
+            let mut params = SigningParams::builder()
+                .access_key(self.access_key_id)
+                .secret_key(self.secret)
+                .region(self.region)
+                .service_name("weather")
+                .time(self.time)
+                .settings(settings);
+            params.set_security_token(self.security_token);
+            let signable_request =
+                SignableRequest::new(request.method(), request.uri(), request.headers(), signable_body);
+            let (instructions, _signature) = sign(signable_request, &params.build().unwrap()).unwrap().into_parts();
+            instructions.apply_to_request(&mut request);
+
+            let (mut parts, body) = request.into_parts();
+            parts.headers.insert("host", "weather.example.com".parse().unwrap());
+            parts.uri = parts.uri.path_and_query().unwrap().as_str().parse().unwrap();
+            Request::from_parts(parts, Body::from(body))
+        }
+    }
+
+    /// Sends `request` through the layer to a service that responds with the principal.
+    async fn call(layer: &SigV4AuthLayer<HashMap<String, Credentials>>, request: Request<Body>) -> Response<BoxBody> {
+        let service = service_fn(|request: Request<Body>| async move {
+            let principal = request.extensions().get::<AuthenticatedPrincipal>().unwrap().clone();
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            assert_eq!(&b"{}"[..], &body[..], "the body is passed on");
+            Ok::<_, Infallible>(Response::new(boxed(Body::from(String::from(principal.principal())))))
+        });
+        layer.layer(service).oneshot(request).await.unwrap()
+    }
+
+    fn layer() -> SigV4AuthLayer<HashMap<String, Credentials>> {
+        SigV4AuthLayer::new(Protocol::RestJson1, store())
+            .with_region("us-east-1")
+            .with_service("weather")
+    }
+
+    async fn assert_authenticated(response: Response<BoxBody>, principal: &str) {
+        assert_eq!(http::StatusCode::OK, response.status(), "{:?}", response.headers());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(principal.as_bytes(), &body[..]);
+    }
+
+    fn assert_rejected(response: Response<BoxBody>, code: &str) {
+        assert_eq!(http::StatusCode::FORBIDDEN, response.status());
+        assert_eq!(code, response.headers()["x-amzn-errortype"]);
+    }
+
+    #[tokio::test]
+    async fn signed_requests_are_authenticated() {
+        let layer = layer();
+        assert_authenticated(call(&layer, Signer::default().sign("{}")).await, "alice").await;
+
+        let signer = Signer {
+            access_key_id: "ASIAEXAMPLE",
+            security_token: Some("session-token"),
+            ..Default::default()
+        };
+        assert_authenticated(call(&layer, signer.sign("{}")).await, "bob").await;
+    }
+
+    #[tokio::test]
+    async fn invalid_signatures_are_rejected() {
+        let layer = layer();
+        let wrong_secret = Signer {
+            secret: "wrong",
+            ..Default::default()
+        };
+        assert_rejected(call(&layer, wrong_secret.sign("{}")).await, "SignatureDoesNotMatch");
+
+        let tampered = Signer::default().sign("{}").map(|_| Body::from(r#"{"tampered":true}"#));
+        assert_rejected(call(&layer, tampered).await, "SignatureDoesNotMatch");
+
+        let wrong_region = Signer {
+            region: "us-west-2",
+            ..Default::default()
+        };
+        assert_rejected(call(&layer, wrong_region.sign("{}")).await, "SignatureDoesNotMatch");
+
+        let unknown = Signer {
+            access_key_id: "AKIDUNKNOWN",
+            ..Default::default()
+        };
+        assert_rejected(call(&layer, unknown.sign("{}")).await, "UnrecognizedClientException");
+
+        let missing_token = Signer {
+            access_key_id: "ASIAEXAMPLE",
+            ..Default::default()
+        };
+        assert_rejected(
+            call(&layer, missing_token.sign("{}")).await,
+            "UnrecognizedClientException",
+        );
+
+        let unsigned = Request::put("/cities/a%20b").body(Body::from("{}")).unwrap();
+        assert_rejected(call(&layer, unsigned).await, "MissingAuthenticationTokenException");
+
+        let mut malformed = Signer::default().sign("{}");
+        malformed
+            .headers_mut()
+            .insert(AUTHORIZATION, "AWS4-HMAC-SHA256 Signature=abc".parse().unwrap());
+        assert_rejected(call(&layer, malformed).await, "IncompleteSignatureException");
+    }
+
+    #[tokio::test]
+    async fn only_signed_payloads_are_buffered() {
+        // The body is larger than the maximum body size, so it can only be authenticated without
+        // buffering it.
+        let layer = layer().with_max_body_size(1);
+        let unsigned = Signer {
+            payload_hash: Some(UNSIGNED_PAYLOAD),
+            ..Default::default()
+        };
+        assert_authenticated(call(&layer, unsigned.sign("{}")).await, "alice").await;
+        let presigned = Signer {
+            expires_in: Some(Duration::from_secs(60)),
+            payload_hash: Some(UNSIGNED_PAYLOAD),
+            ..Default::default()
+        };
+        assert_authenticated(call(&layer, presigned.sign("{}")).await, "alice").await;
+
+        let response = call(&layer, Signer::default().sign("{}")).await;
+        assert_eq!(http::StatusCode::PAYLOAD_TOO_LARGE, response.status());
+        assert_eq!("PayloadTooLargeException", response.headers()["x-amzn-errortype"]);
+        // The body fits, so its hash is verified.
+        let layer = layer.with_max_body_size(2);
+        assert_authenticated(call(&layer, Signer::default().sign("{}")).await, "alice").await;
+    }
+
+    #[tokio::test]
+    async fn streaming_payloads_are_rejected() {
+        let streaming = Signer {
+            payload_hash: Some("STREAMING-AWS4-HMAC-SHA256-PAYLOAD"),
+            ..Default::default()
+        };
+        assert_rejected(
+            call(&layer(), streaming.sign("{}")).await,
+            "IncompleteSignatureException",
+        );
+    }
+
+    #[tokio::test]
+    async fn clock_skew_is_limited() {
+        let layer = layer();
+        let skewed = Signer {
+            time: SystemTime::now() - Duration::from_secs(10 * 60),
+            ..Default::default()
+        };
+        assert_rejected(call(&layer, skewed.sign("{}")).await, "RequestTimeTooSkewed");
+
+        let layer = layer.with_max_clock_skew(Duration::from_secs(15 * 60));
+        assert_authenticated(call(&layer, skewed.sign("{}")).await, "alice").await;
+    }
+
+    #[tokio::test]
+    async fn presigned_urls_expire() {
+        let layer = layer();
+        let presigned = Signer {
+            expires_in: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let request = presigned.sign("{}");
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+        assert_authenticated(call(&layer, request).await, "alice").await;
+
+        // Presigned URLs may be used long after they were signed, as long as they have not expired.
+        let expired = Signer {
+            time: SystemTime::now() - Duration::from_secs(30 * 60),
+            expires_in: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert_rejected(call(&layer, expired.sign("{}")).await, "RequestExpired");
+        let valid = Signer {
+            expires_in: Some(Duration::from_secs(60 * 60)),
+            ..expired
+        };
+        assert_authenticated(call(&layer, valid.sign("{}")).await, "alice").await;
+    }
+
+    #[tokio::test]
+    async fn failures_are_protocol_specific() {
+        let layer = SigV4AuthLayer::new(Protocol::AwsJson11, store());
+        let unsigned = Request::post("/").body(Body::from("{}")).unwrap();
+        let response = call(&layer, unsigned).await;
+        assert_eq!(http::StatusCode::FORBIDDEN, response.status());
+        assert_eq!("application/x-amz-json-1.1", response.headers()["content-type"]);
+        assert_eq!(
+            "MissingAuthenticationTokenException",
+            response
+                .extensions()
+                .get::<crate::extension::RuntimeErrorExtension>()
+                .unwrap()
+                .as_str()
+        );
+    }
+}
@@ -5,8 +5,9 @@
 
 use crate::SendOperationError;
 use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::interceptor::Interceptors;
 use aws_smithy_http::operation;
-use aws_smithy_http::result::ConnectorError;
+use aws_smithy_http::result::{AttemptHistory, ConnectorError};
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -40,16 +41,36 @@ where
             .map_err(|e| SendOperationError::RequestDispatchError(e.into()))
     }
 
-    fn call(&mut self, req: operation::Request) -> Self::Future {
+    fn call(&mut self, mut req: operation::Request) -> Self::Future {
+        let interceptors = req.properties().get::<Interceptors>().cloned();
+        if let Some(interceptors) = &interceptors {
+            if let Err(err) = interceptors.before_transmit(&mut req) {
+                return Box::pin(std::future::ready(Err(
+                    SendOperationError::RequestConstructionError(err),
+                )));
+            }
+        }
+        let attempt_history = req.properties().get::<AttemptHistory>().cloned();
         let (req, property_bag) = req.into_parts();
         let mut inner = self.inner.clone();
         let future = async move {
             trace!(request = ?req);
-            inner
+            let dispatch_error = |err: ConnectorError| {
+                SendOperationError::RequestDispatchError(
+                    err.with_attempt_history(attempt_history.clone()),
+                )
+            };
+            let mut resp = inner
                 .call(req)
                 .await
                 .map(|resp| operation::Response::from_parts(resp, property_bag))
-                .map_err(|e| SendOperationError::RequestDispatchError(e.into()))
+                .map_err(|e| dispatch_error(e.into()))?;
+            if let Some(interceptors) = interceptors {
+                interceptors
+                    .after_receive(&mut resp)
+                    .map_err(|err| dispatch_error(ConnectorError::other(err, None)))?;
+            }
+            Ok(resp)
         };
         Box::pin(future)
     }
@@ -41,6 +41,9 @@ pub enum SendOperationError {
 impl<E> From<SendOperationError> for SdkError<E> {
     fn from(err: SendOperationError) -> Self {
         match err {
+            SendOperationError::RequestDispatchError(e) if e.is_circuit_breaker_open() => {
+                aws_smithy_http::result::SdkError::CircuitBreakerOpen(e)
+            }
             SendOperationError::RequestDispatchError(e) => {
                 aws_smithy_http::result::SdkError::DispatchFailure(e)
             }
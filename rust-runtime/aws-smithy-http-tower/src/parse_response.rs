@@ -4,6 +4,7 @@
  */
 
 use crate::SendOperationError;
+use aws_smithy_http::interceptor::Interceptors;
 use aws_smithy_http::middleware::load_response;
 use aws_smithy_http::operation;
 use aws_smithy_http::operation::Operation;
@@ -88,7 +89,13 @@ where
     }
 
     fn call(&mut self, req: Operation<ResponseHandler, RetryPolicy>) -> Self::Future {
-        let (req, parts) = req.into_request_response();
+        let (mut req, parts) = req.into_request_response();
+        let interceptors = req.properties().get::<Interceptors>().cloned();
+        if let Some(interceptors) = &interceptors {
+            if let Err(err) = interceptors.before_signing(&mut req) {
+                return Box::pin(async move { Err(SdkError::ConstructionFailure(err)) });
+            }
+        }
         let handler = parts.response_handler;
         // send_operation records the full request-response lifecycle.
         // NOTE: For operations that stream output, only the setup is captured in this span.
@@ -108,13 +115,19 @@ where
         let fut = async move {
             let resp = match resp.await {
                 Err(e) => Err(e.into()),
-                Ok(resp) => {
-                    // load_response contains reading the body as far as is required & parsing the response
-                    let response_span = debug_span!("load_response");
-                    load_response(resp, &handler)
-                        .instrument(response_span)
-                        .await
-                }
+                Ok(mut resp) => match interceptors
+                    .map(|interceptors| interceptors.before_deserialization(&mut resp))
+                    .unwrap_or(Ok(()))
+                {
+                    Err(err) => Err(SdkError::ResponseError { err, raw: resp }),
+                    Ok(()) => {
+                        // load_response contains reading the body as far as is required & parsing the response
+                        let response_span = debug_span!("load_response");
+                        load_response(resp, &handler)
+                            .instrument(response_span)
+                            .await
+                    }
+                },
             };
             match &resp {
                 Ok(_) => inner_span.record("status", &"ok"),
@@ -133,6 +146,9 @@ where
                 Err(SdkError::TimeoutError(err)) => inner_span
                     .record("status", &"timeout_error")
                     .record("message", &display(err)),
+                Err(SdkError::CircuitBreakerOpen(err)) => inner_span
+                    .record("status", "circuit_breaker_open")
+                    .record("message", display(err)),
             };
             resp
         }
@@ -0,0 +1,388 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Interceptors that are called at specific phases of an operation's lifecycle.
+//!
+//! An [`Interceptor`] can read and modify the request, its properties and the response as the
+//! operation passes through the client. Interceptors are registered on the client, or for a single
+//! operation by inserting [`Interceptors`] into the operation's property bag. Operation interceptors
+//! are called after the client's interceptors.
+//!
+//! For each attempt, the hooks are called in this order:
+//! 1. [`before_serialization`](Interceptor::before_serialization) (once per operation, client
+//!    interceptors only)
+//! 2. [`before_first_attempt`](Interceptor::before_first_attempt) (once per operation)
+//! 3. [`before_signing`](Interceptor::before_signing)
+//! 4. [`before_transmit`](Interceptor::before_transmit)
+//! 5. [`after_receive`](Interceptor::after_receive)
+//! 6. [`before_deserialization`](Interceptor::before_deserialization)
+//! 7. [`on_error`](Interceptor::on_error) (once per operation, if it failed)
+
+use crate::operation;
+use crate::result::{describe_error, SdkError};
+use std::any::{self, Any};
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::Arc;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Hooks that are called at specific phases of an operation's lifecycle
+///
+/// Every hook does nothing by default. Returning an error from a hook fails the attempt: errors
+/// returned by request hooks are construction failures, errors returned by `after_receive` are
+/// dispatch failures and errors returned by `before_deserialization` are response errors.
+pub trait Interceptor: Debug + Send + Sync {
+    /// Called once per operation, before its input is serialized into a request
+    ///
+    /// The input can be modified after downcasting it to the input type of the operation. This
+    /// hook is only called on the client's interceptors, since the interceptors of an operation are
+    /// registered after its input has been serialized.
+    fn before_serialization(&self, input: &mut OperationInput<'_>) -> Result<(), BoxError> {
+        let _ = input;
+        Ok(())
+    }
+
+    /// Called once per operation, before its first attempt
+    ///
+    /// The request has been serialized, but no middleware has modified it yet.
+    fn before_first_attempt(&self, request: &mut operation::Request) -> Result<(), BoxError> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called for every attempt, before middleware (such as request signing) is applied to the request
+    fn before_signing(&self, request: &mut operation::Request) -> Result<(), BoxError> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called for every attempt, right before the request is sent over the wire
+    fn before_transmit(&self, request: &mut operation::Request) -> Result<(), BoxError> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called for every attempt, right after a response is received
+    fn after_receive(&self, response: &mut operation::Response) -> Result<(), BoxError> {
+        let _ = response;
+        Ok(())
+    }
+
+    /// Called for every attempt, after middleware has processed the response but before it is parsed
+    fn before_deserialization(&self, response: &mut operation::Response) -> Result<(), BoxError> {
+        let _ = response;
+        Ok(())
+    }
+
+    /// Called once per operation when the operation fails, after all retries are exhausted
+    fn on_error(&self, error: &OperationError<'_>) {
+        let _ = error;
+    }
+}
+
+/// The input of an operation, as seen by [`Interceptor::before_serialization`]
+///
+/// The input type is erased so that interceptors can observe the input of any operation. Use
+/// [`downcast_mut`](OperationInput::downcast_mut) to access the input of a specific operation.
+#[derive(Debug)]
+pub struct OperationInput<'a> {
+    input: &'a mut (dyn Any + Send),
+    type_name: &'static str,
+}
+
+impl<'a> OperationInput<'a> {
+    /// Erase the type of an operation's input
+    pub fn new<T: Any + Send>(input: &'a mut T) -> Self {
+        Self {
+            input,
+            type_name: any::type_name::<T>(),
+        }
+    }
+
+    /// The name of the input type, for diagnostics
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the input if it is of type `T`
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.input.downcast_ref()
+    }
+
+    /// Returns the input mutably if it is of type `T`
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.input.downcast_mut()
+    }
+}
+
+/// The error an operation failed with, as seen by [`Interceptor::on_error`]
+///
+/// The modeled error of the operation is erased, so interceptors can observe errors of any
+/// operation. The underlying construction, timeout, dispatch or response error is available from
+/// [`Error::source`], and the raw response from [`OperationError::raw_response`].
+#[derive(Debug)]
+pub struct OperationError<'a> {
+    description: String,
+    source: Option<&'a (dyn Error + 'static)>,
+    raw: Option<&'a operation::Response>,
+}
+
+impl<'a> OperationError<'a> {
+    /// The raw response, if the operation failed after a response was received
+    pub fn raw_response(&self) -> Option<&'a operation::Response> {
+        self.raw
+    }
+}
+
+impl<'a, E> From<&'a SdkError<E>> for OperationError<'a> {
+    fn from(err: &'a SdkError<E>) -> Self {
+        let (source, raw): (Option<&(dyn Error + 'static)>, _) = match err {
+            SdkError::ConstructionFailure(err) | SdkError::TimeoutError(err) => {
+                (Some(err.as_ref()), None)
+            }
+            SdkError::DispatchFailure(err) | SdkError::CircuitBreakerOpen(err) => (Some(err), None),
+            SdkError::ResponseError { err, raw } => (Some(err.as_ref()), Some(raw)),
+            SdkError::ServiceError { raw, .. } => (None, Some(raw)),
+        };
+        Self {
+            description: describe_error(err),
+            source,
+            raw,
+        }
+    }
+}
+
+impl Display for OperationError<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+impl Error for OperationError<'_> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+    }
+}
+
+/// An ordered list of [`Interceptor`]s
+///
+/// Insert `Interceptors` into an operation's property bag to register interceptors for that
+/// operation.
+#[derive(Clone, Debug, Default)]
+pub struct Interceptors {
+    interceptors: Vec<Arc<dyn Interceptor>>,
+}
+
+macro_rules! run_hook {
+    ($(#[$doc:meta])* $hook:ident, $ty:ty) => {
+        $(#[$doc])*
+        pub fn $hook(&self, target: &mut $ty) -> Result<(), BoxError> {
+            for interceptor in &self.interceptors {
+                interceptor.$hook(target)?;
+            }
+            Ok(())
+        }
+    };
+}
+
+impl Interceptors {
+    /// Create an empty list of interceptors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an interceptor to the end of the list
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.push(interceptor);
+        self
+    }
+
+    /// Add an interceptor to the end of the list
+    pub fn push(&mut self, interceptor: impl Interceptor + 'static) {
+        self.interceptors.push(Arc::new(interceptor));
+    }
+
+    /// Add all the interceptors of `other` to the end of the list
+    pub fn extend(&mut self, other: &Interceptors) {
+        self.interceptors.extend(other.interceptors.iter().cloned());
+    }
+
+    /// Returns true if the list doesn't contain any interceptors
+    pub fn is_empty(&self) -> bool {
+        self.interceptors.is_empty()
+    }
+
+    run_hook!(
+        /// Call [`Interceptor::before_serialization`] on every interceptor, stopping at the first error
+        before_serialization,
+        OperationInput<'_>
+    );
+    run_hook!(
+        /// Call [`Interceptor::before_first_attempt`] on every interceptor, stopping at the first error
+        before_first_attempt,
+        operation::Request
+    );
+    run_hook!(
+        /// Call [`Interceptor::before_signing`] on every interceptor, stopping at the first error
+        before_signing,
+        operation::Request
+    );
+    run_hook!(
+        /// Call [`Interceptor::before_transmit`] on every interceptor, stopping at the first error
+        before_transmit,
+        operation::Request
+    );
+    run_hook!(
+        /// Call [`Interceptor::after_receive`] on every interceptor, stopping at the first error
+        after_receive,
+        operation::Response
+    );
+    run_hook!(
+        /// Call [`Interceptor::before_deserialization`] on every interceptor, stopping at the first error
+        before_deserialization,
+        operation::Response
+    );
+
+    /// Call [`Interceptor::on_error`] on every interceptor
+    pub fn on_error<E>(&self, error: &SdkError<E>) {
+        if self.interceptors.is_empty() {
+            return;
+        }
+        let error = OperationError::from(error);
+        for interceptor in &self.interceptors {
+            interceptor.on_error(&error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BoxError, Interceptor, Interceptors, OperationError, OperationInput};
+    use crate::body::SdkBody;
+    use crate::operation;
+    use crate::result::{ConnectorError, SdkError};
+    use std::error::Error;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug)]
+    struct AddHeader(&'static str, Arc<Mutex<Vec<&'static str>>>);
+
+    impl Interceptor for AddHeader {
+        fn before_transmit(&self, request: &mut operation::Request) -> Result<(), BoxError> {
+            self.1.lock().unwrap().push(self.0);
+            request
+                .http_mut()
+                .headers_mut()
+                .insert(self.0, "true".parse().unwrap());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct Fail;
+
+    impl Interceptor for Fail {
+        fn before_transmit(&self, _request: &mut operation::Request) -> Result<(), BoxError> {
+            Err("interceptor failed".into())
+        }
+    }
+
+    #[test]
+    fn interceptors_are_called_in_order_until_an_error() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut request = operation::Request::new(http::Request::new(SdkBody::empty()));
+
+        let mut interceptors = Interceptors::new().with_interceptor(AddHeader("a", calls.clone()));
+        interceptors.extend(&Interceptors::new().with_interceptor(AddHeader("b", calls.clone())));
+        interceptors.before_transmit(&mut request).unwrap();
+        assert_eq!(*calls.lock().unwrap(), vec!["a", "b"]);
+        assert!(request.http().headers().contains_key("b"));
+
+        let interceptors = Interceptors::new()
+            .with_interceptor(Fail)
+            .with_interceptor(AddHeader("c", calls.clone()));
+        let err = interceptors.before_transmit(&mut request).unwrap_err();
+        assert_eq!(err.to_string(), "interceptor failed");
+        assert_eq!(*calls.lock().unwrap(), vec!["a", "b"]);
+        // other hooks are no-ops by default
+        interceptors.before_signing(&mut request).unwrap();
+    }
+
+    #[derive(Debug)]
+    struct UppercaseInput;
+
+    impl Interceptor for UppercaseInput {
+        fn before_serialization(&self, input: &mut OperationInput<'_>) -> Result<(), BoxError> {
+            let type_name = input.type_name();
+            let input = input
+                .downcast_mut::<String>()
+                .ok_or_else(|| format!("unexpected input: {}", type_name))?;
+            *input = input.to_uppercase();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn inputs_can_be_modified_before_serialization() {
+        let interceptors = Interceptors::new().with_interceptor(UppercaseInput);
+
+        let mut input = String::from("input");
+        interceptors
+            .before_serialization(&mut OperationInput::new(&mut input))
+            .unwrap();
+        assert_eq!(input, "INPUT");
+
+        let err = interceptors
+            .before_serialization(&mut OperationInput::new(&mut 5_u32))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "unexpected input: u32");
+    }
+
+    #[derive(Debug)]
+    struct RecordError(Arc<Mutex<Vec<String>>>);
+
+    impl Interceptor for RecordError {
+        fn on_error(&self, error: &OperationError<'_>) {
+            let source = error.source().map(|source| source.to_string());
+            self.0.lock().unwrap().push(format!(
+                "{} / {:?} / {:?}",
+                error,
+                source,
+                error.raw_response().map(|raw| raw.http().status().as_u16())
+            ));
+        }
+    }
+
+    #[test]
+    fn errors_are_erased_for_on_error() {
+        // the modeled error doesn't need to implement `Error`
+        struct Unmodeled;
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let interceptors = Interceptors::new().with_interceptor(RecordError(errors.clone()));
+
+        let raw = operation::Response::new(
+            http::Response::builder()
+                .status(503)
+                .body(SdkBody::empty())
+                .unwrap(),
+        );
+        interceptors.on_error(&SdkError::ServiceError {
+            err: Unmodeled,
+            raw,
+        });
+        interceptors.on_error(&SdkError::<Unmodeled>::DispatchFailure(ConnectorError::io(
+            "connection reset".into(),
+        )));
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec![
+                "service error (HTTP 503) / None / Some(503)",
+                "dispatch failure: io error: connection reset / Some(\"io error: connection reset\") / None"
+            ]
+        );
+    }
+}
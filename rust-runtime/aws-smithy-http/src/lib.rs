@@ -22,6 +22,7 @@ pub mod callback;
 pub mod endpoint;
 pub mod header;
 pub mod http_versions;
+pub mod interceptor;
 pub mod label;
 pub mod middleware;
 pub mod operation;
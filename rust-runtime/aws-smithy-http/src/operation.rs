@@ -5,6 +5,7 @@
 
 use crate::body::SdkBody;
 use crate::property_bag::{PropertyBag, SharedPropertyBag};
+use crate::retry::RetryClassifierChain;
 use aws_smithy_types::date_time::DateTimeFormatError;
 use http::uri::InvalidUri;
 use std::borrow::Cow;
@@ -193,6 +194,10 @@ impl<H, R> Operation<H, R> {
         self
     }
 
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.parts.metadata.as_ref()
+    }
+
     pub fn with_retry_policy<R2>(self, retry_policy: R2) -> Operation<H, R2> {
         Operation {
             request: self.request,
@@ -204,6 +209,25 @@ impl<H, R> Operation<H, R> {
         }
     }
 
+    /// Add a retry classifier that takes precedence over this operation's current retry policy
+    ///
+    /// This can be used to retry additional responses (e.g. specific status codes or modeled
+    /// errors) for a single operation. See [`ClassifyRetry`](crate::retry::ClassifyRetry).
+    pub fn with_retry_classifier<C>(
+        self,
+        classifier: C,
+    ) -> Operation<H, RetryClassifierChain<R, C>> {
+        let retry_policy = RetryClassifierChain::new(self.parts.retry_policy, classifier);
+        Operation {
+            request: self.request,
+            parts: Parts {
+                response_handler: self.parts.response_handler,
+                retry_policy,
+                metadata: self.parts.metadata,
+            },
+        }
+    }
+
     pub fn retry_policy(&self) -> &R {
         &self.parts.retry_policy
     }
@@ -13,10 +13,12 @@
 //! `Result` wrapper types for [success](SdkSuccess) and [failure](SdkError) responses.
 
 use crate::operation;
-use aws_smithy_types::retry::ErrorKind;
+use aws_smithy_types::retry::{ErrorKind, RetryKind};
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 type BoxError = Box<dyn Error + Send + Sync>;
 
@@ -30,6 +32,13 @@ pub struct SdkSuccess<O> {
     pub parsed: O,
 }
 
+impl<O> SdkSuccess<O> {
+    /// Returns the history of the attempts made for this request, if it was recorded
+    pub fn attempt_history(&self) -> Option<AttemptHistory> {
+        self.raw.properties().get::<AttemptHistory>().cloned()
+    }
+}
+
 /// Failed SDK Result
 #[derive(Debug)]
 pub enum SdkError<E, R = operation::Response> {
@@ -59,8 +68,249 @@ pub enum SdkError<E, R = operation::Response> {
         /// Raw response from the service
         raw: R,
     },
+
+    /// The request was rejected by a circuit breaker because the endpoint is failing. It was not
+    /// dispatched over the network.
+    CircuitBreakerOpen(ConnectorError),
+}
+
+impl<E> SdkError<E> {
+    /// Returns the history of the attempts made for this request, if it was recorded
+    ///
+    /// The history is available from every error returned by the client except construction
+    /// failures, which happen before the request is dispatched. To access the history of those
+    /// requests, insert an [`AttemptHistory`] into the operation's property bag before sending it
+    /// and keep a clone of it.
+    pub fn attempt_history(&self) -> Option<AttemptHistory> {
+        match self {
+            SdkError::ResponseError { raw, .. } | SdkError::ServiceError { raw, .. } => {
+                raw.properties().get::<AttemptHistory>().cloned()
+            }
+            SdkError::DispatchFailure(err) | SdkError::CircuitBreakerOpen(err) => {
+                err.attempt_history.clone()
+            }
+            SdkError::TimeoutError(err) => err
+                .downcast_ref::<RequestTimeoutError>()?
+                .attempt_history
+                .clone(),
+            SdkError::ConstructionFailure(_) => None,
+        }
+    }
+}
+
+/// A single attempt made while sending a request
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attempt {
+    retry_kind: RetryKind,
+    status: Option<u16>,
+    error: Option<String>,
+    backoff: Option<Duration>,
 }
 
+impl Attempt {
+    /// Create an attempt from its result and how the result was classified for retries
+    pub fn new<T, E>(result: Result<&SdkSuccess<T>, &SdkError<E>>, retry_kind: RetryKind) -> Self {
+        let (status, error) = match result {
+            Ok(success) => (Some(success.raw.http().status().as_u16()), None),
+            Err(err) => {
+                let status = match err {
+                    SdkError::ResponseError { raw, .. } | SdkError::ServiceError { raw, .. } => {
+                        Some(raw.http().status().as_u16())
+                    }
+                    _ => None,
+                };
+                (status, Some(describe_error(err)))
+            }
+        };
+        Self {
+            retry_kind,
+            status,
+            error,
+            backoff: None,
+        }
+    }
+
+    /// Set how long the client backed off after this attempt before making the next attempt
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    /// How the result of this attempt was classified for retries
+    pub fn retry_kind(&self) -> &RetryKind {
+        &self.retry_kind
+    }
+
+    /// The HTTP status code of the response, if a response was received
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    /// A description of the error, if this attempt failed
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// How long the client backed off after this attempt, if it was retried
+    pub fn backoff(&self) -> Option<Duration> {
+        self.backoff
+    }
+}
+
+/// Describes an error without requiring the modeled error to implement `Display`
+pub(crate) fn describe_error<E>(err: &SdkError<E>) -> String {
+    match err {
+        SdkError::ConstructionFailure(err) => format!("failed to construct request: {}", err),
+        SdkError::TimeoutError(err) => format!("request has timed out: {}", err),
+        SdkError::DispatchFailure(err) => format!("dispatch failure: {}", err),
+        SdkError::CircuitBreakerOpen(err) => format!("request was not sent: {}", err),
+        SdkError::ResponseError { err, .. } => format!("response error: {}", err),
+        SdkError::ServiceError { raw, .. } => {
+            format!("service error (HTTP {})", raw.http().status().as_u16())
+        }
+    }
+}
+
+/// The history of the attempts made for a single request
+///
+/// The client records attempts into the `AttemptHistory` found in the operation's property bag.
+/// Clones of an `AttemptHistory` share the same underlying history.
+#[derive(Clone, Debug, Default)]
+pub struct AttemptHistory {
+    inner: Arc<Mutex<AttemptHistoryInner>>,
+}
+
+#[derive(Debug, Default)]
+struct AttemptHistoryInner {
+    attempts: Vec<Attempt>,
+    timeouts: Vec<String>,
+    rate_limit_delay: Duration,
+}
+
+impl AttemptHistory {
+    /// Create an empty attempt history
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a completed attempt
+    pub fn record_attempt(&self, attempt: Attempt) {
+        self.inner.lock().unwrap().attempts.push(attempt);
+    }
+
+    /// Record a timeout that cut a request or an attempt short
+    pub fn record_timeout(&self, description: impl Into<String>) {
+        self.inner.lock().unwrap().timeouts.push(description.into());
+    }
+
+    /// Returns the recorded attempts, in the order they were made
+    pub fn attempts(&self) -> Vec<Attempt> {
+        self.inner.lock().unwrap().attempts.clone()
+    }
+
+    /// Returns the number of completed attempts
+    pub fn attempt_count(&self) -> usize {
+        self.inner.lock().unwrap().attempts.len()
+    }
+
+    /// Record that a client-side rate limiter delayed an attempt by `delay`
+    pub fn record_rate_limit_delay(&self, delay: Duration) {
+        self.inner.lock().unwrap().rate_limit_delay += delay;
+    }
+
+    /// Returns the total time spent backing off between attempts
+    ///
+    /// This includes the time that attempts were delayed by the client-side rate limiter of the
+    /// adaptive retry mode.
+    pub fn total_backoff(&self) -> Duration {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .attempts
+            .iter()
+            .filter_map(Attempt::backoff)
+            .sum::<Duration>()
+            + inner.rate_limit_delay
+    }
+
+    /// Returns the errors of attempts that were retried
+    pub fn retried_errors(&self) -> Vec<String> {
+        self.inner
+            .lock()
+            .unwrap()
+            .attempts
+            .iter()
+            .filter(|attempt| attempt.backoff.is_some())
+            .filter_map(|attempt| attempt.error.clone())
+            .collect()
+    }
+
+    /// Returns descriptions of the timeouts that occurred
+    pub fn timeouts(&self) -> Vec<String> {
+        self.inner.lock().unwrap().timeouts.clone()
+    }
+}
+
+/// Error of a request or an attempt that took longer than its timeout
+///
+/// The client's timeouts fail requests with an [`SdkError::TimeoutError`] whose source is a
+/// `RequestTimeoutError`.
+pub struct RequestTimeoutError {
+    kind: &'static str,
+    duration: Duration,
+    attempt_history: Option<AttemptHistory>,
+}
+
+impl RequestTimeoutError {
+    /// Construct a [`RequestTimeoutError`] for a timeout of `kind` (e.g. `"API call (single
+    /// attempt)"`) that occurred after `duration`
+    pub fn new(kind: &'static str, duration: Duration) -> Self {
+        Self {
+            kind,
+            duration,
+            attempt_history: None,
+        }
+    }
+
+    /// Attach the history of the request that timed out
+    pub fn with_attempt_history(mut self, attempt_history: Option<AttemptHistory>) -> Self {
+        self.attempt_history = attempt_history;
+        self
+    }
+
+    /// The kind of timeout that occurred
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    /// The duration of the timeout
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+// The attempt history is left out to keep timeout errors concise
+impl Debug for RequestTimeoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestTimeoutError")
+            .field("kind", &self.kind)
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
+
+impl Display for RequestTimeoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} timeout occurred after {:?}",
+            self.kind, self.duration
+        )
+    }
+}
+
+impl Error for RequestTimeoutError {}
+
 /// Error from the underlying Connector
 ///
 /// Connector exists to attach a `ConnectorErrorKind` to what would otherwise be an opaque `Box<dyn Error>`
@@ -71,6 +321,7 @@ pub enum SdkError<E, R = operation::Response> {
 pub struct ConnectorError {
     err: BoxError,
     kind: ConnectorErrorKind,
+    attempt_history: Option<AttemptHistory>,
 }
 
 impl Display for ConnectorError {
@@ -93,6 +344,7 @@ impl ConnectorError {
         Self {
             err,
             kind: ConnectorErrorKind::Timeout,
+            attempt_history: None,
         }
     }
 
@@ -101,6 +353,7 @@ impl ConnectorError {
         Self {
             err,
             kind: ConnectorErrorKind::User,
+            attempt_history: None,
         }
     }
 
@@ -109,6 +362,7 @@ impl ConnectorError {
         Self {
             err,
             kind: ConnectorErrorKind::Io,
+            attempt_history: None,
         }
     }
 
@@ -119,6 +373,7 @@ impl ConnectorError {
         Self {
             err,
             kind: ConnectorErrorKind::Other(kind),
+            attempt_history: None,
         }
     }
 
@@ -137,6 +392,26 @@ impl ConnectorError {
         matches!(self.kind, ConnectorErrorKind::User)
     }
 
+    /// Construct a [`ConnectorError`] for a request that a circuit breaker refused to send
+    pub fn circuit_breaker_open(err: BoxError) -> Self {
+        Self {
+            err,
+            kind: ConnectorErrorKind::CircuitBreakerOpen,
+            attempt_history: None,
+        }
+    }
+
+    /// Attach the history of the request that failed to be dispatched
+    pub fn with_attempt_history(mut self, attempt_history: Option<AttemptHistory>) -> Self {
+        self.attempt_history = attempt_history;
+        self
+    }
+
+    /// Returns true if the request was refused by a circuit breaker
+    pub fn is_circuit_breaker_open(&self) -> bool {
+        matches!(self.kind, ConnectorErrorKind::CircuitBreakerOpen)
+    }
+
     /// Returns the optional error kind associated with an unclassified error
     pub fn is_other(&self) -> Option<ErrorKind> {
         match &self.kind {
@@ -157,6 +432,9 @@ enum ConnectorErrorKind {
     /// Socket/IO error
     Io,
 
+    /// The request was refused by a circuit breaker
+    CircuitBreakerOpen,
+
     /// An unclassified Error with an explicit error kind
     Other(Option<ErrorKind>),
 }
@@ -167,6 +445,7 @@ impl Display for ConnectorErrorKind {
             ConnectorErrorKind::Timeout => write!(f, "timeout"),
             ConnectorErrorKind::User => write!(f, "user error"),
             ConnectorErrorKind::Io => write!(f, "io error"),
+            ConnectorErrorKind::CircuitBreakerOpen => write!(f, "circuit breaker open"),
             ConnectorErrorKind::Other(Some(kind)) => write!(f, "{:?}", kind),
             ConnectorErrorKind::Other(None) => write!(f, "other"),
         }
@@ -181,7 +460,9 @@ where
         match self {
             SdkError::ConstructionFailure(err) => write!(f, "failed to construct request: {}", err),
             SdkError::TimeoutError(err) => write!(f, "request has timed out: {}", err),
-            SdkError::DispatchFailure(err) => Display::fmt(&err, f),
+            SdkError::DispatchFailure(err) | SdkError::CircuitBreakerOpen(err) => {
+                Display::fmt(&err, f)
+            }
             SdkError::ResponseError { err, .. } => Display::fmt(&err, f),
             SdkError::ServiceError { err, .. } => Display::fmt(&err, f),
         }
@@ -199,8 +480,113 @@ where
             ConstructionFailure(err) | TimeoutError(err) | ResponseError { err, .. } => {
                 Some(err.as_ref())
             }
-            DispatchFailure(err) => Some(err),
+            DispatchFailure(err) | CircuitBreakerOpen(err) => Some(err),
             ServiceError { err, .. } => Some(err),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Attempt, AttemptHistory, ConnectorError, RequestTimeoutError, SdkError, SdkSuccess,
+    };
+    use crate::body::SdkBody;
+    use crate::operation;
+    use aws_smithy_types::retry::{ErrorKind, RetryKind};
+    use std::time::Duration;
+
+    fn response(status: u16) -> operation::Response {
+        operation::Response::new(
+            http::Response::builder()
+                .status(status)
+                .body(SdkBody::empty())
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn attempt_history_summarizes_attempts() {
+        let history = AttemptHistory::new();
+        let throttled: SdkError<()> = SdkError::ServiceError {
+            err: (),
+            raw: response(429),
+        };
+        history.record_attempt(
+            Attempt::new::<(), _>(
+                Err(&throttled),
+                RetryKind::Error(ErrorKind::ThrottlingError),
+            )
+            .with_backoff(Duration::from_millis(100)),
+        );
+        let timeout: SdkError<()> = SdkError::TimeoutError("too slow".into());
+        history.record_attempt(
+            Attempt::new::<(), _>(Err(&timeout), RetryKind::Error(ErrorKind::TransientError))
+                .with_backoff(Duration::from_millis(200)),
+        );
+        let success = SdkSuccess {
+            raw: response(200),
+            parsed: (),
+        };
+        history.record_attempt(Attempt::new::<_, ()>(Ok(&success), RetryKind::Unnecessary));
+
+        assert_eq!(history.attempt_count(), 3);
+        assert_eq!(history.total_backoff(), Duration::from_millis(300));
+        assert_eq!(
+            history.retried_errors(),
+            vec![
+                "service error (HTTP 429)".to_string(),
+                "request has timed out: too slow".to_string()
+            ]
+        );
+        let attempts = history.attempts();
+        assert_eq!(attempts[0].status(), Some(429));
+        assert_eq!(attempts[1].status(), None);
+        assert_eq!(attempts[2].error(), None);
+        assert_eq!(attempts[2].retry_kind(), &RetryKind::Unnecessary);
+    }
+
+    #[test]
+    fn attempt_history_is_available_from_raw_response() {
+        let history = AttemptHistory::new();
+        let mut raw = response(500);
+        raw.properties_mut().insert(history.clone());
+        history.record_timeout("API call (single attempt)");
+
+        let err: SdkError<()> = SdkError::ServiceError { err: (), raw };
+        let from_err = err.attempt_history().expect("history was inserted");
+        assert_eq!(from_err.timeouts(), vec!["API call (single attempt)"]);
+        assert!(SdkError::<()>::TimeoutError("timeout".into())
+            .attempt_history()
+            .is_none());
+    }
+
+    #[test]
+    fn attempt_history_is_available_from_errors_without_response() {
+        let history = AttemptHistory::new();
+        history.record_timeout("API call (single attempt)");
+
+        let timeout = RequestTimeoutError::new("API call", Duration::from_secs(1))
+            .with_attempt_history(Some(history.clone()));
+        assert_eq!("API call timeout occurred after 1s", format!("{}", timeout));
+        let err: SdkError<()> = SdkError::TimeoutError(Box::new(timeout));
+        assert_eq!(err.attempt_history().unwrap().timeouts().len(), 1);
+
+        let err: SdkError<()> = SdkError::DispatchFailure(
+            ConnectorError::io("connection reset".into()).with_attempt_history(Some(history)),
+        );
+        assert_eq!(err.attempt_history().unwrap().timeouts().len(), 1);
+    }
+
+    #[test]
+    fn total_backoff_includes_rate_limit_delays() {
+        let history = AttemptHistory::new();
+        let err: SdkError<()> = SdkError::TimeoutError("too slow".into());
+        history.record_attempt(
+            Attempt::new::<(), _>(Err(&err), RetryKind::Error(ErrorKind::TransientError))
+                .with_backoff(Duration::from_millis(100)),
+        );
+        history.record_rate_limit_delay(Duration::from_millis(50));
+        assert_eq!(history.total_backoff(), Duration::from_millis(150));
+    }
+}
@@ -7,7 +7,10 @@
 //!
 //! For protocol agnostic retries, see `aws_smithy_types::Retry`.
 
-use aws_smithy_types::retry::RetryKind;
+use crate::operation;
+use crate::result::{SdkError, SdkSuccess};
+use aws_smithy_types::retry::{ErrorKind, ProvideErrorKind, RetryKind};
+use std::borrow::Cow;
 
 pub trait ClassifyResponse<T, E>: Clone {
     fn classify(&self, response: Result<&T, &E>) -> RetryKind;
@@ -18,3 +21,238 @@ impl<T, E> ClassifyResponse<T, E> for () {
         RetryKind::Unnecessary
     }
 }
+
+/// An additional retry classifier that can be composed with a [`ClassifyResponse`] implementation
+///
+/// Unlike [`ClassifyResponse`], a `ClassifyRetry` implementation only needs to have an opinion about
+/// the responses it cares about: returning `None` defers the decision to the next classifier.
+/// Classifiers are composed with [`RetryClassifierChain`], usually via
+/// [`Operation::with_retry_classifier`](crate::operation::Operation::with_retry_classifier).
+pub trait ClassifyRetry<T, E>: Clone {
+    /// Classify `response`, returning `None` if this classifier has no opinion about it
+    fn classify_retry(&self, response: Result<&T, &E>) -> Option<RetryKind>;
+}
+
+/// A [`ClassifyResponse`] implementation that consults an additional [`ClassifyRetry`] before
+/// falling back to the base classifier
+#[derive(Clone, Debug)]
+pub struct RetryClassifierChain<R, C> {
+    base: R,
+    classifier: C,
+}
+
+impl<R, C> RetryClassifierChain<R, C> {
+    /// Create a new chain where `classifier` takes precedence over `base`
+    pub fn new(base: R, classifier: C) -> Self {
+        Self { base, classifier }
+    }
+}
+
+impl<T, E, R, C> ClassifyResponse<T, E> for RetryClassifierChain<R, C>
+where
+    R: ClassifyResponse<T, E>,
+    C: ClassifyRetry<T, E>,
+{
+    fn classify(&self, response: Result<&T, &E>) -> RetryKind {
+        self.classifier
+            .classify_retry(response)
+            .unwrap_or_else(|| self.base.classify(response))
+    }
+}
+
+/// Returns the raw response for an attempt, if one was received
+pub fn raw_response<'a, T, E>(
+    response: Result<&'a SdkSuccess<T>, &'a SdkError<E>>,
+) -> Option<&'a operation::Response> {
+    match response {
+        Ok(success) => Some(&success.raw),
+        Err(SdkError::ServiceError { raw, .. }) | Err(SdkError::ResponseError { raw, .. }) => {
+            Some(raw)
+        }
+        Err(_) => None,
+    }
+}
+
+/// Retry classifier that retries responses with specific HTTP status codes
+///
+/// # Examples
+/// ```rust
+/// use aws_smithy_http::retry::HttpStatusCodeClassifier;
+/// use aws_smithy_types::retry::ErrorKind;
+/// // retry conflicts as if they were throttling errors
+/// let classifier = HttpStatusCodeClassifier::new().retry_on(409, ErrorKind::ThrottlingError);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HttpStatusCodeClassifier {
+    status_codes: Vec<(u16, ErrorKind)>,
+}
+
+impl HttpStatusCodeClassifier {
+    /// Create a classifier that doesn't retry any status codes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retry responses with the given `status_code`, treating them as `kind`
+    pub fn retry_on(mut self, status_code: u16, kind: ErrorKind) -> Self {
+        self.status_codes.push((status_code, kind));
+        self
+    }
+
+    /// Classify a raw HTTP response, returning `None` if its status code wasn't registered
+    pub fn classify_raw(&self, response: &operation::Response) -> Option<RetryKind> {
+        let status = response.http().status().as_u16();
+        self.status_codes
+            .iter()
+            .find(|(code, _)| *code == status)
+            .map(|(_, kind)| RetryKind::Error(*kind))
+    }
+}
+
+impl<T, E> ClassifyRetry<SdkSuccess<T>, SdkError<E>> for HttpStatusCodeClassifier {
+    fn classify_retry(&self, response: Result<&SdkSuccess<T>, &SdkError<E>>) -> Option<RetryKind> {
+        match response {
+            // successfully parsed responses are never retried
+            Ok(_) => None,
+            Err(_) => self.classify_raw(raw_response(response)?),
+        }
+    }
+}
+
+/// Retry classifier that retries modeled errors with specific error codes
+///
+/// # Examples
+/// ```rust
+/// use aws_smithy_http::retry::ErrorCodeClassifier;
+/// use aws_smithy_types::retry::ErrorKind;
+/// let classifier = ErrorCodeClassifier::new()
+///     .retry_on("ResourceNotReady", ErrorKind::ServerError);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ErrorCodeClassifier {
+    error_codes: Vec<(Cow<'static, str>, ErrorKind)>,
+}
+
+impl ErrorCodeClassifier {
+    /// Create a classifier that doesn't retry any error codes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retry errors with the given `code`, treating them as `kind`
+    pub fn retry_on(mut self, code: impl Into<Cow<'static, str>>, kind: ErrorKind) -> Self {
+        self.error_codes.push((code.into(), kind));
+        self
+    }
+}
+
+impl<T, E> ClassifyRetry<SdkSuccess<T>, SdkError<E>> for ErrorCodeClassifier
+where
+    E: ProvideErrorKind,
+{
+    fn classify_retry(&self, response: Result<&SdkSuccess<T>, &SdkError<E>>) -> Option<RetryKind> {
+        let code = match response {
+            Err(SdkError::ServiceError { err, .. }) => err.code()?,
+            _ => return None,
+        };
+        self.error_codes
+            .iter()
+            .find(|(expected, _)| expected == code)
+            .map(|(_, kind)| RetryKind::Error(*kind))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClassifyResponse, ErrorCodeClassifier, HttpStatusCodeClassifier};
+    use crate::body::SdkBody;
+    use crate::operation;
+    use crate::result::{SdkError, SdkSuccess};
+    use crate::retry::RetryClassifierChain;
+    use aws_smithy_types::retry::{ErrorKind, ProvideErrorKind, RetryKind};
+
+    #[derive(Debug)]
+    struct CodedError(&'static str);
+
+    impl ProvideErrorKind for CodedError {
+        fn retryable_error_kind(&self) -> Option<ErrorKind> {
+            None
+        }
+
+        fn code(&self) -> Option<&str> {
+            Some(self.0)
+        }
+    }
+
+    fn service_error(status: u16, code: &'static str) -> SdkError<CodedError> {
+        SdkError::ServiceError {
+            err: CodedError(code),
+            raw: operation::Response::new(
+                http::Response::builder()
+                    .status(status)
+                    .body(SdkBody::empty())
+                    .unwrap(),
+            ),
+        }
+    }
+
+    #[derive(Clone)]
+    struct NeverRetry;
+
+    impl<T, E> ClassifyResponse<T, E> for NeverRetry {
+        fn classify(&self, response: Result<&T, &E>) -> RetryKind {
+            match response {
+                Ok(_) => RetryKind::Unnecessary,
+                Err(_) => RetryKind::UnretryableFailure,
+            }
+        }
+    }
+
+    #[test]
+    fn status_code_classifier_overrides_base_classifier() {
+        let chain = RetryClassifierChain::new(
+            NeverRetry,
+            HttpStatusCodeClassifier::new().retry_on(409, ErrorKind::ThrottlingError),
+        );
+        let classify = |err: &SdkError<CodedError>| {
+            ClassifyResponse::<SdkSuccess<()>, _>::classify(&chain, Err(err))
+        };
+        assert_eq!(
+            classify(&service_error(409, "Conflict")),
+            RetryKind::Error(ErrorKind::ThrottlingError)
+        );
+        assert_eq!(
+            classify(&service_error(400, "Conflict")),
+            RetryKind::UnretryableFailure
+        );
+    }
+
+    #[test]
+    fn error_code_classifier_overrides_base_classifier() {
+        let chain = RetryClassifierChain::new(
+            NeverRetry,
+            ErrorCodeClassifier::new().retry_on("ResourceNotReady", ErrorKind::ServerError),
+        );
+        let classify = |err: &SdkError<CodedError>| {
+            ClassifyResponse::<SdkSuccess<()>, _>::classify(&chain, Err(err))
+        };
+        assert_eq!(
+            classify(&service_error(400, "ResourceNotReady")),
+            RetryKind::Error(ErrorKind::ServerError)
+        );
+        assert_eq!(
+            classify(&service_error(400, "ValidationError")),
+            RetryKind::UnretryableFailure
+        );
+        assert_eq!(
+            ClassifyResponse::<SdkSuccess<()>, SdkError<CodedError>>::classify(
+                &chain,
+                Ok(&SdkSuccess {
+                    raw: operation::Response::new(http::Response::new(SdkBody::empty())),
+                    parsed: ()
+                })
+            ),
+            RetryKind::Unnecessary
+        );
+    }
+}
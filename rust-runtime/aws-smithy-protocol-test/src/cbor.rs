@@ -0,0 +1,184 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::{pretty_comparison, ProtocolTestFailure};
+use ciborium::value::Value;
+use std::fmt::Write;
+
+/// Compares a CBOR body with the base64-encoded CBOR from a protocol test
+///
+/// Both bodies are decoded and rendered in a diagnostic notation where map entries are sorted, so
+/// the comparison ignores map ordering and the difference between definite and indefinite length
+/// items.
+pub(crate) fn try_cbor_eq(actual: &[u8], expected: &str) -> Result<(), ProtocolTestFailure> {
+    let invalid_expected = |found: String| ProtocolTestFailure::InvalidBodyFormat {
+        expected: "base64-encoded CBOR in the expected body".to_owned(),
+        found,
+    };
+    let expected =
+        aws_smithy_types::base64::decode(expected).map_err(|e| invalid_expected(e.to_string()))?;
+    let expected: Value = ciborium::de::from_reader(expected.as_slice())
+        .map_err(|e| invalid_expected(e.to_string()))?;
+    let actual: Value =
+        ciborium::de::from_reader(actual).map_err(|e| ProtocolTestFailure::InvalidBodyFormat {
+            expected: "cbor".to_owned(),
+            found: e.to_string(),
+        })?;
+    let (actual, expected) = (diagnostic(&actual), diagnostic(&expected));
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(ProtocolTestFailure::BodyDidNotMatch {
+            comparison: pretty_comparison(&actual, &expected),
+            hint: "media type: application/cbor".into(),
+        })
+    }
+}
+
+/// Renders a CBOR value on multiple lines, with map entries sorted by key
+pub(crate) fn diagnostic(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, 0);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Integer(int) => write!(out, "{}", i128::from(*int)).unwrap(),
+        Value::Bytes(bytes) => write_bytes(out, bytes),
+        Value::Float(float) if float.is_nan() => out.push_str("NaN"),
+        Value::Float(float) => write!(out, "{:?}", float).unwrap(),
+        Value::Text(text) => out.push_str(&serde_json::to_string(text).unwrap()),
+        Value::Bool(b) => write!(out, "{}", b).unwrap(),
+        Value::Null => out.push_str("null"),
+        Value::Tag(tag, value) => {
+            write!(out, "{}(", tag).unwrap();
+            write_value(out, value, indent);
+            out.push(')');
+        }
+        Value::Array(items) => {
+            let items: Vec<_> = items
+                .iter()
+                .map(|item| {
+                    let mut out = String::new();
+                    write_value(&mut out, item, indent + 1);
+                    out
+                })
+                .collect();
+            write_items(out, "[", items, "]", indent);
+        }
+        Value::Map(entries) => {
+            let mut entries: Vec<_> = entries
+                .iter()
+                .map(|(key, value)| {
+                    let mut out = String::new();
+                    write_value(&mut out, key, indent + 1);
+                    out.push_str(": ");
+                    write_value(&mut out, value, indent + 1);
+                    out
+                })
+                .collect();
+            entries.sort();
+            write_items(out, "{", entries, "}", indent);
+        }
+        other => write!(out, "{:?}", other).unwrap(),
+    }
+}
+
+pub(crate) fn write_bytes(out: &mut String, bytes: &[u8]) {
+    out.push_str("h'");
+    for byte in bytes {
+        write!(out, "{:02x}", byte).unwrap();
+    }
+    out.push('\'');
+}
+
+fn write_items(out: &mut String, open: &str, items: Vec<String>, close: &str, indent: usize) {
+    out.push_str(open);
+    if !items.is_empty() {
+        for item in items {
+            out.push('\n');
+            out.push_str(&"  ".repeat(indent + 1));
+            out.push_str(&item);
+            out.push(',');
+        }
+        out.push('\n');
+        out.push_str(&"  ".repeat(indent));
+    }
+    out.push_str(close);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{validate_body, validate_cbor_body, MediaType, ProtocolTestFailure};
+    use ciborium::value::Value;
+
+    fn encode(value: &Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        ciborium::ser::into_writer(value, &mut out).unwrap();
+        out
+    }
+
+    fn map(entries: Vec<(&str, Value)>) -> Value {
+        Value::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (Value::Text(key.to_string()), value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn cbor_maps_are_compared_regardless_of_order() {
+        let actual = map(vec![
+            ("string", Value::Text("hello".into())),
+            ("list", Value::Array(vec![Value::Integer(1.into())])),
+        ]);
+        let expected = map(vec![
+            ("list", Value::Array(vec![Value::Integer(1.into())])),
+            ("string", Value::Text("hello".into())),
+        ]);
+        let expected = aws_smithy_types::base64::encode(encode(&expected));
+        validate_cbor_body(encode(&actual), &expected).expect("bodies are equivalent");
+    }
+
+    #[test]
+    fn cbor_media_type_is_compared_literally() {
+        let body = encode(&Value::Text("hello".into()));
+        let expected = aws_smithy_types::base64::encode(&body);
+        // `validate_body` doesn't decode the expected body of `application/cbor` requests
+        validate_body(
+            expected.as_bytes(),
+            &expected,
+            MediaType::from("application/cbor"),
+        )
+        .expect("bodies are identical");
+    }
+
+    #[test]
+    fn cbor_differences_are_readable() {
+        let actual = map(vec![
+            ("blob", Value::Bytes(vec![0xca, 0xfe])),
+            ("float", Value::Float(1.5)),
+        ]);
+        let expected = map(vec![
+            ("blob", Value::Bytes(vec![0xca, 0xfe])),
+            ("float", Value::Float(2.5)),
+        ]);
+        let expected = aws_smithy_types::base64::encode(encode(&expected));
+        let err = validate_cbor_body(encode(&actual), &expected).expect_err("bodies are different");
+        // changed lines are colorized, but unchanged lines are printed as-is
+        let message = format!("{}", err);
+        assert!(message.contains("\"blob\": h'cafe',"), "{}", message);
+        assert!(
+            matches!(err, ProtocolTestFailure::BodyDidNotMatch { hint, .. } if hint == "media type: application/cbor")
+        );
+
+        validate_cbor_body(b"\xff", &expected).expect_err("invalid CBOR");
+        let err =
+            validate_cbor_body(encode(&actual), "not base64!").expect_err("invalid expected body");
+        assert!(matches!(err, ProtocolTestFailure::InvalidBodyFormat { .. }));
+    }
+}
@@ -0,0 +1,194 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::cbor::{diagnostic, write_bytes};
+use crate::{pretty_comparison, ProtocolTestFailure};
+use aws_smithy_eventstream::frame::{HeaderValue, Message};
+use std::fmt::Write;
+
+/// Compares an event stream body with the base64-encoded event stream from a protocol test
+///
+/// Both bodies are decoded into messages, and each message is rendered with its headers sorted by
+/// name. Payloads with a JSON or CBOR `:content-type` are compared semantically; other payloads
+/// are compared as text or, if they aren't valid UTF-8, as bytes.
+pub(crate) fn try_event_stream_eq(
+    actual: &[u8],
+    expected: &str,
+) -> Result<(), ProtocolTestFailure> {
+    let invalid_expected = |found: String| ProtocolTestFailure::InvalidBodyFormat {
+        expected: "base64-encoded event stream in the expected body".to_owned(),
+        found,
+    };
+    let expected =
+        aws_smithy_types::base64::decode(expected).map_err(|e| invalid_expected(e.to_string()))?;
+    let expected = read_messages(&expected).map_err(invalid_expected)?;
+    let actual = read_messages(actual).map_err(|e| ProtocolTestFailure::InvalidBodyFormat {
+        expected: "event stream".to_owned(),
+        found: e,
+    })?;
+    let (actual, expected) = (render_messages(&actual), render_messages(&expected));
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(ProtocolTestFailure::BodyDidNotMatch {
+            comparison: pretty_comparison(&actual, &expected),
+            hint: "media type: application/vnd.amazon.eventstream".into(),
+        })
+    }
+}
+
+fn read_messages(mut body: &[u8]) -> Result<Vec<Message>, String> {
+    let mut messages = Vec::new();
+    while !body.is_empty() {
+        let message = Message::read_from(&mut body)
+            .map_err(|e| format!("invalid message {}: {}", messages.len(), e))?;
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+fn render_messages(messages: &[Message]) -> String {
+    let mut out = String::new();
+    for (index, message) in messages.iter().enumerate() {
+        writeln!(out, "message {} {{", index).unwrap();
+        let mut headers: Vec<_> = message
+            .headers()
+            .iter()
+            .map(|header| {
+                format!(
+                    "{}: {}",
+                    header.name().as_str(),
+                    render_header(header.value())
+                )
+            })
+            .collect();
+        headers.sort();
+        for header in headers {
+            writeln!(out, "  {}", header).unwrap();
+        }
+        let content_type = message
+            .headers()
+            .iter()
+            .find(|header| header.name().as_str() == ":content-type")
+            .and_then(|header| header.value().as_string().ok())
+            .map(|content_type| content_type.as_str());
+        writeln!(
+            out,
+            "  payload: {}",
+            render_payload(message.payload(), content_type).replace('\n', "\n  ")
+        )
+        .unwrap();
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn render_header(value: &HeaderValue) -> String {
+    match value {
+        HeaderValue::Bool(b) => format!("bool({})", b),
+        HeaderValue::Byte(b) => format!("byte({})", b),
+        HeaderValue::Int16(i) => format!("int16({})", i),
+        HeaderValue::Int32(i) => format!("int32({})", i),
+        HeaderValue::Int64(i) => format!("int64({})", i),
+        HeaderValue::ByteArray(bytes) => {
+            let mut out = String::new();
+            write_bytes(&mut out, bytes);
+            format!("byte_array({})", out)
+        }
+        HeaderValue::String(s) => format!("string({:?})", s.as_str()),
+        HeaderValue::Timestamp(ts) => format!("timestamp({:?})", ts),
+        HeaderValue::Uuid(uuid) => format!("uuid({:032x})", uuid),
+        other => format!("{:?}", other),
+    }
+}
+
+fn render_payload(payload: &[u8], content_type: Option<&str>) -> String {
+    let decoded = match content_type {
+        Some(content_type) if content_type.contains("json") => {
+            serde_json::from_slice::<serde_json::Value>(payload)
+                .ok()
+                .and_then(|json| ciborium::value::Value::serialized(&json).ok())
+        }
+        Some("application/cbor") => ciborium::de::from_reader(payload).ok(),
+        _ => None,
+    };
+    match (decoded, std::str::from_utf8(payload)) {
+        (Some(value), _) => diagnostic(&value),
+        (None, Ok(text)) => format!("{:?}", text),
+        (None, Err(_)) => {
+            let mut out = String::new();
+            write_bytes(&mut out, payload);
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{validate_event_stream_body, ProtocolTestFailure};
+    use aws_smithy_eventstream::frame::{Header, HeaderValue, Message};
+
+    fn encode(messages: &[Message]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for message in messages {
+            message.write_to(&mut out).unwrap();
+        }
+        out
+    }
+
+    fn event(headers: Vec<(&'static str, &'static str)>, payload: &'static str) -> Message {
+        Message::new_from_parts(
+            headers
+                .into_iter()
+                .map(|(name, value)| Header::new(name, HeaderValue::String(value.into())))
+                .collect(),
+            payload,
+        )
+    }
+
+    #[test]
+    fn event_streams_are_compared_semantically() {
+        let actual = encode(&[event(
+            vec![
+                (":event-type", "Greeting"),
+                (":content-type", "application/json"),
+            ],
+            r#"{"hello": "world", "count": 1}"#,
+        )]);
+        let expected = encode(&[event(
+            vec![
+                (":content-type", "application/json"),
+                (":event-type", "Greeting"),
+            ],
+            r#"{"count":1,"hello":"world"}"#,
+        )]);
+        let expected = aws_smithy_types::base64::encode(expected);
+        validate_event_stream_body(actual, &expected).expect("event streams are equivalent");
+    }
+
+    #[test]
+    fn event_stream_differences_are_readable() {
+        let actual = encode(&[
+            event(vec![(":event-type", "A")], "first"),
+            event(vec![(":event-type", "B")], "second"),
+        ]);
+        let expected = encode(&[
+            event(vec![(":event-type", "A")], "first"),
+            event(vec![(":event-type", "C")], "second"),
+        ]);
+        let expected = aws_smithy_types::base64::encode(expected);
+        let err =
+            validate_event_stream_body(&actual, &expected).expect_err("event types are different");
+        // changed lines are colorized, but unchanged lines are printed as-is
+        let message = format!("{}", err);
+        assert!(message.contains(r#"payload: "second""#), "{}", message);
+        assert!(matches!(err, ProtocolTestFailure::BodyDidNotMatch { .. }));
+
+        validate_event_stream_body(&actual[..10], &expected).expect_err("truncated event stream");
+        let err = validate_event_stream_body(&actual, &aws_smithy_types::base64::encode("event"))
+            .expect_err("invalid expected body");
+        assert!(matches!(err, ProtocolTestFailure::InvalidBodyFormat { .. }));
+    }
+}
@@ -0,0 +1,218 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::{pretty_comparison, ProtocolTestFailure};
+use assert_json_diff::assert_json_eq_no_panic;
+use serde_json::{Number, Value};
+
+/// Options for comparing JSON bodies with [`validate_json_body`](crate::validate_json_body)
+///
+/// By default, JSON bodies are compared exactly, like [`validate_body`](crate::validate_body)
+/// does. [`tolerant`](JsonComparison::tolerant) creates options that normalize numbers.
+#[derive(Clone, Debug, Default)]
+pub struct JsonComparison {
+    normalize_numbers: bool,
+    ignored_paths: Vec<String>,
+}
+
+impl JsonComparison {
+    /// Compare JSON values exactly
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare JSON values with numbers normalized
+    pub fn tolerant() -> Self {
+        Self::new().normalize_numbers(true)
+    }
+
+    /// Normalize numeric representations before comparing
+    ///
+    /// When enabled, integral floats are equal to the corresponding integers (`1.0` equals `1`,
+    /// so epoch seconds match whether they are written as floats or integers). Where the expected
+    /// value is `"NaN"`, `"Infinity"` or `"-Infinity"`, the strings Smithy serializes those floats
+    /// as, alternate spellings such as `"nan"`, `"inf"` and `"+Infinity"` are accepted. Other
+    /// strings are always compared exactly.
+    pub fn normalize_numbers(mut self, normalize: bool) -> Self {
+        self.normalize_numbers = normalize;
+        self
+    }
+
+    /// Ignore the value at the JSON pointer `path`, e.g. `/Item/UpdatedAt` or `/Items/0/Id`
+    ///
+    /// Ignored object members are removed from both values before comparing. Ignored array
+    /// elements are replaced with `null`, so that other elements keep their position.
+    pub fn ignore_path(mut self, path: impl Into<String>) -> Self {
+        self.ignored_paths.push(path.into());
+        self
+    }
+
+    fn normalize(&self, mut value: Value) -> Value {
+        for path in &self.ignored_paths {
+            remove_path(&mut value, path);
+        }
+        if self.normalize_numbers {
+            normalize_numbers(&mut value);
+        }
+        value
+    }
+
+    fn normalize_pair(&self, actual: Value, expected: Value) -> (Value, Value) {
+        let (mut actual, expected) = (self.normalize(actual), self.normalize(expected));
+        if self.normalize_numbers {
+            normalize_special_floats(&mut actual, &expected);
+        }
+        (actual, expected)
+    }
+}
+
+fn remove_path(value: &mut Value, path: &str) {
+    let (parent, key) = match path.rsplit_once('/') {
+        Some(split) => split,
+        None => return,
+    };
+    let key = key.replace("~1", "/").replace("~0", "~");
+    match value.pointer_mut(parent) {
+        Some(Value::Object(members)) => {
+            members.remove(&key);
+        }
+        Some(Value::Array(items)) => {
+            if let Some(item) = key.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                *item = Value::Null;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn normalize_numbers(value: &mut Value) {
+    match value {
+        Value::Number(number) => {
+            if let Some(float) = number.as_f64().filter(|_| number.is_f64()) {
+                if float.fract() == 0.0 && float.abs() < i64::MAX as f64 {
+                    *number = Number::from(float as i64);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(normalize_numbers),
+        Value::Object(members) => members.values_mut().for_each(normalize_numbers),
+        Value::Null | Value::Bool(_) | Value::String(_) => {}
+    }
+}
+
+/// Rewrite alternate spellings of special floats in `actual` where `expected` has the canonical one
+fn normalize_special_floats(actual: &mut Value, expected: &Value) {
+    match (actual, expected) {
+        (Value::String(actual), Value::String(expected)) => {
+            let canonical = match actual.to_ascii_lowercase().as_str() {
+                "nan" => "NaN",
+                "infinity" | "+infinity" | "inf" | "+inf" => "Infinity",
+                "-infinity" | "-inf" => "-Infinity",
+                _ => return,
+            };
+            if expected == canonical {
+                *actual = canonical.to_string();
+            }
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            for (actual, expected) in actual.iter_mut().zip(expected) {
+                normalize_special_floats(actual, expected);
+            }
+        }
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (name, actual) in actual.iter_mut() {
+                if let Some(expected) = expected.get(name) {
+                    normalize_special_floats(actual, expected);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn try_json_eq_with(
+    actual: &str,
+    expected: &str,
+    comparison: &JsonComparison,
+) -> Result<(), ProtocolTestFailure> {
+    let actual_json: Value =
+        serde_json::from_str(actual).map_err(|e| ProtocolTestFailure::InvalidBodyFormat {
+            expected: "json".to_owned(),
+            found: e.to_string() + actual,
+        })?;
+    let expected_json: Value =
+        serde_json::from_str(expected).expect("expected value must be valid JSON");
+    let (actual_json, expected_json) = comparison.normalize_pair(actual_json, expected_json);
+    match assert_json_eq_no_panic(&actual_json, &expected_json) {
+        Ok(()) => Ok(()),
+        Err(message) => Err(ProtocolTestFailure::BodyDidNotMatch {
+            comparison: pretty_comparison(
+                &serde_json::to_string_pretty(&actual_json).unwrap(),
+                &serde_json::to_string_pretty(&expected_json).unwrap(),
+            ),
+            hint: message,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{validate_json_body, JsonComparison};
+
+    #[test]
+    fn exact_comparison_is_the_default() {
+        validate_json_body(r#"{"a": 1.0}"#, r#"{"a": 1}"#, &JsonComparison::new())
+            .expect_err("numbers are compared exactly");
+        validate_json_body(r#"{"a": 1}"#, r#"{"a": 1}"#, &JsonComparison::new())
+            .expect("values are equal");
+    }
+
+    #[test]
+    fn tolerant_comparison_normalizes_numbers() {
+        let tolerant = JsonComparison::tolerant();
+        validate_json_body(
+            r#"{"epoch": 1515531081.0, "list": [2.0, "inf", "nan"]}"#,
+            r#"{"epoch": 1515531081, "list": [2, "Infinity", "NaN"]}"#,
+            &tolerant,
+        )
+        .expect("values are equal once normalized");
+        validate_json_body(r#"{"a": 1.5}"#, r#"{"a": 1}"#, &tolerant)
+            .expect_err("values are different");
+        validate_json_body(r#"{"a": "-Infinity"}"#, r#"{"a": "Infinity"}"#, &tolerant)
+            .expect_err("values are different");
+    }
+
+    #[test]
+    fn only_canonical_special_floats_accept_alternate_spellings() {
+        let tolerant = JsonComparison::tolerant();
+        // strings that happen to spell a special float are compared exactly
+        validate_json_body(r#"{"name": "nan"}"#, r#"{"name": "Nan"}"#, &tolerant)
+            .expect_err("values are different");
+        validate_json_body(r#"{"name": "Inf"}"#, r#"{"name": "inf"}"#, &tolerant)
+            .expect_err("values are different");
+        validate_json_body(r#"{"name": "nan"}"#, r#"{"name": "nan"}"#, &tolerant)
+            .expect("values are equal");
+    }
+
+    #[test]
+    fn ignored_paths_are_not_compared() {
+        let comparison = JsonComparison::new()
+            .ignore_path("/Item/UpdatedAt")
+            .ignore_path("/Items/0")
+            .ignore_path("/a~1b");
+        validate_json_body(
+            r#"{"Item": {"Id": 1, "UpdatedAt": 10}, "Items": [1, 2], "a/b": true}"#,
+            r#"{"Item": {"Id": 1}, "Items": [3, 2]}"#,
+            &comparison,
+        )
+        .expect("only ignored values differ");
+        validate_json_body(
+            r#"{"Item": {"Id": 2, "UpdatedAt": 10}, "Items": [1, 2]}"#,
+            r#"{"Item": {"Id": 1}, "Items": [3, 2]}"#,
+            &comparison,
+        )
+        .expect_err("Id differs");
+    }
+}
@@ -3,9 +3,15 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+mod cbor;
+mod eventstream;
+mod json;
 mod urlencoded;
 mod xml;
 
+use crate::cbor::try_cbor_eq;
+use crate::eventstream::try_event_stream_eq;
+use crate::json::try_json_eq_with;
 use crate::xml::try_xml_equivalent;
 use assert_json_diff::assert_json_eq_no_panic;
 use http::{header::HeaderMap, Request, Uri};
@@ -15,6 +21,8 @@ use std::fmt::{self, Debug};
 use thiserror::Error;
 use urlencoded::try_url_encoded_form_equivalent;
 
+pub use json::JsonComparison;
+
 /// Helper trait for tests for float comparisons
 ///
 /// This trait differs in float's default `PartialEq` implementation by considering all `NaN` values to
@@ -334,6 +342,42 @@ pub fn validate_body<T: AsRef<[u8]>>(
     }
 }
 
+/// Validate a JSON body, using `comparison` to decide which differences are tolerated
+pub fn validate_json_body<T: AsRef<[u8]>>(
+    actual_body: T,
+    expected_body: &str,
+    comparison: &JsonComparison,
+) -> Result<(), ProtocolTestFailure> {
+    match std::str::from_utf8(actual_body.as_ref()) {
+        Ok(actual_body) => try_json_eq_with(actual_body, expected_body, comparison),
+        Err(_) => Err(ProtocolTestFailure::InvalidBodyFormat {
+            expected: "json".to_owned(),
+            found: "input was not valid UTF-8".to_owned(),
+        }),
+    }
+}
+
+/// Validate a CBOR body against the base64-encoded CBOR of a protocol test
+///
+/// Both bodies are decoded before they're compared, so map ordering is ignored.
+pub fn validate_cbor_body<T: AsRef<[u8]>>(
+    actual_body: T,
+    expected_body: &str,
+) -> Result<(), ProtocolTestFailure> {
+    try_cbor_eq(actual_body.as_ref(), expected_body)
+}
+
+/// Validate an event stream body against the base64-encoded event stream of a protocol test
+///
+/// Both bodies are decoded into messages before they're compared, so header ordering is ignored
+/// and JSON or CBOR payloads are compared semantically.
+pub fn validate_event_stream_body<T: AsRef<[u8]>>(
+    actual_body: T,
+    expected_body: &str,
+) -> Result<(), ProtocolTestFailure> {
+    try_event_stream_eq(actual_body.as_ref(), expected_body)
+}
+
 #[derive(Eq, PartialEq)]
 struct PrettyStr<'a>(&'a str);
 impl Debug for PrettyStr<'_> {
@@ -11,6 +11,10 @@ use aws_smithy_types::{DateTime, Number};
 use std::borrow::Cow;
 use urlencoding::encode;
 
+mod reader;
+
+pub use reader::{QueryError, QueryReader, QueryValueReader};
+
 pub struct QueryWriter<'a> {
     output: &'a mut String,
 }
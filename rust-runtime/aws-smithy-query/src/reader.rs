@@ -0,0 +1,335 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::primitive::Parse;
+use aws_smithy_types::DateTime;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::Bound;
+
+/// An error reading the parameters of a Query request.
+#[derive(Debug)]
+pub enum QueryError {
+    /// A parameter name or value is not valid percent-encoded UTF-8.
+    InvalidEncoding(String),
+    /// A parameter has a value that is not valid for its type.
+    InvalidValue {
+        name: String,
+        message: Cow<'static, str>,
+    },
+    Custom(Cow<'static, str>),
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::InvalidEncoding(param) => write!(f, "Invalid query encoding: {}", param),
+            QueryError::InvalidValue { name, message } => {
+                write!(
+                    f,
+                    "Invalid value for query parameter `{}`: {}",
+                    name, message
+                )
+            }
+            QueryError::Custom(msg) => write!(f, "Error parsing query: {}", msg),
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+impl QueryError {
+    pub fn custom(msg: impl Into<Cow<'static, str>>) -> Self {
+        QueryError::Custom(msg.into())
+    }
+
+    fn invalid_value(name: &str, message: impl Into<Cow<'static, str>>) -> Self {
+        QueryError::InvalidValue {
+            name: name.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Reads the parameters of a form-encoded AWS Query or EC2 Query request.
+///
+/// This is the inverse of [`QueryWriter`](crate::QueryWriter): values are looked up by the same
+/// dotted prefixes that the writer produces. The parameters are indexed by name when they are
+/// decoded, so looking up a value doesn't scan every parameter.
+#[derive(Debug)]
+pub struct QueryReader {
+    params: BTreeMap<String, String>,
+}
+
+impl QueryReader {
+    /// Decodes `input`, e.g. `Action=SomeAction&Version=1.0&ListArg.member.1=foo`.
+    ///
+    /// If a parameter is set more than once, its first value is used.
+    pub fn new(input: &str) -> Result<Self, QueryError> {
+        let mut params = BTreeMap::new();
+        for param in input.split('&').filter(|param| !param.is_empty()) {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            params.entry(decode(name)?).or_insert(decode(value)?);
+        }
+        Ok(QueryReader { params })
+    }
+
+    /// Returns the value of the `Action` parameter, which names the operation.
+    pub fn action(&self) -> Option<&str> {
+        self.prefix("Action").string()
+    }
+
+    /// Returns the value of the `Version` parameter, which is the version of the service.
+    pub fn version(&self) -> Option<&str> {
+        self.prefix("Version").string()
+    }
+
+    pub fn prefix(&self, prefix: &str) -> QueryValueReader<'_> {
+        QueryValueReader::new(&self.params, prefix.to_string())
+    }
+}
+
+fn decode(input: &str) -> Result<String, QueryError> {
+    // Form encoding encodes spaces as `+`, which percent-decoding leaves as is
+    let input = input.replace('+', " ");
+    urlencoding::decode(&input)
+        .map(Cow::into_owned)
+        .map_err(|_| QueryError::InvalidEncoding(input.clone()))
+}
+
+/// Reads the value at a prefix, e.g. `ListArg.member.1`.
+#[derive(Debug, Clone)]
+pub struct QueryValueReader<'a> {
+    params: &'a BTreeMap<String, String>,
+    prefix: String,
+}
+
+impl<'a> QueryValueReader<'a> {
+    fn new(params: &'a BTreeMap<String, String>, prefix: String) -> Self {
+        QueryValueReader { params, prefix }
+    }
+
+    /// Returns the prefix this reader reads values from.
+    pub fn name(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Starts a new prefix.
+    pub fn prefix(&self, prefix: &str) -> QueryValueReader<'a> {
+        QueryValueReader::new(self.params, format!("{}.{}", self.prefix, prefix))
+    }
+
+    /// Whether any parameter is set at or below this prefix.
+    pub fn is_present(&self) -> bool {
+        if self.params.contains_key(&self.prefix) {
+            return true;
+        }
+        // The parameters below this prefix are sorted right after `<prefix>.`
+        let nested = format!("{}.", self.prefix);
+        self.params
+            .range::<str, _>((Bound::Included(nested.as_str()), Bound::Unbounded))
+            .next()
+            .map(|(name, _)| name.starts_with(&nested))
+            .unwrap_or(false)
+    }
+
+    /// Reads a string value, or `None` if the parameter is not set.
+    pub fn string(&self) -> Option<&'a str> {
+        self.params.get(&self.prefix).map(String::as_str)
+    }
+
+    /// Reads a boolean value.
+    pub fn boolean(&self) -> Result<Option<bool>, QueryError> {
+        self.primitive()
+    }
+
+    /// Reads a number, e.g. an `i32` or an `f64`.
+    pub fn number<T: Parse>(&self) -> Result<Option<T>, QueryError> {
+        self.primitive()
+    }
+
+    fn primitive<T: Parse>(&self) -> Result<Option<T>, QueryError> {
+        self.string()
+            .map(|value| {
+                T::parse_smithy_primitive(value)
+                    .map_err(|err| QueryError::invalid_value(&self.prefix, err.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Reads a date-time value in the given `format`.
+    pub fn date_time(&self, format: Format) -> Result<Option<DateTime>, QueryError> {
+        self.string()
+            .map(|value| {
+                DateTime::from_str(value, format)
+                    .map_err(|err| QueryError::invalid_value(&self.prefix, err.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Reads the members of a list, in order.
+    pub fn list(&self, flat: bool, member_override: Option<&str>) -> Vec<QueryValueReader<'a>> {
+        let entry = match (flat, member_override) {
+            (true, _) => String::new(),
+            (false, Some(member)) => format!(".{}", member),
+            (false, None) => String::from(".member"),
+        };
+        (1..)
+            .map(|index| {
+                QueryValueReader::new(self.params, format!("{}{}.{}", self.prefix, entry, index))
+            })
+            .take_while(QueryValueReader::is_present)
+            .collect()
+    }
+
+    /// Reads the entries of a map, in order, as pairs of keys and value readers.
+    pub fn map(
+        &self,
+        flat: bool,
+        key_name: &str,
+        value_name: &str,
+    ) -> Result<Vec<(&'a str, QueryValueReader<'a>)>, QueryError> {
+        let entry = if flat { "" } else { ".entry" };
+        (1..)
+            .map(|index| {
+                QueryValueReader::new(self.params, format!("{}{}.{}", self.prefix, entry, index))
+            })
+            .take_while(QueryValueReader::is_present)
+            .map(|entry| {
+                let key = entry.prefix(key_name);
+                let key = key.string().ok_or_else(|| {
+                    QueryError::invalid_value(key.name(), "map entry is missing its key")
+                })?;
+                Ok((key, entry.prefix(value_name)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{QueryError, QueryReader, QueryWriter};
+    use aws_smithy_types::date_time::Format;
+    use aws_smithy_types::{DateTime, Number};
+
+    #[test]
+    fn action_and_version() {
+        let reader = QueryReader::new("Action=Some%20Action&Version=1.0").unwrap();
+        assert_eq!(Some("Some Action"), reader.action());
+        assert_eq!(Some("1.0"), reader.version());
+
+        let reader = QueryReader::new("").unwrap();
+        assert_eq!(None, reader.action());
+    }
+
+    #[test]
+    fn reads_what_the_writer_writes() {
+        let mut out = String::new();
+        let mut writer = QueryWriter::new(&mut out, "SomeAction", "1.0");
+        writer.prefix("String").string("a b&c=d+e");
+        writer.prefix("Int").number(Number::NegInt(-5));
+        writer.prefix("Float").number(Number::Float(f64::INFINITY));
+        writer.prefix("Bool").boolean(true);
+        writer
+            .prefix("Time")
+            .date_time(&DateTime::from_secs(1576540098), Format::DateTime)
+            .unwrap();
+        let mut nested = writer.prefix("Nested");
+        nested.prefix("Inner").string("inner");
+        let mut list = writer.prefix("ListArg").start_list(false, None);
+        list.entry().string("foo");
+        list.entry().string("bar");
+        list.finish();
+        let mut list = writer.prefix("Flat").start_list(true, None);
+        list.entry().string("A");
+        list.finish();
+        let mut list = writer.prefix("ItemList").start_list(false, Some("item"));
+        list.entry().string("foo");
+        list.finish();
+        let mut map = writer.prefix("MapArg").start_map(false, "key", "value");
+        map.entry("bar").string("Bar");
+        map.entry("foo").prefix("Inner").string("Foo");
+        map.finish();
+        let mut map = writer.prefix("FlatMap").start_map(true, "K", "V");
+        map.entry("k").string("v");
+        map.finish();
+        writer.finish();
+
+        let reader = QueryReader::new(&out).unwrap();
+        assert_eq!(Some("a b&c=d+e"), reader.prefix("String").string());
+        assert_eq!(Some(-5), reader.prefix("Int").number::<i32>().unwrap());
+        assert_eq!(
+            Some(f64::INFINITY),
+            reader.prefix("Float").number::<f64>().unwrap()
+        );
+        assert_eq!(Some(true), reader.prefix("Bool").boolean().unwrap());
+        assert_eq!(
+            Some(DateTime::from_secs(1576540098)),
+            reader.prefix("Time").date_time(Format::DateTime).unwrap()
+        );
+        assert!(reader.prefix("Nested").is_present());
+        assert_eq!(None, reader.prefix("Nested").string());
+        assert_eq!(
+            Some("inner"),
+            reader.prefix("Nested").prefix("Inner").string()
+        );
+        assert!(!reader.prefix("Nest").is_present());
+        assert!(!reader.prefix("List").is_present());
+        assert!(!reader.prefix("Missing").is_present());
+        assert_eq!(None, reader.prefix("Missing").number::<i32>().unwrap());
+
+        let strings = |list: Vec<_>| {
+            list.iter()
+                .map(|value: &crate::QueryValueReader<'_>| value.string().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec!["foo", "bar"],
+            strings(reader.prefix("ListArg").list(false, None))
+        );
+        assert_eq!(vec!["A"], strings(reader.prefix("Flat").list(true, None)));
+        assert_eq!(
+            vec!["foo"],
+            strings(reader.prefix("ItemList").list(false, Some("item")))
+        );
+        assert!(reader.prefix("Missing").list(false, None).is_empty());
+
+        let map = reader.prefix("MapArg").map(false, "key", "value").unwrap();
+        assert_eq!(2, map.len());
+        assert_eq!(("bar", Some("Bar")), (map[0].0, map[0].1.string()));
+        assert_eq!(
+            ("foo", Some("Foo")),
+            (map[1].0, map[1].1.prefix("Inner").string())
+        );
+        let map = reader.prefix("FlatMap").map(true, "K", "V").unwrap();
+        assert_eq!(("k", Some("v")), (map[0].0, map[0].1.string()));
+    }
+
+    #[test]
+    fn invalid_values() {
+        let reader = QueryReader::new("Int=abc&Map.entry.1.value=v").unwrap();
+        match reader.prefix("Int").number::<i32>() {
+            Err(QueryError::InvalidValue { name, .. }) => assert_eq!("Int", name),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(reader.prefix("Map").map(false, "key", "value").is_err());
+        assert!(matches!(
+            QueryReader::new("a=%FF"),
+            Err(QueryError::InvalidEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn first_value_wins_and_siblings_are_not_nested() {
+        let reader = QueryReader::new("A=1&A=2&Ab.c=3&A-b=4").unwrap();
+        assert_eq!(Some("1"), reader.prefix("A").string());
+        assert!(reader.prefix("Ab").is_present());
+        assert!(!reader.prefix("Ab").prefix("d").is_present());
+        assert!(!reader.prefix("A").prefix("b").is_present());
+    }
+}
@@ -61,7 +61,7 @@ pub trait ProvideErrorKind {
 /// - The required retry delay exceeds the maximum backoff configured by the client
 /// - No retry tokens are available due to service health
 #[non_exhaustive]
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub enum RetryKind {
     /// Retry the associated request due to a known `ErrorKind`.
     Error(ErrorKind),
@@ -93,7 +93,7 @@ pub enum RetryMode {
     Adaptive,
 }
 
-const VALID_RETRY_MODES: &[RetryMode] = &[RetryMode::Standard];
+const VALID_RETRY_MODES: &[RetryMode] = &[RetryMode::Standard, RetryMode::Adaptive];
 
 /// Failure to parse a `RetryMode` from string.
 #[derive(Debug)]
@@ -119,9 +119,8 @@ impl FromStr for RetryMode {
         // eq_ignore_ascii_case is OK here because the only strings we need to check for are ASCII
         if string.eq_ignore_ascii_case("standard") {
             Ok(RetryMode::Standard)
-        // TODO(https://github.com/awslabs/aws-sdk-rust/issues/247): adaptive retries
-        // } else if string.eq_ignore_ascii_case("adaptive") {
-        //     Ok(RetryMode::Adaptive)
+        } else if string.eq_ignore_ascii_case("adaptive") {
+            Ok(RetryMode::Adaptive)
         } else {
             Err(RetryModeParseErr(string.to_owned()))
         }
@@ -320,6 +319,10 @@ pub enum RetryConfigErr {
         set_by: Cow<'static, str>,
     },
     /// The adaptive retry mode hasn't been implemented yet.
+    #[deprecated(
+        since = "0.47.0",
+        note = "The adaptive retry mode is supported, so this error is never returned"
+    )]
     AdaptiveModeIsNotSupported {
         /// Where the invalid retry mode value originated from.
         set_by: Cow<'static, str>,
@@ -327,6 +330,7 @@ pub enum RetryConfigErr {
 }
 
 impl Display for RetryConfigErr {
+    #[allow(deprecated)]
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         use RetryConfigErr::*;
         match self {
@@ -394,18 +398,18 @@ mod tests {
             RetryMode::from_str("StAnDaRd").ok(),
             Some(RetryMode::Standard)
         );
-        // assert_eq!(
-        //     RetryMode::from_str("adaptive").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
-        // assert_eq!(
-        //     RetryMode::from_str("ADAPTIVE").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
-        // assert_eq!(
-        //     RetryMode::from_str("aDaPtIvE").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
+        assert_eq!(
+            RetryMode::from_str("adaptive").ok(),
+            Some(RetryMode::Adaptive)
+        );
+        assert_eq!(
+            RetryMode::from_str("ADAPTIVE").ok(),
+            Some(RetryMode::Adaptive)
+        );
+        assert_eq!(
+            RetryMode::from_str("aDaPtIvE").ok(),
+            Some(RetryMode::Adaptive)
+        );
     }
 
     #[test]
@@ -422,18 +426,18 @@ mod tests {
             RetryMode::from_str("  StAnDaRd   ").ok(),
             Some(RetryMode::Standard)
         );
-        // assert_eq!(
-        //     RetryMode::from_str("  adaptive  ").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
-        // assert_eq!(
-        //     RetryMode::from_str("   ADAPTIVE ").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
-        // assert_eq!(
-        //     RetryMode::from_str("  aDaPtIvE    ").ok(),
-        //     Some(RetryMode::Adaptive)
-        // );
+        assert_eq!(
+            RetryMode::from_str("  adaptive  ").ok(),
+            Some(RetryMode::Adaptive)
+        );
+        assert_eq!(
+            RetryMode::from_str("   ADAPTIVE ").ok(),
+            Some(RetryMode::Adaptive)
+        );
+        assert_eq!(
+            RetryMode::from_str("  aDaPtIvE    ").ok(),
+            Some(RetryMode::Adaptive)
+        );
     }
 
     #[test]